@@ -0,0 +1,835 @@
+use anyhow::{bail, Context, Result};
+use git2::{Oid, Repository as Git2Repository};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Generation of commits the commit-graph does not have
+pub const GENERATION_INFINITY: u32 = u32::MAX;
+
+const GENERATION_V1_MAX: u32 = 0x3FFF_FFFF;
+const NO_PARENT: u32 = 0x7000_0000;
+const EXTRA_EDGES: u32 = 0x8000_0000;
+const HASH_LEN: usize = 20;
+const HEADER_LEN: usize = 8;
+const CHUNK_ENTRY_LEN: usize = 12;
+const CDAT_ENTRY_LEN: usize = HASH_LEN + 16;
+
+/// What the commit-graph records about a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphCommit {
+    pub tree: Oid,
+    pub parents: Vec<Oid>,
+    /// Committer date, seconds since the epoch
+    pub time: i64,
+    /// Topological level: 1 for root commits, one more than the highest parent
+    /// otherwise, `GENERATION_INFINITY` if the commit-graph does not have it
+    pub generation: u32,
+}
+
+/// One file of a commit-graph
+struct Layer {
+    data: Vec<u8>,
+    commits: u32,
+    /// Commits in the files below this one in a chain
+    base: u32,
+    fanout: usize,
+    oids: usize,
+    cdat: usize,
+    edges: Option<(usize, usize)>,
+}
+
+impl Layer {
+    fn parse(data: Vec<u8>, base: u32) -> Result<Self> {
+        if data.len() < HEADER_LEN + CHUNK_ENTRY_LEN + HASH_LEN || &data[..4] != b"CGPH" {
+            bail!("Not a commit-graph file");
+        }
+        if data[4] != 1 {
+            bail!("Unsupported commit-graph version {}", data[4]);
+        }
+        if data[5] != 1 {
+            bail!("Unsupported commit-graph hash version {}", data[5]);
+        }
+
+        let chunk_count = data[6] as usize;
+        let end = data.len() - HASH_LEN;
+        let table_end = HEADER_LEN + (chunk_count + 1) * CHUNK_ENTRY_LEN;
+        if table_end > end {
+            bail!("Truncated commit-graph chunk table");
+        }
+
+        let mut chunks = HashMap::new();
+        for i in 0..chunk_count {
+            let entry = HEADER_LEN + i * CHUNK_ENTRY_LEN;
+            let id: [u8; 4] = data[entry..entry + 4].try_into()?;
+            let start = read_u64(&data, entry + 4) as usize;
+            let next = read_u64(&data, entry + 4 + CHUNK_ENTRY_LEN) as usize;
+            if start < table_end || start > next || next > end {
+                bail!("Invalid offset for commit-graph chunk {}", String::from_utf8_lossy(&id));
+            }
+            chunks.insert(id, (start, next - start));
+        }
+
+        let chunk = |id: &[u8; 4], name: &str| {
+            chunks.get(id).copied().with_context(|| format!("Commit-graph has no {} chunk", name))
+        };
+        let (fanout, fanout_len) = chunk(b"OIDF", "OID fanout")?;
+        let (oids, oids_len) = chunk(b"OIDL", "OID lookup")?;
+        let (cdat, cdat_len) = chunk(b"CDAT", "commit data")?;
+        if fanout_len != 256 * 4 {
+            bail!("Invalid commit-graph OID fanout chunk");
+        }
+
+        let commits = read_u32(&data, fanout + 255 * 4);
+        if oids_len != commits as usize * HASH_LEN || cdat_len != commits as usize * CDAT_ENTRY_LEN {
+            bail!("Commit-graph chunks do not match its {} commits", commits);
+        }
+
+        Ok(Self {
+            commits,
+            base,
+            fanout,
+            oids,
+            cdat,
+            edges: chunks.get(b"EDGE").copied(),
+            data,
+        })
+    }
+
+    /// Index in this file of a commit
+    fn find(&self, oid: Oid) -> Option<u32> {
+        let bytes = oid.as_bytes();
+        let first = bytes[0] as usize;
+        let mut lo = if first == 0 { 0 } else { read_u32(&self.data, self.fanout + (first - 1) * 4) };
+        let mut hi = read_u32(&self.data, self.fanout + first * 4).min(self.commits);
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.oid_bytes(mid).cmp(bytes) {
+                std::cmp::Ordering::Equal => return Some(mid),
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+            }
+        }
+        None
+    }
+
+    fn oid_bytes(&self, idx: u32) -> &[u8] {
+        let start = self.oids + idx as usize * HASH_LEN;
+        &self.data[start..start + HASH_LEN]
+    }
+
+    fn edge(&self, idx: usize) -> Option<u32> {
+        let (start, len) = self.edges?;
+        (idx * 4 + 4 <= len).then(|| read_u32(&self.data, start + idx * 4))
+    }
+}
+
+/// A commit-graph: `objects/info/commit-graph`, or the chain of split files
+/// under `objects/info/commit-graphs`
+///
+/// Gives parents, dates and generation numbers of commits without inflating
+/// commit objects. Positions count from the base of a chain upwards.
+pub struct CommitGraph {
+    layers: Vec<Layer>,
+}
+
+impl CommitGraph {
+    /// Read the commit-graph of an object directory, `None` if there is none
+    ///
+    /// A single `commit-graph` file takes precedence over a chain, as in git.
+    pub fn open(objects_dir: &Path) -> Result<Option<Self>> {
+        let info = objects_dir.join("info");
+        let single = info.join("commit-graph");
+        if single.exists() {
+            let layer = Layer::parse(fs::read(&single)?, 0)
+                .with_context(|| format!("Failed to read {}", single.display()))?;
+            return Ok(Some(Self { layers: vec![layer] }));
+        }
+
+        let chain_dir = info.join("commit-graphs");
+        let chain = match fs::read_to_string(chain_dir.join("commit-graph-chain")) {
+            Ok(chain) => chain,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut layers: Vec<Layer> = Vec::new();
+        for hash in chain.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let path = chain_dir.join(format!("graph-{}.graph", hash));
+            let base = layers.last().map(|layer| layer.base + layer.commits).unwrap_or(0);
+            let layer = Layer::parse(fs::read(&path)?, base)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            layers.push(layer);
+        }
+        Ok((!layers.is_empty()).then_some(Self { layers }))
+    }
+
+    /// The repository's commit-graph, shared until its files change
+    ///
+    /// `None` when there is none, when `core.commitGraph` is off, or in a
+    /// shallow repository, whose grafted parents the file does not know.
+    pub fn load(repo: &Git2Repository) -> Result<Option<Arc<Self>>> {
+        let enabled = repo.config()
+            .and_then(|config| config.get_bool("core.commitGraph"))
+            .unwrap_or(true);
+        if !enabled || repo.is_shallow() {
+            return Ok(None);
+        }
+
+        static CACHE: OnceLock<Mutex<GraphCache>> = OnceLock::new();
+        let objects = objects_dir(repo);
+        let stamp = graph_stamp(&objects);
+        if stamp.is_empty() {
+            return Ok(None);
+        }
+
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some((cached, graph)) = cache.lock().unwrap().get(&objects) {
+            if *cached == stamp {
+                return Ok(Some(graph.clone()));
+            }
+        }
+
+        let Some(graph) = Self::open(&objects)? else {
+            return Ok(None);
+        };
+        let graph = Arc::new(graph);
+        cache.lock().unwrap().insert(objects, (stamp, graph.clone()));
+        Ok(Some(graph))
+    }
+
+    /// Number of commits in all files
+    pub fn len(&self) -> usize {
+        self.layers.last().map(|layer| (layer.base + layer.commits) as usize).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of files, more than one for a split chain
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Position of a commit in the graph
+    pub fn position(&self, oid: Oid) -> Option<u32> {
+        self.layers.iter().rev().find_map(|layer| layer.find(oid).map(|idx| layer.base + idx))
+    }
+
+    pub fn contains(&self, oid: Oid) -> bool {
+        self.position(oid).is_some()
+    }
+
+    /// Commit at a position
+    pub fn oid_at(&self, pos: u32) -> Option<Oid> {
+        let (layer, idx) = self.locate(pos)?;
+        Oid::from_bytes(layer.oid_bytes(idx)).ok()
+    }
+
+    /// Parents, date and generation of a commit, if the graph has it
+    pub fn commit(&self, oid: Oid) -> Option<GraphCommit> {
+        self.commit_at(self.position(oid)?)
+    }
+
+    pub fn commit_at(&self, pos: u32) -> Option<GraphCommit> {
+        let (layer, idx) = self.locate(pos)?;
+        let data = &layer.data;
+        let entry = layer.cdat + idx as usize * CDAT_ENTRY_LEN;
+
+        let tree = Oid::from_bytes(&data[entry..entry + HASH_LEN]).ok()?;
+        let first = read_u32(data, entry + HASH_LEN);
+        let second = read_u32(data, entry + HASH_LEN + 4);
+        let level_and_time = read_u32(data, entry + HASH_LEN + 8);
+        let time_low = read_u32(data, entry + HASH_LEN + 12);
+
+        let mut parents = Vec::new();
+        if first != NO_PARENT {
+            parents.push(self.oid_at(first)?);
+        }
+        if second & EXTRA_EDGES != 0 {
+            // Octopus merge: the other parents are in the extra edge list
+            let mut edge_idx = (second & !EXTRA_EDGES) as usize;
+            loop {
+                let edge = layer.edge(edge_idx)?;
+                parents.push(self.oid_at(edge & !EXTRA_EDGES)?);
+                if edge & EXTRA_EDGES != 0 {
+                    break;
+                }
+                edge_idx += 1;
+            }
+        } else if second != NO_PARENT {
+            parents.push(self.oid_at(second)?);
+        }
+
+        Some(GraphCommit {
+            tree,
+            parents,
+            time: (((level_and_time & 0x3) as i64) << 32) | time_low as i64,
+            generation: level_and_time >> 2,
+        })
+    }
+
+    /// Generation of a commit, `GENERATION_INFINITY` if the graph lacks it
+    pub fn generation(&self, oid: Oid) -> u32 {
+        self.position(oid)
+            .and_then(|pos| self.locate(pos))
+            .map(|(layer, idx)| {
+                read_u32(&layer.data, layer.cdat + idx as usize * CDAT_ENTRY_LEN + HASH_LEN + 8) >> 2
+            })
+            .unwrap_or(GENERATION_INFINITY)
+    }
+
+    fn locate(&self, pos: u32) -> Option<(&Layer, u32)> {
+        self.layers.iter()
+            .find(|layer| pos >= layer.base && pos < layer.base + layer.commits)
+            .map(|layer| (layer, pos - layer.base))
+    }
+}
+
+/// Parents, dates and generations of commits: from the commit-graph where it
+/// has them, from the object database otherwise
+pub struct CommitLookup<'a> {
+    repo: &'a Git2Repository,
+    graph: Option<Arc<CommitGraph>>,
+}
+
+impl<'a> CommitLookup<'a> {
+    /// Uses the repository's commit-graph if it has a readable one
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        Self { repo, graph: CommitGraph::load(repo).ok().flatten() }
+    }
+
+    /// Always read commit objects
+    pub fn without_graph(repo: &'a Git2Repository) -> Self {
+        Self { repo, graph: None }
+    }
+
+    pub fn graph(&self) -> Option<&CommitGraph> {
+        self.graph.as_deref()
+    }
+
+    pub fn get(&self, oid: Oid) -> Result<GraphCommit> {
+        if let Some(commit) = self.graph.as_ref().and_then(|graph| graph.commit(oid)) {
+            return Ok(commit);
+        }
+        let commit = self.repo.find_commit(oid)?;
+        Ok(GraphCommit {
+            tree: commit.tree_id(),
+            parents: commit.parent_ids().collect(),
+            time: commit.time().seconds(),
+            generation: GENERATION_INFINITY,
+        })
+    }
+
+    pub fn generation(&self, oid: Oid) -> u32 {
+        self.graph.as_ref().map(|graph| graph.generation(oid)).unwrap_or(GENERATION_INFINITY)
+    }
+
+    /// Check if `ancestor` is in the history of `commit`, not counting `commit` itself
+    ///
+    /// With generation numbers the walk stops at commits older than
+    /// `ancestor` by generation instead of going down to the roots.
+    pub fn is_descendant_of(&self, commit: Oid, ancestor: Oid) -> Result<bool> {
+        let target = self.generation(ancestor);
+        if target == GENERATION_INFINITY || commit == ancestor {
+            return Ok(self.repo.graph_descendant_of(commit, ancestor)?);
+        }
+
+        let mut seen = HashSet::new();
+        let mut stack = vec![commit];
+        while let Some(oid) = stack.pop() {
+            for parent in self.get(oid)?.parents {
+                if parent == ancestor {
+                    return Ok(true);
+                }
+                // Commits at or below the ancestor's generation cannot reach it
+                if self.generation(parent) > target && seen.insert(parent) {
+                    stack.push(parent);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Commits only `local` reaches and commits only `upstream` reaches
+    pub fn ahead_behind(&self, local: Oid, upstream: Oid) -> Result<(usize, usize)> {
+        const LOCAL: u8 = 1;
+        const UPSTREAM: u8 = 2;
+        const BOTH: u8 = LOCAL | UPSTREAM;
+
+        if local == upstream {
+            return Ok((0, 0));
+        }
+        // Walking by generation needs every commit involved in the graph
+        if self.generation(local) == GENERATION_INFINITY || self.generation(upstream) == GENERATION_INFINITY {
+            return Ok(self.repo.graph_ahead_behind(local, upstream)?);
+        }
+
+        // Highest generation first, so each commit's flags are final when it
+        // is popped; stop once only commits both sides reach are left
+        let mut flags: HashMap<Oid, u8> = HashMap::from([(local, LOCAL), (upstream, UPSTREAM)]);
+        let mut queue = BinaryHeap::from([
+            (self.generation(local), local),
+            (self.generation(upstream), upstream),
+        ]);
+        let mut unsettled = 2;
+        let (mut ahead, mut behind) = (0, 0);
+
+        while unsettled > 0 {
+            let Some((_, oid)) = queue.pop() else {
+                break;
+            };
+            let flag = flags[&oid];
+            match flag {
+                LOCAL => ahead += 1,
+                UPSTREAM => behind += 1,
+                _ => {}
+            }
+            if flag != BOTH {
+                unsettled -= 1;
+            }
+
+            for parent in self.get(oid)?.parents {
+                match flags.get(&parent).copied() {
+                    None => {
+                        flags.insert(parent, flag);
+                        queue.push((self.generation(parent), parent));
+                        if flag != BOTH {
+                            unsettled += 1;
+                        }
+                    }
+                    Some(old) if old | flag != old => {
+                        flags.insert(parent, old | flag);
+                        if old | flag == BOTH {
+                            unsettled -= 1;
+                        }
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        Ok((ahead, behind))
+    }
+}
+
+/// Writes the commit-graph file
+pub struct CommitGraphOps<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> CommitGraphOps<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        Self { repo }
+    }
+
+    /// `objects/info/commit-graph`
+    pub fn path(&self) -> PathBuf {
+        objects_dir(self.repo).join("info").join("commit-graph")
+    }
+
+    /// Write a commit-graph of every commit reachable from HEAD or a ref
+    ///
+    /// Replaces `objects/info/commit-graph` and, like `git commit-graph write
+    /// --no-split`, removes a split chain it supersedes; returns the number
+    /// of commits.
+    pub fn write(&self) -> Result<usize> {
+        if self.repo.is_shallow() {
+            bail!("Cannot write a commit-graph in a shallow repository");
+        }
+
+        // Read through the current graph so rewriting it stays cheap
+        let lookup = CommitLookup::new(self.repo);
+        let mut commits: HashMap<Oid, GraphCommit> = HashMap::new();
+        let mut stack = self.tips()?;
+        while let Some(oid) = stack.pop() {
+            if commits.contains_key(&oid) {
+                continue;
+            }
+            let commit = lookup.get(oid)?;
+            stack.extend(commit.parents.iter().filter(|parent| !commits.contains_key(parent)));
+            commits.insert(oid, commit);
+        }
+
+        let mut oids: Vec<Oid> = commits.keys().copied().collect();
+        oids.sort();
+        let positions: HashMap<Oid, u32> = oids.iter().enumerate().map(|(pos, oid)| (*oid, pos as u32)).collect();
+        let generations = topological_levels(&oids, &positions, &commits);
+
+        let data = encode(&oids, &positions, &commits, &generations);
+        let path = self.path();
+        let dir = path.parent().context("Invalid objects directory")?;
+        fs::create_dir_all(dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(&data)?;
+        file.persist(&path).map_err(|e| e.error)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+
+        // The single file takes precedence, so a chain left behind would only go stale
+        let chain_dir = dir.join("commit-graphs");
+        if chain_dir.join("commit-graph-chain").exists() {
+            for entry in fs::read_dir(&chain_dir)? {
+                let file = entry?.path();
+                let is_layer = file.extension().is_some_and(|ext| ext == "graph");
+                if is_layer || file.file_name().is_some_and(|name| name == "commit-graph-chain") {
+                    fs::remove_file(&file)
+                        .with_context(|| format!("Failed to remove {}", file.display()))?;
+                }
+            }
+            let _ = fs::remove_dir(&chain_dir);
+        }
+        Ok(oids.len())
+    }
+
+    /// Write the commit-graph if a ref points at a commit it does not have
+    ///
+    /// Returns the number of commits written, `None` if it was up to date.
+    pub fn update(&self) -> Result<Option<usize>> {
+        if let Some(graph) = CommitGraph::load(self.repo)? {
+            if self.tips()?.into_iter().all(|tip| graph.contains(tip)) {
+                return Ok(None);
+            }
+        }
+        self.write().map(Some)
+    }
+
+    /// HEAD and the commits all refs point at
+    fn tips(&self) -> Result<Vec<Oid>> {
+        let mut tips = Vec::new();
+        if let Ok(head) = self.repo.head().and_then(|head| head.peel_to_commit()) {
+            tips.push(head.id());
+        }
+        for reference in self.repo.references()? {
+            if let Ok(commit) = reference?.peel_to_commit() {
+                tips.push(commit.id());
+            }
+        }
+        tips.sort();
+        tips.dedup();
+        Ok(tips)
+    }
+}
+
+/// Size and modification time of a commit-graph file
+type FileStamp = (u64, Option<SystemTime>);
+
+/// Loaded graphs by objects directory, with the file stamps they were read at
+type GraphCache = HashMap<PathBuf, (Vec<FileStamp>, Arc<CommitGraph>)>;
+
+/// Stamps of the files a commit-graph is read from, empty if there are none
+fn graph_stamp(objects: &Path) -> Vec<FileStamp> {
+    let info = objects.join("info");
+    let stamp = |path: &Path| fs::metadata(path).ok().map(|meta| (meta.len(), meta.modified().ok()));
+    if let Some(single) = stamp(&info.join("commit-graph")) {
+        return vec![single];
+    }
+    let chain_dir = info.join("commit-graphs");
+    let Some(chain) = stamp(&chain_dir.join("commit-graph-chain")) else {
+        return Vec::new();
+    };
+    let mut stamps = vec![chain];
+    if let Ok(entries) = fs::read_dir(&chain_dir) {
+        let mut files: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+        files.sort();
+        stamps.extend(files.iter().filter_map(|path| stamp(path)));
+    }
+    stamps
+}
+
+/// The object directory, shared by linked worktrees
+fn objects_dir(repo: &Git2Repository) -> PathBuf {
+    let git_dir = repo.path();
+    let common = fs::read_to_string(git_dir.join("commondir"))
+        .map(|dir| git_dir.join(dir.trim()))
+        .unwrap_or_else(|_| git_dir.to_path_buf());
+    common.join("objects")
+}
+
+/// Generation numbers v1, indexed by position
+fn topological_levels(oids: &[Oid], positions: &HashMap<Oid, u32>, commits: &HashMap<Oid, GraphCommit>) -> Vec<u32> {
+    let mut levels = vec![0u32; oids.len()];
+    for start in 0..oids.len() {
+        let mut stack = vec![start];
+        while let Some(&pos) = stack.last() {
+            if levels[pos] != 0 {
+                stack.pop();
+                continue;
+            }
+            let parents = &commits[&oids[pos]].parents;
+            let pending: Vec<usize> = parents.iter()
+                .map(|parent| positions[parent] as usize)
+                .filter(|&parent| levels[parent] == 0)
+                .collect();
+            if pending.is_empty() {
+                let highest = parents.iter().map(|parent| levels[positions[parent] as usize]).max().unwrap_or(0);
+                levels[pos] = (highest + 1).min(GENERATION_V1_MAX);
+                stack.pop();
+            } else {
+                stack.extend(pending);
+            }
+        }
+    }
+    levels
+}
+
+/// The commit-graph file format, version 1 with SHA-1 hashes
+fn encode(
+    oids: &[Oid],
+    positions: &HashMap<Oid, u32>,
+    commits: &HashMap<Oid, GraphCommit>,
+    generations: &[u32],
+) -> Vec<u8> {
+    let mut fanout = Vec::with_capacity(256 * 4);
+    let mut count = 0;
+    for byte in 0..=255u8 {
+        while count < oids.len() && oids[count].as_bytes()[0] <= byte {
+            count += 1;
+        }
+        fanout.extend_from_slice(&(count as u32).to_be_bytes());
+    }
+
+    let mut lookup = Vec::with_capacity(oids.len() * HASH_LEN);
+    let mut cdat = Vec::with_capacity(oids.len() * CDAT_ENTRY_LEN);
+    let mut edges = Vec::new();
+    for (pos, oid) in oids.iter().enumerate() {
+        lookup.extend_from_slice(oid.as_bytes());
+
+        let commit = &commits[oid];
+        let parent = |idx: usize| commit.parents.get(idx).map(|parent| positions[parent]).unwrap_or(NO_PARENT);
+        let second = if commit.parents.len() > 2 {
+            let start = (edges.len() / 4) as u32 | EXTRA_EDGES;
+            for (idx, parent) in commit.parents[1..].iter().enumerate() {
+                let last = if idx == commit.parents.len() - 2 { EXTRA_EDGES } else { 0 };
+                edges.extend_from_slice(&(positions[parent] | last).to_be_bytes());
+            }
+            start
+        } else {
+            parent(1)
+        };
+        let time = commit.time.clamp(0, (1 << 34) - 1) as u64;
+
+        cdat.extend_from_slice(commit.tree.as_bytes());
+        cdat.extend_from_slice(&parent(0).to_be_bytes());
+        cdat.extend_from_slice(&second.to_be_bytes());
+        cdat.extend_from_slice(&((generations[pos] << 2) | (time >> 32) as u32).to_be_bytes());
+        cdat.extend_from_slice(&(time as u32).to_be_bytes());
+    }
+
+    let mut chunks: Vec<(&[u8; 4], Vec<u8>)> = vec![(b"OIDF", fanout), (b"OIDL", lookup), (b"CDAT", cdat)];
+    if !edges.is_empty() {
+        chunks.push((b"EDGE", edges));
+    }
+
+    let mut data = Vec::new();
+    data.extend_from_slice(b"CGPH");
+    data.extend_from_slice(&[1, 1, chunks.len() as u8, 0]);
+    let mut offset = (HEADER_LEN + (chunks.len() + 1) * CHUNK_ENTRY_LEN) as u64;
+    for (id, chunk) in &chunks {
+        data.extend_from_slice(*id);
+        data.extend_from_slice(&offset.to_be_bytes());
+        offset += chunk.len() as u64;
+    }
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(&offset.to_be_bytes());
+    for (_, chunk) in &chunks {
+        data.extend_from_slice(chunk);
+    }
+
+    let checksum = sha1_smol::Sha1::from(&data).digest().bytes();
+    data.extend_from_slice(&checksum);
+    data
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(data[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+    use tempfile::TempDir;
+
+    /// Run git in `dir` without user or system config, feeding it `input`
+    fn git(dir: &Path, args: &[&str], input: &str, date: i64) -> String {
+        let mut child = Command::new("git")
+            .current_dir(dir)
+            .args(args)
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com")
+            .env("GIT_AUTHOR_DATE", format!("@{} +0000", date))
+            .env("GIT_COMMITTER_DATE", format!("@{} +0000", date))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("git must be installed to run these tests");
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    /// A repository driven through the git command line
+    struct Repo {
+        dir: TempDir,
+        date: i64,
+    }
+
+    impl Repo {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            git(dir.path(), &["init", "-q", "."], "", 0);
+            Repo { dir, date: 1_700_000_000 }
+        }
+
+        fn git(&self, args: &[&str]) -> String {
+            git(self.dir.path(), args, "", self.date)
+        }
+
+        /// Commit a one-file tree with `parents` and point `branch` at it
+        fn commit(&mut self, branch: &str, message: &str, parents: &[&str]) -> String {
+            let path = self.dir.path();
+            let blob = git(path, &["hash-object", "-w", "--stdin"], message, self.date);
+            let tree = git(path, &["mktree"], &format!("100644 blob {}\tf\n", blob), self.date);
+            let mut args = vec!["commit-tree", tree.as_str(), "-m", message];
+            for parent in parents {
+                args.extend(["-p", parent]);
+            }
+            let commit = git(path, &args, "", self.date);
+            self.git(&["update-ref", &format!("refs/heads/{}", branch), &commit]);
+            self.date += 60;
+            commit
+        }
+
+        fn open(&self) -> Git2Repository {
+            Git2Repository::open(self.dir.path()).unwrap()
+        }
+    }
+
+    /// Every commit in the repository, with its topological level from the object database
+    fn levels(repo: &Git2Repository) -> HashMap<Oid, u32> {
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_glob("refs/heads/*").unwrap();
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE).unwrap();
+
+        let lookup = CommitLookup::without_graph(repo);
+        let mut levels = HashMap::new();
+        for oid in walk {
+            let oid = oid.unwrap();
+            let parents = lookup.get(oid).unwrap().parents;
+            let level = parents.iter().map(|parent| levels[parent]).max().unwrap_or(0) + 1;
+            levels.insert(oid, level);
+        }
+        levels
+    }
+
+    /// The graph has every commit, with the object database's tree, parents and date
+    fn assert_matches_odb(repo: &Git2Repository) {
+        let lookup = CommitLookup::new(repo);
+        let graph = lookup.graph().expect("no commit-graph");
+        let without = CommitLookup::without_graph(repo);
+        let levels = levels(repo);
+        assert_eq!(graph.len(), levels.len());
+
+        for (&oid, &level) in &levels {
+            let commit = lookup.get(oid).unwrap();
+            let expected = without.get(oid).unwrap();
+            assert_eq!((commit.tree, &commit.parents, commit.time), (expected.tree, &expected.parents, expected.time), "{}", oid);
+            assert_eq!(commit.generation, level, "{}", oid);
+            assert_eq!(lookup.generation(oid), level, "{}", oid);
+            assert_eq!(graph.oid_at(graph.position(oid).unwrap()), Some(oid));
+        }
+
+        // Ancestry walks by generation agree with libgit2's
+        for &a in levels.keys() {
+            for &b in levels.keys() {
+                assert_eq!(lookup.is_descendant_of(a, b).unwrap(), repo.graph_descendant_of(a, b).unwrap());
+                assert_eq!(lookup.ahead_behind(a, b).unwrap(), repo.graph_ahead_behind(a, b).unwrap());
+            }
+        }
+    }
+
+    fn has_chunk(path: &Path, id: &[u8; 4]) -> bool {
+        fs::read(path).unwrap().windows(4).any(|window| window == id)
+    }
+
+    #[test]
+    fn test_read_split_chain_with_octopus_merges() {
+        let mut repo = Repo::new();
+        let root = repo.commit("main", "root", &[]);
+        let mut tip = root.clone();
+        for i in 0..3 {
+            tip = repo.commit("main", &format!("main {}", i), &[&tip]);
+        }
+        repo.git(&["commit-graph", "write", "--reachable", "--split=no-merge"]);
+
+        // Octopus in the second layer, its parents spread over both layers
+        let side: Vec<String> = (0..3).map(|i| repo.commit(&format!("side{}", i), &format!("side {}", i), &[&root])).collect();
+        tip = repo.commit("main", "octopus", &[&tip, &side[0], &side[1], &side[2]]);
+        repo.git(&["commit-graph", "write", "--reachable", "--split=no-merge"]);
+
+        // Another octopus on top, and a date past 32 bits
+        let first = repo.commit("a", "a", &[&tip]);
+        let second = repo.commit("b", "b", &[&side[2]]);
+        repo.date = 5_000_000_000;
+        repo.commit("main", "late octopus", &[&tip, &first, &second, &root, &side[1]]);
+        repo.git(&["commit-graph", "write", "--reachable", "--split=no-merge"]);
+
+        let git_repo = repo.open();
+        let objects = objects_dir(&git_repo);
+        let graph = CommitGraph::open(&objects).unwrap().unwrap();
+        assert_eq!(graph.layer_count(), 3);
+        let chain_dir = objects.join("info").join("commit-graphs");
+        let layers: Vec<PathBuf> = fs::read_dir(&chain_dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "graph"))
+            .collect();
+        assert!(layers.iter().all(|layer| has_chunk(layer, b"GDA2")));
+        assert!(layers.iter().any(|layer| has_chunk(layer, b"EDGE")));
+
+        assert_matches_odb(&git_repo);
+        let late = CommitLookup::new(&git_repo).get(git_repo.refname_to_id("refs/heads/main").unwrap()).unwrap();
+        assert_eq!((late.parents.len(), late.time), (5, 5_000_000_000));
+    }
+
+    #[test]
+    fn test_write_verifies_with_git() {
+        let mut repo = Repo::new();
+        let root = repo.commit("main", "root", &[]);
+        let side: Vec<String> = (0..4).map(|i| repo.commit(&format!("side{}", i), &format!("side {}", i), &[&root])).collect();
+        let merge = repo.commit("main", "merge", &[&root, &side[0]]);
+        repo.commit("main", "octopus", &[&merge, &side[1], &side[2], &side[3]]);
+        repo.date = 5_000_000_000;
+        repo.commit("late", "late", &[&side[3]]);
+
+        // Written over a split chain, which it replaces
+        repo.git(&["commit-graph", "write", "--reachable", "--split=no-merge"]);
+        let git_repo = repo.open();
+        let ops = CommitGraphOps::new(&git_repo);
+        assert_eq!(ops.write().unwrap(), 8);
+        assert!(!objects_dir(&git_repo).join("info").join("commit-graphs").exists());
+        assert!(has_chunk(&ops.path(), b"EDGE"));
+
+        repo.git(&["commit-graph", "verify"]);
+        assert_matches_odb(&git_repo);
+
+        // git reads the same ancestry from it
+        let mut walk = git_repo.revwalk().unwrap();
+        walk.push_glob("refs/heads/*").unwrap();
+        let ours = walk.count();
+        assert_eq!(repo.git(&["rev-list", "--count", "--branches"]), ours.to_string());
+    }
+}
@@ -0,0 +1,395 @@
+use anyhow::Result;
+use chrono::{Datelike, FixedOffset, TimeZone, Timelike};
+use git2::{DiffOptions, Patch, Repository as Git2Repository, Sort};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Granularity of per-author activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimeBucket {
+    Day,
+    Week,
+    #[default]
+    Month,
+}
+
+impl TimeBucket {
+    /// Bucket label for a commit time, e.g. "2024-03", "2024-W11" or "2024-03-14"
+    fn label(&self, time: &chrono::DateTime<FixedOffset>) -> String {
+        match self {
+            TimeBucket::Day => time.format("%Y-%m-%d").to_string(),
+            TimeBucket::Week => {
+                let week = time.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            TimeBucket::Month => time.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// What to include in the analytics
+#[derive(Debug, Clone)]
+pub struct AnalyticsOptions {
+    /// Stop after this many commits
+    pub max_commits: Option<usize>,
+    /// Only commits made at or after this Unix time
+    pub since: Option<i64>,
+    /// Only commits made before this Unix time
+    pub until: Option<i64>,
+    /// Include merge commits (their changes are counted against the first parent)
+    pub include_merges: bool,
+    pub bucket: TimeBucket,
+    /// Number of files kept in the churn ranking
+    pub top_files: usize,
+    /// Number of file pairs kept in the coupling ranking
+    pub top_couplings: usize,
+    /// File pairs changed together fewer times than this are not reported as coupled
+    pub min_shared_commits: usize,
+    /// Commits touching more files than this (mass renames, reformatting) are
+    /// left out of the coupling analysis
+    pub max_coupling_files: usize,
+}
+
+impl Default for AnalyticsOptions {
+    fn default() -> Self {
+        AnalyticsOptions {
+            max_commits: None,
+            since: None,
+            until: None,
+            include_merges: false,
+            bucket: TimeBucket::Month,
+            top_files: 20,
+            top_couplings: 20,
+            min_shared_commits: 2,
+            max_coupling_files: 50,
+        }
+    }
+}
+
+/// Commits in one time bucket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeriodCount {
+    pub period: String,
+    pub commits: usize,
+}
+
+/// Activity of one author (identities are merged through `.mailmap`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorStats {
+    pub name: String,
+    pub email: String,
+    pub commits: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub first_commit: i64,
+    pub last_commit: i64,
+    /// Commits per time bucket, oldest first
+    pub activity: Vec<PeriodCount>,
+}
+
+/// How much a file changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileChurn {
+    pub path: String,
+    pub commits: usize,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    /// Number of distinct authors that changed the file
+    pub authors: usize,
+}
+
+impl FileChurn {
+    pub fn churn(&self) -> usize {
+        self.lines_added + self.lines_removed
+    }
+}
+
+/// Two files that tend to change in the same commits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileCoupling {
+    pub file_a: String,
+    pub file_b: String,
+    /// Commits that changed both files
+    pub shared_commits: usize,
+    /// Share of the commits changing either file that changed both (0.0 to 1.0)
+    pub degree: f64,
+}
+
+/// Contributor, churn, coupling and activity statistics of a history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoAnalytics {
+    pub commits: usize,
+    pub first_commit: Option<i64>,
+    pub last_commit: Option<i64>,
+    /// Sorted by number of commits, most active first
+    pub authors: Vec<AuthorStats>,
+    /// Files with the most changed lines first
+    pub churn: Vec<FileChurn>,
+    /// Most strongly coupled file pairs first
+    pub coupling: Vec<FileCoupling>,
+    /// Commits per weekday (Monday first) and hour, in each commit's own time zone
+    pub heatmap: [[usize; 24]; 7],
+}
+
+impl RepoAnalytics {
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Per-file totals while walking
+#[derive(Default)]
+struct FileTotals {
+    commits: usize,
+    added: usize,
+    removed: usize,
+    authors: HashSet<String>,
+}
+
+/// History analytics for a repository
+pub struct AnalyticsOps<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> AnalyticsOps<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        AnalyticsOps { repo }
+    }
+
+    /// Analyze the history of HEAD
+    pub fn compute(&self, options: &AnalyticsOptions) -> Result<RepoAnalytics> {
+        self.compute_with_progress(options, &mut |_| true)
+    }
+
+    /// Analyze the history of HEAD, reporting the number of commits walked so far
+    ///
+    /// Returning `false` from `progress` stops the walk early with an error.
+    pub fn compute_with_progress(
+        &self,
+        options: &AnalyticsOptions,
+        progress: &mut dyn FnMut(usize) -> bool,
+    ) -> Result<RepoAnalytics> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(Sort::TIME)?;
+        if self.repo.head().is_ok() {
+            revwalk.push_head()?;
+        }
+
+        let mailmap = self.repo.mailmap().ok();
+        let mut diff_options = DiffOptions::new();
+        diff_options.context_lines(0);
+
+        let mut authors: HashMap<String, AuthorStats> = HashMap::new();
+        let mut activity: HashMap<String, BTreeMap<String, usize>> = HashMap::new();
+        let mut files: HashMap<String, FileTotals> = HashMap::new();
+        let mut pairs: HashMap<(String, String), usize> = HashMap::new();
+        let mut heatmap = [[0usize; 24]; 7];
+        let mut count = 0;
+        let mut first_commit: Option<i64> = None;
+        let mut last_commit: Option<i64> = None;
+
+        for oid in revwalk {
+            let commit = self.repo.find_commit(oid?)?;
+            let time = commit.time();
+
+            if options.until.is_some_and(|until| time.seconds() >= until) {
+                continue;
+            }
+            // Commits are walked newest first
+            if options.since.is_some_and(|since| time.seconds() < since) {
+                break;
+            }
+            if !options.include_merges && commit.parent_count() > 1 {
+                continue;
+            }
+            if options.max_commits.is_some_and(|max| count >= max) {
+                break;
+            }
+
+            count += 1;
+            if !progress(count) {
+                return Err(anyhow::anyhow!("Analytics cancelled"));
+            }
+            first_commit = Some(first_commit.map_or(time.seconds(), |t| t.min(time.seconds())));
+            last_commit = Some(last_commit.map_or(time.seconds(), |t| t.max(time.seconds())));
+
+            let signature = match &mailmap {
+                Some(mailmap) => commit.author_with_mailmap(mailmap)?,
+                None => commit.author(),
+            };
+            let name = signature.name().unwrap_or("").to_string();
+            let email = signature.email().unwrap_or("").to_string();
+            let key = email.to_lowercase();
+
+            let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+                .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+            let local = offset.timestamp_opt(time.seconds(), 0).single()
+                .ok_or_else(|| anyhow::anyhow!("Invalid commit time in {}", commit.id()))?;
+            heatmap[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += 1;
+
+            // Lines changed per file, against the first parent
+            let tree = commit.tree()?;
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+            let diff = self.repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut diff_options))?;
+
+            let mut changed = Vec::new();
+            let (mut commit_added, mut commit_removed) = (0, 0);
+            for idx in 0..diff.deltas().len() {
+                let delta = diff.get_delta(idx).expect("delta index in range");
+                let path = delta.new_file().path()
+                    .or_else(|| delta.old_file().path())
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                let (added, removed) = match Patch::from_diff(&diff, idx)? {
+                    Some(patch) => {
+                        let (_, added, removed) = patch.line_stats()?;
+                        (added, removed)
+                    }
+                    None => (0, 0),
+                };
+                commit_added += added;
+                commit_removed += removed;
+
+                let totals = files.entry(path.clone()).or_default();
+                totals.commits += 1;
+                totals.added += added;
+                totals.removed += removed;
+                totals.authors.insert(key.clone());
+                changed.push(path);
+            }
+
+            if changed.len() <= options.max_coupling_files {
+                changed.sort();
+                for (i, a) in changed.iter().enumerate() {
+                    for b in &changed[i + 1..] {
+                        *pairs.entry((a.clone(), b.clone())).or_default() += 1;
+                    }
+                }
+            }
+
+            let author = authors.entry(key.clone()).or_insert_with(|| AuthorStats {
+                name,
+                email,
+                commits: 0,
+                lines_added: 0,
+                lines_removed: 0,
+                first_commit: time.seconds(),
+                last_commit: time.seconds(),
+                activity: Vec::new(),
+            });
+            author.commits += 1;
+            author.lines_added += commit_added;
+            author.lines_removed += commit_removed;
+            author.first_commit = author.first_commit.min(time.seconds());
+            author.last_commit = author.last_commit.max(time.seconds());
+            *activity.entry(key).or_default().entry(options.bucket.label(&local)).or_default() += 1;
+        }
+
+        let mut authors: Vec<AuthorStats> = authors.into_iter()
+            .map(|(key, mut author)| {
+                author.activity = activity.remove(&key).unwrap_or_default().into_iter()
+                    .map(|(period, commits)| PeriodCount { period, commits })
+                    .collect();
+                author
+            })
+            .collect();
+        authors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+
+        let mut coupling: Vec<FileCoupling> = pairs.into_iter()
+            .filter(|(_, shared)| *shared >= options.min_shared_commits.max(1))
+            .map(|((file_a, file_b), shared)| {
+                let either = files[&file_a].commits + files[&file_b].commits - shared;
+                FileCoupling {
+                    degree: shared as f64 / either as f64,
+                    file_a,
+                    file_b,
+                    shared_commits: shared,
+                }
+            })
+            .collect();
+        coupling.sort_by(|a, b| {
+            b.degree.total_cmp(&a.degree)
+                .then_with(|| b.shared_commits.cmp(&a.shared_commits))
+                .then_with(|| (&a.file_a, &a.file_b).cmp(&(&b.file_a, &b.file_b)))
+        });
+        coupling.truncate(options.top_couplings);
+
+        let mut churn: Vec<FileChurn> = files.into_iter()
+            .map(|(path, totals)| FileChurn {
+                path,
+                commits: totals.commits,
+                lines_added: totals.added,
+                lines_removed: totals.removed,
+                authors: totals.authors.len(),
+            })
+            .collect();
+        churn.sort_by(|a, b| {
+            b.churn().cmp(&a.churn())
+                .then_with(|| b.commits.cmp(&a.commits))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        churn.truncate(options.top_files);
+
+        Ok(RepoAnalytics {
+            commits: count,
+            first_commit,
+            last_commit,
+            authors,
+            churn,
+            coupling,
+            heatmap,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::{write_file};
+    use crate::repository::Repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_analytics_authors_churn_and_coupling() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        for (i, content) in ["1\n", "1\n2\n"].iter().enumerate() {
+            write_file(dir.join("a.txt"), content);
+            write_file(dir.join("b.txt"), content);
+            repo.stage_all().unwrap();
+            repo.commit(&format!("pair {}", i), "Alice", "alice@example.com").unwrap();
+        }
+        write_file(dir.join("c.txt"), "x\n");
+        repo.stage_all().unwrap();
+        repo.commit("single", "Bob", "bob@example.com").unwrap();
+
+        let stats = repo.analytics(&AnalyticsOptions::default()).unwrap();
+        assert_eq!(stats.commits, 3);
+        assert_eq!(stats.heatmap.iter().flatten().sum::<usize>(), 3);
+
+        assert_eq!(stats.authors[0].name, "Alice");
+        assert_eq!(stats.authors[0].commits, 2);
+        assert_eq!((stats.authors[0].lines_added, stats.authors[0].lines_removed), (4, 0));
+        assert_eq!(stats.authors[0].activity.iter().map(|p| p.commits).sum::<usize>(), 2);
+        assert_eq!(stats.authors[1].lines_added, 1);
+
+        assert_eq!(stats.churn[0].path, "a.txt");
+        assert_eq!((stats.churn[0].commits, stats.churn[0].authors), (2, 1));
+
+        assert_eq!(stats.coupling.len(), 1);
+        let pair = &stats.coupling[0];
+        assert_eq!((pair.file_a.as_str(), pair.file_b.as_str(), pair.shared_commits), ("a.txt", "b.txt", 2));
+        assert_eq!(pair.degree, 1.0);
+
+        let limited = repo.analytics(&AnalyticsOptions { max_commits: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(limited.commits, 1);
+        assert_eq!(limited.authors[0].name, "Bob");
+        assert!(limited.to_json().unwrap().contains("\"heatmap\""));
+    }
+}
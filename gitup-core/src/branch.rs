@@ -0,0 +1,231 @@
+use anyhow::Result;
+use git2::{Branch, BranchType, Repository as Git2Repository};
+use crate::commit_graph::CommitLookup;
+use crate::repository::BranchInfo;
+
+/// Branch operations for a repository
+pub struct BranchOps<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> BranchOps<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        BranchOps { repo }
+    }
+
+    /// List local and remote branches with tracking information
+    pub fn list(&self) -> Result<Vec<BranchInfo>> {
+        let mut branches = Vec::new();
+        let head_name = self.repo.head().ok()
+            .and_then(|head| head.shorthand().map(|s| s.to_string()))
+            .unwrap_or_default();
+
+        // Local branches
+        for branch in self.repo.branches(Some(BranchType::Local))? {
+            let (branch, _) = branch?;
+            let name = branch.name()?.unwrap_or("").to_string();
+            let is_head = name == head_name;
+            branches.push(self.branch_info(&branch, name, is_head, false)?);
+        }
+
+        // Remote branches
+        for branch in self.repo.branches(Some(BranchType::Remote))? {
+            let (branch, _) = branch?;
+            let name = branch.name()?.unwrap_or("").to_string();
+            branches.push(self.branch_info(&branch, name, false, true)?);
+        }
+
+        Ok(branches)
+    }
+
+    /// Build branch info, including upstream divergence for local branches
+    fn branch_info(&self, branch: &Branch, name: String, is_head: bool, is_remote: bool) -> Result<BranchInfo> {
+        let commit = branch.get().peel_to_commit()?;
+
+        let mut info = BranchInfo {
+            name,
+            commit_id: commit.id().to_string(),
+            is_head,
+            is_remote,
+            upstream: None,
+            ahead: 0,
+            behind: 0,
+            last_commit_timestamp: commit.time().seconds(),
+            upstream_gone: false,
+        };
+
+        if is_remote {
+            return Ok(info);
+        }
+
+        let refname = branch.get().name().unwrap_or("");
+        if let Ok(upstream_ref) = self.repo.branch_upstream_name(refname) {
+            let upstream_ref = upstream_ref.as_str().unwrap_or("").to_string();
+            info.upstream = Some(
+                upstream_ref
+                    .strip_prefix("refs/remotes/")
+                    .or_else(|| upstream_ref.strip_prefix("refs/heads/"))
+                    .unwrap_or(&upstream_ref)
+                    .to_string(),
+            );
+
+            // Configured upstream whose ref no longer exists (deleted on the remote and pruned)
+            match self.repo.find_reference(&upstream_ref).and_then(|r| r.peel_to_commit()) {
+                Ok(upstream_commit) => {
+                    let lookup = CommitLookup::new(self.repo);
+                    let (ahead, behind) = lookup.ahead_behind(commit.id(), upstream_commit.id())?;
+                    info.ahead = ahead;
+                    info.behind = behind;
+                }
+                Err(_) => info.upstream_gone = true,
+            }
+        }
+
+        Ok(info)
+    }
+
+    /// Delete a local branch
+    ///
+    /// Without `force` the branch must be fully merged into its upstream,
+    /// or into HEAD when it has no upstream.
+    pub fn delete(&self, name: &str, force: bool) -> Result<String> {
+        let mut branch = self.repo.find_branch(name, BranchType::Local)?;
+
+        if branch.is_head() {
+            return Err(anyhow::anyhow!("Cannot delete the branch '{}' which is currently checked out", name));
+        }
+
+        let tip = branch.get().peel_to_commit()?.id();
+        if !force {
+            let (target, target_name) = match branch.upstream() {
+                Ok(upstream) => (
+                    upstream.get().peel_to_commit()?.id(),
+                    upstream.name()?.unwrap_or("upstream").to_string(),
+                ),
+                Err(_) => (self.repo.head()?.peel_to_commit()?.id(), "HEAD".to_string()),
+            };
+
+            let lookup = CommitLookup::new(self.repo);
+            if tip != target && !lookup.is_descendant_of(target, tip)? {
+                let (unmerged, _) = lookup.ahead_behind(tip, target)?;
+                return Err(anyhow::anyhow!(
+                    "The branch '{}' is not fully merged into {} ({} unmerged commits); use force to delete it",
+                    name,
+                    target_name,
+                    unmerged
+                ));
+            }
+        }
+
+        branch.delete()?;
+        Ok(format!("Deleted branch {} (was {})", name, &tip.to_string()[..8]))
+    }
+
+    /// Rename a local branch
+    pub fn rename(&self, old_name: &str, new_name: &str, force: bool) -> Result<String> {
+        let mut branch = self.repo.find_branch(old_name, BranchType::Local)?;
+        branch.rename(new_name, force)?;
+        Ok(format!("Renamed branch '{}' to '{}'", old_name, new_name))
+    }
+
+    /// Set (or with `None`, unset) the upstream of a local branch, e.g. "origin/main"
+    pub fn set_upstream(&self, name: &str, upstream: Option<&str>) -> Result<String> {
+        let mut branch = self.repo.find_branch(name, BranchType::Local)?;
+        branch.set_upstream(upstream)?;
+
+        match upstream {
+            Some(upstream) => Ok(format!("Branch '{}' set up to track '{}'", name, upstream)),
+            None => Ok(format!("Removed upstream of branch '{}'", name)),
+        }
+    }
+
+    /// Check out a remote branch (e.g. "origin/feature") by creating a local tracking branch
+    ///
+    /// Returns the name of the local branch.
+    pub fn checkout_remote(&self, remote_branch: &str, local_name: Option<&str>) -> Result<String> {
+        let remote = self.repo.find_branch(remote_branch, BranchType::Remote)?;
+        let remote_ref = remote.get().name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid remote branch name"))?
+            .to_string();
+
+        // Default local name: the remote branch name without the remote prefix
+        let remote_name = self.repo.branch_remote_name(&remote_ref)?;
+        let remote_name = remote_name.as_str().unwrap_or("");
+        let local_name = match local_name {
+            Some(name) => name.to_string(),
+            None => remote_branch
+                .strip_prefix(&format!("{}/", remote_name))
+                .unwrap_or(remote_branch)
+                .to_string(),
+        };
+
+        if self.repo.find_branch(&local_name, BranchType::Local).is_ok() {
+            return Err(anyhow::anyhow!("A branch named '{}' already exists", local_name));
+        }
+
+        if !Branch::name_is_valid(&local_name)? {
+            return Err(anyhow::anyhow!("'{}' is not a valid branch name", local_name));
+        }
+
+        // Check out first, so a checkout blocked by local changes leaves no branch behind
+        let commit = remote.get().peel_to_commit()?;
+        self.repo.checkout_tree(commit.as_object(), None)?;
+        let mut local = self.repo.branch(&local_name, &commit, false)?;
+        local.set_upstream(Some(remote_branch))?;
+
+        let local_ref = local.get().name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid branch name"))?
+            .to_string();
+        self.repo.set_head(&local_ref)?;
+
+        Ok(local_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::Commit;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkout_remote_blocked_by_local_changes_leaves_no_branch() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Git2Repository::init(dir).unwrap();
+        fs::write(dir.join("t.txt"), "1\n").unwrap();
+        let base = {
+            let commit = Commit::new(&repo);
+            commit.stage_all().unwrap();
+            commit.create("c1", "Tester", "tester@example.com").unwrap()
+        };
+
+        // origin/feature changes t.txt
+        let base = repo.find_commit(git2::Oid::from_str(&base).unwrap()).unwrap();
+        let mut index = repo.index().unwrap();
+        let blob = repo.blob(b"2\n").unwrap();
+        let mut entry = index.get_path(std::path::Path::new("t.txt"), 0).unwrap();
+        entry.id = blob;
+        index.add(&entry).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("Tester", "tester@example.com").unwrap();
+        let feature = repo.commit(None, &sig, &sig, "c2", &tree, &[&base]).unwrap();
+        index.read(true).unwrap();
+        repo.remote("origin", "file:///nonexistent").unwrap();
+        repo.reference("refs/remotes/origin/feature", feature, false, "").unwrap();
+
+        let ops = BranchOps::new(&repo);
+        fs::write(dir.join("t.txt"), "local edit\n").unwrap();
+        assert!(ops.checkout_remote("origin/feature", None).is_err());
+        assert!(repo.find_branch("feature", BranchType::Local).is_err());
+
+        // Once the change is gone the retry succeeds
+        fs::write(dir.join("t.txt"), "1\n").unwrap();
+        assert_eq!(ops.checkout_remote("origin/feature", None).unwrap(), "feature");
+        let local = repo.find_branch("feature", BranchType::Local).unwrap();
+        assert_eq!(local.upstream().unwrap().name().unwrap(), Some("origin/feature"));
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+        assert_eq!(fs::read_to_string(dir.join("t.txt")).unwrap(), "2\n");
+    }
+}
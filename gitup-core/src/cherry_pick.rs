@@ -1,9 +1,12 @@
 use anyhow::Result;
 use git2::{
     Repository as Git2Repository, Oid, build::CheckoutBuilder,
-    CherrypickOptions, Index,
+    Index, ObjectType, Sort, Status, StatusOptions, TreeWalkMode, TreeWalkResult,
 };
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::message::{parse_trailers, sign_off};
 
 /// Cherry-pick result information
 #[derive(Debug, Clone)]
@@ -12,6 +15,45 @@ pub struct CherryPickResult {
     pub message: String,
     pub picked_commit: Option<String>,
     pub conflicts: Vec<String>,
+    /// The commit was left out because its patch is already applied
+    pub skipped: bool,
+}
+
+/// Options shared by cherry-pick and revert
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PickOptions {
+    /// Parent (1-based) whose changes a merge commit is compared against
+    pub mainline: Option<u32>,
+    /// Append "(cherry picked from commit ...)" to the message (`-x`)
+    pub record_origin: bool,
+    /// Stage the changes without committing them
+    pub no_commit: bool,
+    /// Add a `Signed-off-by` trailer for the current user
+    pub signoff: bool,
+}
+
+/// What a sequence of commits is being applied for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PickAction {
+    Pick,
+    Revert,
+}
+
+impl PickAction {
+    fn name(&self) -> &'static str {
+        match self {
+            PickAction::Pick => "Cherry-pick",
+            PickAction::Revert => "Revert",
+        }
+    }
+}
+
+/// Commits still to apply after a conflict, kept in `.git/gitup/sequencer.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sequence {
+    action: PickAction,
+    options: PickOptions,
+    remaining: Vec<String>,
 }
 
 /// Cherry-pick operations for a repository
@@ -25,86 +67,259 @@ impl CherryPickOps {
         Ok(CherryPickOps { repo })
     }
 
+    /// Use an already opened repository
+    pub fn from_repo(repo: Git2Repository) -> Self {
+        CherryPickOps { repo }
+    }
+
     /// Cherry-pick a single commit
     pub fn pick_commit(&self, commit_ref: &str) -> Result<CherryPickResult> {
-        // Find the commit to cherry-pick
+        self.pick_commit_with(commit_ref, &PickOptions::default())
+    }
+
+    /// Cherry-pick a single commit with options
+    pub fn pick_commit_with(&self, commit_ref: &str, options: &PickOptions) -> Result<CherryPickResult> {
         let commit = self.find_commit_from_ref(commit_ref)?;
-        let commit_id = commit.id();
+        let mut results = self.run_sequence(PickAction::Pick, vec![commit.id()], options)?;
+        Ok(results.remove(0))
+    }
+
+    /// Cherry-pick multiple commits
+    pub fn pick_range(&self, start_ref: &str, end_ref: &str) -> Result<Vec<CherryPickResult>> {
+        self.pick_range_with(start_ref, end_ref, &PickOptions::default())
+    }
+
+    /// Cherry-pick the commits in `start..end`, oldest first
+    ///
+    /// Commits whose patch is already in HEAD's history since it forked
+    /// from `end` are skipped. Stops at the first conflict; the rest is
+    /// picked by [`CherryPickOps::continue_sequence`].
+    pub fn pick_range_with(&self, start_ref: &str, end_ref: &str, options: &PickOptions) -> Result<Vec<CherryPickResult>> {
+        let start_commit = self.find_commit_from_ref(start_ref)?;
+        let end_commit = self.find_commit_from_ref(end_ref)?;
+
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        revwalk.push(end_commit.id())?;
+        revwalk.hide(start_commit.id())?;
+        let commits = revwalk.collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let applied = self.upstream_patch_ids(end_commit.id())?;
+        let mut results = Vec::new();
+        let mut todo = Vec::new();
+        for oid in commits {
+            let commit = self.repo.find_commit(oid)?;
+            match self.patch_id(&commit)? {
+                Some(id) if applied.contains(&id) => results.push(CherryPickResult {
+                    success: true,
+                    message: format!("Skipped commit {}, already applied", &oid.to_string()[..8]),
+                    picked_commit: None,
+                    conflicts: vec![],
+                    skipped: true,
+                }),
+                _ => todo.push(oid),
+            }
+        }
+
+        if !todo.is_empty() {
+            results.extend(self.run_sequence(PickAction::Pick, todo, options)?);
+        }
+        Ok(results)
+    }
+
+    /// Revert commits one after the other, newest first as given
+    ///
+    /// Stops at the first conflict; the rest is reverted by
+    /// [`CherryPickOps::continue_sequence`].
+    pub fn revert_commits(&self, commit_refs: &[String], options: &PickOptions) -> Result<Vec<CherryPickResult>> {
+        let commits = commit_refs.iter()
+            .map(|r| Ok(self.find_commit_from_ref(r)?.id()))
+            .collect::<Result<Vec<_>>>()?;
+        self.run_sequence(PickAction::Revert, commits, options)
+    }
 
-        // Set up cherry-pick options
-        let mut opts = CherrypickOptions::new();
+    /// Apply commits in order, saving the rest of the sequence on conflict
+    fn run_sequence(&self, action: PickAction, commits: Vec<Oid>, options: &PickOptions) -> Result<Vec<CherryPickResult>> {
+        if self.repo.state() != git2::RepositoryState::Clean {
+            return Err(anyhow::anyhow!("Another operation is in progress ({:?})", self.repo.state()));
+        }
+        self.clear_sequence()?;
+
+        let mut results = Vec::new();
+        for (i, oid) in commits.iter().enumerate() {
+            let result = self.apply(action, *oid, options)?;
+            let conflict = !result.success;
+            results.push(result);
+            if conflict {
+                self.save_sequence(&Sequence {
+                    action,
+                    options: options.clone(),
+                    remaining: commits[i + 1..].iter().map(|id| id.to_string()).collect(),
+                })?;
+                break;
+            }
+        }
+        Ok(results)
+    }
 
-        // Perform the cherry-pick
-        self.repo.cherrypick(&commit, Some(&mut opts))?;
+    /// Cherry-pick or revert one commit, committing unless `no_commit`
+    fn apply(&self, action: PickAction, oid: Oid, options: &PickOptions) -> Result<CherryPickResult> {
+        let commit = self.repo.find_commit(oid)?;
+        let short = &oid.to_string()[..8];
+        let mainline = match (commit.parent_count(), options.mainline) {
+            (n, None) if n > 1 => {
+                return Err(anyhow::anyhow!("Commit {} is a merge but no mainline parent was given", short));
+            }
+            (n, Some(m)) if m == 0 || m as usize > n.max(1) => {
+                return Err(anyhow::anyhow!("Commit {} has no parent {}", short, m));
+            }
+            (n, m) if n > 1 => m.unwrap_or(0),
+            _ => 0,
+        };
+
+        // Apply the commit in memory on top of the index, which holds the
+        // changes staged by earlier no-commit steps: the same three-way merge
+        // `cherrypick_commit`/`revert_commit` do, with the index tree as ours
+        let head_tree = self.repo.head()?.peel_to_tree()?;
+        let index_tree = self.repo.find_tree(self.repo.index()?.write_tree()?)?;
+        if !options.no_commit && index_tree.id() != head_tree.id() {
+            return Err(anyhow::anyhow!("Your index has uncommitted changes; commit or stash them first"));
+        }
+        let parent_tree = match commit.parent_count() {
+            0 => self.repo.find_tree(self.repo.treebuilder(None)?.write()?)?,
+            _ => commit.parent(mainline.max(1) as usize - 1)?.tree()?,
+        };
+        let (ancestor, theirs) = match action {
+            PickAction::Pick => (parent_tree, commit.tree()?),
+            PickAction::Revert => (commit.tree()?, parent_tree),
+        };
+        let merged = self.repo.merge_trees(&ancestor, &index_tree, &theirs, None)?;
+        self.write_merge(action, &commit, &index_tree, &merged)?;
 
-        // Check for conflicts
         let index = self.repo.index()?;
         if index.has_conflicts() {
             let conflicts = self.get_conflicts(&index)?;
-            Ok(CherryPickResult {
+            return Ok(CherryPickResult {
                 success: false,
-                message: format!("Cherry-pick conflict with commit {}", &commit_id.to_string()[..8]),
+                message: format!("{} conflict with commit {}", action.name(), short),
                 picked_commit: None,
                 conflicts,
-            })
-        } else {
-            // Create the commit
-            let picked = self.complete_cherry_pick(&commit)?;
-            Ok(CherryPickResult {
+                skipped: false,
+            });
+        }
+
+        if options.no_commit {
+            return Ok(CherryPickResult {
                 success: true,
-                message: format!("Successfully cherry-picked commit {}", &commit_id.to_string()[..8]),
-                picked_commit: Some(picked),
+                message: format!("Staged {} of commit {}", action.name().to_lowercase(), short),
+                picked_commit: None,
                 conflicts: vec![],
-            })
+                skipped: false,
+            });
         }
-    }
-
-    /// Cherry-pick multiple commits
-    pub fn pick_range(&self, start_ref: &str, end_ref: &str) -> Result<Vec<CherryPickResult>> {
-        // Find the commit range
-        let start_commit = self.find_commit_from_ref(start_ref)?;
-        let end_commit = self.find_commit_from_ref(end_ref)?;
 
-        // Build list of commits to cherry-pick
-        let mut commits = Vec::new();
-        let mut revwalk = self.repo.revwalk()?;
-        revwalk.push(end_commit.id())?;
+        let created = self.complete(action, &commit, options)?;
+        self.repo.cleanup_state()?;
+        Ok(CherryPickResult {
+            success: true,
+            message: match action {
+                PickAction::Pick => format!("Successfully cherry-picked commit {}", short),
+                PickAction::Revert => format!("Reverted commit {}", short),
+            },
+            picked_commit: Some(created),
+            conflicts: vec![],
+            skipped: false,
+        })
+    }
 
-        for oid in revwalk {
-            let oid = oid?;
-            if oid == start_commit.id() {
-                break;
+    /// Write the result of applying `commit` to the index and working tree
+    ///
+    /// Only paths the step changes are written, and like git these must not
+    /// have unstaged changes. On conflict the markers are written and the
+    /// repository is put in the cherry-pick or revert state.
+    fn write_merge(&self, action: PickAction, commit: &git2::Commit, base: &git2::Tree, merged: &Index) -> Result<()> {
+        let mut before = HashMap::new();
+        base.walk(TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() != Some(ObjectType::Tree) {
+                before.insert(format!("{}{}", root, entry.name().unwrap_or("")), (entry.id(), entry.filemode() as u32));
+            }
+            TreeWalkResult::Ok
+        })?;
+
+        let mut changed = HashSet::new();
+        let mut after = HashSet::new();
+        for entry in merged.iter() {
+            let path = String::from_utf8_lossy(&entry.path).into_owned();
+            let stage = (entry.flags >> 12) & 0x3;
+            if stage != 0 || before.get(&path) != Some(&(entry.id, entry.mode)) {
+                changed.insert(path.clone());
             }
-            commits.push(oid);
+            after.insert(path);
+        }
+        changed.extend(before.keys().filter(|path| !after.contains(*path)).cloned());
+        if changed.is_empty() {
+            return Ok(());
         }
 
-        // Reverse to apply in chronological order
-        commits.reverse();
-
-        // Cherry-pick each commit
-        let mut results = Vec::new();
-        for oid in commits {
-            let commit_ref = oid.to_string();
-            let result = self.pick_commit(&commit_ref)?;
+        let mut status_opts = StatusOptions::new();
+        status_opts.include_untracked(true).recurse_untracked_dirs(true);
+        let dirty: Vec<String> = self.repo.statuses(Some(&mut status_opts))?.iter()
+            .filter(|entry| entry.status().intersects(
+                Status::WT_NEW | Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_TYPECHANGE | Status::WT_RENAMED
+            ))
+            .filter_map(|entry| entry.path().map(str::to_string))
+            .filter(|path| changed.contains(path))
+            .collect();
+        if !dirty.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Your local changes to {} would be overwritten by {}",
+                dirty.join(", "),
+                action.name().to_lowercase()
+            ));
+        }
 
-            if !result.success {
-                // Stop on first conflict
-                results.push(result);
-                break;
-            }
+        let mut index = self.repo.index()?;
+        index.clear()?;
+        for entry in merged.iter() {
+            index.add(&entry)?;
+        }
+        index.write()?;
 
-            results.push(result);
+        let short = &commit.id().to_string()[..8];
+        let their_label = match action {
+            PickAction::Pick => format!("{}... {}", short, commit.summary().unwrap_or("")),
+            PickAction::Revert => format!("parent of {}... {}", short, commit.summary().unwrap_or("")),
+        };
+        let mut checkout = CheckoutBuilder::new();
+        checkout.force()
+            .remove_untracked(true)
+            .allow_conflicts(true)
+            .conflict_style_merge(true)
+            .our_label("HEAD")
+            .their_label(&their_label);
+        for path in &changed {
+            checkout.path(path);
         }
+        self.repo.checkout_index(Some(&mut index), Some(&mut checkout))?;
 
-        Ok(results)
+        if index.has_conflicts() {
+            let head_file = match action {
+                PickAction::Pick => "CHERRY_PICK_HEAD",
+                PickAction::Revert => "REVERT_HEAD",
+            };
+            std::fs::write(self.repo.path().join(head_file), format!("{}\n", commit.id()))?;
+            std::fs::write(self.repo.path().join("MERGE_MSG"), commit.message().unwrap_or(""))?;
+        }
+        Ok(())
     }
 
-    /// Continue a cherry-pick after resolving conflicts
+    /// Continue a cherry-pick or revert after resolving conflicts
     pub fn continue_pick(&self) -> Result<CherryPickResult> {
-        // Check repository state
-        if self.repo.state() != git2::RepositoryState::CherryPick {
-            return Err(anyhow::anyhow!("No cherry-pick in progress"));
-        }
+        let action = match self.repo.state() {
+            git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => PickAction::Pick,
+            git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => PickAction::Revert,
+            _ => return Err(anyhow::anyhow!("No cherry-pick or revert in progress")),
+        };
 
         // Check for remaining conflicts
         let index = self.repo.index()?;
@@ -115,41 +330,71 @@ impl CherryPickOps {
                 message: "Conflicts must be resolved before continuing".to_string(),
                 picked_commit: None,
                 conflicts,
+                skipped: false,
             });
         }
 
-        // Read CHERRY_PICK_HEAD to get the commit being picked
-        let cherry_pick_head_path = self.repo.path().join("CHERRY_PICK_HEAD");
-        let cherry_pick_oid = if cherry_pick_head_path.exists() {
-            let content = std::fs::read_to_string(&cherry_pick_head_path)?;
+        // Read CHERRY_PICK_HEAD or REVERT_HEAD to get the commit being applied
+        let head_file = match action {
+            PickAction::Pick => "CHERRY_PICK_HEAD",
+            PickAction::Revert => "REVERT_HEAD",
+        };
+        let head_path = self.repo.path().join(head_file);
+        let oid = if head_path.exists() {
+            let content = std::fs::read_to_string(&head_path)?;
             Oid::from_str(content.trim())?
         } else {
-            return Err(anyhow::anyhow!("Cannot find CHERRY_PICK_HEAD"));
+            return Err(anyhow::anyhow!("Cannot find {}", head_file));
         };
 
-        let commit = self.repo.find_commit(cherry_pick_oid)?;
+        let commit = self.repo.find_commit(oid)?;
+        let options = self.load_sequence()?.map(|s| s.options).unwrap_or_default();
 
-        // Complete the cherry-pick
-        let picked = self.complete_cherry_pick(&commit)?;
+        let picked = if options.no_commit {
+            None
+        } else {
+            Some(self.complete(action, &commit, &options)?)
+        };
 
         // Clean up state
         self.repo.cleanup_state()?;
 
         Ok(CherryPickResult {
             success: true,
-            message: format!("Cherry-pick completed for commit {}",
-                &cherry_pick_oid.to_string()[..8]),
-            picked_commit: Some(picked),
+            message: format!("{} completed for commit {}", action.name(), &oid.to_string()[..8]),
+            picked_commit: picked,
             conflicts: vec![],
+            skipped: false,
         })
     }
 
-    /// Abort a cherry-pick in progress
-    pub fn abort_pick(&self) -> Result<String> {
-        // Check repository state
-        if self.repo.state() != git2::RepositoryState::CherryPick {
-            return Err(anyhow::anyhow!("No cherry-pick in progress"));
+    /// Continue after resolving conflicts, then apply the rest of the sequence
+    pub fn continue_sequence(&self) -> Result<Vec<CherryPickResult>> {
+        let current = self.continue_pick()?;
+        if !current.success {
+            return Ok(vec![current]);
+        }
+
+        let mut results = vec![current];
+        if let Some(sequence) = self.load_sequence()? {
+            self.clear_sequence()?;
+            let remaining = sequence.remaining.iter()
+                .map(|id| Ok(Oid::from_str(id)?))
+                .collect::<Result<Vec<_>>>()?;
+            if !remaining.is_empty() {
+                results.extend(self.run_sequence(sequence.action, remaining, &sequence.options)?);
+            }
         }
+        Ok(results)
+    }
+
+    /// Abort a cherry-pick or revert in progress, including the rest of its sequence
+    pub fn abort_pick(&self) -> Result<String> {
+        let action = match self.repo.state() {
+            git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => PickAction::Pick,
+            git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => PickAction::Revert,
+            _ => return Err(anyhow::anyhow!("No cherry-pick or revert in progress")),
+        };
 
         // Reset to HEAD
         let head = self.repo.head()?.peel_to_commit()?;
@@ -161,27 +406,34 @@ impl CherryPickOps {
 
         // Clean up state
         self.repo.cleanup_state()?;
+        self.clear_sequence()?;
 
-        Ok("Cherry-pick aborted".to_string())
+        Ok(format!("{} aborted", action.name()))
     }
 
     /// Get cherry-pick status
     pub fn pick_status(&self) -> Result<String> {
-        if self.repo.state() == git2::RepositoryState::CherryPick {
-            let index = self.repo.index()?;
-            if index.has_conflicts() {
-                let conflicts = self.get_conflicts(&index)?;
-                Ok(format!("Cherry-picking with {} conflicts", conflicts.len()))
-            } else {
-                Ok("Cherry-pick in progress (no conflicts)".to_string())
-            }
+        let action = match self.repo.state() {
+            git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => PickAction::Pick,
+            git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => PickAction::Revert,
+            _ => return Ok("No cherry-pick or revert in progress".to_string()),
+        };
+        let remaining = self.load_sequence()?.map(|s| s.remaining.len()).unwrap_or(0);
+        let index = self.repo.index()?;
+        let mut status = if index.has_conflicts() {
+            let conflicts = self.get_conflicts(&index)?;
+            format!("{} in progress with {} conflicts", action.name(), conflicts.len())
         } else {
-            Ok("No cherry-pick in progress".to_string())
+            format!("{} in progress (no conflicts)", action.name())
+        };
+        if remaining > 0 {
+            status.push_str(&format!(", {} more commits to go", remaining));
         }
+        Ok(status)
     }
 
-    /// Complete a cherry-pick by creating the commit
-    fn complete_cherry_pick(&self, original_commit: &git2::Commit) -> Result<String> {
+    /// Create the commit for a cherry-pick or revert from the index
+    fn complete(&self, action: PickAction, original_commit: &git2::Commit, options: &PickOptions) -> Result<String> {
         // Get the tree from the index
         let mut index = self.repo.index()?;
         let tree_oid = index.write_tree()?;
@@ -189,21 +441,53 @@ impl CherryPickOps {
 
         // Get HEAD as parent
         let head = self.repo.head()?.peel_to_commit()?;
+        let sig = self.get_signature()?;
 
         // Create commit message
-        let message = format!(
-            "{}\n\n(cherry picked from commit {})",
-            original_commit.message().unwrap_or(""),
-            original_commit.id()
-        );
+        let mut message = match action {
+            PickAction::Pick => original_commit.message().unwrap_or("").to_string(),
+            PickAction::Revert => {
+                let mut message = format!(
+                    "Revert \"{}\"\n\nThis reverts commit {}",
+                    original_commit.summary().unwrap_or(""),
+                    original_commit.id()
+                );
+                match options.mainline.filter(|_| original_commit.parent_count() > 1) {
+                    Some(mainline) => message.push_str(&format!(
+                        ", reversing\nchanges made to {}.",
+                        original_commit.parent_id(mainline as usize - 1)?
+                    )),
+                    None => message.push('.'),
+                }
+                message
+            }
+        };
+        // Like git, the origin line comes first and joins a trailer block
+        // rather than starting a new paragraph; the sign-off follows it
+        let record_origin = options.record_origin && action == PickAction::Pick;
+        if record_origin {
+            let separator = if parse_trailers(&message).is_empty() { "\n\n" } else { "\n" };
+            message = format!("{}{}(cherry picked from commit {})\n", message.trim_end(), separator, original_commit.id());
+        }
+        if options.signoff {
+            let (name, email) = (sig.name().unwrap_or(""), sig.email().unwrap_or(""));
+            message = if record_origin {
+                format!("{}Signed-off-by: {} <{}>\n", message, name, email)
+            } else {
+                sign_off(&message, name, email)
+            };
+        }
 
-        // Get signature
-        let sig = self.get_signature()?;
+        // A cherry-pick keeps the original author
+        let author = match action {
+            PickAction::Pick => original_commit.author().to_owned(),
+            PickAction::Revert => sig.clone(),
+        };
 
         // Create the commit
         let commit_oid = self.repo.commit(
             Some("HEAD"),
-            &sig,
+            &author,
             &sig,
             &message,
             &tree,
@@ -213,6 +497,65 @@ impl CherryPickOps {
         Ok(commit_oid.to_string())
     }
 
+    /// Patch ID of a commit's changes against its parent; `None` for merges
+    fn patch_id(&self, commit: &git2::Commit) -> Result<Option<Oid>> {
+        if commit.parent_count() > 1 {
+            return Ok(None);
+        }
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = self.repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        Ok(Some(diff.patchid(None)?))
+    }
+
+    /// Patch IDs of the commits on HEAD since it forked from `other`
+    fn upstream_patch_ids(&self, other: Oid) -> Result<HashSet<Oid>> {
+        let head = self.repo.head()?.peel_to_commit()?.id();
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push(head)?;
+        if let Ok(base) = self.repo.merge_base(head, other) {
+            revwalk.hide(base)?;
+        }
+
+        let mut ids = HashSet::new();
+        for oid in revwalk {
+            if let Some(id) = self.patch_id(&self.repo.find_commit(oid?)?)? {
+                ids.insert(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn sequence_path(&self) -> PathBuf {
+        self.repo.path().join("gitup").join("sequencer.json")
+    }
+
+    fn save_sequence(&self, sequence: &Sequence) -> Result<()> {
+        let path = self.sequence_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(sequence)?)?;
+        Ok(())
+    }
+
+    fn load_sequence(&self) -> Result<Option<Sequence>> {
+        match std::fs::read_to_string(self.sequence_path()) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn clear_sequence(&self) -> Result<()> {
+        match std::fs::remove_file(self.sequence_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
     /// Find commit from reference string
     fn find_commit_from_ref(&self, reference: &str) -> Result<git2::Commit> {
         // Try as commit SHA
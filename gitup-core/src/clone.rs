@@ -0,0 +1,433 @@
+use anyhow::Result;
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    Direction, FetchOptions, Remote, RemoteCallbacks, Repository as Git2Repository,
+    SubmoduleUpdateOptions,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::{hash_map::Entry, BTreeSet, HashMap, VecDeque};
+use std::path::Path;
+use crate::remote::{credentials, TransferProgress};
+
+/// Options for cloning a repository
+#[derive(Debug, Clone, Default)]
+pub struct CloneOptions {
+    /// Branch or tag to check out instead of the remote's HEAD (`--branch`)
+    pub branch: Option<String>,
+    /// Truncate history to this many commits (`--depth`)
+    pub depth: Option<u32>,
+    /// Create a bare repository (`--bare`)
+    pub bare: bool,
+    /// Bare clone that maps all remote refs one to one (`--mirror`)
+    pub mirror: bool,
+    /// Clone and check out submodules, recursively (`--recurse-submodules`)
+    pub recurse_submodules: bool,
+}
+
+/// Progress reported while cloning
+#[derive(Debug, Clone)]
+pub enum CloneProgress {
+    /// Objects being received, for the repository or the current submodule
+    Transfer(TransferProgress),
+    /// Files being written to the working tree
+    Checkout {
+        path: Option<String>,
+        completed: usize,
+        total: usize,
+    },
+    /// A submodule is about to be cloned; `path` is relative to the top-level working tree
+    Submodule { path: String },
+}
+
+/// Progress callback for clones; returning `false` cancels the clone
+pub type CloneProgressCallback<'a> = Box<dyn FnMut(CloneProgress) -> bool + 'a>;
+
+/// Clone `url` into `path`
+///
+/// Like `git clone`, a directory created for the clone is removed again when the
+/// clone fails or is cancelled.
+pub fn clone_repository(
+    url: &str,
+    path: &Path,
+    options: &CloneOptions,
+    progress_callback: Option<CloneProgressCallback<'_>>,
+) -> Result<Git2Repository> {
+    if path.exists() && path.read_dir()?.next().is_some() {
+        return Err(anyhow::anyhow!(
+            "Destination path '{}' already exists and is not an empty directory",
+            path.display()
+        ));
+    }
+    let created = !path.exists();
+
+    let progress = Progress {
+        callback: RefCell::new(progress_callback),
+        cancelled: Cell::new(false),
+    };
+
+    let result = clone_with(url, path, options, &progress).and_then(|repo| {
+        if options.recurse_submodules && !repo.is_bare() {
+            update_submodules(&repo, "", &progress)?;
+        }
+        Ok(repo)
+    });
+
+    match result {
+        Ok(repo) => Ok(repo),
+        Err(e) => {
+            if created {
+                let _ = std::fs::remove_dir_all(path);
+            }
+            if progress.cancelled.get() {
+                Err(anyhow::anyhow!("Clone of '{}' cancelled", url))
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Shared progress reporting for the clone and its submodules
+struct Progress<'a> {
+    callback: RefCell<Option<CloneProgressCallback<'a>>>,
+    cancelled: Cell<bool>,
+}
+
+impl Progress<'_> {
+    /// Report progress; returns `false` once the clone has been cancelled
+    fn report(&self, progress: CloneProgress) -> bool {
+        if self.cancelled.get() {
+            return false;
+        }
+        if let Some(callback) = self.callback.borrow_mut().as_mut() {
+            if !callback(progress) {
+                self.cancelled.set(true);
+            }
+        }
+        !self.cancelled.get()
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        if self.cancelled.get() {
+            Err(anyhow::anyhow!("Clone cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn fetch_options(&self, depth: Option<u32>) -> FetchOptions<'_> {
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.transfer_progress(|progress| self.report(CloneProgress::Transfer(progress.into())));
+        callbacks.credentials(credentials);
+
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(callbacks);
+        if let Some(depth) = depth {
+            fetch_options.depth(depth.min(i32::MAX as u32) as i32);
+        }
+        fetch_options
+    }
+
+    /// Checkout reporting file progress. Checkout itself cannot be interrupted,
+    /// a cancellation takes effect once it finishes.
+    fn checkout(&self) -> CheckoutBuilder<'_> {
+        let mut checkout = CheckoutBuilder::new();
+        checkout.progress(|path, completed, total| {
+            self.report(CloneProgress::Checkout {
+                path: path.map(|p| p.to_string_lossy().into_owned()),
+                completed,
+                total,
+            });
+        });
+        checkout
+    }
+}
+
+fn clone_with(url: &str, path: &Path, options: &CloneOptions, progress: &Progress) -> Result<Git2Repository> {
+    let bare = options.bare || options.mirror;
+
+    // `--branch` may name a tag, which libgit2 cannot check out while cloning
+    let tag = match &options.branch {
+        Some(name) => Some(resolve_branch_or_tag(url, name)?).filter(|is_tag| *is_tag).map(|_| name.clone()),
+        None => None,
+    };
+
+    // The local transport cannot negotiate a shallow fetch, its history is cut afterwards
+    let local = is_local_url(url);
+    let fetch_depth = options.depth.filter(|_| !local);
+
+    let mut builder = RepoBuilder::new();
+    builder.bare(bare);
+    builder.fetch_options(progress.fetch_options(fetch_depth));
+
+    if let (Some(branch), None) = (&options.branch, &tag) {
+        builder.branch(branch);
+    }
+
+    if options.mirror {
+        builder.remote_create(|repo, name, url| {
+            let remote = repo.remote_with_fetch(name, url, "+refs/*:refs/*")?;
+            repo.config()?.set_bool(&format!("remote.{}.mirror", name), true)?;
+            Ok(remote)
+        });
+    }
+
+    if !bare {
+        let mut checkout = progress.checkout();
+        if tag.is_some() {
+            // The tag is checked out below, after HEAD has been detached
+            checkout.dry_run();
+        }
+        builder.with_checkout(checkout);
+    }
+
+    let mut repo = builder.clone(url, path)?;
+    progress.check_cancelled()?;
+
+    if let (Some(depth), true) = (options.depth, local) {
+        write_shallow(&repo, depth)?;
+        repo = Git2Repository::open(repo.path())?;
+    }
+
+    if let Some(tag) = tag {
+        let commit = repo.find_reference(&format!("refs/tags/{}", tag))?.peel_to_commit()?;
+        repo.set_head_detached(commit.id())?;
+        if !bare {
+            repo.checkout_head(Some(progress.checkout().force()))?;
+            progress.check_cancelled()?;
+        }
+    }
+
+    Ok(repo)
+}
+
+/// Make `repo` shallow at `depth` commits from its refs, as a shallow fetch would
+///
+/// The objects past the boundary stay in the copied pack; git drops them on the next gc.
+fn write_shallow(repo: &Git2Repository, depth: u32) -> Result<()> {
+    let mut distance = HashMap::new();
+    let mut queue = VecDeque::new();
+    for reference in repo.references()? {
+        if let Ok(commit) = reference?.peel_to_commit() {
+            if distance.insert(commit.id(), 1).is_none() {
+                queue.push_back(commit);
+            }
+        }
+    }
+
+    // Breadth first, so each commit is reached at its shortest distance from a tip
+    let mut boundary = BTreeSet::new();
+    while let Some(commit) = queue.pop_front() {
+        let d = distance[&commit.id()];
+        if d >= depth.max(1) {
+            if commit.parent_count() > 0 {
+                boundary.insert(commit.id());
+            }
+            continue;
+        }
+        for parent in commit.parents() {
+            if let Entry::Vacant(entry) = distance.entry(parent.id()) {
+                entry.insert(d + 1);
+                queue.push_back(parent);
+            }
+        }
+    }
+
+    if !boundary.is_empty() {
+        let contents: String = boundary.iter().map(|oid| format!("{}\n", oid)).collect();
+        std::fs::write(repo.path().join("shallow"), contents)?;
+    }
+    Ok(())
+}
+
+/// Whether libgit2 uses its local transport for `url`
+pub fn is_local_url(url: &str) -> bool {
+    url.starts_with("file://") || (!url.contains("://") && Path::new(url).exists())
+}
+
+/// Whether `name` is a tag rather than a branch on the remote at `url`
+fn resolve_branch_or_tag(url: &str, name: &str) -> Result<bool> {
+    let mut remote = Remote::create_detached(url)?;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(credentials);
+    let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+
+    let refs: Vec<String> = connection.list()?.iter().map(|head| head.name().to_string()).collect();
+    if refs.iter().any(|r| *r == format!("refs/heads/{}", name)) {
+        Ok(false)
+    } else if refs.iter().any(|r| *r == format!("refs/tags/{}", name)) {
+        Ok(true)
+    } else {
+        Err(anyhow::anyhow!("Remote branch {} not found in upstream {}", name, url))
+    }
+}
+
+/// Initialize, clone and check out all submodules of `repo`, recursively
+fn update_submodules(repo: &Git2Repository, prefix: &str, progress: &Progress) -> Result<()> {
+    for mut submodule in repo.submodules()? {
+        let path = format!("{}{}", prefix, submodule.path().display());
+        if !progress.report(CloneProgress::Submodule { path: path.clone() }) {
+            return progress.check_cancelled();
+        }
+
+        let mut update_options = SubmoduleUpdateOptions::new();
+        update_options.fetch(progress.fetch_options(None));
+        update_options.checkout(progress.checkout());
+        submodule.update(true, Some(&mut update_options))
+            .map_err(|e| anyhow::anyhow!("Failed to clone submodule '{}': {}", path, e.message()))?;
+        progress.check_cancelled()?;
+
+        let sub_repo = submodule.open()?;
+        update_submodules(&sub_repo, &format!("{}/", path), progress)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use tempfile::TempDir;
+
+    /// Source repository with two commits on main, a "feature" branch and a "v1" tag
+    fn source_repo() -> (TempDir, String) {
+        let dir = TempDir::new().unwrap();
+        let repo = Git2Repository::init(dir.path()).unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+
+        let mut parent = None;
+        for (i, name) in ["a.txt", "b.txt"].iter().enumerate() {
+            std::fs::write(dir.path().join(name), format!("{}\n", i)).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(name)).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<_> = parent.iter().collect();
+            let oid = repo.commit(Some("HEAD"), &sig, &sig, name, &tree, &parents).unwrap();
+            parent = Some(repo.find_commit(oid).unwrap());
+        }
+
+        let head = parent.unwrap();
+        repo.branch("feature", &head, false).unwrap();
+        repo.tag_lightweight("v1", head.parent(0).unwrap().as_object(), false).unwrap();
+
+        let url = format!("file://{}", dir.path().display());
+        (dir, url)
+    }
+
+    #[test]
+    fn test_clone_with_progress_branch_and_tag() {
+        let (_source, url) = source_repo();
+        let target = TempDir::new().unwrap();
+
+        let mut transfers = 0;
+        let mut checkouts = 0;
+        let path = target.path().join("full");
+        let repo = clone_repository(&url, &path, &CloneOptions {
+            branch: Some("feature".to_string()),
+            ..Default::default()
+        }, Some(Box::new(|progress| {
+            match progress {
+                CloneProgress::Transfer(_) => transfers += 1,
+                CloneProgress::Checkout { .. } => checkouts += 1,
+                CloneProgress::Submodule { .. } => {}
+            }
+            true
+        }))).unwrap();
+        assert!(transfers > 0 && checkouts > 0);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("feature"));
+        assert!(path.join("b.txt").exists());
+
+        let path = target.path().join("tag");
+        let repo = clone_repository(&url, &path, &CloneOptions {
+            branch: Some("v1".to_string()),
+            ..Default::default()
+        }, None).unwrap();
+        assert!(repo.head_detached().unwrap());
+        assert!(path.join("a.txt").exists() && !path.join("b.txt").exists());
+
+        let err = clone_repository(&url, &target.path().join("missing"), &CloneOptions {
+            branch: Some("nope".to_string()),
+            ..Default::default()
+        }, None).err().unwrap();
+        assert!(err.to_string().contains("not found"));
+    }
+
+    #[test]
+    fn test_clone_bare_mirror_and_cancel() {
+        let (_source, url) = source_repo();
+        let target = TempDir::new().unwrap();
+
+        let repo = clone_repository(&url, &target.path().join("bare.git"), &CloneOptions {
+            bare: true,
+            ..Default::default()
+        }, None).unwrap();
+        assert!(repo.is_bare());
+        assert!(repo.find_reference("refs/remotes/origin/feature").is_ok());
+
+        let repo = clone_repository(&url, &target.path().join("mirror.git"), &CloneOptions {
+            mirror: true,
+            ..Default::default()
+        }, None).unwrap();
+        assert!(repo.is_bare());
+        assert!(repo.find_reference("refs/heads/feature").is_ok());
+        assert!(repo.find_reference("refs/tags/v1").is_ok());
+        assert!(repo.config().unwrap().get_bool("remote.origin.mirror").unwrap());
+
+        let path = target.path().join("cancelled");
+        let err = clone_repository(&url, &path, &CloneOptions::default(), Some(Box::new(|_| false))).err().unwrap();
+        assert!(err.to_string().contains("cancelled"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_clone_shallow_and_submodules() {
+        let (_source, url) = source_repo();
+        let target = TempDir::new().unwrap();
+
+        // Local sources are copied, then cut at the requested depth
+        let count = |repo: &Git2Repository| {
+            let mut walk = repo.revwalk().unwrap();
+            walk.push_head().unwrap();
+            walk.count()
+        };
+        let repo = clone_repository(&url, &target.path().join("shallow"), &CloneOptions {
+            depth: Some(1),
+            ..Default::default()
+        }, None).unwrap();
+        assert!(repo.is_shallow());
+        assert_eq!(count(&repo), 1);
+        assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().parent_count(), 0);
+
+        let repo = clone_repository(&url, &target.path().join("deep"), &CloneOptions {
+            depth: Some(5),
+            ..Default::default()
+        }, None).unwrap();
+        assert!(!repo.is_shallow());
+        assert_eq!(count(&repo), 2);
+
+        // Superproject with the source repository as submodule "lib"
+        let super_dir = TempDir::new().unwrap();
+        let super_repo = Git2Repository::init(super_dir.path()).unwrap();
+        let mut submodule = super_repo.submodule(&url, Path::new("lib"), true).unwrap();
+        submodule.clone(None).unwrap();
+        submodule.add_finalize().unwrap();
+        let sig = Signature::now("Test", "test@example.com").unwrap();
+        let tree = super_repo.find_tree(super_repo.index().unwrap().write_tree().unwrap()).unwrap();
+        super_repo.commit(Some("HEAD"), &sig, &sig, "Add lib", &tree, &[]).unwrap();
+
+        let mut submodules = Vec::new();
+        let path = target.path().join("super");
+        clone_repository(&format!("file://{}", super_dir.path().display()), &path, &CloneOptions {
+            recurse_submodules: true,
+            ..Default::default()
+        }, Some(Box::new(|progress| {
+            if let CloneProgress::Submodule { path } = progress {
+                submodules.push(path);
+            }
+            true
+        }))).unwrap();
+        assert_eq!(submodules, vec!["lib".to_string()]);
+        assert!(path.join("lib/b.txt").exists());
+    }
+}
@@ -1,7 +1,20 @@
 use anyhow::Result;
 use git2::{IndexAddOption, Repository as Git2Repository, Signature, Time};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
+/// Kind of commit made by [`Commit::create_fixup`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FixupKind {
+    /// `fixup!`: fold the changes in, keep the target's message
+    Fixup,
+    /// `squash!`: fold the changes in, combine the messages
+    Squash,
+    /// `amend!`: fold the changes in, replace the target's message
+    Amend,
+}
+
 pub struct Commit<'repo> {
     repo: &'repo Git2Repository,
 }
@@ -125,6 +138,38 @@ impl<'repo> Commit<'repo> {
         Ok(oid.to_string())
     }
 
+    /// Commit the index as a fixup of `target`, to be folded in by an autosquash
+    ///
+    /// For [`FixupKind::Amend`] the new message defaults to the target's message.
+    pub fn create_fixup(
+        &self,
+        target: &str,
+        kind: FixupKind,
+        message: Option<&str>,
+        author_name: &str,
+        author_email: &str,
+    ) -> Result<String> {
+        let target = self.repo.revparse_single(target)?.peel_to_commit()?;
+        let subject = target.summary().unwrap_or("");
+
+        let message = match kind {
+            FixupKind::Fixup | FixupKind::Squash => {
+                let prefix = if kind == FixupKind::Fixup { "fixup!" } else { "squash!" };
+                match message {
+                    Some(body) if !body.trim().is_empty() => format!("{} {}\n\n{}", prefix, subject, body),
+                    _ => format!("{} {}\n", prefix, subject),
+                }
+            }
+            FixupKind::Amend => format!(
+                "amend! {}\n\n{}",
+                subject,
+                message.unwrap_or(target.message().unwrap_or(""))
+            ),
+        };
+
+        self.create(&message, author_name, author_email)
+    }
+
     /// Amend the current commit
     pub fn amend(
         &self,
@@ -166,12 +211,18 @@ impl<'repo> Commit<'repo> {
         Ok(())
     }
 
-    /// Revert a commit
-    pub fn revert(&self, commit_id: &str) -> Result<()> {
+    /// Revert a commit into the index and working tree
+    ///
+    /// Merge commits need the `mainline` parent (1-based) to revert to.
+    pub fn revert(&self, commit_id: &str, mainline: Option<u32>) -> Result<()> {
         let oid = git2::Oid::from_str(commit_id)?;
         let commit = self.repo.find_commit(oid)?;
+        if commit.parent_count() > 1 && mainline.is_none() {
+            return Err(anyhow::anyhow!("Commit {} is a merge but no mainline parent was given", &commit_id[..8.min(commit_id.len())]));
+        }
 
         let mut options = git2::RevertOptions::new();
+        options.mainline(mainline.unwrap_or(0));
         self.repo.revert(&commit, Some(&mut options))?;
 
         Ok(())
@@ -196,11 +247,175 @@ pub enum StatusType {
     Deleted,
     Renamed,
     Copied,
+    TypeChange,
     UpdatedButUnmerged,
     Untracked,
     Ignored,
 }
 
+/// State of one side (index or working tree) of a status entry,
+/// mirroring the XY codes of `git status --porcelain=v2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChangeState {
+    Unmodified,
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChange,
+    Unmerged,
+}
+
+impl ChangeState {
+    /// Single-character porcelain code
+    pub fn code(&self) -> char {
+        match self {
+            ChangeState::Unmodified => '.',
+            ChangeState::Added => 'A',
+            ChangeState::Modified => 'M',
+            ChangeState::Deleted => 'D',
+            ChangeState::Renamed => 'R',
+            ChangeState::Copied => 'C',
+            ChangeState::TypeChange => 'T',
+            ChangeState::Unmerged => 'U',
+        }
+    }
+}
+
+/// Kind of status entry, matching the porcelain v2 line types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusEntryKind {
+    /// `1` - changed tracked entry
+    Ordinary,
+    /// `2` - renamed or copied entry
+    Renamed,
+    /// `u` - unmerged entry
+    Unmerged,
+    /// `?` - untracked file
+    Untracked,
+    /// `!` - ignored file
+    Ignored,
+}
+
+/// Which sides of a conflict are present, as in porcelain v2 unmerged XY codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConflictType {
+    BothDeleted,
+    AddedByUs,
+    DeletedByThem,
+    AddedByThem,
+    DeletedByUs,
+    BothAdded,
+    BothModified,
+}
+
+impl ConflictType {
+    fn from_stages(ancestor: bool, ours: bool, theirs: bool) -> Self {
+        match (ancestor, ours, theirs) {
+            (true, false, false) => ConflictType::BothDeleted,
+            (false, true, false) => ConflictType::AddedByUs,
+            (true, true, false) => ConflictType::DeletedByThem,
+            (false, false, true) => ConflictType::AddedByThem,
+            (true, false, true) => ConflictType::DeletedByUs,
+            (false, true, true) => ConflictType::BothAdded,
+            _ => ConflictType::BothModified,
+        }
+    }
+
+    /// Two-character porcelain code (e.g. "UU")
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConflictType::BothDeleted => "DD",
+            ConflictType::AddedByUs => "AU",
+            ConflictType::DeletedByThem => "UD",
+            ConflictType::AddedByThem => "UA",
+            ConflictType::DeletedByUs => "DU",
+            ConflictType::BothAdded => "AA",
+            ConflictType::BothModified => "UU",
+        }
+    }
+}
+
+/// Submodule state of an entry (porcelain v2 `S<c><m><u>` field)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmoduleState {
+    /// The checked out commit differs from the recorded one
+    pub commit_changed: bool,
+    /// The submodule has tracked modifications
+    pub modified: bool,
+    /// The submodule has untracked files
+    pub untracked: bool,
+}
+
+impl SubmoduleState {
+    /// Four-character porcelain code (e.g. "SC.U")
+    pub fn code(&self) -> String {
+        format!(
+            "S{}{}{}",
+            if self.commit_changed { 'C' } else { '.' },
+            if self.modified { 'M' } else { '.' },
+            if self.untracked { 'U' } else { '.' },
+        )
+    }
+}
+
+/// A single porcelain-v2-like status entry with separate index and working tree state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusEntry {
+    pub path: String,
+    pub kind: StatusEntryKind,
+    pub index: ChangeState,
+    pub worktree: ChangeState,
+    /// Source path of a rename
+    pub orig_path: Option<String>,
+    /// Rename similarity score (0-100)
+    pub similarity: Option<u16>,
+    pub conflict: Option<ConflictType>,
+    pub submodule: Option<SubmoduleState>,
+}
+
+impl StatusEntry {
+    /// Two-character XY code as shown by `git status --short`
+    pub fn xy(&self) -> String {
+        match (self.kind, self.conflict) {
+            (_, Some(conflict)) => conflict.code().to_string(),
+            (StatusEntryKind::Untracked, _) => "??".to_string(),
+            (StatusEntryKind::Ignored, _) => "!!".to_string(),
+            _ => format!("{}{}", self.index.code(), self.worktree.code()),
+        }
+    }
+
+    /// Whether the entry has staged changes
+    pub fn is_staged(&self) -> bool {
+        !matches!(self.index, ChangeState::Unmodified | ChangeState::Unmerged)
+    }
+
+    /// Whether the entry has unstaged changes in the working tree
+    pub fn is_unstaged(&self) -> bool {
+        !matches!(self.worktree, ChangeState::Unmodified | ChangeState::Unmerged)
+    }
+}
+
+/// Branch header of a status report (porcelain v2 `# branch.*` lines)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BranchHeader {
+    /// Current commit, `None` on an unborn branch
+    pub oid: Option<String>,
+    /// Current branch name, `None` when HEAD is detached
+    pub head: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+/// Full status of the repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusReport {
+    pub branch: BranchHeader,
+    pub entries: Vec<StatusEntry>,
+}
+
 impl<'repo> Status<'repo> {
     pub fn new(repo: &'repo Git2Repository) -> Self {
         Status { repo }
@@ -219,7 +434,13 @@ impl<'repo> Status<'repo> {
             let status = entry.status();
             let path = entry.path().unwrap_or("").to_string();
 
-            let status_type = if status.is_wt_new() || status.is_index_new() {
+            let status_type = if status.is_conflicted() {
+                StatusType::UpdatedButUnmerged
+            } else if status.is_ignored() {
+                StatusType::Ignored
+            } else if status.is_wt_new() && !status.is_index_new() {
+                StatusType::Untracked
+            } else if status.is_index_new() {
                 StatusType::New
             } else if status.is_wt_modified() || status.is_index_modified() {
                 StatusType::Modified
@@ -228,13 +449,7 @@ impl<'repo> Status<'repo> {
             } else if status.is_wt_renamed() || status.is_index_renamed() {
                 StatusType::Renamed
             } else if status.is_wt_typechange() || status.is_index_typechange() {
-                StatusType::Copied
-            } else if status.is_conflicted() {
-                StatusType::UpdatedButUnmerged
-            } else if status.is_wt_new() && !status.is_index_new() {
-                StatusType::Untracked
-            } else if status.is_ignored() {
-                StatusType::Ignored
+                StatusType::TypeChange
             } else {
                 continue;
             };
@@ -248,6 +463,216 @@ impl<'repo> Status<'repo> {
         Ok(result)
     }
 
+    /// Get a porcelain-v2-like report with separate index and working tree states
+    pub fn report(&self, include_ignored: bool) -> Result<StatusReport> {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(include_ignored)
+            .renames_head_to_index(true)
+            .renames_index_to_workdir(true);
+
+        let conflicts = self.conflict_types()?;
+        let submodules: Vec<String> = self.repo.submodules()
+            .map(|subs| subs.iter().filter_map(|s| s.path().to_str().map(|p| p.to_string())).collect())
+            .unwrap_or_default();
+
+        let statuses = self.repo.statuses(Some(&mut options))?;
+        let mut entries = Vec::new();
+
+        for entry in statuses.iter() {
+            let status = entry.status();
+            let head_to_index = entry.head_to_index();
+            let index_to_workdir = entry.index_to_workdir();
+
+            // Most recent path of the entry
+            let path = index_to_workdir.as_ref()
+                .and_then(|d| d.new_file().path())
+                .or_else(|| head_to_index.as_ref().and_then(|d| d.new_file().path()))
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_else(|| entry.path().unwrap_or("").to_string());
+
+            let index = if status.is_index_new() {
+                ChangeState::Added
+            } else if status.is_index_modified() {
+                ChangeState::Modified
+            } else if status.is_index_deleted() {
+                ChangeState::Deleted
+            } else if status.is_index_renamed() {
+                ChangeState::Renamed
+            } else if status.is_index_typechange() {
+                ChangeState::TypeChange
+            } else {
+                ChangeState::Unmodified
+            };
+
+            let worktree = if status.is_wt_modified() {
+                ChangeState::Modified
+            } else if status.is_wt_deleted() {
+                ChangeState::Deleted
+            } else if status.is_wt_renamed() {
+                ChangeState::Renamed
+            } else if status.is_wt_typechange() {
+                ChangeState::TypeChange
+            } else {
+                ChangeState::Unmodified
+            };
+
+            let mut status_entry = StatusEntry {
+                path: path.clone(),
+                kind: StatusEntryKind::Ordinary,
+                index,
+                worktree,
+                orig_path: None,
+                similarity: None,
+                conflict: None,
+                submodule: None,
+            };
+
+            if status.is_conflicted() {
+                status_entry.kind = StatusEntryKind::Unmerged;
+                status_entry.index = ChangeState::Unmerged;
+                status_entry.worktree = ChangeState::Unmerged;
+                status_entry.conflict = Some(
+                    conflicts.get(&path).copied().unwrap_or(ConflictType::BothModified),
+                );
+            } else if status.is_ignored() {
+                status_entry.kind = StatusEntryKind::Ignored;
+            } else if status.is_wt_new() {
+                status_entry.kind = StatusEntryKind::Untracked;
+            } else if status.is_index_renamed() {
+                if let Some(delta) = head_to_index.as_ref() {
+                    status_entry.kind = StatusEntryKind::Renamed;
+                    status_entry.orig_path = delta.old_file().path()
+                        .map(|p| p.to_string_lossy().into_owned());
+                    let old = self.repo.find_blob(delta.old_file().id())?;
+                    let new = self.repo.find_blob(delta.new_file().id())?;
+                    status_entry.similarity = Some(Self::similarity(old.content(), new.content()));
+                }
+            } else if status.is_wt_renamed() {
+                if let Some(delta) = index_to_workdir.as_ref() {
+                    status_entry.kind = StatusEntryKind::Renamed;
+                    status_entry.orig_path = delta.old_file().path()
+                        .map(|p| p.to_string_lossy().into_owned());
+                    let old = self.repo.find_blob(delta.old_file().id())?;
+                    let workdir = self.repo.workdir()
+                        .ok_or_else(|| anyhow::anyhow!("Repository has no working directory"))?;
+                    let new = std::fs::read(workdir.join(&path)).unwrap_or_default();
+                    status_entry.similarity = Some(Self::similarity(old.content(), &new));
+                }
+            }
+
+            if submodules.contains(&path) {
+                let sub_status = self.repo.submodule_status(&path, git2::SubmoduleIgnore::None)?;
+                status_entry.submodule = Some(SubmoduleState {
+                    commit_changed: sub_status.is_wd_modified(),
+                    modified: sub_status.is_wd_wd_modified() || sub_status.contains(git2::SubmoduleStatus::WD_INDEX_MODIFIED),
+                    untracked: sub_status.is_wd_untracked(),
+                });
+            }
+
+            entries.push(status_entry);
+        }
+
+        Ok(StatusReport {
+            branch: self.branch_header()?,
+            entries,
+        })
+    }
+
+    /// Branch name, upstream and divergence for the status header
+    fn branch_header(&self) -> Result<BranchHeader> {
+        let mut header = BranchHeader::default();
+
+        let head = match self.repo.head() {
+            Ok(head) => head,
+            Err(_) => {
+                // Unborn branch: HEAD is a symbolic ref to a branch without commits
+                header.head = self.repo.find_reference("HEAD").ok()
+                    .and_then(|r| r.symbolic_target().map(|t| t.to_string()))
+                    .map(|t| t.strip_prefix("refs/heads/").unwrap_or(&t).to_string());
+                return Ok(header);
+            }
+        };
+
+        header.oid = head.target().map(|oid| oid.to_string());
+        if !head.is_branch() {
+            return Ok(header);
+        }
+        header.head = head.shorthand().map(|s| s.to_string());
+
+        let refname = head.name().unwrap_or("");
+        if let Ok(upstream_ref) = self.repo.branch_upstream_name(refname) {
+            let upstream_ref = upstream_ref.as_str().unwrap_or("").to_string();
+            header.upstream = Some(
+                upstream_ref
+                    .strip_prefix("refs/remotes/")
+                    .or_else(|| upstream_ref.strip_prefix("refs/heads/"))
+                    .unwrap_or(&upstream_ref)
+                    .to_string(),
+            );
+
+            let upstream_oid = self.repo.find_reference(&upstream_ref).ok().and_then(|r| r.target());
+            if let (Some(local), Some(upstream)) = (head.target(), upstream_oid) {
+                let (ahead, behind) = self.repo.graph_ahead_behind(local, upstream)?;
+                header.ahead = ahead;
+                header.behind = behind;
+            }
+        }
+
+        Ok(header)
+    }
+
+    /// Conflict type of every conflicted path in the index
+    fn conflict_types(&self) -> Result<HashMap<String, ConflictType>> {
+        let mut types = HashMap::new();
+        let index = self.repo.index()?;
+        if !index.has_conflicts() {
+            return Ok(types);
+        }
+
+        for conflict in index.conflicts()?.flatten() {
+            let side = conflict.our.as_ref()
+                .or(conflict.their.as_ref())
+                .or(conflict.ancestor.as_ref());
+            if let Some(side) = side {
+                let path = String::from_utf8_lossy(&side.path).into_owned();
+                types.insert(path, ConflictType::from_stages(
+                    conflict.ancestor.is_some(),
+                    conflict.our.is_some(),
+                    conflict.their.is_some(),
+                ));
+            }
+        }
+
+        Ok(types)
+    }
+
+    /// Similarity score (0-100) between two versions of a file: the share of
+    /// the larger version that is carried over unchanged
+    fn similarity(old: &[u8], new: &[u8]) -> u16 {
+        let max_len = old.len().max(new.len());
+        if max_len == 0 {
+            return 100;
+        }
+
+        let mut deleted = 0;
+        if let Ok(patch) = git2::Patch::from_buffers(old, None, new, None, None) {
+            for hunk_idx in 0..patch.num_hunks() {
+                let num_lines = patch.num_lines_in_hunk(hunk_idx).unwrap_or(0);
+                for line_idx in 0..num_lines {
+                    if let Ok(line) = patch.line_in_hunk(hunk_idx, line_idx) {
+                        if line.origin() == '-' {
+                            deleted += line.content().len();
+                        }
+                    }
+                }
+            }
+        }
+
+        (old.len().saturating_sub(deleted) * 100 / max_len) as u16
+    }
+
     /// Check if there are any staged changes
     pub fn has_staged_changes(&self) -> Result<bool> {
         let statuses = self.repo.statuses(None)?;
@@ -0,0 +1,436 @@
+use anyhow::Result;
+use git2::{AttrCheckFlags, AttrValue, Repository as Git2Repository};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Command;
+
+const LFS_POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+
+/// Git LFS pointer files are always smaller than this
+pub(crate) const LFS_POINTER_MAX_SIZE: usize = 1024;
+
+/// How the content of a changed file was interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentKind {
+    /// Opaque binary data
+    Binary,
+    /// Binary data in a recognized image format
+    Image,
+    /// Git LFS pointer; the change is summarized instead of diffed as text
+    LfsPointer,
+    /// Has a `diff.<driver>.textconv` command: diffed as its output, or, when
+    /// `ContentChange::textconv_error` is set, as raw text because it failed
+    TextConv,
+}
+
+/// Format and dimensions decoded from an image header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageInfo {
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Object referenced by a Git LFS pointer file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LfsPointer {
+    /// Object id including the hash algorithm, e.g. "sha256:4d7a..."
+    pub oid: String,
+    /// Size of the real object in bytes
+    pub size: u64,
+}
+
+/// Summary of one side of a content change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobSummary {
+    /// Size of the blob (for LFS pointers, of the pointer file itself)
+    pub size: u64,
+    pub image: Option<ImageInfo>,
+    pub lfs: Option<LfsPointer>,
+}
+
+/// Content-aware description of a change that is not a plain text diff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentChange {
+    pub kind: ContentKind,
+    /// `None` when the file was added
+    pub old: Option<BlobSummary>,
+    /// `None` when the file was deleted
+    pub new: Option<BlobSummary>,
+    /// Why the configured textconv driver could not convert the file
+    #[serde(default)]
+    pub textconv_error: Option<String>,
+}
+
+impl ContentChange {
+    /// One-line human readable description, e.g. "PNG 64x64 -> PNG 128x128 (1.2 KiB -> 3.4 KiB)"
+    pub fn describe(&self) -> String {
+        let side = |summary: &Option<BlobSummary>| -> String {
+            match summary {
+                None => "(none)".to_string(),
+                Some(summary) => match (&summary.lfs, &summary.image) {
+                    (Some(lfs), _) => format!(
+                        "{} ({})",
+                        lfs.oid.get(..19).unwrap_or(&lfs.oid),
+                        format_size(lfs.size)
+                    ),
+                    (None, Some(image)) => format!(
+                        "{} {}x{} ({})",
+                        image.format,
+                        image.width,
+                        image.height,
+                        format_size(summary.size)
+                    ),
+                    (None, None) => format_size(summary.size),
+                },
+            }
+        };
+
+        let label = match self.kind {
+            ContentKind::Binary => "Binary file",
+            ContentKind::Image => "Image",
+            ContentKind::LfsPointer => "LFS object",
+            ContentKind::TextConv if self.textconv_error.is_some() => "Text",
+            ContentKind::TextConv => "Converted text",
+        };
+        let summary = format!("{}: {} -> {}", label, side(&self.old), side(&self.new));
+        match &self.textconv_error {
+            Some(error) => format!("{} ({})", summary, error),
+            None => summary,
+        }
+    }
+}
+
+impl BlobSummary {
+    pub fn new(content: &[u8]) -> Self {
+        BlobSummary {
+            size: content.len() as u64,
+            image: ImageInfo::detect(content),
+            lfs: LfsPointer::parse(content),
+        }
+    }
+}
+
+impl LfsPointer {
+    /// Parse a Git LFS pointer file
+    pub fn parse(content: &[u8]) -> Option<Self> {
+        if content.len() > LFS_POINTER_MAX_SIZE || !content.starts_with(LFS_POINTER_PREFIX) {
+            return None;
+        }
+
+        let text = std::str::from_utf8(content).ok()?;
+        let mut oid = None;
+        let mut size = None;
+        for line in text.lines() {
+            if let Some(value) = line.strip_prefix("oid ") {
+                oid = Some(value.trim().to_string());
+            } else if let Some(value) = line.strip_prefix("size ") {
+                size = value.trim().parse().ok();
+            }
+        }
+
+        Some(LfsPointer { oid: oid?, size: size? })
+    }
+}
+
+impl ImageInfo {
+    /// Decode format and dimensions from a PNG, GIF, JPEG, BMP or WebP header
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        let be16 = |at: usize| -> Option<u32> {
+            Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+        };
+        let le16 = |at: usize| -> Option<u32> {
+            Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+        };
+        let le24 = |at: usize| -> Option<u32> {
+            let b = data.get(at..at + 3)?;
+            Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+        };
+        let image = |format: &str, width: u32, height: u32| ImageInfo {
+            format: format.to_string(),
+            width,
+            height,
+        };
+
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+            let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+            return Some(image("PNG", width, height));
+        }
+
+        if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            return Some(image("GIF", le16(6)?, le16(8)?));
+        }
+
+        if data.starts_with(b"BM") && data.len() >= 26 {
+            let width = i32::from_le_bytes(data.get(18..22)?.try_into().ok()?);
+            let height = i32::from_le_bytes(data.get(22..26)?.try_into().ok()?);
+            return Some(image("BMP", width.unsigned_abs(), height.unsigned_abs()));
+        }
+
+        if data.starts_with(&[0xFF, 0xD8]) {
+            // Walk the segments up to the first start-of-frame marker
+            let mut pos = 2;
+            while pos + 4 <= data.len() {
+                if data[pos] != 0xFF {
+                    return None;
+                }
+                let marker = data[pos + 1];
+                if marker == 0xFF {
+                    pos += 1;
+                    continue;
+                }
+                if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                    return Some(image("JPEG", be16(pos + 7)?, be16(pos + 5)?));
+                }
+                pos += 2 + be16(pos + 2)? as usize;
+            }
+            return None;
+        }
+
+        if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            return match data.get(12..16)? {
+                b"VP8 " => Some(image("WebP", le16(26)? & 0x3FFF, le16(28)? & 0x3FFF)),
+                b"VP8L" => {
+                    let b = data.get(21..25)?;
+                    let width = 1 + (((b[1] as u32 & 0x3F) << 8) | b[0] as u32);
+                    let height = 1 + (((b[3] as u32 & 0xF) << 10) | (b[2] as u32) << 2 | (b[1] as u32 & 0xC0) >> 6);
+                    Some(image("WebP", width, height))
+                }
+                b"VP8X" => Some(image("WebP", 1 + le24(24)?, 1 + le24(27)?)),
+                _ => None,
+            };
+        }
+
+        None
+    }
+}
+
+/// Bytes of each side read to recognize LFS pointers and image headers
+const SNIFF_LEN: usize = 256 * 1024;
+
+/// Summary of one side of a delta, read from the start of its content
+pub(crate) fn side_summary(repo: &Git2Repository, file: &git2::DiffFile) -> Option<BlobSummary> {
+    let prefix = side_prefix(repo, file)?;
+    Some(BlobSummary { size: file.size(), ..BlobSummary::new(&prefix) })
+}
+
+/// Up to `SNIFF_LEN` bytes of one side of a delta
+fn side_prefix(repo: &Git2Repository, file: &git2::DiffFile) -> Option<Vec<u8>> {
+    if !file.exists() {
+        return None;
+    }
+
+    let mut prefix = Vec::new();
+    if !file.id().is_zero() {
+        // Loose objects can be streamed; packed ones have to be read whole
+        if let Ok(odb) = repo.odb() {
+            if let Ok((reader, _, _)) = odb.reader(file.id()) {
+                if reader.take(SNIFF_LEN as u64).read_to_end(&mut prefix).is_ok() {
+                    return Some(prefix);
+                }
+                prefix.clear();
+            }
+        }
+        if let Ok(blob) = repo.find_blob(file.id()) {
+            let content = blob.content();
+            return Some(content[..content.len().min(SNIFF_LEN)].to_vec());
+        }
+    }
+
+    let path = repo.workdir()?.join(file.path()?);
+    std::fs::File::open(path).ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut prefix)
+        .ok()?;
+    Some(prefix)
+}
+
+/// Content of one side of a delta: the blob when it is in the object database,
+/// otherwise the file in the working directory
+pub(crate) fn side_content(repo: &Git2Repository, file: &git2::DiffFile) -> Option<Vec<u8>> {
+    if !file.exists() {
+        return None;
+    }
+
+    if !file.id().is_zero() {
+        if let Ok(blob) = repo.find_blob(file.id()) {
+            return Some(blob.content().to_vec());
+        }
+    }
+
+    let path = repo.workdir()?.join(file.path()?);
+    std::fs::read(path).ok()
+}
+
+/// The `diff.<driver>.textconv` command configured for a path through its `diff` attribute
+pub(crate) fn textconv_command(repo: &Git2Repository, path: &Path) -> Option<String> {
+    let value = repo.get_attr_bytes(path, "diff", AttrCheckFlags::FILE_THEN_INDEX).ok()?;
+    let driver = match AttrValue::from_bytes(value) {
+        AttrValue::String(driver) => driver.to_string(),
+        _ => return None,
+    };
+
+    repo.config().ok()?
+        .get_string(&format!("diff.{}.textconv", driver))
+        .ok()
+}
+
+/// Run a textconv command on content, the way git does: the content is written
+/// to a temporary file whose path is appended to the command
+pub(crate) fn run_textconv(repo: &Git2Repository, command: &str, content: &[u8]) -> Result<Vec<u8>> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all(content)?;
+    file.flush()?;
+
+    let cwd = repo.workdir().unwrap_or_else(|| repo.path());
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", command))
+        .arg(command)
+        .arg(file.path())
+        .current_dir(cwd)
+        .output()?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "textconv command '{}' failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Human readable byte size
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfs_pointer() {
+        let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n";
+        let lfs = LfsPointer::parse(pointer).unwrap();
+        assert_eq!(lfs.size, 12345);
+        assert!(lfs.oid.starts_with("sha256:4d7a"));
+        assert!(LfsPointer::parse(b"plain text\n").is_none());
+
+        // The shortened oid never splits a character
+        let pointer = "version https://git-lfs.github.com/spec/v1\noid sha256:\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}\nsize 1\n";
+        let lfs = LfsPointer::parse(pointer.as_bytes()).unwrap();
+        let summary = BlobSummary { size: pointer.len() as u64, image: None, lfs: Some(lfs) };
+        let change = ContentChange { kind: ContentKind::LfsPointer, old: None, new: Some(summary), textconv_error: None };
+        assert!(change.describe().contains("sha256:"));
+    }
+
+    #[test]
+    fn test_image_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&64u32.to_be_bytes());
+        png.extend_from_slice(&32u32.to_be_bytes());
+        assert_eq!(ImageInfo::detect(&png), Some(ImageInfo { format: "PNG".to_string(), width: 64, height: 32 }));
+
+        let gif = b"GIF89a\x10\x00\x08\x00";
+        assert_eq!(ImageInfo::detect(gif).map(|i| (i.width, i.height)), Some((16, 8)));
+
+        // SOI, APP0 segment of length 4, SOF0 with height 20 and width 40
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00,
+            0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x14, 0x00, 0x28,
+        ];
+        assert_eq!(ImageInfo::detect(&jpeg).map(|i| (i.width, i.height)), Some((40, 20)));
+        assert!(ImageInfo::detect(b"not an image").is_none());
+    }
+
+    #[test]
+    fn test_large_image_sizes_come_from_the_diff() {
+        use crate::commit::Commit;
+        use crate::diff::Diff;
+
+        let png = |width: u32, size: usize| {
+            let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+            data.extend_from_slice(&width.to_be_bytes());
+            data.extend_from_slice(&8u32.to_be_bytes());
+            data.resize(size, 0);
+            data
+        };
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Git2Repository::init(dir).unwrap();
+        std::fs::write(dir.join("big.png"), png(16, 3 * SNIFF_LEN)).unwrap();
+        {
+            let commit = Commit::new(&repo);
+            commit.stage_all().unwrap();
+            commit.create("init", "Tester", "tester@example.com").unwrap();
+        }
+        std::fs::write(dir.join("big.png"), png(32, 2 * SNIFF_LEN)).unwrap();
+
+        let diffs = Diff::new(&repo).workdir_to_index().unwrap();
+        let change = diffs[0].content.as_ref().unwrap();
+        assert_eq!(change.kind, ContentKind::Image);
+        let old = change.old.as_ref().unwrap();
+        let new = change.new.as_ref().unwrap();
+        assert_eq!((old.size, old.image.as_ref().unwrap().width), (3 * SNIFF_LEN as u64, 16));
+        assert_eq!((new.size, new.image.as_ref().unwrap().width), (2 * SNIFF_LEN as u64, 32));
+    }
+
+    #[test]
+    fn test_failing_textconv_falls_back_to_raw_diff() {
+        use crate::commit::Commit;
+        use crate::diff::Diff;
+        use std::fs;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Git2Repository::init(dir).unwrap();
+        repo.config().unwrap().set_str("diff.broken.textconv", "exit 3; :").unwrap();
+        fs::write(dir.join(".gitattributes"), "*.dat diff=broken\n*.txt diff=broken\n").unwrap();
+        fs::write(dir.join("blob.dat"), b"\0\x01old").unwrap();
+        fs::write(dir.join("notes.txt"), "old\n").unwrap();
+        fs::write(dir.join("plain.md"), "old\n").unwrap();
+        {
+            let commit = Commit::new(&repo);
+            commit.stage_all().unwrap();
+            commit.create("init", "Tester", "tester@example.com").unwrap();
+        }
+        fs::write(dir.join("blob.dat"), b"\0\x01new data").unwrap();
+        fs::write(dir.join("notes.txt"), "new\n").unwrap();
+        fs::write(dir.join("plain.md"), "new\n").unwrap();
+
+        // The broken driver does not hide the other changes
+        let diffs = Diff::new(&repo).workdir_to_index().unwrap();
+        let find = |path: &str| diffs.iter().find(|d| d.file.path == path).unwrap();
+        assert_eq!(diffs.len(), 3);
+
+        let blob = find("blob.dat").content.clone().unwrap();
+        assert_eq!(blob.kind, ContentKind::Binary);
+        assert!(blob.textconv_error.as_deref().unwrap().contains("failed"));
+        assert!(blob.describe().starts_with("Binary file: 5 B -> 10 B ("));
+
+        let notes = find("notes.txt");
+        let change = notes.content.clone().unwrap();
+        assert_eq!(change.kind, ContentKind::TextConv);
+        assert!(change.textconv_error.is_some());
+        assert!(notes.lines.iter().any(|line| line.content == "new\n"));
+
+        assert!(find("plain.md").content.is_none());
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::Result;
-use git2::{Delta, DiffOptions, Repository as Git2Repository};
+use git2::{Delta, DiffFindOptions, DiffOptions, Repository as Git2Repository};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use crate::content::{self, BlobSummary, ContentChange, ContentKind, LFS_POINTER_MAX_SIZE};
+use crate::word_diff::{self, ChangeRange, TokenMode, Tokenizer};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiffFile {
@@ -37,6 +39,9 @@ pub struct DiffLine {
     pub content: String,
     pub old_lineno: Option<u32>,
     pub new_lineno: Option<u32>,
+    /// Changed ranges within `content` when paired with a line on the other side
+    #[serde(default)]
+    pub changes: Vec<ChangeRange>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,21 +57,146 @@ pub struct FileDiff {
     pub hunks: Vec<DiffHunk>,
     pub lines: Vec<DiffLine>,
     pub binary: bool,
+    /// Summary of binary, image, LFS pointer or textconv changes
+    #[serde(default)]
+    pub content: Option<ContentChange>,
+}
+
+/// How whitespace differences are treated when comparing lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WhitespaceMode {
+    /// Whitespace is significant
+    Default,
+    /// Ignore all whitespace (`-w`)
+    IgnoreAll,
+    /// Ignore changes in the amount of whitespace (`-b`)
+    IgnoreChange,
+    /// Ignore whitespace at end of line
+    IgnoreEol,
+}
+
+/// Line diff algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiffAlgorithm {
+    Myers,
+    Minimal,
+    Patience,
+    /// libgit2 does not expose the histogram algorithm; this falls back to
+    /// patience, which histogram extends
+    Histogram,
+}
+
+/// Whether files are diffed as text or binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BinaryMode {
+    /// Detect binary files from their content
+    Auto,
+    /// Treat all files as text
+    Text,
+    /// Treat all files as binary
+    Binary,
+}
+
+/// Options for the diff engine, applied to every `Diff` method
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffConfig {
+    pub context_lines: u32,
+    pub interhunk_lines: u32,
+    pub whitespace: WhitespaceMode,
+    pub algorithm: DiffAlgorithm,
+    /// Detect renamed files
+    pub renames: bool,
+    /// Similarity (0-100) needed to consider a file renamed
+    pub rename_threshold: u16,
+    /// Detect copied files
+    pub copies: bool,
+    /// Similarity (0-100) needed to consider a file copied
+    pub copy_threshold: u16,
+    /// Limit the diff to these paths (empty for all)
+    pub pathspecs: Vec<String>,
+    pub binary: BinaryMode,
+    /// Annotate paired deletion/addition lines with intra-line changes
+    pub word_diff: Option<TokenMode>,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        DiffConfig {
+            context_lines: 3,
+            interhunk_lines: 0,
+            whitespace: WhitespaceMode::Default,
+            algorithm: DiffAlgorithm::Myers,
+            renames: true,
+            rename_threshold: 50,
+            copies: false,
+            copy_threshold: 50,
+            pathspecs: Vec::new(),
+            binary: BinaryMode::Auto,
+            word_diff: None,
+        }
+    }
+}
+
+impl DiffConfig {
+    /// Build libgit2 diff options, limited to `path` when given instead of the configured pathspecs
+    fn diff_options(&self, path: Option<&Path>) -> DiffOptions {
+        let mut opts = DiffOptions::new();
+        opts.context_lines(self.context_lines)
+            .interhunk_lines(self.interhunk_lines)
+            .ignore_whitespace(self.whitespace == WhitespaceMode::IgnoreAll)
+            .ignore_whitespace_change(self.whitespace == WhitespaceMode::IgnoreChange)
+            .ignore_whitespace_eol(self.whitespace == WhitespaceMode::IgnoreEol)
+            .minimal(self.algorithm == DiffAlgorithm::Minimal)
+            .patience(matches!(self.algorithm, DiffAlgorithm::Patience | DiffAlgorithm::Histogram))
+            .force_text(self.binary == BinaryMode::Text)
+            .force_binary(self.binary == BinaryMode::Binary);
+
+        match path {
+            Some(path) => {
+                opts.pathspec(path);
+            }
+            None => {
+                for pathspec in &self.pathspecs {
+                    opts.pathspec(pathspec);
+                }
+            }
+        }
+
+        opts
+    }
+
+    /// Build rename/copy detection options for `find_similar`
+    fn find_options(&self) -> DiffFindOptions {
+        let mut opts = DiffFindOptions::new();
+        opts.renames(self.renames)
+            .rename_threshold(self.rename_threshold)
+            .copies(self.copies)
+            .copy_threshold(self.copy_threshold)
+            .ignore_whitespace(self.whitespace != WhitespaceMode::Default);
+        opts
+    }
 }
 
 pub struct Diff<'repo> {
     repo: &'repo Git2Repository,
+    config: DiffConfig,
 }
 
 impl<'repo> Diff<'repo> {
     pub fn new(repo: &'repo Git2Repository) -> Self {
-        Diff { repo }
+        Self::with_config(repo, DiffConfig::default())
+    }
+
+    /// Create a diff engine with custom options
+    pub fn with_config(repo: &'repo Git2Repository, config: DiffConfig) -> Self {
+        Diff { repo, config }
     }
 
     /// Get diff between working directory and index
     pub fn workdir_to_index(&self) -> Result<Vec<FileDiff>> {
         let index = self.repo.index()?;
-        let diff = self.repo.diff_index_to_workdir(Some(&index), None)?;
+        let mut opts = self.config.diff_options(None);
+        let diff = self.repo.diff_index_to_workdir(Some(&index), Some(&mut opts))?;
         self.process_diff(diff)
     }
 
@@ -74,7 +204,8 @@ impl<'repo> Diff<'repo> {
     pub fn index_to_head(&self) -> Result<Vec<FileDiff>> {
         let head = self.repo.head()?.peel_to_tree()?;
         let index = self.repo.index()?;
-        let diff = self.repo.diff_tree_to_index(Some(&head), Some(&index), None)?;
+        let mut opts = self.config.diff_options(None);
+        let diff = self.repo.diff_tree_to_index(Some(&head), Some(&index), Some(&mut opts))?;
         self.process_diff(diff)
     }
 
@@ -86,7 +217,8 @@ impl<'repo> Diff<'repo> {
         let old_tree = old_commit.tree()?;
         let new_tree = new_commit.tree()?;
 
-        let diff = self.repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+        let mut opts = self.config.diff_options(None);
+        let diff = self.repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut opts))?;
         self.process_diff(diff)
     }
 
@@ -101,15 +233,22 @@ impl<'repo> Diff<'repo> {
             None
         };
 
-        let diff = self.repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        let mut opts = self.config.diff_options(None);
+        let diff = self.repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), Some(&mut opts))?;
+        self.process_diff(diff)
+    }
+
+    /// Get diff between two trees (either side may be empty)
+    pub fn between_trees(&self, old: Option<&git2::Tree>, new: Option<&git2::Tree>) -> Result<Vec<FileDiff>> {
+        let mut opts = self.config.diff_options(None);
+        let diff = self.repo.diff_tree_to_tree(old, new, Some(&mut opts))?;
         self.process_diff(diff)
     }
 
     /// Get diff for a specific file in working directory
     pub fn file_diff(&self, path: &Path) -> Result<FileDiff> {
         // First try to get diff between index and working directory
-        let mut opts = git2::DiffOptions::new();
-        opts.pathspec(path);
+        let mut opts = self.config.diff_options(Some(path));
 
         let diff = self.repo.diff_index_to_workdir(None, Some(&mut opts))?;
         let mut file_diffs = self.process_diff(diff)?;
@@ -129,8 +268,7 @@ impl<'repo> Diff<'repo> {
 
     /// Get staged diff for a specific file
     pub fn staged_file_diff(&self, path: &Path) -> Result<FileDiff> {
-        let mut opts = git2::DiffOptions::new();
-        opts.pathspec(path);
+        let mut opts = self.config.diff_options(Some(path));
 
         // Try to get diff from HEAD to index
         let diff = if let Ok(head) = self.repo.head() {
@@ -149,8 +287,13 @@ impl<'repo> Diff<'repo> {
     fn process_diff(&self, mut diff: git2::Diff) -> Result<Vec<FileDiff>> {
         let mut file_diffs = Vec::new();
 
-        // First, find similar files (for renames/copies)
-        diff.find_similar(None)?;
+        // First, find similar files (for renames/copies). With no detection flags
+        // libgit2 falls back to the `diff.renames` config, so skip it entirely.
+        if self.config.renames || self.config.copies {
+            diff.find_similar(Some(&mut self.config.find_options()))?;
+        }
+
+        let tokenizer = self.config.word_diff.clone().map(Tokenizer::new).transpose()?;
 
         let stats = diff.stats()?;
         let num_deltas = stats.files_changed();
@@ -171,40 +314,21 @@ impl<'repo> Diff<'repo> {
                     hunks: Vec::new(),
                     lines: Vec::new(),
                     binary: delta.new_file().is_binary() || delta.old_file().is_binary(),
+                    content: None,
                 };
 
                 // Get patch for this file using Patch::from_diff
                 if let Ok(Some(patch)) = git2::Patch::from_diff(&diff, idx) {
-                    // Process hunks and lines
-                    let num_hunks = patch.num_hunks();
-                    for hunk_idx in 0..num_hunks {
-                        if let Ok((hunk, num_lines)) = patch.hunk(hunk_idx) {
-                            file_diff.hunks.push(DiffHunk {
-                                old_start: hunk.old_start(),
-                                old_lines: hunk.old_lines(),
-                                new_start: hunk.new_start(),
-                                new_lines: hunk.new_lines(),
-                                header: String::from_utf8_lossy(hunk.header()).into_owned(),
-                            });
-
-                            for line_idx in 0..num_lines {
-                                if let Ok(line) = patch.line_in_hunk(hunk_idx, line_idx) {
-                                    let origin = match line.origin() {
-                                        '+' => LineOrigin::Addition,
-                                        '-' => LineOrigin::Deletion,
-                                        _ => LineOrigin::Context,
-                                    };
-
-                                    file_diff.lines.push(DiffLine {
-                                        origin,
-                                        content: String::from_utf8_lossy(line.content()).into_owned(),
-                                        old_lineno: line.old_lineno(),
-                                        new_lineno: line.new_lineno(),
-                                    });
-                                }
-                            }
-                        }
-                    }
+                    // Binary detection happens while the patch is generated
+                    let patch_delta = patch.delta();
+                    file_diff.binary |= patch_delta.new_file().is_binary() || patch_delta.old_file().is_binary();
+                    Self::push_patch(&patch, &mut file_diff);
+                }
+
+                self.inspect_content(&delta, &mut file_diff)?;
+
+                if let Some(tokenizer) = &tokenizer {
+                    word_diff::annotate(&mut file_diff, tokenizer);
                 }
 
                 file_diffs.push(file_diff);
@@ -214,6 +338,117 @@ impl<'repo> Diff<'repo> {
         Ok(file_diffs)
     }
 
+    /// Append the hunks and lines of a patch
+    fn push_patch(patch: &git2::Patch, file_diff: &mut FileDiff) {
+        let num_hunks = patch.num_hunks();
+        for hunk_idx in 0..num_hunks {
+            if let Ok((hunk, num_lines)) = patch.hunk(hunk_idx) {
+                file_diff.hunks.push(DiffHunk {
+                    old_start: hunk.old_start(),
+                    old_lines: hunk.old_lines(),
+                    new_start: hunk.new_start(),
+                    new_lines: hunk.new_lines(),
+                    header: String::from_utf8_lossy(hunk.header()).into_owned(),
+                });
+
+                for line_idx in 0..num_lines {
+                    if let Ok(line) = patch.line_in_hunk(hunk_idx, line_idx) {
+                        let origin = match line.origin() {
+                            '+' => LineOrigin::Addition,
+                            '-' => LineOrigin::Deletion,
+                            _ => LineOrigin::Context,
+                        };
+
+                        file_diff.lines.push(DiffLine {
+                            origin,
+                            content: String::from_utf8_lossy(line.content()).into_owned(),
+                            old_lineno: line.old_lineno(),
+                            new_lineno: line.new_lineno(),
+                            changes: Vec::new(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Apply textconv drivers and summarize binary, image and LFS pointer changes
+    fn inspect_content(&self, delta: &git2::DiffDelta, file_diff: &mut FileDiff) -> Result<()> {
+        let path = Path::new(&file_diff.file.path);
+        let textconv = content::textconv_command(self.repo, path);
+
+        // Only small text files can be LFS pointers; avoid reading every text blob
+        let small = |file: git2::DiffFile| file.size() as usize <= LFS_POINTER_MAX_SIZE;
+        if textconv.is_none() && !file_diff.binary && !(small(delta.old_file()) && small(delta.new_file())) {
+            return Ok(());
+        }
+
+        let old_summary = content::side_summary(self.repo, &delta.old_file());
+        let new_summary = content::side_summary(self.repo, &delta.new_file());
+
+        let mut textconv_error = None;
+        if let Some(command) = textconv {
+            // The driver is given the whole content
+            let old = content::side_content(self.repo, &delta.old_file());
+            let new = content::side_content(self.repo, &delta.new_file());
+            // A broken driver only affects this file: fall back to the raw summary
+            let convert = |side: &Option<Vec<u8>>| -> Result<Vec<u8>> {
+                side.as_deref()
+                    .map(|c| content::run_textconv(self.repo, &command, c))
+                    .transpose()
+                    .map(Option::unwrap_or_default)
+            };
+            match convert(&old).and_then(|old_text| Ok((old_text, convert(&new)?))) {
+                Ok((old_text, new_text)) => {
+                    let mut opts = self.config.diff_options(None);
+                    opts.force_text(true).force_binary(false);
+                    let patch = git2::Patch::from_buffers(&old_text, Some(path), &new_text, Some(path), Some(&mut opts))?;
+
+                    file_diff.hunks.clear();
+                    file_diff.lines.clear();
+                    file_diff.binary = false;
+                    Self::push_patch(&patch, file_diff);
+                    file_diff.content = Some(ContentChange {
+                        kind: ContentKind::TextConv,
+                        old: old_summary,
+                        new: new_summary,
+                        textconv_error: None,
+                    });
+                    return Ok(());
+                }
+                Err(e) => textconv_error = Some(e.to_string()),
+            }
+        }
+
+        let is_lfs = |summary: &Option<BlobSummary>| summary.as_ref().is_some_and(|s| s.lfs.is_some());
+        let is_image = |summary: &Option<BlobSummary>| summary.as_ref().is_some_and(|s| s.image.is_some());
+
+        let kind = if is_lfs(&old_summary) || is_lfs(&new_summary) {
+            // Show the pointer change instead of the raw pointer text
+            file_diff.hunks.clear();
+            file_diff.lines.clear();
+            ContentKind::LfsPointer
+        } else if !file_diff.binary {
+            if textconv_error.is_none() {
+                return Ok(());
+            }
+            // The raw text diff is kept; the summary only carries the error
+            ContentKind::TextConv
+        } else if is_image(&old_summary) || is_image(&new_summary) {
+            ContentKind::Image
+        } else {
+            ContentKind::Binary
+        };
+
+        file_diff.content = Some(ContentChange {
+            kind,
+            old: old_summary,
+            new: new_summary,
+            textconv_error,
+        });
+        Ok(())
+    }
+
     fn convert_status(status: Delta) -> FileStatus {
         match status {
             Delta::Added => FileStatus::Added,
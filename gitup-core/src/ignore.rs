@@ -0,0 +1,559 @@
+use anyhow::Result;
+use git2::Repository as Git2Repository;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Which ignore file a new rule goes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum IgnoreFile {
+    /// The closest existing `.gitignore` above the path, or the root one
+    Nearest,
+    /// `.gitignore` at the top of the working tree
+    Root,
+    /// `.git/info/exclude`, which is not shared with others
+    Exclude,
+}
+
+/// A rule written to an ignore file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IgnoreRule {
+    /// Ignore file, relative to the working tree when inside it
+    pub file: String,
+    pub pattern: String,
+    /// False when the file already had the rule
+    pub added: bool,
+}
+
+/// The rule deciding whether a path is ignored, as `git check-ignore -v` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IgnoreMatch {
+    pub path: String,
+    /// Ignore file the rule comes from
+    pub source: String,
+    /// 1-based line of the rule in `source`
+    pub line: usize,
+    /// The rule as written, `!` included
+    pub pattern: String,
+    /// False when the deciding rule is a negation
+    pub ignored: bool,
+}
+
+impl fmt::Display for IgnoreMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}\t{}", self.source, self.line, self.pattern, self.path)
+    }
+}
+
+/// One line of an ignore file
+struct Pattern {
+    line: usize,
+    text: String,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole path below the file's directory rather than the basename
+    anchored: bool,
+    regex: Regex,
+}
+
+/// The parsed rules of one ignore file
+struct Source {
+    name: String,
+    /// Directory the patterns are relative to, "" for the top of the working tree
+    base: String,
+    patterns: Vec<Pattern>,
+}
+
+impl Source {
+    /// Last rule in the file matching `path`
+    fn last_match(&self, path: &str, is_dir: bool) -> Option<&Pattern> {
+        let rel = if self.base.is_empty() {
+            path
+        } else {
+            path.strip_prefix(&self.base)?.strip_prefix('/')?
+        };
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        self.patterns.iter().rev().find(|p| {
+            (!p.dir_only || is_dir) && p.regex.is_match(if p.anchored { rel } else { name })
+        })
+    }
+}
+
+/// Ignore rules of a working tree
+pub struct IgnoreOps<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> IgnoreOps<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        IgnoreOps { repo }
+    }
+
+    /// Whether git ignores a path, given relative to the working tree
+    pub fn is_ignored(&self, path: &str) -> Result<bool> {
+        Ok(self.repo.is_path_ignored(path)?)
+    }
+
+    /// Ignore exactly one path, anchored to the directory of the ignore file
+    pub fn ignore_path(&self, path: &str, file: IgnoreFile) -> Result<IgnoreRule> {
+        let workdir = self.workdir()?;
+        let path = path.trim_matches('/');
+        let (target, base) = self.target(file, Some(path))?;
+        let rel = if base.is_empty() { path } else { &path[base.len() + 1..] };
+
+        let mut pattern = format!("/{}", escape(rel));
+        if workdir.join(path).is_dir() {
+            pattern.push('/');
+        }
+        self.append(&target, &pattern)
+    }
+
+    /// Ignore every file with the extension of `path`
+    pub fn ignore_extension(&self, path: &str, file: IgnoreFile) -> Result<IgnoreRule> {
+        let extension = Path::new(path).extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no extension", path))?;
+        let (target, _) = self.target(file, Some(path.trim_matches('/')))?;
+        self.append(&target, &format!("*.{}", escape(extension)))
+    }
+
+    /// Add a pattern as written; `Nearest` means the root `.gitignore` here
+    pub fn add_pattern(&self, pattern: &str, file: IgnoreFile) -> Result<IgnoreRule> {
+        let pattern = pattern.trim_end();
+        if pattern.is_empty() || pattern.contains('\n') {
+            return Err(anyhow::anyhow!("Ignore pattern must be a single non-empty line"));
+        }
+        let (target, _) = self.target(file, None)?;
+        self.append(&target, pattern)
+    }
+
+    /// The rule deciding whether a path is ignored, `None` when no rule matches it
+    ///
+    /// Like `git check-ignore`, tracked paths are not subject to ignore rules and
+    /// give `None` unless `no_index` is set.
+    pub fn explain(&self, path: &str, no_index: bool) -> Result<Option<IgnoreMatch>> {
+        let workdir = self.workdir()?;
+        let is_dir = path.ends_with('/') || workdir.join(path).is_dir();
+        let clean = path.trim_matches('/');
+        if !no_index && self.repo.index()?.get_path(Path::new(clean), 0).is_some() {
+            return Ok(None);
+        }
+        let components: Vec<&str> = clean.split('/').collect();
+
+        // A file inside an ignored directory is ignored whatever rules name the file
+        for n in 1..=components.len() {
+            let candidate = components[..n].join("/");
+            let last = n == components.len();
+            let dir = if last { is_dir } else { true };
+            let parent = components[..n - 1].join("/");
+
+            for source in self.sources(&parent)? {
+                if let Some(pattern) = source.last_match(&candidate, dir) {
+                    if last || !pattern.negated {
+                        return Ok(Some(IgnoreMatch {
+                            path: path.to_string(),
+                            source: source.name.clone(),
+                            line: pattern.line,
+                            pattern: pattern.text.clone(),
+                            ignored: !pattern.negated,
+                        }));
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Ignored paths in the working tree; wholly ignored directories are listed once, with a trailing `/`
+    pub fn list_ignored(&self) -> Result<Vec<String>> {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(true)
+            .recurse_ignored_dirs(false);
+
+        let statuses = self.repo.statuses(Some(&mut options))?;
+        let mut paths: Vec<String> = statuses.iter()
+            .filter(|entry| entry.status().is_ignored())
+            .filter_map(|entry| entry.path().map(|p| p.to_string()))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    fn workdir(&self) -> Result<&Path> {
+        self.repo.workdir()
+            .ok_or_else(|| anyhow::anyhow!("Ignore rules require a working tree"))
+    }
+
+    /// Ignore file to write and the directory its patterns are relative to
+    fn target(&self, file: IgnoreFile, near: Option<&str>) -> Result<(PathBuf, String)> {
+        let workdir = self.workdir()?;
+        match file {
+            IgnoreFile::Root => Ok((workdir.join(".gitignore"), String::new())),
+            IgnoreFile::Exclude => Ok((self.exclude_file(), String::new())),
+            IgnoreFile::Nearest => {
+                let mut dir = near.and_then(|p| Path::new(p).parent()).map(|p| p.to_path_buf());
+                while let Some(current) = dir {
+                    if current.as_os_str().is_empty() {
+                        break;
+                    }
+                    if workdir.join(&current).join(".gitignore").is_file() {
+                        let base = current.to_string_lossy().replace('\\', "/");
+                        return Ok((workdir.join(&current).join(".gitignore"), base));
+                    }
+                    dir = current.parent().map(|p| p.to_path_buf());
+                }
+                Ok((workdir.join(".gitignore"), String::new()))
+            }
+        }
+    }
+
+    fn append(&self, target: &Path, pattern: &str) -> Result<IgnoreRule> {
+        let existing = fs::read_to_string(target).unwrap_or_default();
+        let file = self.display_name(target);
+
+        if existing.lines().any(|line| line.trim_end() == pattern) {
+            return Ok(IgnoreRule { file, pattern: pattern.to_string(), added: false });
+        }
+
+        let mut content = existing;
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(pattern);
+        content.push('\n');
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(target, content)?;
+        Ok(IgnoreRule { file, pattern: pattern.to_string(), added: true })
+    }
+
+    /// Ignore files that apply to entries of `dir`, highest precedence first
+    fn sources(&self, dir: &str) -> Result<Vec<Source>> {
+        let workdir = self.workdir()?;
+        let mut sources = Vec::new();
+
+        let mut dirs = vec![String::new()];
+        let mut prefix = String::new();
+        for component in dir.split('/').filter(|c| !c.is_empty()) {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(component);
+            dirs.push(prefix.clone());
+        }
+        for base in dirs.into_iter().rev() {
+            let path = workdir.join(&base).join(".gitignore");
+            if let Some(source) = self.load(&path, base)? {
+                sources.push(source);
+            }
+        }
+
+        let exclude = self.exclude_file();
+        if let Some(source) = self.load(&exclude, String::new())? {
+            sources.push(source);
+        }
+        if let Some(global) = self.global_excludes_file() {
+            if let Some(source) = self.load(&global, String::new())? {
+                sources.push(source);
+            }
+        }
+        Ok(sources)
+    }
+
+    fn load(&self, path: &Path, base: String) -> Result<Option<Source>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return Ok(None),
+        };
+        let ignore_case = self.repo.config()
+            .and_then(|config| config.get_bool("core.ignoreCase"))
+            .unwrap_or(false);
+        let patterns = content.lines()
+            .enumerate()
+            .filter_map(|(i, line)| parse_pattern(i + 1, line, ignore_case))
+            .collect();
+        Ok(Some(Source { name: self.display_name(path), base, patterns }))
+    }
+
+    /// `info/exclude` in the git directory, shared by linked worktrees
+    fn exclude_file(&self) -> PathBuf {
+        let git_dir = self.repo.path();
+        let common = fs::read_to_string(git_dir.join("commondir"))
+            .map(|dir| git_dir.join(dir.trim()))
+            .unwrap_or_else(|_| git_dir.to_path_buf());
+        common.join("info").join("exclude")
+    }
+
+    /// `core.excludesFile`, or `$XDG_CONFIG_HOME/git/ignore`
+    fn global_excludes_file(&self) -> Option<PathBuf> {
+        if let Ok(path) = self.repo.config().and_then(|config| config.get_path("core.excludesFile")) {
+            return Some(path);
+        }
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("git").join("ignore"))
+    }
+
+    fn display_name(&self, path: &Path) -> String {
+        self.repo.workdir()
+            .and_then(|workdir| path.strip_prefix(workdir).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+/// Parse one line of an ignore file; blank lines and comments give `None`.
+/// With `ignore_case` (`core.ignoreCase`) the pattern matches regardless of case.
+fn parse_pattern(line: usize, raw: &str, ignore_case: bool) -> Option<Pattern> {
+    let text = trim_trailing_spaces(raw);
+    if text.is_empty() || text.starts_with('#') {
+        return None;
+    }
+
+    let (negated, mut glob) = match text.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let dir_only = glob.ends_with('/') && !glob.ends_with("\\/");
+    if dir_only {
+        glob = glob.trim_end_matches('/');
+    }
+    let anchored = glob.contains('/');
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    if glob.is_empty() {
+        return None;
+    }
+
+    let regex = RegexBuilder::new(&glob_to_regex(glob)).case_insensitive(ignore_case).build().ok()?;
+    Some(Pattern { line, text: text.to_string(), negated, dir_only, anchored, regex })
+}
+
+/// Trailing spaces are dropped unless escaped with a backslash
+fn trim_trailing_spaces(line: &str) -> &str {
+    let trimmed = line.trim_end_matches(' ');
+    if trimmed.ends_with('\\') && trimmed.len() < line.len() {
+        &line[..trimmed.len() + 1]
+    } else {
+        trimmed
+    }
+}
+
+/// Translate a gitignore glob to an anchored regex over `/`-separated paths
+fn glob_to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*')
+                && (i == 0 || chars[i - 1] == '/')
+                && matches!(chars.get(i + 2), None | Some('/')) =>
+            {
+                if i + 2 == chars.len() {
+                    regex.push_str(".*");
+                    i += 2;
+                } else {
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                }
+            }
+            '*' => {
+                regex.push_str("[^/]*");
+                while chars.get(i + 1) == Some(&'*') {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '?' => {
+                regex.push_str("[^/]");
+                i += 1;
+            }
+            '[' => match chars[i + 1..].iter().skip(1).position(|&c| c == ']') {
+                Some(offset) => {
+                    let end = i + 2 + offset;
+                    let mut class: String = chars[i + 1..end].iter().collect();
+                    if let Some(rest) = class.strip_prefix('!') {
+                        class = format!("^{}", rest);
+                    }
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                    i = end + 1;
+                }
+                None => {
+                    regex.push_str("\\[");
+                    i += 1;
+                }
+            },
+            '\\' if i + 1 < chars.len() => {
+                regex.push_str(&regex::escape(&chars[i + 1].to_string()));
+                i += 2;
+            }
+            c => {
+                regex.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+/// Escape glob characters so a path is matched literally
+fn escape(path: &str) -> String {
+    let mut escaped = String::new();
+    for (i, c) in path.chars().enumerate() {
+        if matches!(c, '*' | '?' | '[' | '\\') || (i == 0 && matches!(c, '#' | '!')) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::write_file;
+    use crate::repository::Repository;
+    use tempfile::TempDir;
+
+    /// Whether the single-line ignore file `pattern` matches `path` at the top of the working tree
+    fn matches(pattern: &str, path: &str, is_dir: bool) -> bool {
+        let source = Source {
+            name: String::new(),
+            base: String::new(),
+            patterns: parse_pattern(1, pattern, false).into_iter().collect(),
+        };
+        source.last_match(path, is_dir).is_some()
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!(glob_to_regex("a/**/b"), "^a/(?:.*/)?b$");
+        assert_eq!(glob_to_regex("[!a-c]*"), "^[^a-c][^/]*$");
+        assert_eq!(glob_to_regex("\\*.o"), "^\\*\\.o$");
+
+        let cases = [
+            // `**` spans directories only as a whole component
+            ("**/foo", "foo", false, true),
+            ("**/foo", "a/b/foo", false, true),
+            ("a/**/b", "a/b", false, true),
+            ("a/**/b", "a/x/y/b", false, true),
+            ("a/**/b", "ab", false, false),
+            ("abc/**", "abc/x/y", false, true),
+            ("abc/**", "abc", true, false),
+            ("foo**bar", "fooxbar", false, true),
+            ("a/foo**bar", "a/foo/bar", false, false),
+            // Character classes
+            ("[!a]x", "bx", false, true),
+            ("[!a]x", "ax", false, false),
+            ("[a-c]x", "cx", false, true),
+            ("[a-c]x", "dx", false, false),
+            ("[x", "[x", false, true),
+            // Escapes
+            ("\\*x", "*x", false, true),
+            ("\\*x", "ax", false, false),
+            ("\\!keep", "!keep", false, true),
+            ("\\#c", "#c", false, true),
+            ("x\\ ", "x ", false, true),
+            ("x ", "x", false, true),
+            // A trailing `/` matches directories only
+            ("build/", "build", true, true),
+            ("build/", "build", false, false),
+            ("build/", "a/build", true, true),
+            // A `/` anywhere but at the end anchors the pattern
+            ("*.o", "dir/x.o", false, true),
+            ("/*.o", "dir/x.o", false, false),
+            ("/*.o", "x.o", false, true),
+            ("doc/*.txt", "doc/a.txt", false, true),
+            ("doc/*.txt", "doc/sub/a.txt", false, false),
+            ("doc/*.txt", "x/doc/a.txt", false, false),
+            ("a?c", "abc", false, true),
+            ("a/?c", "a//c", false, false),
+        ];
+        for (pattern, path, is_dir, expected) in cases {
+            assert_eq!(matches(pattern, path, is_dir), expected, "{} against {}", pattern, path);
+        }
+    }
+
+    #[test]
+    fn test_explain_skips_tracked_paths_and_honours_ignore_case() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Git2Repository::init(dir).unwrap();
+        repo.config().unwrap().set_bool("core.ignoreCase", false).unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n*.TMP\n").unwrap();
+        fs::write(dir.join("tracked.log"), "x").unwrap();
+        fs::write(dir.join("a.tmp"), "x").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("tracked.log")).unwrap();
+        index.write().unwrap();
+
+        let ops = IgnoreOps::new(&repo);
+        assert!(ops.explain("tracked.log", false).unwrap().is_none());
+        assert_eq!(ops.explain("tracked.log", true).unwrap().unwrap().pattern, "*.log");
+
+        assert!(ops.explain("a.tmp", false).unwrap().is_none());
+        repo.config().unwrap().set_bool("core.ignoreCase", true).unwrap();
+        assert_eq!(ops.explain("a.tmp", false).unwrap().unwrap().pattern, "*.TMP");
+        assert!(ops.is_ignored("a.tmp").unwrap());
+    }
+
+    #[test]
+    fn test_ignore_rules_add_explain_and_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+        write_file(dir.join(".gitignore"), "# build output\ntarget/\n*.log\n!keep.log\n");
+        fs::create_dir_all(dir.join("sub/target")).unwrap();
+        write_file(dir.join("sub/.gitignore"), "*.tmp\n");
+        write_file(dir.join("sub/target/out.bin"), "x");
+        write_file(dir.join("sub/a.tmp"), "x");
+        write_file(dir.join("debug.log"), "x");
+        write_file(dir.join("keep.log"), "x");
+        write_file(dir.join("notes.md"), "x");
+        write_file(dir.join("sub/data.csv"), "x");
+
+        let ops = repo.ignore_ops();
+        let matched = ops.explain("sub/target/out.bin", false).unwrap().unwrap();
+        assert_eq!(matched.to_string(), ".gitignore:2:target/\tsub/target/out.bin");
+        let matched = ops.explain("sub/a.tmp", false).unwrap().unwrap();
+        assert_eq!((matched.source.as_str(), matched.line), ("sub/.gitignore", 1));
+        let matched = ops.explain("keep.log", false).unwrap().unwrap();
+        assert!(!matched.ignored);
+        assert_eq!(matched.pattern, "!keep.log");
+        assert!(ops.explain("notes.md", false).unwrap().is_none());
+        for path in ["sub/target/out.bin", "sub/a.tmp", "debug.log", "keep.log", "notes.md"] {
+            let ignored = ops.explain(path, false).unwrap().is_some_and(|m| m.ignored);
+            assert_eq!(ignored, ops.is_ignored(path).unwrap(), "{}", path);
+        }
+
+        // Nearest picks sub/.gitignore and anchors the path to it
+        let rule = ops.ignore_path("sub/data.csv", IgnoreFile::Nearest).unwrap();
+        assert_eq!((rule.file.as_str(), rule.pattern.as_str(), rule.added), ("sub/.gitignore", "/data.csv", true));
+        assert!(!ops.ignore_path("sub/data.csv", IgnoreFile::Nearest).unwrap().added);
+        assert!(ops.is_ignored("sub/data.csv").unwrap());
+
+        let rule = ops.ignore_extension("notes.md", IgnoreFile::Exclude).unwrap();
+        assert_eq!(rule.pattern, "*.md");
+        assert!(rule.file.ends_with("info/exclude"));
+        assert_eq!(ops.explain("notes.md", false).unwrap().unwrap().pattern, "*.md");
+
+        let ignored = ops.list_ignored().unwrap();
+        assert!(ignored.contains(&"sub/target/".to_string()), "{:?}", ignored);
+        assert!(ignored.contains(&"debug.log".to_string()));
+        assert!(!ignored.contains(&"keep.log".to_string()));
+    }
+}
@@ -0,0 +1,472 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use crate::analytics::{AnalyticsOptions, RepoAnalytics};
+use crate::diff::FileDiff;
+use crate::merge::MergePrediction;
+use crate::remote::{FetchOptions, FetchReport, TransferProgress};
+use crate::repository::{CommitInfo, Repository};
+
+pub type JobId = u64;
+
+/// Progress reported by a running job
+#[derive(Debug, Clone)]
+pub enum JobProgress {
+    /// Network transfer progress (fetch, pull)
+    Transfer(TransferProgress),
+    /// Generic progress: `current` of `total` units (`total` is 0 when unknown)
+    Steps { current: usize, total: usize },
+}
+
+impl JobProgress {
+    /// Completed fraction in `0.0..=1.0`, if known
+    pub fn ratio(&self) -> Option<f64> {
+        let (current, total) = match self {
+            JobProgress::Transfer(p) if p.total_objects > 0 && p.received_objects < p.total_objects => {
+                (p.received_objects, p.total_objects)
+            }
+            // Objects are all received, deltas are being resolved
+            JobProgress::Transfer(p) if p.total_deltas > 0 => (p.indexed_deltas, p.total_deltas),
+            JobProgress::Transfer(p) => (p.received_objects, p.total_objects),
+            JobProgress::Steps { current, total } => (*current, *total),
+        };
+
+        if total == 0 {
+            None
+        } else {
+            Some((current as f64 / total as f64).min(1.0))
+        }
+    }
+}
+
+/// Result of a finished job
+#[derive(Debug)]
+pub enum JobOutput {
+    Message(String),
+    Diffs(Vec<FileDiff>),
+    Commits(Vec<CommitInfo>),
+    Fetch(Vec<FetchReport>),
+    Analytics(Box<RepoAnalytics>),
+    MergePrediction(Box<MergePrediction>),
+}
+
+/// Events delivered by the job runner
+#[derive(Debug)]
+pub enum JobEvent {
+    Started { id: JobId, name: String },
+    Progress { id: JobId, progress: JobProgress },
+    /// The job completed; errors are carried as their message
+    Finished { id: JobId, result: std::result::Result<JobOutput, String> },
+    /// The job stopped after its cancellation was requested
+    Cancelled { id: JobId },
+}
+
+impl JobEvent {
+    pub fn id(&self) -> JobId {
+        match self {
+            JobEvent::Started { id, .. }
+            | JobEvent::Progress { id, .. }
+            | JobEvent::Finished { id, .. }
+            | JobEvent::Cancelled { id } => *id,
+        }
+    }
+}
+
+/// Shared flag used to request cooperative cancellation
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Handle given to a running job for progress reporting and cancellation checks
+pub struct JobContext {
+    id: JobId,
+    cancel: CancelToken,
+    sender: Sender<JobEvent>,
+}
+
+impl JobContext {
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Return an error if cancellation was requested
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(anyhow::anyhow!("Job cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn progress(&self, progress: JobProgress) {
+        let _ = self.sender.send(JobEvent::Progress { id: self.id, progress });
+    }
+}
+
+/// Runs gitup-core operations on background threads
+///
+/// Each job gets its own thread and opens its own `Repository`, since libgit2
+/// handles cannot be shared across threads. Events are collected with
+/// [`JobRunner::try_recv`] from the owning (e.g. UI) thread.
+pub struct JobRunner {
+    sender: Sender<JobEvent>,
+    receiver: Receiver<JobEvent>,
+    next_id: JobId,
+    active: HashMap<JobId, (String, CancelToken)>,
+}
+
+impl Default for JobRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobRunner {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        JobRunner {
+            sender,
+            receiver,
+            next_id: 1,
+            active: HashMap::new(),
+        }
+    }
+
+    /// Run a closure on a background thread
+    pub fn spawn<F>(&mut self, name: &str, job: F) -> JobId
+    where
+        F: FnOnce(&JobContext) -> Result<JobOutput> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let cancel = CancelToken::default();
+        self.active.insert(id, (name.to_string(), cancel.clone()));
+
+        let context = JobContext {
+            id,
+            cancel,
+            sender: self.sender.clone(),
+        };
+        let name = name.to_string();
+
+        thread::spawn(move || {
+            let _ = context.sender.send(JobEvent::Started { id, name });
+            // A panicking job still finishes, so it does not stay active forever
+            let result = panic::catch_unwind(AssertUnwindSafe(|| job(&context)))
+                .unwrap_or_else(|payload| {
+                    let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown panic".to_string());
+                    Err(anyhow::anyhow!("Job panicked: {}", message))
+                });
+
+            // A job that completed is reported as such, even if cancelled late
+            let event = match result {
+                Err(_) if context.is_cancelled() => JobEvent::Cancelled { id },
+                result => JobEvent::Finished { id, result: result.map_err(|e| e.to_string()) },
+            };
+            let _ = context.sender.send(event);
+        });
+
+        id
+    }
+
+    /// Request cancellation of a job; returns false if it is not running
+    pub fn cancel(&self, id: JobId) -> bool {
+        match self.active.get(&id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        for (_, token) in self.active.values() {
+            token.cancel();
+        }
+    }
+
+    /// Next pending event, if any
+    pub fn try_recv(&mut self) -> Option<JobEvent> {
+        let event = self.receiver.try_recv().ok()?;
+        if matches!(event, JobEvent::Finished { .. } | JobEvent::Cancelled { .. }) {
+            self.active.remove(&event.id());
+        }
+        Some(event)
+    }
+
+    /// Wait for the next event
+    pub fn recv(&mut self) -> Option<JobEvent> {
+        let event = self.receiver.recv().ok()?;
+        if matches!(event, JobEvent::Finished { .. } | JobEvent::Cancelled { .. }) {
+            self.active.remove(&event.id());
+        }
+        Some(event)
+    }
+
+    /// Running jobs with their names
+    pub fn active_jobs(&self) -> Vec<(JobId, String)> {
+        let mut jobs: Vec<_> = self.active.iter().map(|(id, (name, _))| (*id, name.clone())).collect();
+        jobs.sort_by_key(|(id, _)| *id);
+        jobs
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    /// Fetch from a remote, or from all remotes when `remote` is `None`,
+    /// reporting transfer progress
+    pub fn fetch(&mut self, repo_path: PathBuf, remote: Option<String>, options: FetchOptions) -> JobId {
+        let name = match &remote {
+            Some(remote) => format!("fetch {}", remote),
+            None => "fetch --all".to_string(),
+        };
+        self.spawn(&name, move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            let remote_ops = repo.remote_ops();
+            let progress: Box<dyn FnMut(TransferProgress) -> bool + '_> = Box::new(|progress| {
+                ctx.progress(JobProgress::Transfer(progress));
+                !ctx.is_cancelled()
+            });
+            let reports = match remote {
+                Some(remote) => vec![remote_ops.fetch_with_options(&remote, &options, Some(progress))?],
+                None => remote_ops.fetch_all(&options, Some(progress))?,
+            };
+            Ok(JobOutput::Fetch(reports))
+        })
+    }
+
+    /// Pull (fetch and fast-forward) from a remote, reporting transfer progress
+    pub fn pull(&mut self, repo_path: PathBuf, remote: String, branch: String) -> JobId {
+        self.spawn(&format!("pull {} {}", remote, branch), move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            let message = repo.remote_ops().pull(&remote, &branch, Some(Box::new(|progress| {
+                ctx.progress(JobProgress::Transfer(progress));
+                !ctx.is_cancelled()
+            })))?;
+            Ok(JobOutput::Message(message))
+        })
+    }
+
+    /// Push to a remote, reporting packing and upload progress
+    pub fn push(&mut self, repo_path: PathBuf, remote: String) -> JobId {
+        self.spawn(&format!("push {}", remote), move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            ctx.check_cancelled()?;
+            let message = repo.remote_ops().push(&remote, &[], Some(Box::new(|progress| {
+                ctx.progress(JobProgress::Transfer(progress));
+                !ctx.is_cancelled()
+            })))?;
+            Ok(JobOutput::Message(message))
+        })
+    }
+
+    /// Diff two commits
+    pub fn diff_between_commits(&mut self, repo_path: PathBuf, old: String, new: String) -> JobId {
+        self.spawn("diff", move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            ctx.check_cancelled()?;
+            let diffs = repo.diff_between_commits(&old, &new)?;
+            ctx.check_cancelled()?;
+            Ok(JobOutput::Diffs(diffs))
+        })
+    }
+
+    /// Walk up to `count` commits from HEAD
+    pub fn history(&mut self, repo_path: PathBuf, count: usize) -> JobId {
+        self.spawn("history", move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            let commits = repo.get_commits_with_progress(count, &mut |walked| {
+                ctx.progress(JobProgress::Steps { current: walked, total: count });
+                !ctx.is_cancelled()
+            })?;
+            Ok(JobOutput::Commits(commits))
+        })
+    }
+
+    /// Work out in memory how merging `rev` into HEAD would go
+    pub fn predict_merge(&mut self, repo_path: PathBuf, rev: String) -> JobId {
+        self.spawn(&format!("merge check {}", rev), move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            ctx.check_cancelled()?;
+            let prediction = repo.predict_merge(&rev)?;
+            Ok(JobOutput::MergePrediction(Box::new(prediction)))
+        })
+    }
+
+    /// Compute history analytics
+    pub fn analytics(&mut self, repo_path: PathBuf, options: AnalyticsOptions) -> JobId {
+        self.spawn("stats", move |ctx| {
+            let repo = Repository::discover(&repo_path)?;
+            let total = options.max_commits.unwrap_or(0);
+            let analytics = repo.analytics_ops().compute_with_progress(&options, &mut |walked| {
+                ctx.progress(JobProgress::Steps { current: walked, total });
+                !ctx.is_cancelled()
+            })?;
+            Ok(JobOutput::Analytics(Box::new(analytics)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_job_completes_with_result() {
+        let mut runner = JobRunner::new();
+        let id = runner.spawn("sum", |ctx| {
+            ctx.progress(JobProgress::Steps { current: 1, total: 2 });
+            Ok(JobOutput::Message("done".to_string()))
+        });
+
+        let mut saw_progress = false;
+        loop {
+            match runner.recv().unwrap() {
+                JobEvent::Progress { .. } => saw_progress = true,
+                JobEvent::Finished { id: finished, result } => {
+                    assert_eq!(finished, id);
+                    assert!(matches!(result, Ok(JobOutput::Message(ref m)) if m == "done"));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        assert!(saw_progress);
+        assert!(!runner.is_busy());
+    }
+
+    #[test]
+    fn test_job_cancellation() {
+        let mut runner = JobRunner::new();
+        let id = runner.spawn("spin", |ctx| {
+            while !ctx.is_cancelled() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            ctx.check_cancelled()?;
+            Ok(JobOutput::Message("unreachable".to_string()))
+        });
+
+        assert!(runner.cancel(id));
+        loop {
+            match runner.recv().unwrap() {
+                JobEvent::Cancelled { id: cancelled } => {
+                    assert_eq!(cancelled, id);
+                    break;
+                }
+                JobEvent::Finished { .. } => panic!("cancelled job reported as finished"),
+                _ => {}
+            }
+        }
+        assert!(!runner.cancel(id));
+    }
+
+    #[test]
+    fn test_panicking_and_late_cancelled_jobs_finish() {
+        let mut runner = JobRunner::new();
+        runner.spawn("panic", |_| panic!("boom"));
+        loop {
+            if let JobEvent::Finished { result, .. } = runner.recv().unwrap() {
+                assert!(result.unwrap_err().contains("boom"));
+                break;
+            }
+        }
+        assert!(!runner.is_busy());
+
+        // Cancelled after it already succeeded: the result still counts
+        let id = runner.spawn("done", |ctx| {
+            while !ctx.is_cancelled() {
+                thread::sleep(Duration::from_millis(5));
+            }
+            Ok(JobOutput::Message("done".to_string()))
+        });
+        assert!(runner.cancel(id));
+        loop {
+            match runner.recv().unwrap() {
+                JobEvent::Finished { result, .. } => {
+                    assert!(result.is_ok());
+                    break;
+                }
+                JobEvent::Cancelled { .. } => panic!("completed job reported as cancelled"),
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_push_reports_progress_and_cancels() {
+        use crate::commit::Commit;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path().join("work");
+        let bare = temp_dir.path().join("remote.git");
+        git2::Repository::init_bare(&bare).unwrap();
+        let git = git2::Repository::init(&dir).unwrap();
+        for i in 0..20 {
+            std::fs::write(dir.join(format!("f{}.txt", i)), format!("content {}\n", i)).unwrap();
+        }
+        {
+            let commit = Commit::new(&git);
+            commit.stage_all().unwrap();
+            commit.create("init", "Tester", "tester@example.com").unwrap();
+        }
+        let repo = Repository::open(&dir).unwrap();
+        repo.add_remote("origin", &format!("file://{}", bare.display())).unwrap();
+
+        // Declining to continue stops the push before any ref is updated
+        let err = repo.remote_ops().push("origin", &[], Some(Box::new(|_| false))).unwrap_err();
+        assert_eq!(err.to_string(), "Push cancelled");
+        let remote = git2::Repository::open_bare(&bare).unwrap();
+        assert!(remote.find_reference("refs/heads/master").is_err());
+
+        let mut runner = JobRunner::new();
+        runner.push(dir.clone(), "origin".to_string());
+        let mut progress = Vec::new();
+        loop {
+            match runner.recv().unwrap() {
+                JobEvent::Progress { progress: p, .. } => progress.push(p),
+                JobEvent::Finished { result, .. } => {
+                    assert!(result.is_ok(), "{:?}", result);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        assert!(progress.iter().any(|p| p.ratio() == Some(1.0)));
+        assert!(remote.find_reference("refs/heads/master").is_ok());
+
+        // A cancel request seen during one refspec skips the remaining ones
+        let mut calls = 0;
+        let refspecs = ["refs/heads/master:refs/heads/first", "refs/heads/master:refs/heads/second"];
+        let err = repo.remote_ops().push("origin", &refspecs, Some(Box::new(|_| {
+            calls += 1;
+            calls == 1
+        }))).unwrap_err();
+        assert_eq!(err.to_string(), "Push cancelled");
+        assert!(remote.find_reference("refs/heads/first").is_ok());
+        assert!(remote.find_reference("refs/heads/second").is_err());
+    }
+}
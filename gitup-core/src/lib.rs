@@ -1,19 +1,59 @@
 pub mod repository;
+pub mod branch;
 pub mod diff;
+pub mod word_diff;
+pub mod content;
 pub mod commit;
+pub mod message;
+pub mod notes;
+pub mod ignore;
 pub mod remote;
+pub mod clone;
 pub mod stash;
 pub mod tag;
 pub mod merge;
 pub mod rebase;
+pub mod undo;
+pub mod range_diff;
 pub mod cherry_pick;
+pub mod jobs;
+pub mod analytics;
+pub use gitup_commit_graph as commit_graph;
 
 pub use repository::{Repository, CommitInfo, BranchInfo, CommitWithParents, RefInfo, RefType};
-pub use diff::{Diff, FileDiff, DiffFile, DiffHunk, DiffLine, DiffStats, FileStatus, LineOrigin};
-pub use commit::{Commit, Status, FileStatus as CommitFileStatus, StatusType};
-pub use remote::{RemoteInfo, RemoteOps, TransferProgress};
-pub use stash::{StashInfo, StashOps};
-pub use tag::{TagInfo, TagOps};
-pub use merge::{MergeOps, MergeResult, ConflictResolution};
-pub use rebase::{RebaseOps, RebaseResult, RebaseOperation};
-pub use cherry_pick::{CherryPickOps, CherryPickResult};
+pub use diff::{
+    Diff, FileDiff, DiffFile, DiffHunk, DiffLine, DiffStats, FileStatus, LineOrigin,
+    DiffConfig, DiffAlgorithm, WhitespaceMode, BinaryMode,
+};
+pub use content::{ContentChange, ContentKind, BlobSummary, ImageInfo, LfsPointer};
+pub use word_diff::{TokenMode, Tokenizer, ChangeRange, WordOp, WordOpKind};
+pub use commit::{
+    Commit, FixupKind, Status, FileStatus as CommitFileStatus, StatusType,
+    StatusReport, StatusEntry, StatusEntryKind, ChangeState, ConflictType, SubmoduleState, BranchHeader,
+};
+pub use message::{
+    MessageOps, LintConfig, LintFinding, LintRule, LintSeverity, LintSpan, ConventionalCommit,
+    lint_message, clean_message, parse_trailers, add_trailer, remove_trailer, sign_off, co_authored_by,
+};
+pub use notes::{NoteOps, NoteInfo, DEFAULT_NOTES_REF, full_notes_ref};
+pub use ignore::{IgnoreOps, IgnoreFile, IgnoreRule, IgnoreMatch};
+pub use branch::BranchOps;
+pub use remote::{
+    RemoteInfo, RemoteOps, TransferProgress, FetchOptions, FetchReport, RefUpdate, RefUpdateKind, TagMode,
+};
+pub use clone::{CloneOptions, CloneProgress, CloneProgressCallback, is_local_url};
+pub use stash::{StashInfo, StashOps, StashSaveOptions, StashDiff};
+pub use tag::{TagInfo, TagOps, TagSort, DescribeOptions, Description, compare_versions};
+pub use merge::{MergeOps, MergeResult, MergeKind, MergePrediction, ConflictResolution};
+pub use rebase::{
+    RebaseOps, RebaseResult, RebaseOperation, RebaseTodo, TodoAction, TodoItem, SplitPart, SplitSelection, MoveDestination,
+    CommitMetadata, Identity, IdentityRewrite, RebasePrediction, ConflictingCommit,
+};
+pub use undo::{UndoLog, UndoEntry, RefChange};
+pub use range_diff::{RangeDiffOps, RangeDiff, RangeDiffPair, RangeDiffCommit, RangeDiffLine, RangeDiffStatus};
+pub use cherry_pick::{CherryPickOps, CherryPickResult, PickOptions, PickAction};
+pub use jobs::{JobRunner, JobEvent, JobProgress, JobOutput, JobId, JobContext, CancelToken};
+pub use commit_graph::{CommitGraph, CommitGraphOps, CommitLookup, GraphCommit, GENERATION_INFINITY};
+pub use analytics::{
+    AnalyticsOps, AnalyticsOptions, RepoAnalytics, AuthorStats, FileChurn, FileCoupling, PeriodCount, TimeBucket,
+};
@@ -1,9 +1,12 @@
 use anyhow::Result;
 use git2::{
     Repository as Git2Repository, Oid, AnnotatedCommit, MergeOptions,
-    build::CheckoutBuilder, Reference, BranchType,
+    build::CheckoutBuilder, Reference, BranchType, Index, ObjectType, Tree, TreeWalkMode, TreeWalkResult,
 };
+use std::collections::HashSet;
 use std::path::Path;
+use crate::commit_graph::CommitLookup;
+use crate::diff::FileStatus;
 
 /// Merge result information
 #[derive(Debug, Clone)]
@@ -14,6 +17,33 @@ pub struct MergeResult {
     pub merged_commit: Option<String>,
 }
 
+/// What merging a commit into HEAD would do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeKind {
+    UpToDate,
+    FastForward,
+    Normal,
+}
+
+/// Outcome of a merge worked out in memory, without touching the index
+/// or working tree
+#[derive(Debug, Clone)]
+pub struct MergePrediction {
+    pub kind: MergeKind,
+    /// Paths that would conflict
+    pub conflicts: Vec<String>,
+    /// Paths the merge would change in HEAD's tree, conflicting ones included
+    pub changes: Vec<(String, FileStatus)>,
+    /// Merged tree, when there are no conflicts
+    pub tree: Option<String>,
+}
+
+impl MergePrediction {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
 /// Merge operations for a repository
 pub struct MergeOps {
     repo: Git2Repository,
@@ -25,6 +55,11 @@ impl MergeOps {
         Ok(MergeOps { repo })
     }
 
+    /// Use an already opened repository
+    pub fn from_repo(repo: Git2Repository) -> Self {
+        MergeOps { repo }
+    }
+
     /// Merge a branch into the current branch
     pub fn merge_branch(&self, branch_name: &str, message: Option<&str>) -> Result<MergeResult> {
         // Get the branch to merge
@@ -66,6 +101,36 @@ impl MergeOps {
         }
     }
 
+    /// Work out what merging `rev` into HEAD would do, in memory
+    pub fn predict_merge(&self, rev: &str) -> Result<MergePrediction> {
+        let theirs = self.repo.revparse_single(rev)?.peel_to_commit()?;
+        let ours = self.repo.head()?.peel_to_commit()?;
+        let ours_tree = ours.tree()?;
+        let lookup = CommitLookup::new(&self.repo);
+
+        if theirs.id() == ours.id() || lookup.is_descendant_of(ours.id(), theirs.id())? {
+            return Ok(MergePrediction {
+                kind: MergeKind::UpToDate,
+                conflicts: vec![],
+                changes: vec![],
+                tree: Some(ours_tree.id().to_string()),
+            });
+        }
+
+        let (kind, mut index) = if lookup.is_descendant_of(theirs.id(), ours.id())? {
+            let mut index = Index::new()?;
+            index.read_tree(&theirs.tree()?)?;
+            (MergeKind::FastForward, index)
+        } else {
+            (MergeKind::Normal, self.repo.merge_commits(&ours, &theirs, None)?)
+        };
+
+        let conflicts = index_conflicts(&index)?;
+        let changes = tree_changes(&ours_tree, &index)?;
+        let tree = if conflicts.is_empty() { Some(index.write_tree_to(&self.repo)?.to_string()) } else { None };
+        Ok(MergePrediction { kind, conflicts, changes, tree })
+    }
+
     /// Perform a fast-forward merge
     fn fast_forward_merge(&self, head: &Reference, target_commit: &git2::Commit) -> Result<()> {
         let target_oid = target_commit.id();
@@ -373,6 +438,52 @@ impl MergeOps {
     }
 }
 
+/// Conflicted paths of an in-memory merge index
+pub(crate) fn index_conflicts(index: &Index) -> Result<Vec<String>> {
+    let mut conflicts = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+        if let Some(entry) = entry {
+            let path = String::from_utf8_lossy(&entry.path).to_string();
+            if !conflicts.contains(&path) {
+                conflicts.push(path);
+            }
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Paths whose content in `index` differs from `base`
+pub(crate) fn tree_changes(base: &Tree, index: &Index) -> Result<Vec<(String, FileStatus)>> {
+    let mut changes = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in index.iter() {
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        let conflicted = (entry.flags >> 12) & 0x3 != 0;
+        match base.get_path(Path::new(&path)) {
+            Ok(old) if !conflicted && old.id() == entry.id && old.filemode() as u32 == entry.mode => {}
+            Ok(_) => changes.push((path, FileStatus::Modified)),
+            Err(_) => changes.push((path, FileStatus::Added)),
+        }
+    }
+
+    base.walk(TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() != Some(ObjectType::Tree) {
+            let path = format!("{}{}", dir, entry.name().unwrap_or(""));
+            if !seen.contains(&path) {
+                changes.push((path, FileStatus::Deleted));
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(changes)
+}
+
 /// Conflict resolution strategy
 #[derive(Debug, Clone, Copy)]
 pub enum ConflictResolution {
@@ -0,0 +1,581 @@
+use anyhow::Result;
+use git2::{Config, Repository as Git2Repository};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+const CONVENTIONAL_SUBJECT: &str = r"^(?P<type>[A-Za-z]+)(?:\((?P<scope>[^()]*)\))?(?P<breaking>!)?: (?P<description>.*)$";
+
+/// Types accepted by the conventional-commit check unless configured otherwise
+pub const DEFAULT_CONVENTIONAL_TYPES: &[&str] = &[
+    "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test",
+];
+
+/// Commit message rules
+///
+/// Read from the `gitup.lint.*` git config keys by [`LintConfig::from_git_config`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintConfig {
+    /// Longest allowed subject, in characters (`gitup.lint.subjectMaxLength`, 0 disables)
+    pub subject_max_length: usize,
+    /// Subjects longer than this only produce a warning (`gitup.lint.subjectSoftLength`)
+    pub subject_soft_length: Option<usize>,
+    /// Require an empty line between subject and body (`gitup.lint.requireBlankLine`)
+    pub require_blank_line: bool,
+    /// Longest body line before a warning (`gitup.lint.bodyMaxLineLength`, 0 disables)
+    pub body_max_line_length: usize,
+    /// Require a conventional-commit subject, `type(scope)!: description` (`gitup.lint.conventional`)
+    pub conventional: bool,
+    /// Allowed conventional-commit types (`gitup.lint.types`, comma separated)
+    pub types: Vec<String>,
+    /// Allowed scopes, empty for any (`gitup.lint.scopes`, comma separated)
+    pub scopes: Vec<String>,
+    /// Require a scope on conventional subjects (`gitup.lint.requireScope`)
+    pub require_scope: bool,
+    /// Trailer keys that must be present, e.g. "Signed-off-by" (`gitup.lint.requiredTrailers`)
+    pub required_trailers: Vec<String>,
+    /// Regex that must match somewhere in the message, e.g. a ticket id (`gitup.lint.ticketPattern`)
+    pub ticket_pattern: Option<String>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            subject_max_length: 72,
+            subject_soft_length: None,
+            require_blank_line: true,
+            body_max_line_length: 72,
+            conventional: false,
+            types: DEFAULT_CONVENTIONAL_TYPES.iter().map(|t| t.to_string()).collect(),
+            scopes: Vec::new(),
+            require_scope: false,
+            required_trailers: Vec::new(),
+            ticket_pattern: None,
+        }
+    }
+}
+
+impl LintConfig {
+    /// Defaults overridden by the `gitup.lint.*` keys of `config`
+    pub fn from_git_config(config: &Config) -> Self {
+        let mut lint = LintConfig::default();
+        let list = |key: &str| -> Option<Vec<String>> {
+            config.get_string(key).ok().map(|value| {
+                value.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+            })
+        };
+
+        if let Ok(value) = config.get_i64("gitup.lint.subjectMaxLength") {
+            lint.subject_max_length = value.max(0) as usize;
+        }
+        if let Ok(value) = config.get_i64("gitup.lint.subjectSoftLength") {
+            lint.subject_soft_length = Some(value).filter(|v| *v > 0).map(|v| v as usize);
+        }
+        if let Ok(value) = config.get_bool("gitup.lint.requireBlankLine") {
+            lint.require_blank_line = value;
+        }
+        if let Ok(value) = config.get_i64("gitup.lint.bodyMaxLineLength") {
+            lint.body_max_line_length = value.max(0) as usize;
+        }
+        if let Ok(value) = config.get_bool("gitup.lint.conventional") {
+            lint.conventional = value;
+        }
+        if let Some(types) = list("gitup.lint.types") {
+            lint.types = types;
+        }
+        if let Some(scopes) = list("gitup.lint.scopes") {
+            lint.scopes = scopes;
+        }
+        if let Ok(value) = config.get_bool("gitup.lint.requireScope") {
+            lint.require_scope = value;
+        }
+        if let Some(trailers) = list("gitup.lint.requiredTrailers") {
+            lint.required_trailers = trailers;
+        }
+        if let Ok(pattern) = config.get_string("gitup.lint.ticketPattern") {
+            lint.ticket_pattern = Some(pattern).filter(|p| !p.is_empty());
+        }
+        lint
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintSeverity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LintRule {
+    EmptySubject,
+    SubjectLength,
+    SubjectTrailingPeriod,
+    BlankLineAfterSubject,
+    BodyLineLength,
+    ConventionalFormat,
+    ConventionalType,
+    ConventionalScope,
+    MissingTrailer,
+    MissingTicket,
+}
+
+impl LintRule {
+    /// Rule name as shown to users, e.g. "subject-length"
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::EmptySubject => "empty-subject",
+            LintRule::SubjectLength => "subject-length",
+            LintRule::SubjectTrailingPeriod => "subject-trailing-period",
+            LintRule::BlankLineAfterSubject => "blank-line-after-subject",
+            LintRule::BodyLineLength => "body-line-length",
+            LintRule::ConventionalFormat => "conventional-format",
+            LintRule::ConventionalType => "conventional-type",
+            LintRule::ConventionalScope => "conventional-scope",
+            LintRule::MissingTrailer => "missing-trailer",
+            LintRule::MissingTicket => "missing-ticket",
+        }
+    }
+}
+
+/// Location of a problem: a byte range within one line of the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintSpan {
+    /// Zero-based line index in the linted text
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A problem found in a commit message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintFinding {
+    pub rule: LintRule,
+    pub severity: LintSeverity,
+    pub message: String,
+    /// `None` for problems with the message as a whole, such as a missing trailer
+    pub span: Option<LintSpan>,
+}
+
+impl LintFinding {
+    pub fn is_error(&self) -> bool {
+        self.severity == LintSeverity::Error
+    }
+}
+
+/// A parsed conventional-commit subject, `type(scope)!: description`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConventionalCommit {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl ConventionalCommit {
+    pub fn parse(subject: &str) -> Option<Self> {
+        let captures = Regex::new(CONVENTIONAL_SUBJECT).ok()?.captures(subject)?;
+        Some(ConventionalCommit {
+            kind: captures["type"].to_string(),
+            scope: captures.name("scope").map(|m| m.as_str().to_string()),
+            breaking: captures.name("breaking").is_some(),
+            description: captures["description"].to_string(),
+        })
+    }
+
+    /// Format back into a subject line
+    pub fn subject(&self) -> String {
+        format!(
+            "{}{}{}: {}",
+            self.kind,
+            self.scope.as_ref().map(|s| format!("({})", s)).unwrap_or_default(),
+            if self.breaking { "!" } else { "" },
+            self.description
+        )
+    }
+}
+
+/// Check a commit message against `config`
+///
+/// Lines starting with `#` are treated as comments and ignored, as git does
+/// when cleaning up an edited message.
+pub fn lint_message(message: &str, config: &LintConfig) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    let lines: Vec<(usize, &str)> = message.lines().enumerate().collect();
+
+    let span = |line: usize, start: usize, end: usize| Some(LintSpan { line, start, end });
+    let mut push = |rule: LintRule, severity: LintSeverity, message: String, span: Option<LintSpan>| {
+        findings.push(LintFinding { rule, severity, message, span });
+    };
+
+    // The subject is the first non-empty line
+    let Some(subject_pos) = lines.iter().position(|(_, line)| !line.trim().is_empty()) else {
+        push(LintRule::EmptySubject, LintSeverity::Error, "Commit message is empty".to_string(), None);
+        return findings;
+    };
+    let (subject_line, subject) = lines[subject_pos];
+
+    let subject_chars = subject.chars().count();
+    if config.subject_max_length > 0 && subject_chars > config.subject_max_length {
+        let start = char_offset(subject, config.subject_max_length);
+        push(
+            LintRule::SubjectLength,
+            LintSeverity::Error,
+            format!("Subject is {} characters long, the limit is {}", subject_chars, config.subject_max_length),
+            span(subject_line, start, subject.len()),
+        );
+    } else if let Some(soft) = config.subject_soft_length.filter(|soft| subject_chars > *soft) {
+        push(
+            LintRule::SubjectLength,
+            LintSeverity::Warning,
+            format!("Subject is {} characters long, keep it under {}", subject_chars, soft),
+            span(subject_line, char_offset(subject, soft), subject.len()),
+        );
+    }
+
+    if subject.trim_end().ends_with('.') {
+        let end = subject.trim_end().len();
+        push(
+            LintRule::SubjectTrailingPeriod,
+            LintSeverity::Warning,
+            "Subject ends with a period".to_string(),
+            span(subject_line, end - 1, end),
+        );
+    }
+
+    if config.require_blank_line {
+        if let Some((line, text)) = lines.get(subject_pos + 1).filter(|(_, text)| !text.trim().is_empty()) {
+            push(
+                LintRule::BlankLineAfterSubject,
+                LintSeverity::Error,
+                "The second line must be empty".to_string(),
+                span(*line, 0, text.len()),
+            );
+        }
+    }
+
+    let trailers = parse_trailers(message);
+    if config.body_max_line_length > 0 {
+        for (line, text) in &lines[subject_pos + 1..] {
+            let chars = text.chars().count();
+            // Long unbreakable lines such as URLs cannot be wrapped
+            let breakable = text.trim().contains(char::is_whitespace);
+            let is_trailer = trailers.iter().any(|(key, _)| text.starts_with(&format!("{}:", key)));
+            if chars > config.body_max_line_length && breakable && !is_trailer {
+                push(
+                    LintRule::BodyLineLength,
+                    LintSeverity::Warning,
+                    format!("Body line is {} characters long, wrap at {}", chars, config.body_max_line_length),
+                    span(*line, char_offset(text, config.body_max_line_length), text.len()),
+                );
+            }
+        }
+    }
+
+    if config.conventional {
+        match Regex::new(CONVENTIONAL_SUBJECT).ok().and_then(|re| re.captures(subject)) {
+            None => push(
+                LintRule::ConventionalFormat,
+                LintSeverity::Error,
+                "Subject must look like 'type(scope): description'".to_string(),
+                span(subject_line, 0, subject.len()),
+            ),
+            Some(captures) => {
+                let kind = captures.name("type").unwrap();
+                if !config.types.is_empty() && !config.types.iter().any(|t| t == kind.as_str()) {
+                    push(
+                        LintRule::ConventionalType,
+                        LintSeverity::Error,
+                        format!("Unknown type '{}', expected one of: {}", kind.as_str(), config.types.join(", ")),
+                        span(subject_line, kind.start(), kind.end()),
+                    );
+                }
+
+                match captures.name("scope") {
+                    None if config.require_scope => push(
+                        LintRule::ConventionalScope,
+                        LintSeverity::Error,
+                        "A scope is required, e.g. 'fix(parser): ...'".to_string(),
+                        span(subject_line, kind.start(), kind.end()),
+                    ),
+                    Some(scope) if !config.scopes.is_empty() && !config.scopes.iter().any(|s| s == scope.as_str()) => push(
+                        LintRule::ConventionalScope,
+                        LintSeverity::Error,
+                        format!("Unknown scope '{}', expected one of: {}", scope.as_str(), config.scopes.join(", ")),
+                        span(subject_line, scope.start(), scope.end()),
+                    ),
+                    _ => {}
+                }
+
+                let description = captures.name("description").unwrap();
+                if description.as_str().trim().is_empty() {
+                    push(
+                        LintRule::ConventionalFormat,
+                        LintSeverity::Error,
+                        "The description after ':' is empty".to_string(),
+                        span(subject_line, description.start(), subject.len()),
+                    );
+                }
+            }
+        }
+    }
+
+    for required in &config.required_trailers {
+        if !trailers.iter().any(|(key, _)| key.eq_ignore_ascii_case(required)) {
+            push(
+                LintRule::MissingTrailer,
+                LintSeverity::Error,
+                format!("Missing '{}:' trailer", required),
+                None,
+            );
+        }
+    }
+
+    if let Some(pattern) = &config.ticket_pattern {
+        match Regex::new(pattern) {
+            Ok(re) => {
+                let text: Vec<&str> = lines.iter().map(|(_, line)| *line).collect();
+                if !re.is_match(&text.join("\n")) {
+                    push(
+                        LintRule::MissingTicket,
+                        LintSeverity::Error,
+                        format!("No ticket reference matching '{}'", pattern),
+                        None,
+                    );
+                }
+            }
+            Err(e) => push(
+                LintRule::MissingTicket,
+                LintSeverity::Warning,
+                format!("Invalid ticket pattern '{}': {}", pattern, e),
+                None,
+            ),
+        }
+    }
+
+    findings
+}
+
+/// Byte offset of the character at index `chars`
+fn char_offset(text: &str, chars: usize) -> usize {
+    text.char_indices().nth(chars).map(|(i, _)| i).unwrap_or(text.len())
+}
+
+/// Strip surplus whitespace, as `git commit -m` does
+///
+/// Lines starting with `#` are kept; only messages edited in the editor
+/// have comments stripped.
+pub fn clean_message(message: &str) -> Result<String> {
+    Ok(git2::message_prettify(message, None)?)
+}
+
+/// Trailers (`Key: value` lines in the last paragraph) of a message
+pub fn parse_trailers(message: &str) -> Vec<(String, String)> {
+    match git2::message_trailers_strs(message) {
+        Ok(trailers) => trailers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Add a trailer, unless the message already has the same one
+pub fn add_trailer(message: &str, key: &str, value: &str) -> String {
+    let trailers = parse_trailers(message);
+    let body = message.trim_end();
+    if trailers.iter().any(|(k, v)| k.eq_ignore_ascii_case(key) && v == value) {
+        return format!("{}\n", body);
+    }
+
+    // Join an existing trailer block, otherwise start a new paragraph
+    let separator = if trailers.is_empty() { "\n\n" } else { "\n" };
+    format!("{}{}{}: {}\n", body, separator, key, value)
+}
+
+/// Remove all trailers with `key`
+pub fn remove_trailer(message: &str, key: &str) -> String {
+    let trailers = parse_trailers(message);
+    if !trailers.iter().any(|(k, _)| k.eq_ignore_ascii_case(key)) {
+        return message.to_string();
+    }
+
+    // Trailers live in the last paragraph
+    let body = message.trim_end();
+    let (head, block) = match body.rfind("\n\n") {
+        Some(pos) => (&body[..pos], &body[pos + 2..]),
+        None => ("", body),
+    };
+    let prefix = format!("{}:", key.to_lowercase());
+    let kept: Vec<&str> = block.lines()
+        .filter(|line| !line.to_lowercase().starts_with(&prefix))
+        .collect();
+
+    if kept.is_empty() {
+        format!("{}\n", head.trim_end())
+    } else {
+        format!("{}\n\n{}\n", head, kept.join("\n"))
+    }
+}
+
+/// Add a `Signed-off-by` trailer
+pub fn sign_off(message: &str, name: &str, email: &str) -> String {
+    add_trailer(message, "Signed-off-by", &format!("{} <{}>", name, email))
+}
+
+/// Add a `Co-authored-by` trailer
+pub fn co_authored_by(message: &str, name: &str, email: &str) -> String {
+    add_trailer(message, "Co-authored-by", &format!("{} <{}>", name, email))
+}
+
+/// Commit message support for a repository: template, lint rules and editor
+pub struct MessageOps<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> MessageOps<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        MessageOps { repo }
+    }
+
+    /// Contents of the `commit.template` file, if one is configured
+    ///
+    /// Relative paths are resolved against the working tree.
+    pub fn template(&self) -> Result<Option<String>> {
+        let config = self.repo.config()?;
+        let path = match config.get_path("commit.template") {
+            Ok(path) => path,
+            Err(_) => return Ok(None),
+        };
+        let path = match (path.is_relative(), self.repo.workdir()) {
+            (true, Some(workdir)) => workdir.join(path),
+            _ => path,
+        };
+
+        std::fs::read_to_string(&path)
+            .map(Some)
+            .map_err(|e| anyhow::anyhow!("Could not read commit message template {}: {}", path.display(), e))
+    }
+
+    /// Lint rules from the repository's git config
+    pub fn lint_config(&self) -> Result<LintConfig> {
+        Ok(LintConfig::from_git_config(&self.repo.config()?))
+    }
+
+    /// Lint a message with the repository's rules
+    pub fn lint(&self, message: &str) -> Result<Vec<LintFinding>> {
+        Ok(lint_message(message, &self.lint_config()?))
+    }
+
+    /// Editor command for commit messages, looked up like git does:
+    /// `GIT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR`, then `vi`
+    pub fn editor(&self) -> Result<String> {
+        if let Ok(editor) = std::env::var("GIT_EDITOR") {
+            return Ok(editor);
+        }
+        if let Ok(editor) = self.repo.config()?.get_string("core.editor") {
+            return Ok(editor);
+        }
+        Ok(std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string()))
+    }
+
+    /// File used to edit commit messages (`.git/COMMIT_EDITMSG`)
+    pub fn edit_message_path(&self) -> PathBuf {
+        self.repo.path().join("COMMIT_EDITMSG")
+    }
+
+    /// Full message of the HEAD commit
+    pub fn head_message(&self) -> Result<String> {
+        let commit = self.repo.head()?.peel_to_commit()?;
+        Ok(commit.message().unwrap_or("").to_string())
+    }
+
+    /// Let the user edit `initial` in their editor and return the cleaned-up result
+    ///
+    /// Fails when the editor exits with an error or the message is left empty.
+    pub fn edit(&self, initial: &str) -> Result<String> {
+        let path = self.edit_message_path();
+        let mut content = initial.to_string();
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(
+            "\n# Please enter the commit message for your changes. Lines starting\n\
+             # with '#' will be ignored, and an empty message aborts the commit.\n",
+        );
+        std::fs::write(&path, content)?;
+
+        let editor = self.editor()?;
+        let status = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", editor))
+            .arg(&editor)
+            .arg(&path)
+            .status()
+            .map_err(|e| anyhow::anyhow!("Could not run editor '{}': {}", editor, e))?;
+        if !status.success() {
+            return Err(anyhow::anyhow!("Editor '{}' exited with {}", editor, status));
+        }
+
+        let message = git2::message_prettify(std::fs::read_to_string(&path)?, Some(b'#'))?;
+        if message.trim().is_empty() {
+            return Err(anyhow::anyhow!("Aborting commit due to empty commit message"));
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_rules_and_spans() {
+        let config = LintConfig { conventional: true, scopes: vec!["core".to_string()], ..Default::default() };
+
+        assert!(lint_message("feat(core): add thing\n\nBody text.\n", &config).is_empty());
+
+        let findings = lint_message("feature(ui): add thing.\nno blank line\n# comment\n", &config);
+        let rules: Vec<LintRule> = findings.iter().map(|f| f.rule).collect();
+        assert_eq!(rules, vec![
+            LintRule::SubjectTrailingPeriod,
+            LintRule::BlankLineAfterSubject,
+            LintRule::ConventionalType,
+            LintRule::ConventionalScope,
+        ]);
+        assert_eq!(findings[2].span, Some(LintSpan { line: 0, start: 0, end: 7 }));
+        assert_eq!(findings[3].span, Some(LintSpan { line: 0, start: 8, end: 10 }));
+
+        let long = "x".repeat(80);
+        let findings = lint_message(&long, &LintConfig::default());
+        assert_eq!(findings[0].rule, LintRule::SubjectLength);
+        assert_eq!(findings[0].span, Some(LintSpan { line: 0, start: 72, end: 80 }));
+
+        let config = LintConfig {
+            required_trailers: vec!["Signed-off-by".to_string()],
+            ticket_pattern: Some(r"[A-Z]+-\d+".to_string()),
+            ..Default::default()
+        };
+        let rules: Vec<LintRule> = lint_message("Fix bug\n", &config).iter().map(|f| f.rule).collect();
+        assert_eq!(rules, vec![LintRule::MissingTrailer, LintRule::MissingTicket]);
+        assert!(lint_message("Fix bug\n\nRefs: ABC-12\nSigned-off-by: A <a@b.c>\n", &config).is_empty());
+    }
+
+    #[test]
+    fn test_trailers_and_conventional_parse() {
+        let message = sign_off("Fix bug\n\nSome body.\n", "Ann", "ann@example.com");
+        assert_eq!(message, "Fix bug\n\nSome body.\n\nSigned-off-by: Ann <ann@example.com>\n");
+
+        let message = co_authored_by(&message, "Bo", "bo@example.com");
+        assert!(message.ends_with("Signed-off-by: Ann <ann@example.com>\nCo-authored-by: Bo <bo@example.com>\n"));
+        assert_eq!(sign_off(&message, "Ann", "ann@example.com"), message);
+        assert_eq!(parse_trailers(&message).len(), 2);
+
+        let message = remove_trailer(&message, "signed-off-by");
+        assert_eq!(message, "Fix bug\n\nSome body.\n\nCo-authored-by: Bo <bo@example.com>\n");
+        assert_eq!(remove_trailer(&message, "Co-authored-by"), "Fix bug\n\nSome body.\n");
+
+        let parsed = ConventionalCommit::parse("fix(api)!: drop v1").unwrap();
+        assert_eq!((parsed.kind.as_str(), parsed.scope.as_deref(), parsed.breaking), ("fix", Some("api"), true));
+        assert_eq!(parsed.subject(), "fix(api)!: drop v1");
+        assert!(ConventionalCommit::parse("Fix the api").is_none());
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use git2::{Oid, Repository as Git2Repository};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Notes ref used when `core.notesRef` is not set
+pub const DEFAULT_NOTES_REF: &str = "refs/notes/commits";
+
+/// A note attached to a commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteInfo {
+    pub commit: String,
+    /// Blob holding the note
+    pub note_id: String,
+    pub message: String,
+}
+
+/// Notes on commits, kept under one notes ref
+pub struct NoteOps<'a> {
+    repo: &'a Git2Repository,
+    notes_ref: String,
+}
+
+impl<'a> NoteOps<'a> {
+    /// Notes in `core.notesRef`, or `refs/notes/commits`
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        let notes_ref = repo.config()
+            .and_then(|config| config.get_string("core.notesRef"))
+            .unwrap_or_else(|_| DEFAULT_NOTES_REF.to_string());
+        NoteOps { repo, notes_ref }
+    }
+
+    /// Notes in another ref; a short name like "ci" means `refs/notes/ci`
+    pub fn with_ref(repo: &'a Git2Repository, notes_ref: &str) -> Self {
+        NoteOps { repo, notes_ref: full_notes_ref(notes_ref) }
+    }
+
+    pub fn notes_ref(&self) -> &str {
+        &self.notes_ref
+    }
+
+    /// All notes refs in the repository
+    pub fn list_refs(&self) -> Result<Vec<String>> {
+        let mut refs = Vec::new();
+        for reference in self.repo.references_glob("refs/notes/*")? {
+            if let Some(name) = reference?.name() {
+                refs.push(name.to_string());
+            }
+        }
+        refs.sort();
+        Ok(refs)
+    }
+
+    /// Every note in the ref
+    pub fn list(&self) -> Result<Vec<NoteInfo>> {
+        let notes = match self.repo.notes(Some(&self.notes_ref)) {
+            Ok(notes) => notes,
+            Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut infos = Vec::new();
+        for note in notes {
+            let (note_id, commit) = note?;
+            let blob = self.repo.find_blob(note_id)?;
+            infos.push(NoteInfo {
+                commit: commit.to_string(),
+                note_id: note_id.to_string(),
+                message: String::from_utf8_lossy(blob.content()).to_string(),
+            });
+        }
+        Ok(infos)
+    }
+
+    /// The note on a commit, if any
+    pub fn read(&self, commit_ref: &str) -> Result<Option<String>> {
+        let commit = self.resolve(commit_ref)?;
+        self.read_oid(commit)
+    }
+
+    fn read_oid(&self, commit: Oid) -> Result<Option<String>> {
+        match self.repo.find_note(Some(&self.notes_ref), commit) {
+            Ok(note) => Ok(Some(note.message().unwrap_or("").to_string())),
+            Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Notes for many commits at once, keyed by commit id
+    pub fn read_many(&self, commits: &[String]) -> Result<HashMap<String, String>> {
+        let mut notes = HashMap::new();
+        if self.repo.find_reference(&self.notes_ref).is_err() {
+            return Ok(notes);
+        }
+        for commit in commits {
+            if let Some(note) = self.read_oid(Oid::from_str(commit)?)? {
+                notes.insert(commit.clone(), note);
+            }
+        }
+        Ok(notes)
+    }
+
+    /// Add a note, replacing an existing one only when `force` is set
+    pub fn add(&self, commit_ref: &str, message: &str, force: bool) -> Result<String> {
+        let commit = self.resolve(commit_ref)?;
+        if !force && self.read_oid(commit)?.is_some() {
+            return Err(anyhow::anyhow!(
+                "Commit {} already has a note in {}, edit or append to it instead",
+                &commit.to_string()[..8], self.notes_ref
+            ));
+        }
+        self.write(commit, message)
+    }
+
+    /// Replace the note on a commit, creating it if needed
+    pub fn edit(&self, commit_ref: &str, message: &str) -> Result<String> {
+        let commit = self.resolve(commit_ref)?;
+        self.write(commit, message)
+    }
+
+    /// Add a paragraph to the note on a commit, creating it if needed
+    pub fn append(&self, commit_ref: &str, message: &str) -> Result<String> {
+        let commit = self.resolve(commit_ref)?;
+        let message = match self.read_oid(commit)? {
+            Some(existing) if !existing.trim().is_empty() => format!("{}\n\n{}", existing.trim_end(), message),
+            _ => message.to_string(),
+        };
+        self.write(commit, &message)
+    }
+
+    /// Remove the note on a commit
+    pub fn remove(&self, commit_ref: &str) -> Result<()> {
+        let commit = self.resolve(commit_ref)?;
+        if self.read_oid(commit)?.is_none() {
+            return Err(anyhow::anyhow!("Commit {} has no note in {}", &commit.to_string()[..8], self.notes_ref));
+        }
+        let sig = self.get_signature()?;
+        self.repo.note_delete(commit, Some(&self.notes_ref), &sig, &sig)?;
+        Ok(())
+    }
+
+    fn write(&self, commit: Oid, message: &str) -> Result<String> {
+        let message = if message.ends_with('\n') { message.to_string() } else { format!("{}\n", message) };
+        let sig = self.get_signature()?;
+        let note = self.repo.note(&sig, &sig, Some(&self.notes_ref), commit, &message, true)?;
+        Ok(note.to_string())
+    }
+
+    fn resolve(&self, commit_ref: &str) -> Result<Oid> {
+        let object = self.repo.revparse_single(commit_ref)?;
+        Ok(object.peel_to_commit()
+            .map_err(|e| anyhow::anyhow!("Failed to resolve '{}' to a commit: {}", commit_ref, e))?
+            .id())
+    }
+
+    fn get_signature(&self) -> Result<git2::Signature<'static>> {
+        let config = self.repo.config()?;
+
+        let name = config.get_string("user.name")
+            .unwrap_or_else(|_| "GitUp User".to_string());
+        let email = config.get_string("user.email")
+            .unwrap_or_else(|_| "gitup@local".to_string());
+
+        Ok(git2::Signature::now(&name, &email)?)
+    }
+}
+
+/// `refs/notes/<name>` for a short notes ref name
+pub fn full_notes_ref(name: &str) -> String {
+    if name.starts_with("refs/") {
+        name.to_string()
+    } else {
+        format!("refs/notes/{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::tests::{make_commit};
+    use crate::repository::Repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_notes_read_write_and_transfer() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+        let first = make_commit(&repo, dir, "a.txt", "a\n", "A");
+        let second = make_commit(&repo, dir, "b.txt", "b\n", "B");
+
+        let notes = repo.notes_ops();
+        assert_eq!(notes.notes_ref(), "refs/notes/commits");
+        assert!(notes.list().unwrap().is_empty());
+        notes.add(&first, "reviewed", false).unwrap();
+        assert!(notes.add(&first, "again", false).is_err());
+        notes.append(&first, "approved").unwrap();
+        assert_eq!(notes.read(&first).unwrap().as_deref(), Some("reviewed\n\napproved\n"));
+        notes.edit("HEAD", "tip").unwrap();
+        assert_eq!(notes.list().unwrap().len(), 2);
+
+        // Notes come with the history on request only
+        let commits = repo.get_commits_with_notes(10, None).unwrap();
+        assert_eq!(commits[0].notes.as_deref(), Some("tip\n"));
+        assert!(repo.get_commits_with_parents(10).unwrap().iter().all(|c| c.notes.is_none()));
+
+        // A separate ref for CI results
+        let ci = repo.notes_ops_for("ci");
+        ci.add(&second, "build: passed", false).unwrap();
+        assert_eq!(ci.notes_ref(), "refs/notes/ci");
+        assert_eq!(repo.notes_ops().list_refs().unwrap(), vec!["refs/notes/ci", "refs/notes/commits"]);
+        let commits = repo.get_commits_with_notes(10, Some("ci")).unwrap();
+        assert_eq!(commits[0].notes.as_deref(), Some("build: passed\n"));
+        assert!(commits[1].notes.is_none());
+
+        notes.remove(&first).unwrap();
+        assert!(notes.read(&first).unwrap().is_none());
+        assert!(notes.remove(&first).is_err());
+
+        // Push to a bare remote, fetch into a clone
+        let bare_path = temp_dir.path().join("remote.git");
+        git2::Repository::init_bare(&bare_path).unwrap();
+        let url = format!("file://{}", bare_path.display());
+        repo.add_remote("origin", &url).unwrap();
+        let head = repo.git_repo.head().unwrap().name().unwrap().to_string();
+        repo.remote_ops().push("origin", &[&format!("{0}:{0}", head)], None).unwrap();
+        repo.push_notes("origin", "ci").unwrap();
+
+        let clone_dir = TempDir::new().unwrap();
+        let clone = Repository::clone_from(&url, clone_dir.path().join("c"), &Default::default(), None).unwrap();
+        assert!(clone.notes_ops_for("ci").read(&second).unwrap().is_none());
+        let report = clone.fetch_notes("origin", "*").unwrap();
+        assert!(report.updates.iter().any(|u| u.name == "refs/notes/ci"));
+        assert_eq!(clone.notes_ops_for("ci").read(&second).unwrap().as_deref(), Some("build: passed\n"));
+
+        // Notes added on both sides diverge; the local ones are kept and the ref reported
+        repo.notes_ops_for("ci").add(&first, "build: flaky\n", false).unwrap();
+        repo.push_notes("origin", "ci").unwrap();
+        clone.notes_ops_for("ci").add(&first, "build: rerun\n", false).unwrap();
+        let report = clone.fetch_notes("origin", "ci").unwrap();
+        let update = report.updates.iter().find(|u| u.name == "refs/notes/ci").unwrap();
+        assert_eq!(update.kind, crate::remote::RefUpdateKind::Rejected);
+        assert_eq!(clone.notes_ops_for("ci").read(&first).unwrap().as_deref(), Some("build: rerun\n"));
+    }
+}
@@ -0,0 +1,386 @@
+use anyhow::Result;
+use git2::{DiffOptions, Oid, Patch, Repository as Git2Repository, RevparseMode, Sort};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Pairs whose changed lines are less similar than this are reported as a
+/// removal plus an addition rather than a modification
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// How a commit of the old series relates to the new one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RangeDiffStatus {
+    /// Same message and patch
+    Unchanged,
+    /// Paired, but the message or the patch changed
+    Modified,
+    /// Only in the new series
+    Added,
+    /// Only in the old series
+    Removed,
+}
+
+impl RangeDiffStatus {
+    /// Marker used by `git range-diff`
+    pub fn symbol(&self) -> char {
+        match self {
+            RangeDiffStatus::Unchanged => '=',
+            RangeDiffStatus::Modified => '!',
+            RangeDiffStatus::Added => '>',
+            RangeDiffStatus::Removed => '<',
+        }
+    }
+}
+
+/// A commit of one of the two series, with its 1-based position
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeDiffCommit {
+    pub position: usize,
+    pub id: String,
+    pub summary: String,
+}
+
+/// One line of the diff between two versions of a patch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeDiffLine {
+    /// '-' old version only, '+' new version only, ' ' both
+    pub origin: char,
+    pub content: String,
+}
+
+/// An old commit and its new version, or a commit only in one series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeDiffPair {
+    pub status: RangeDiffStatus,
+    pub old: Option<RangeDiffCommit>,
+    pub new: Option<RangeDiffCommit>,
+    /// Diff of the two patches (message included); empty unless `Modified`
+    pub diff: Vec<RangeDiffLine>,
+}
+
+/// Comparison of two versions of a patch series, in new-series order with
+/// removed commits placed after their old predecessor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeDiff {
+    pub old_range: String,
+    pub new_range: String,
+    pub pairs: Vec<RangeDiffPair>,
+}
+
+impl fmt::Display for RangeDiff {
+    /// `git range-diff` style listing, e.g. `1:  1a2b3c4 ! 1:  5d6e7f8 Subject`
+    /// followed by the indented diff of modified pairs
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.pairs.iter()
+            .flat_map(|p| [&p.old, &p.new])
+            .flatten()
+            .map(|c| c.position.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let side = |commit: &Option<RangeDiffCommit>| match commit {
+            Some(c) => format!("{:>width$}:  {}", c.position, &c.id[..7]),
+            None => format!("{:>width$}:  {}", "-", "-------"),
+        };
+
+        for pair in &self.pairs {
+            let summary = pair.new.as_ref().or(pair.old.as_ref()).map(|c| c.summary.as_str()).unwrap_or("");
+            writeln!(f, "{} {} {} {}", side(&pair.old), pair.status.symbol(), side(&pair.new), summary)?;
+            for line in &pair.diff {
+                writeln!(f, "    {}{}", line.origin, line.content)?;
+            }
+            if !pair.diff.is_empty() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A commit with its patch rendered for comparison
+struct SeriesCommit {
+    id: Oid,
+    summary: String,
+    patch_id: Option<Oid>,
+    /// Message and patch, without line numbers or blob ids
+    text: Vec<String>,
+    /// Added and removed lines, for similarity
+    changes: HashMap<String, usize>,
+}
+
+/// Range-diff between two versions of a branch
+pub struct RangeDiffOps<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> RangeDiffOps<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        RangeDiffOps { repo }
+    }
+
+    /// Compare two ranges, each `A..B`
+    pub fn compare(&self, old_range: &str, new_range: &str) -> Result<RangeDiff> {
+        let old = self.range(old_range)?;
+        let new = self.range(new_range)?;
+        self.compare_commits(old, new, old_range, new_range)
+    }
+
+    /// Compare two tips of a branch, e.g. before and after a rebase, each
+    /// from their merge base
+    pub fn compare_tips(&self, old_tip: &str, new_tip: &str) -> Result<RangeDiff> {
+        let old = self.repo.revparse_single(old_tip)?.peel_to_commit()?.id();
+        let new = self.repo.revparse_single(new_tip)?.peel_to_commit()?.id();
+        let base = self.repo.merge_base(old, new).ok();
+        let old_commits = self.walk(old, base)?;
+        let new_commits = self.walk(new, base)?;
+        self.compare_commits(old_commits, new_commits, old_tip, new_tip)
+    }
+
+    /// Commits of `A..B`, oldest first
+    fn range(&self, spec: &str) -> Result<Vec<Oid>> {
+        let revspec = self.repo.revparse(spec)?;
+        if !revspec.mode().contains(RevparseMode::RANGE) {
+            return Err(anyhow::anyhow!("'{}' is not a range (A..B)", spec));
+        }
+        let from = revspec.from().map(|o| o.peel_to_commit()).transpose()?.map(|c| c.id());
+        let to = revspec.to()
+            .ok_or_else(|| anyhow::anyhow!("'{}' has no end", spec))?
+            .peel_to_commit()?.id();
+        self.walk(to, from)
+    }
+
+    fn walk(&self, tip: Oid, hide: Option<Oid>) -> Result<Vec<Oid>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        revwalk.push(tip)?;
+        if let Some(hide) = hide {
+            revwalk.hide(hide)?;
+        }
+        Ok(revwalk.collect::<std::result::Result<Vec<_>, _>>()?)
+    }
+
+    fn compare_commits(&self, old: Vec<Oid>, new: Vec<Oid>, old_range: &str, new_range: &str) -> Result<RangeDiff> {
+        let old = old.into_iter().map(|id| self.series_commit(id)).collect::<Result<Vec<_>>>()?;
+        let new = new.into_iter().map(|id| self.series_commit(id)).collect::<Result<Vec<_>>>()?;
+
+        // Identical patches pair first, then the most similar remaining ones
+        let mut old_match: Vec<Option<usize>> = vec![None; old.len()];
+        let mut new_match: Vec<Option<usize>> = vec![None; new.len()];
+        for (j, n) in new.iter().enumerate() {
+            let same = old.iter().enumerate()
+                .position(|(i, o)| old_match[i].is_none() && o.patch_id.is_some() && o.patch_id == n.patch_id);
+            if let Some(i) = same {
+                old_match[i] = Some(j);
+                new_match[j] = Some(i);
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for (i, o) in old.iter().enumerate().filter(|(i, _)| old_match[*i].is_none()) {
+            for (j, n) in new.iter().enumerate().filter(|(j, _)| new_match[*j].is_none()) {
+                let score = similarity(&o.changes, &n.changes);
+                if score >= MIN_SIMILARITY {
+                    candidates.push((score, i, j));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+        for (_, i, j) in candidates {
+            if old_match[i].is_none() && new_match[j].is_none() {
+                old_match[i] = Some(j);
+                new_match[j] = Some(i);
+            }
+        }
+
+        let describe = |commits: &[SeriesCommit], i: usize| RangeDiffCommit {
+            position: i + 1,
+            id: commits[i].id.to_string(),
+            summary: commits[i].summary.clone(),
+        };
+        let removed = |i: usize| RangeDiffPair {
+            status: RangeDiffStatus::Removed,
+            old: Some(describe(&old, i)),
+            new: None,
+            diff: vec![],
+        };
+
+        let mut pairs = Vec::new();
+        let mut next_old = 0;
+        for (j, matched) in new_match.iter().enumerate() {
+            match *matched {
+                Some(i) => {
+                    // Removed commits that came before this one in the old series
+                    while next_old < i {
+                        if old_match[next_old].is_none() {
+                            pairs.push(removed(next_old));
+                        }
+                        next_old += 1;
+                    }
+                    next_old = next_old.max(i + 1);
+
+                    let diff = if old[i].text == new[j].text { vec![] } else { diff_lines(&old[i].text, &new[j].text)? };
+                    pairs.push(RangeDiffPair {
+                        status: if diff.is_empty() { RangeDiffStatus::Unchanged } else { RangeDiffStatus::Modified },
+                        old: Some(describe(&old, i)),
+                        new: Some(describe(&new, j)),
+                        diff,
+                    });
+                }
+                None => pairs.push(RangeDiffPair {
+                    status: RangeDiffStatus::Added,
+                    old: None,
+                    new: Some(describe(&new, j)),
+                    diff: vec![],
+                }),
+            }
+        }
+        for (i, _) in old_match.iter().enumerate().skip(next_old).filter(|(_, m)| m.is_none()) {
+            pairs.push(removed(i));
+        }
+
+        Ok(RangeDiff {
+            old_range: old_range.to_string(),
+            new_range: new_range.to_string(),
+            pairs,
+        })
+    }
+
+    fn series_commit(&self, id: Oid) -> Result<SeriesCommit> {
+        let commit = self.repo.find_commit(id)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let mut opts = DiffOptions::new();
+        opts.context_lines(3);
+        let diff = self.repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), Some(&mut opts))?;
+
+        let mut text = vec!["## Commit message ##".to_string()];
+        text.extend(commit.message().unwrap_or("").trim_end().lines().map(|line| format!("    {}", line)));
+        let mut changes = HashMap::new();
+        for i in 0..diff.deltas().len() {
+            let Some(patch) = Patch::from_diff(&diff, i)? else { continue };
+            let delta = patch.delta();
+            let path = delta.new_file().path().or(delta.old_file().path())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default();
+            text.push(format!("## {} ##", path));
+            for h in 0..patch.num_hunks() {
+                // Hunk headers without line numbers, which shift with every rebase
+                let (hunk, lines) = patch.hunk(h)?;
+                let header = String::from_utf8_lossy(hunk.header());
+                let section = header.rsplit("@@").next().unwrap_or("").trim();
+                text.push(format!("@@ {}", section).trim_end().to_string());
+                for l in 0..lines {
+                    let line = patch.line_in_hunk(h, l)?;
+                    let content = String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string();
+                    let origin = line.origin();
+                    if origin == '+' || origin == '-' {
+                        *changes.entry(format!("{}{}", origin, content)).or_insert(0) += 1;
+                    }
+                    text.push(format!("{}{}", origin, content));
+                }
+            }
+        }
+
+        let patch_id = if commit.parent_count() > 1 { None } else { Some(diff.patchid(None)?) };
+        Ok(SeriesCommit {
+            id,
+            summary: commit.summary().unwrap_or("").to_string(),
+            patch_id,
+            text,
+            changes,
+        })
+    }
+}
+
+/// Dice coefficient of two multisets of changed lines
+fn similarity(a: &HashMap<String, usize>, b: &HashMap<String, usize>) -> f64 {
+    let total: usize = a.values().sum::<usize>() + b.values().sum::<usize>();
+    if total == 0 {
+        return 1.0;
+    }
+    let common: usize = a.iter().map(|(line, n)| (*n).min(*b.get(line).unwrap_or(&0))).sum();
+    2.0 * common as f64 / total as f64
+}
+
+/// Line diff of two rendered patches
+fn diff_lines(old: &[String], new: &[String]) -> Result<Vec<RangeDiffLine>> {
+    let old_text = old.join("\n") + "\n";
+    let new_text = new.join("\n") + "\n";
+    let mut opts = DiffOptions::new();
+    opts.context_lines(3);
+    let patch = Patch::from_buffers(old_text.as_bytes(), None, new_text.as_bytes(), None, Some(&mut opts))?;
+
+    let mut lines = Vec::new();
+    for h in 0..patch.num_hunks() {
+        let (_, count) = patch.hunk(h)?;
+        if h > 0 {
+            lines.push(RangeDiffLine { origin: ' ', content: "...".to_string() });
+        }
+        for l in 0..count {
+            let line = patch.line_in_hunk(h, l)?;
+            let origin = match line.origin() {
+                '+' | '-' => line.origin(),
+                _ => ' ',
+            };
+            lines.push(RangeDiffLine {
+                origin,
+                content: String::from_utf8_lossy(line.content()).trim_end_matches('\n').to_string(),
+            });
+        }
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::{make_commit};
+    use crate::repository::Repository;
+    use crate::rebase::CommitMetadata;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_range_diff_pairs_versions_of_a_series() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let base = make_commit(&repo, dir, "base.txt", "base\n", "Base");
+        repo.create_branch("v1", Some(&base)).unwrap();
+        repo.create_branch("v2", Some(&base)).unwrap();
+
+        repo.checkout_branch("v1").unwrap();
+        make_commit(&repo, dir, "x.txt", "x\n", "Add x");
+        make_commit(&repo, dir, "y.txt", "y\n", "Add y");
+        make_commit(&repo, dir, "z.txt", "1\n2\n3\n4\n", "Add z");
+
+        repo.checkout_branch("v2").unwrap();
+        make_commit(&repo, dir, "x.txt", "x\n", "Add the x file");
+        make_commit(&repo, dir, "z.txt", "1\n2\nthree\n4\n", "Add z");
+        make_commit(&repo, dir, "w.txt", "w\n", "Add w");
+
+        let range_diff = repo.range_diff(&format!("{}..v1", base), &format!("{}..v2", base)).unwrap();
+        let statuses: Vec<_> = range_diff.pairs.iter().map(|p| p.status).collect();
+        assert_eq!(statuses, vec![
+            RangeDiffStatus::Modified, RangeDiffStatus::Removed, RangeDiffStatus::Modified, RangeDiffStatus::Added,
+        ]);
+        let reworded = &range_diff.pairs[0].diff;
+        assert!(reworded.iter().any(|l| l.origin == '-' && l.content == "    Add x"));
+        assert!(reworded.iter().any(|l| l.origin == '+' && l.content == "    Add the x file"));
+        assert_eq!(range_diff.pairs[1].old.as_ref().unwrap().summary, "Add y");
+        let changed = &range_diff.pairs[2].diff;
+        assert!(changed.iter().any(|l| l.origin == '-' && l.content == "+3"));
+        assert!(changed.iter().any(|l| l.origin == '+' && l.content == "+three"));
+        assert!(repo.range_diff("v1", "v2").is_err());
+
+        // Before and after a rewrite, taken from the undo journal
+        let first = repo.resolve_commit("HEAD~2").unwrap();
+        repo.rewrite_commit(&first, &CommitMetadata { message: Some("Add x\n".to_string()), ..Default::default() }).unwrap();
+        let range_diff = repo.range_diff_last_rewrite().unwrap();
+        let statuses: Vec<_> = range_diff.pairs.iter().map(|p| p.status).collect();
+        assert_eq!(statuses, vec![RangeDiffStatus::Modified, RangeDiffStatus::Unchanged, RangeDiffStatus::Unchanged]);
+        assert_eq!(repo.range_diff_tips("v1", "v1").unwrap().pairs.len(), 0);
+    }
+}
@@ -1,10 +1,14 @@
 use anyhow::Result;
 use git2::{
     Repository as Git2Repository, Oid, RebaseOptions,
-    build::CheckoutBuilder, BranchType, Signature,
+    build::CheckoutBuilder, BranchType, Signature, Sort,
 };
+use std::fmt;
 use std::path::Path;
 
+use crate::undo::update_refs;
+use crate::merge::index_conflicts;
+
 /// Rebase result information
 #[derive(Debug, Clone)]
 pub struct RebaseResult {
@@ -22,6 +26,289 @@ pub struct RebaseOperation {
     pub message: String,
 }
 
+/// What to do with a commit when a todo list is replayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TodoAction {
+    Pick,
+    /// Fold into the previous commit, keeping its message
+    Fixup,
+    /// Fold into the previous commit, replacing its message (`fixup -C`)
+    FixupWithMessage,
+    /// Fold into the previous commit, combining both messages
+    Squash,
+    Drop,
+}
+
+impl TodoAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoAction::Pick => "pick",
+            TodoAction::Fixup => "fixup",
+            TodoAction::FixupWithMessage => "fixup -C",
+            TodoAction::Squash => "squash",
+            TodoAction::Drop => "drop",
+        }
+    }
+
+    fn folds(&self) -> bool {
+        matches!(self, TodoAction::Fixup | TodoAction::FixupWithMessage | TodoAction::Squash)
+    }
+}
+
+/// One line of a rebase todo list
+#[derive(Debug, Clone)]
+pub struct TodoItem {
+    pub action: TodoAction,
+    pub commit: String,
+    pub summary: String,
+    /// Message for the resulting commit instead of the original one
+    pub message: Option<String>,
+    /// Author for the resulting commit instead of the original one
+    pub author: Option<Identity>,
+    /// Committer for the resulting commit instead of the current user
+    pub committer: Option<Identity>,
+}
+
+impl TodoItem {
+    fn pick(commit: &git2::Commit) -> Self {
+        TodoItem {
+            action: TodoAction::Pick,
+            commit: commit.id().to_string(),
+            summary: commit.summary().unwrap_or("").to_string(),
+            message: None,
+            author: None,
+            committer: None,
+        }
+    }
+
+    fn rewrites_metadata(&self) -> bool {
+        self.message.is_some() || self.author.is_some() || self.committer.is_some()
+    }
+}
+
+/// Name, email and date of an author or committer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+    /// Unix time
+    pub time: i64,
+    /// UTC offset in minutes
+    pub offset_minutes: i32,
+}
+
+impl Identity {
+    pub fn from_signature(signature: &Signature) -> Self {
+        Identity {
+            name: signature.name().unwrap_or("").to_string(),
+            email: signature.email().unwrap_or("").to_string(),
+            time: signature.when().seconds(),
+            offset_minutes: signature.when().offset_minutes(),
+        }
+    }
+
+    fn to_signature(&self) -> Result<Signature<'static>> {
+        Ok(Signature::new(&self.name, &self.email, &git2::Time::new(self.time, self.offset_minutes))?)
+    }
+}
+
+/// First commit of a rebase that would stop with conflicts
+#[derive(Debug, Clone)]
+pub struct ConflictingCommit {
+    pub id: String,
+    pub summary: String,
+    /// 1-based position among the replayed commits
+    pub position: usize,
+    pub conflicts: Vec<String>,
+}
+
+/// Outcome of a rebase worked out in memory
+#[derive(Debug, Clone)]
+pub struct RebasePrediction {
+    /// Commits that would be replayed (merges are left out, as by `git rebase`)
+    pub commits: usize,
+    pub first_conflict: Option<ConflictingCommit>,
+    /// Tree of the rebased branch, when every commit applies cleanly
+    pub tree: Option<String>,
+}
+
+/// New metadata for one commit; `None` keeps the original value
+#[derive(Debug, Clone, Default)]
+pub struct CommitMetadata {
+    pub message: Option<String>,
+    pub author_name: Option<String>,
+    pub author_email: Option<String>,
+    /// Author date as Unix time and UTC offset in minutes
+    pub author_time: Option<(i64, i32)>,
+    pub committer_name: Option<String>,
+    pub committer_email: Option<String>,
+    /// Committer date as Unix time and UTC offset in minutes
+    pub committer_time: Option<(i64, i32)>,
+}
+
+impl CommitMetadata {
+    fn is_empty(&self) -> bool {
+        self.message.is_none()
+            && self.author_name.is_none() && self.author_email.is_none() && self.author_time.is_none()
+            && self.committer_name.is_none() && self.committer_email.is_none() && self.committer_time.is_none()
+    }
+}
+
+/// Bulk identity rewrite: every commit by `old_email` gets a new name and/or email
+#[derive(Debug, Clone, Default)]
+pub struct IdentityRewrite {
+    /// Matched case-insensitively against author (and committer) emails
+    pub old_email: String,
+    pub new_name: Option<String>,
+    pub new_email: Option<String>,
+    /// Also rewrite the committer of matching commits
+    pub committer: bool,
+}
+
+/// Commits to replay, oldest first
+#[derive(Debug, Clone)]
+pub struct RebaseTodo {
+    /// Commit the list is replayed onto, `None` to start from a new root
+    pub onto: Option<String>,
+    pub items: Vec<TodoItem>,
+}
+
+impl RebaseTodo {
+    /// Move `fixup!`, `squash!` and `amend!` commits right after the commit
+    /// they target and turn them into the matching fold action
+    ///
+    /// Targets are matched by subject or by commit id prefix. Commits whose
+    /// target is not in the list are left as they are.
+    pub fn autosquash(&mut self) {
+        let mut attached: Vec<Vec<TodoItem>> = vec![Vec::new(); self.items.len()];
+        let mut keep = vec![true; self.items.len()];
+
+        for i in 0..self.items.len() {
+            if self.items[i].action != TodoAction::Pick {
+                continue;
+            }
+            let Some((action, target)) = fixup_target(&self.items[i].summary) else {
+                continue;
+            };
+            let found = (0..i).find(|&j| {
+                keep[j] && fixup_target(&self.items[j].summary).is_none()
+                    && matches_target(&self.items[j], target)
+            });
+            if let Some(j) = found {
+                let mut item = self.items[i].clone();
+                item.action = action;
+                attached[j].push(item);
+                keep[i] = false;
+            }
+        }
+
+        let items = std::mem::take(&mut self.items);
+        for ((item, keep), fixups) in items.into_iter().zip(keep).zip(attached) {
+            if keep {
+                self.items.push(item);
+                self.items.extend(fixups);
+            }
+        }
+    }
+}
+
+impl fmt::Display for RebaseTodo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{} {} {}", item.action.name(), &item.commit[..8.min(item.commit.len())], item.summary)?;
+        }
+        Ok(())
+    }
+}
+
+/// Changes of a commit picked for one part of a split
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitSelection {
+    /// All changes to a file
+    File(String),
+    /// One hunk of a file, numbered from 0 as in the commit's diff with default settings
+    Hunk { path: String, index: usize },
+}
+
+impl SplitSelection {
+    pub fn path(&self) -> &str {
+        match self {
+            SplitSelection::File(path) | SplitSelection::Hunk { path, .. } => path,
+        }
+    }
+}
+
+impl std::str::FromStr for SplitSelection {
+    type Err = anyhow::Error;
+
+    /// `path` for a whole file, `path#N` for its N-th hunk
+    fn from_str(s: &str) -> Result<Self> {
+        match s.rsplit_once('#') {
+            Some((path, index)) if !path.is_empty() && !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) => {
+                Ok(SplitSelection::Hunk { path: path.to_string(), index: index.parse()? })
+            }
+            _ if s.is_empty() => Err(anyhow::anyhow!("Empty split selection")),
+            _ => Ok(SplitSelection::File(s.to_string())),
+        }
+    }
+}
+
+/// One commit produced by a split
+#[derive(Debug, Clone)]
+pub struct SplitPart {
+    pub message: String,
+    pub changes: Vec<SplitSelection>,
+}
+
+/// Fold action and target of a `fixup! <subject>` style subject
+///
+/// Nested prefixes (`fixup! fixup! X`) resolve to the innermost subject.
+fn fixup_target(summary: &str) -> Option<(TodoAction, &str)> {
+    let prefixes = [
+        ("fixup! ", TodoAction::Fixup),
+        ("squash! ", TodoAction::Squash),
+        ("amend! ", TodoAction::FixupWithMessage),
+    ];
+    let (action, mut target) = prefixes.iter()
+        .find_map(|(prefix, action)| summary.strip_prefix(prefix).map(|rest| (*action, rest)))?;
+    while let Some(rest) = prefixes.iter().find_map(|(prefix, _)| target.strip_prefix(prefix)) {
+        target = rest;
+    }
+    Some((action, target.trim()))
+}
+
+fn matches_target(item: &TodoItem, target: &str) -> bool {
+    item.summary == target
+        || (target.len() >= 4 && target.chars().all(|c| c.is_ascii_hexdigit()) && item.commit.starts_with(target))
+}
+
+/// Message without its first paragraph, used for `squash!` and `amend!` commits
+fn strip_subject(message: &str) -> &str {
+    match message.split_once("\n\n") {
+        Some((_, body)) => body.trim_start_matches('\n'),
+        None => "",
+    }
+}
+
+/// Where [`RebaseOps::move_commits`] puts the moved commits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveDestination {
+    /// Right after this commit of the current branch
+    After(String),
+    /// On top of another local branch
+    OntoBranch(String),
+}
+
+/// Outcome of replaying a todo list in memory
+enum Replay<'r> {
+    Done {
+        /// Last commit of the new history, `None` if everything was dropped
+        head: Option<git2::Commit<'r>>,
+        rewritten: Vec<String>,
+    },
+    Conflict(RebaseResult),
+}
+
 /// Rebase operations for a repository
 pub struct RebaseOps {
     repo: Git2Repository,
@@ -33,6 +320,11 @@ impl RebaseOps {
         Ok(RebaseOps { repo })
     }
 
+    /// Use an already opened repository
+    pub fn from_repo(repo: Git2Repository) -> Self {
+        RebaseOps { repo }
+    }
+
     /// Start an interactive rebase
     pub fn start_interactive(&self, upstream: &str, onto: Option<&str>) -> Result<RebaseResult> {
         // Get the upstream commit
@@ -288,6 +580,674 @@ impl RebaseOps {
         })
     }
 
+    /// Todo list for the commits in `upstream..HEAD`, or all of HEAD's
+    /// history when `upstream` is `None`
+    pub fn todo(&self, upstream: Option<&str>) -> Result<RebaseTodo> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        revwalk.push_head()?;
+        let onto = match upstream {
+            Some(upstream) => {
+                let commit = self.find_commit_from_ref(upstream)?;
+                revwalk.hide(commit.id())?;
+                Some(commit.id().to_string())
+            }
+            None => None,
+        };
+
+        let mut items = Vec::new();
+        for oid in revwalk {
+            let commit = self.repo.find_commit(oid?)?;
+            if commit.parent_count() > 1 {
+                return Err(anyhow::anyhow!(
+                    "Cannot rewrite history containing merge commit {}",
+                    &commit.id().to_string()[..8]
+                ));
+            }
+            items.push(TodoItem::pick(&commit));
+        }
+
+        Ok(RebaseTodo { onto, items })
+    }
+
+    /// Fold `fixup!`/`squash!`/`amend!` commits into their targets
+    ///
+    /// Without `upstream`, HEAD's history is rewritten back to the oldest
+    /// commit targeted by a fixup.
+    pub fn autosquash(&self, upstream: Option<&str>) -> Result<RebaseResult> {
+        let upstream = match upstream {
+            Some(upstream) => Some(upstream.to_string()),
+            None => match self.autosquash_base()? {
+                Some(base) => base,
+                None => {
+                    return Ok(RebaseResult {
+                        success: true,
+                        message: "Nothing to autosquash".to_string(),
+                        rebased_commits: vec![],
+                        conflicts: vec![],
+                    })
+                }
+            },
+        };
+
+        let mut todo = self.todo(upstream.as_deref())?;
+        let before = todo.items.iter().filter(|item| item.action == TodoAction::Pick).count();
+        todo.autosquash();
+        if todo.items.iter().filter(|item| item.action == TodoAction::Pick).count() == before {
+            return Ok(RebaseResult {
+                success: true,
+                message: "Nothing to autosquash".to_string(),
+                rebased_commits: vec![],
+                conflicts: vec![],
+            });
+        }
+        self.run_todo(&todo, "rebase (autosquash)")
+    }
+
+    /// Upstream for an autosquash without one: the parent of the oldest
+    /// commit targeted by a fixup in HEAD's first-parent history
+    ///
+    /// Returns `None` when there is nothing to squash and `Some(None)` when
+    /// the oldest target is a root commit.
+    fn autosquash_base(&self) -> Result<Option<Option<String>>> {
+        let mut commit = self.repo.head()?.peel_to_commit()?;
+        let mut pending: Vec<String> = Vec::new();
+        let mut base = None;
+
+        loop {
+            let item = TodoItem::pick(&commit);
+            match fixup_target(&item.summary) {
+                Some((_, target)) => pending.push(target.to_string()),
+                None => {
+                    let before = pending.len();
+                    pending.retain(|target| !matches_target(&item, target));
+                    if pending.len() < before {
+                        base = Some(commit.parent_ids().next().map(|id| id.to_string()));
+                    }
+                }
+            }
+
+            if (pending.is_empty() && base.is_some()) || commit.parent_count() != 1 {
+                break;
+            }
+            commit = commit.parent(0)?;
+        }
+
+        Ok(base)
+    }
+
+    /// Replay a todo list in memory and move HEAD to the result
+    ///
+    /// Commits that come out unchanged keep their ids. On a conflict nothing
+    /// is changed and the conflicting paths are reported. The rewrite can be
+    /// undone through the undo log.
+    pub fn run_todo(&self, todo: &RebaseTodo, reason: &str) -> Result<RebaseResult> {
+        self.check_can_rewrite()?;
+        let (new_head, rewritten) = match self.replay(todo)? {
+            Replay::Done { head: Some(head), rewritten } => (head, rewritten),
+            Replay::Done { head: None, .. } => return Err(anyhow::anyhow!("Rewriting would leave no commits")),
+            Replay::Conflict(result) => return Ok(result),
+        };
+
+        update_refs(&self.repo, &[(self.checked_out_ref()?, new_head.id())], reason)?;
+
+        Ok(RebaseResult {
+            success: true,
+            message: format!("Rewrote {} commits", rewritten.len()),
+            rebased_commits: rewritten,
+            conflicts: vec![],
+        })
+    }
+
+    /// History can only be rewritten with no operation in progress and nothing staged
+    fn check_can_rewrite(&self) -> Result<()> {
+        if self.repo.state() != git2::RepositoryState::Clean {
+            return Err(anyhow::anyhow!("Another operation is in progress ({:?})", self.repo.state()));
+        }
+        let head_tree = self.repo.head()?.peel_to_tree()?;
+        let staged = self.repo.diff_tree_to_index(Some(&head_tree), None, None)?;
+        if staged.deltas().len() > 0 {
+            return Err(anyhow::anyhow!("Commit or stash staged changes before rewriting history"));
+        }
+        Ok(())
+    }
+
+    /// Ref HEAD moves with: its branch, or HEAD itself when detached
+    fn checked_out_ref(&self) -> Result<String> {
+        let head = self.repo.head()?;
+        if head.is_branch() {
+            Ok(head.name().ok_or_else(|| anyhow::anyhow!("Invalid branch name"))?.to_string())
+        } else {
+            Ok("HEAD".to_string())
+        }
+    }
+
+    /// Create the commits of a todo list without moving any ref
+    fn replay(&self, todo: &RebaseTodo) -> Result<Replay<'_>> {
+        let sig = self.get_signature()?;
+        let mut current = match &todo.onto {
+            Some(onto) => Some(self.find_commit_from_ref(onto)?),
+            None => None,
+        };
+        let mut rewritten = Vec::new();
+        // Whether `current` came from the list rather than from `onto`
+        let mut replayed = false;
+
+        for item in &todo.items {
+            if item.action == TodoAction::Drop {
+                continue;
+            }
+            let commit = self.repo.find_commit(Oid::from_str(&item.commit)?)?;
+            if commit.parent_count() > 1 {
+                return Err(anyhow::anyhow!("Cannot replay merge commit {}", &item.commit[..8]));
+            }
+
+            let parent_unchanged = commit.parent_ids().next() == current.as_ref().map(|c| c.id());
+            if item.action == TodoAction::Pick && !item.rewrites_metadata() && parent_unchanged {
+                current = Some(commit);
+                replayed = true;
+                continue;
+            }
+
+            // Tree of the commit applied on top of what has been replayed so far
+            let index = match (&current, commit.parents().next()) {
+                _ if parent_unchanged => None,
+                (Some(base), _) => Some(self.repo.cherrypick_commit(&commit, base, 0, None)?),
+                // Replaying onto nothing: the commit's changes against an empty tree
+                (None, Some(parent)) => {
+                    let empty = self.repo.find_tree(self.repo.treebuilder(None)?.write()?)?;
+                    Some(self.repo.merge_trees(&parent.tree()?, &empty, &commit.tree()?, None)?)
+                }
+                (None, None) => None,
+            };
+            let tree = match index {
+                Some(mut index) => {
+                    if index.has_conflicts() {
+                        let mut conflicts = Vec::new();
+                        for conflict in index.conflicts()?.flatten() {
+                            if let Some(entry) = conflict.our.or(conflict.their) {
+                                let path = String::from_utf8_lossy(&entry.path).to_string();
+                                if !conflicts.contains(&path) {
+                                    conflicts.push(path);
+                                }
+                            }
+                        }
+                        return Ok(Replay::Conflict(RebaseResult {
+                            success: false,
+                            message: format!(
+                                "Conflict replaying {} {}; history was left unchanged",
+                                &item.commit[..8], item.summary
+                            ),
+                            rebased_commits: rewritten,
+                            conflicts,
+                        }));
+                    }
+                    self.repo.find_tree(index.write_tree_to(&self.repo)?)?
+                }
+                None => commit.tree()?,
+            };
+
+            let new_id = if item.action.folds() {
+                let previous = match (&current, replayed) {
+                    (Some(previous), true) => previous,
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Cannot {} {} without a previous commit",
+                            item.action.name(), &item.commit[..8]
+                        ))
+                    }
+                };
+                let message = match item.action {
+                    TodoAction::FixupWithMessage => item.message.clone()
+                        .unwrap_or_else(|| strip_subject(commit.message().unwrap_or("")).to_string()),
+                    TodoAction::Squash => {
+                        let addition = item.message.clone()
+                            .unwrap_or_else(|| match fixup_target(&item.summary) {
+                                Some(_) => strip_subject(commit.message().unwrap_or("")).to_string(),
+                                None => commit.message().unwrap_or("").to_string(),
+                            });
+                        let base = previous.message().unwrap_or("").trim_end();
+                        if addition.trim().is_empty() {
+                            format!("{}\n", base)
+                        } else {
+                            format!("{}\n\n{}", base, addition)
+                        }
+                    }
+                    _ => previous.message().unwrap_or("").to_string(),
+                };
+                let parents: Vec<git2::Commit> = previous.parents().collect();
+                let parents: Vec<&git2::Commit> = parents.iter().collect();
+                if rewritten.last() == Some(&previous.id().to_string()) {
+                    rewritten.pop();
+                }
+                self.repo.commit(None, &previous.author(), &sig, &message, &tree, &parents)?
+            } else {
+                let message = item.message.as_deref().unwrap_or(commit.message().unwrap_or(""));
+                let author = match &item.author {
+                    Some(author) => author.to_signature()?,
+                    None => commit.author().to_owned(),
+                };
+                let committer = match &item.committer {
+                    Some(committer) => committer.to_signature()?,
+                    None => sig.clone(),
+                };
+                let parents: Vec<&git2::Commit> = current.iter().collect();
+                self.repo.commit(None, &author, &committer, message, &tree, &parents)?
+            };
+
+            rewritten.push(new_id.to_string());
+            current = Some(self.repo.find_commit(new_id)?);
+            replayed = true;
+        }
+
+        Ok(Replay::Done { head: current, rewritten })
+    }
+
+    /// Move the commits `oldest..=newest` of the current branch to another
+    /// position in it, or onto the tip of another branch
+    ///
+    /// The commits after the moved ones are replayed in memory and all
+    /// affected branches are updated in one transaction.
+    pub fn move_commits(&self, oldest: &str, newest: &str, destination: &MoveDestination) -> Result<RebaseResult> {
+        self.check_can_rewrite()?;
+        let oldest = self.find_commit_from_ref(oldest)?;
+        let newest = self.find_commit_from_ref(newest)?;
+        let head = self.repo.head()?.peel_to_commit()?;
+
+        // First-parent chain from HEAD, newest first, down to the parent of the range
+        let mut chain = Vec::new();
+        let mut commit = head;
+        loop {
+            if commit.parent_count() > 1 {
+                return Err(anyhow::anyhow!("Cannot move commits across merge commit {}", &commit.id().to_string()[..8]));
+            }
+            chain.push(commit.id());
+            if commit.id() == oldest.id() {
+                break;
+            }
+            commit = commit.parent(0).map_err(|_| {
+                anyhow::anyhow!("Commit {} is not in the history of HEAD", &oldest.id().to_string()[..8])
+            })?;
+        }
+        chain.reverse();
+        let end = chain.iter().position(|id| *id == newest.id())
+            .ok_or_else(|| anyhow::anyhow!("{} is not between {} and HEAD", &newest.id().to_string()[..8], &oldest.id().to_string()[..8]))?;
+        let range: Vec<String> = chain[..=end].iter().map(|id| id.to_string()).collect();
+        let base = oldest.parent_ids().next().map(|id| id.to_string());
+        let is_moved = |item: &TodoItem| range.contains(&item.commit);
+
+        match destination {
+            MoveDestination::After(target) => {
+                let target = self.find_commit_from_ref(target)?.id().to_string();
+                if range.contains(&target) {
+                    return Err(anyhow::anyhow!("Cannot move commits after one of themselves"));
+                }
+                let head_id = self.repo.head()?.peel_to_commit()?.id();
+                let target_id = Oid::from_str(&target)?;
+                if head_id != target_id && !self.repo.graph_descendant_of(head_id, target_id)? {
+                    return Err(anyhow::anyhow!("{} is not in the history of HEAD", &target[..8]));
+                }
+                if Some(&target) == base.as_ref() {
+                    return Ok(RebaseResult {
+                        success: true,
+                        message: "Commits are already there".to_string(),
+                        rebased_commits: vec![],
+                        conflicts: vec![],
+                    });
+                }
+
+                // The target is either above the range or below it
+                let mut todo = self.todo(base.as_deref())?;
+                let (moved, mut rest): (Vec<TodoItem>, Vec<TodoItem>) = todo.items.into_iter().partition(is_moved);
+                if let Some(position) = rest.iter().position(|item| item.commit == target) {
+                    rest.splice(position + 1..position + 1, moved);
+                    todo.items = rest;
+                } else {
+                    todo = self.todo(Some(&target))?;
+                    let (moved, rest): (Vec<TodoItem>, Vec<TodoItem>) = todo.items.into_iter().partition(is_moved);
+                    todo.items = moved.into_iter().chain(rest).collect();
+                }
+
+                let mut result = self.run_todo(&todo, "move commits")?;
+                if result.success {
+                    result.message = format!("Moved {} commits after {}", range.len(), &target[..8]);
+                }
+                Ok(result)
+            }
+            MoveDestination::OntoBranch(branch) => {
+                let target = self.repo.find_branch(branch, BranchType::Local)?;
+                let target_ref = target.get().name().ok_or_else(|| anyhow::anyhow!("Invalid branch name"))?.to_string();
+                let source_ref = self.checked_out_ref()?;
+                if target_ref == source_ref {
+                    return Err(anyhow::anyhow!("{} is the current branch; move the commits after a commit instead", branch));
+                }
+                if base.is_none() {
+                    return Err(anyhow::anyhow!("Cannot move the root commit to another branch"));
+                }
+                let tip = target.get().peel_to_commit()?;
+
+                let onto_branch = RebaseTodo {
+                    onto: Some(tip.id().to_string()),
+                    items: self.todo(base.as_deref())?.items.into_iter().filter(is_moved).collect(),
+                };
+                let remaining = RebaseTodo {
+                    onto: base.clone(),
+                    items: self.todo(Some(&newest.id().to_string()))?.items,
+                };
+
+                let (new_tip, mut rewritten) = match self.replay(&onto_branch)? {
+                    Replay::Done { head: Some(head), rewritten } => (head, rewritten),
+                    Replay::Done { head: None, .. } => return Err(anyhow::anyhow!("Nothing to move")),
+                    Replay::Conflict(result) => return Ok(result),
+                };
+                let (new_head, mut replayed) = match self.replay(&remaining)? {
+                    Replay::Done { head, rewritten } => (head, rewritten),
+                    Replay::Conflict(result) => return Ok(result),
+                };
+                let new_head = match new_head {
+                    Some(commit) => commit.id(),
+                    None => oldest.parent_id(0)?,
+                };
+
+                update_refs(
+                    &self.repo,
+                    &[(target_ref, new_tip.id()), (source_ref, new_head)],
+                    &format!("move commits onto {}", branch),
+                )?;
+
+                rewritten.append(&mut replayed);
+                Ok(RebaseResult {
+                    success: true,
+                    message: format!("Moved {} commits onto {}", range.len(), branch),
+                    rebased_commits: rewritten,
+                    conflicts: vec![],
+                })
+            }
+        }
+    }
+
+    /// Work out in memory whether rebasing HEAD onto `upstream` (or `onto`)
+    /// would conflict, and at which commit
+    pub fn predict_rebase(&self, upstream: &str, onto: Option<&str>) -> Result<RebasePrediction> {
+        let upstream_commit = self.find_commit_from_ref(upstream)?;
+        let onto_commit = match onto {
+            Some(onto) => self.find_commit_from_ref(onto)?,
+            None => upstream_commit.clone(),
+        };
+
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        revwalk.push_head()?;
+        revwalk.hide(upstream_commit.id())?;
+
+        let mut tree = onto_commit.tree()?;
+        let mut commits = 0;
+        for oid in revwalk {
+            let commit = self.repo.find_commit(oid?)?;
+            if commit.parent_count() > 1 {
+                continue;
+            }
+            commits += 1;
+
+            let base = match commit.parent(0) {
+                Ok(parent) => parent.tree()?,
+                Err(_) => self.repo.find_tree(self.repo.treebuilder(None)?.write()?)?,
+            };
+            let mut index = self.repo.merge_trees(&base, &tree, &commit.tree()?, None)?;
+            if index.has_conflicts() {
+                return Ok(RebasePrediction {
+                    commits: commits + self.count_remaining(&commit)?,
+                    first_conflict: Some(ConflictingCommit {
+                        id: commit.id().to_string(),
+                        summary: commit.summary().unwrap_or("").to_string(),
+                        position: commits,
+                        conflicts: index_conflicts(&index)?,
+                    }),
+                    tree: None,
+                });
+            }
+            tree = self.repo.find_tree(index.write_tree_to(&self.repo)?)?;
+        }
+
+        Ok(RebasePrediction {
+            commits,
+            first_conflict: None,
+            tree: Some(tree.id().to_string()),
+        })
+    }
+
+    /// Non-merge commits between `commit` (excluded) and HEAD
+    fn count_remaining(&self, commit: &git2::Commit) -> Result<usize> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.push_head()?;
+        revwalk.hide(commit.id())?;
+        let mut count = 0;
+        for oid in revwalk {
+            if self.repo.find_commit(oid?)?.parent_count() <= 1 {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Change the message, author or committer of any commit in HEAD's
+    /// history, replaying the commits after it
+    pub fn rewrite_commit(&self, commit_ref: &str, metadata: &CommitMetadata) -> Result<RebaseResult> {
+        let commit = self.find_commit_from_ref(commit_ref)?;
+        let short = commit.id().to_string()[..8].to_string();
+        if metadata.is_empty() {
+            return Err(anyhow::anyhow!("Nothing to change in {}", short));
+        }
+
+        let upstream = commit.parent_ids().next().map(|id| id.to_string());
+        let mut todo = self.todo(upstream.as_deref())?;
+        let item = todo.items.iter_mut()
+            .find(|item| item.commit == commit.id().to_string())
+            .ok_or_else(|| anyhow::anyhow!("Commit {} is not in the history of HEAD", short))?;
+
+        let mut author = Identity::from_signature(&commit.author());
+        author.name = metadata.author_name.clone().unwrap_or(author.name);
+        author.email = metadata.author_email.clone().unwrap_or(author.email);
+        if let Some((time, offset)) = metadata.author_time {
+            (author.time, author.offset_minutes) = (time, offset);
+        }
+        let committer_changed = metadata.committer_name.is_some()
+            || metadata.committer_email.is_some()
+            || metadata.committer_time.is_some();
+        let committer = if committer_changed {
+            let now = self.get_signature()?;
+            let mut committer = Identity::from_signature(&commit.committer());
+            committer.name = metadata.committer_name.clone().unwrap_or(committer.name);
+            committer.email = metadata.committer_email.clone().unwrap_or(committer.email);
+            (committer.time, committer.offset_minutes) = metadata.committer_time
+                .unwrap_or((now.when().seconds(), now.when().offset_minutes()));
+            Some(committer)
+        } else {
+            None
+        };
+
+        item.message = metadata.message.clone();
+        item.author = Some(author);
+        item.committer = committer;
+
+        let mut result = self.run_todo(&todo, &format!("rewrite {}", short))?;
+        if result.success {
+            result.message = format!("Rewrote {} and {} commits after it", short, result.rebased_commits.len().saturating_sub(1));
+        }
+        Ok(result)
+    }
+
+    /// Rewrite the author (and optionally committer) of every commit by
+    /// `rewrite.old_email` in `upstream..HEAD`, or all of HEAD's history
+    pub fn rewrite_identity(&self, upstream: Option<&str>, rewrite: &IdentityRewrite) -> Result<RebaseResult> {
+        if rewrite.new_name.is_none() && rewrite.new_email.is_none() {
+            return Err(anyhow::anyhow!("Give a new name or email"));
+        }
+        let mut todo = self.todo(upstream)?;
+        let matches = |signature: &Signature| {
+            signature.email().is_some_and(|email| email.eq_ignore_ascii_case(&rewrite.old_email))
+        };
+        let replace = |signature: &Signature| {
+            let mut identity = Identity::from_signature(signature);
+            identity.name = rewrite.new_name.clone().unwrap_or(identity.name);
+            identity.email = rewrite.new_email.clone().unwrap_or(identity.email);
+            identity
+        };
+
+        let mut changed = 0;
+        for item in &mut todo.items {
+            let commit = self.repo.find_commit(Oid::from_str(&item.commit)?)?;
+            let author = commit.author();
+            let committer = commit.committer();
+            if matches(&author) {
+                item.author = Some(replace(&author));
+            }
+            if rewrite.committer && matches(&committer) {
+                item.committer = Some(replace(&committer));
+            }
+            if item.rewrites_metadata() {
+                changed += 1;
+            }
+        }
+        if changed == 0 {
+            return Ok(RebaseResult {
+                success: true,
+                message: format!("No commits by {}", rewrite.old_email),
+                rebased_commits: vec![],
+                conflicts: vec![],
+            });
+        }
+
+        let mut result = self.run_todo(&todo, &format!("rewrite identity {}", rewrite.old_email))?;
+        if result.success {
+            result.message = format!("Rewrote {} commits by {}", changed, rewrite.old_email);
+        }
+        Ok(result)
+    }
+
+    /// Split a commit into one commit per part, followed by a commit with the
+    /// remaining changes and the original message, then replay its descendants
+    ///
+    /// Parts are applied in order, each on top of the previous one.
+    pub fn split_commit(&self, commit_ref: &str, parts: &[SplitPart]) -> Result<RebaseResult> {
+        let commit = self.find_commit_from_ref(commit_ref)?;
+        let short = commit.id().to_string()[..8].to_string();
+        if commit.parent_count() > 1 {
+            return Err(anyhow::anyhow!("Cannot split merge commit {}", short));
+        }
+        if parts.is_empty() {
+            return Err(anyhow::anyhow!("Nothing to split {} into", short));
+        }
+        let head = self.repo.head()?.peel_to_commit()?;
+        if head.id() != commit.id() && !self.repo.graph_descendant_of(head.id(), commit.id())? {
+            return Err(anyhow::anyhow!("Commit {} is not in the history of HEAD", short));
+        }
+
+        let parent = commit.parents().next();
+        let base_tree = match &parent {
+            Some(parent) => parent.tree()?,
+            None => self.repo.find_tree(self.repo.treebuilder(None)?.write()?)?,
+        };
+        let diff = self.repo.diff_tree_to_tree(Some(&base_tree), Some(&commit.tree()?), None)?;
+
+        // Every selection must name a change of the commit
+        for selection in parts.iter().flat_map(|part| &part.changes) {
+            let hunks = (0..diff.deltas().len())
+                .find(|&i| diff.get_delta(i).and_then(|d| d.new_file().path().or(d.old_file().path()))
+                    .is_some_and(|p| p == Path::new(selection.path())))
+                .map(|i| git2::Patch::from_diff(&diff, i))
+                .transpose()?
+                .ok_or_else(|| anyhow::anyhow!("{} does not change {}", short, selection.path()))?
+                .map_or(0, |patch| patch.num_hunks());
+            if let SplitSelection::Hunk { path, index } = selection {
+                if *index >= hunks {
+                    return Err(anyhow::anyhow!("{} has {} hunks in {}, there is no hunk #{}", path, hunks, short, index));
+                }
+            }
+        }
+
+        let sig = self.get_signature()?;
+        let mut created = Vec::new();
+        let mut current = parent;
+        let mut previous_tree = base_tree.id();
+        for (n, part) in parts.iter().enumerate() {
+            if part.message.trim().is_empty() {
+                return Err(anyhow::anyhow!("Part {} of the split has no message", n + 1));
+            }
+            // Each part's tree is the original parent plus everything selected so far
+            let selected: Vec<&SplitSelection> = parts[..=n].iter().flat_map(|p| &p.changes).collect();
+            let tree = self.apply_selection(&base_tree, &diff, &selected)?;
+            if tree.id() == previous_tree {
+                return Err(anyhow::anyhow!("Part {} of the split selects no new changes", n + 1));
+            }
+            previous_tree = tree.id();
+
+            let parents: Vec<&git2::Commit> = current.iter().collect();
+            let id = self.repo.commit(None, &commit.author(), &sig, &part.message, &tree, &parents)?;
+            created.push(id.to_string());
+            current = Some(self.repo.find_commit(id)?);
+        }
+
+        if previous_tree != commit.tree_id() {
+            let parents: Vec<&git2::Commit> = current.iter().collect();
+            let message = commit.message().unwrap_or("");
+            let id = self.repo.commit(None, &commit.author(), &sig, message, &commit.tree()?, &parents)?;
+            created.push(id.to_string());
+            current = Some(self.repo.find_commit(id)?);
+        }
+
+        // Replay the descendants; for HEAD this only moves the branch
+        let descendants = if head.id() == commit.id() {
+            Vec::new()
+        } else {
+            self.todo(Some(&commit.id().to_string()))?.items
+        };
+        let todo = RebaseTodo {
+            onto: current.map(|c| c.id().to_string()),
+            items: descendants,
+        };
+        let mut result = self.run_todo(&todo, "rebase (split)")?;
+        if result.success {
+            result.message = format!("Split {} into {} commits", short, created.len());
+        }
+        created.append(&mut result.rebased_commits);
+        result.rebased_commits = created;
+        Ok(result)
+    }
+
+    /// `base` with the selected deltas and hunks of `diff` applied
+    fn apply_selection(
+        &self,
+        base: &git2::Tree,
+        diff: &git2::Diff,
+        selected: &[&SplitSelection],
+    ) -> Result<git2::Tree<'_>> {
+        // The hunk callback only sees hunks, so track which file they belong to
+        let state = std::cell::RefCell::new((String::new(), 0usize));
+        let mut options = git2::ApplyOptions::new();
+        options.delta_callback(|delta| {
+            let Some(delta) = delta else { return false };
+            let path = delta.new_file().path().or(delta.old_file().path())
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let wanted = selected.iter().any(|s| s.path() == path);
+            *state.borrow_mut() = (path, 0);
+            wanted
+        });
+        options.hunk_callback(|_| {
+            let mut state = state.borrow_mut();
+            let (path, index) = (state.0.clone(), state.1);
+            state.1 += 1;
+            selected.iter().any(|s| match s {
+                SplitSelection::File(p) => *p == path,
+                SplitSelection::Hunk { path: p, index: i } => *p == path && *i == index,
+            })
+        });
+
+        let mut index = self.repo.apply_to_tree(base, diff, Some(&mut options))?;
+        Ok(self.repo.find_tree(index.write_tree_to(&self.repo)?)?)
+    }
+
     /// Get rebase status
     pub fn rebase_status(&self) -> Result<String> {
         match self.repo.open_rebase(None) {
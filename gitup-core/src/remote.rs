@@ -1,9 +1,14 @@
 use anyhow::Result;
 use git2::{
-    BranchType, Cred, Direction, FetchOptions, PushOptions, RemoteCallbacks,
-    Repository as Git2Repository,
+    AutotagOption, BranchType, Cred, CredentialType, FetchOptions as Git2FetchOptions,
+    FetchPrune, PackBuilderStage, PushOptions, RemoteCallbacks, Repository as Git2Repository,
 };
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::path::Path;
+use crate::clone::is_local_url;
+use crate::commit_graph::{CommitGraphOps, CommitLookup};
+use crate::notes::full_notes_ref;
 
 /// Remote repository information
 #[derive(Debug, Clone)]
@@ -15,7 +20,9 @@ pub struct RemoteInfo {
 }
 
 /// Transfer progress information
-#[derive(Debug, Clone)]
+///
+/// For pushes the `received_*` fields count what was sent so far.
+#[derive(Debug, Clone, Default)]
 pub struct TransferProgress {
     pub total_objects: usize,
     pub indexed_objects: usize,
@@ -40,6 +47,145 @@ impl From<git2::Progress<'_>> for TransferProgress {
     }
 }
 
+/// Which tags a fetch downloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMode {
+    /// The remote's `tagOpt` setting; by default tags pointing into fetched history
+    #[default]
+    Auto,
+    /// All tags (`--tags`)
+    All,
+    /// No tags (`--no-tags`)
+    None,
+}
+
+/// Options for fetching a remote
+#[derive(Debug, Clone, Default)]
+pub struct FetchOptions {
+    /// Refspecs to fetch (empty for the remote's configured refspecs)
+    pub refspecs: Vec<String>,
+    /// Remove remote-tracking refs that no longer exist on the remote (`--prune`)
+    pub prune: bool,
+    pub tags: TagMode,
+    /// Limit history to this many commits from the fetched tips (`--depth`)
+    pub depth: Option<u32>,
+    /// Fetch the complete history of a shallow repository (`--unshallow`)
+    pub unshallow: bool,
+    /// Update the commit-graph file afterwards, as `fetch.writeCommitGraph` does
+    pub write_commit_graph: bool,
+}
+
+/// How a fetch changed a ref
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefUpdateKind {
+    New,
+    FastForward,
+    /// The new tip does not contain the old one
+    Forced,
+    /// Pruned because the ref is gone from the remote
+    Deleted,
+    /// Left alone: a refspec without `+` would not fast-forward it
+    Rejected,
+}
+
+/// A ref changed by a fetch
+#[derive(Debug, Clone)]
+pub struct RefUpdate {
+    /// Full local ref name, e.g. "refs/remotes/origin/main"
+    pub name: String,
+    pub kind: RefUpdateKind,
+    /// `None` for new refs
+    pub old: Option<String>,
+    /// `None` for deleted refs
+    pub new: Option<String>,
+}
+
+/// Result of fetching one remote
+#[derive(Debug, Clone)]
+pub struct FetchReport {
+    pub remote: String,
+    pub url: String,
+    pub received_objects: usize,
+    pub received_bytes: usize,
+    pub updates: Vec<RefUpdate>,
+    /// Why fetching this remote failed, when `fetch_all` carried on past it
+    pub error: Option<String>,
+    /// Why the commit-graph could not be updated; the fetch itself succeeded
+    pub commit_graph_error: Option<String>,
+}
+
+impl FetchReport {
+    /// One-line summary, e.g. "Fetched 12 objects, 3400 bytes"
+    pub fn summary(&self) -> String {
+        format!("Fetched {} objects, {} bytes", self.received_objects, self.received_bytes)
+    }
+}
+
+/// Local ref that the fetch refspec `refspec` maps the remote ref `name` to,
+/// `None` when it does not match or is forced with `+`
+fn non_forced_destination(refspec: &str, name: &str) -> Option<String> {
+    if refspec.starts_with('+') {
+        return None;
+    }
+    let (src, dst) = refspec.split_once(':')?;
+    match src.split_once('*') {
+        Some((prefix, suffix)) => {
+            let matched = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+            Some(dst.replacen('*', matched, 1))
+        }
+        None => (src == name).then(|| dst.to_string()),
+    }
+}
+
+/// Credential callback shared by all network operations: SSH keys from
+/// `~/.ssh`, falling back to the SSH agent
+pub(crate) fn credentials(
+    _url: &str,
+    username_from_url: Option<&str>,
+    _allowed_types: CredentialType,
+) -> std::result::Result<Cred, git2::Error> {
+    // Try different SSH key types
+    if let Ok(home) = std::env::var("HOME") {
+        let ssh_dir = Path::new(&home).join(".ssh");
+
+        // Try ed25519 key first (modern and secure)
+        let ed25519_key = ssh_dir.join("id_ed25519");
+        if ed25519_key.exists() {
+            return Cred::ssh_key(
+                username_from_url.unwrap_or("git"),
+                None,
+                &ed25519_key,
+                None,
+            );
+        }
+
+        // Try RSA key
+        let rsa_key = ssh_dir.join("id_rsa");
+        if rsa_key.exists() {
+            return Cred::ssh_key(
+                username_from_url.unwrap_or("git"),
+                None,
+                &rsa_key,
+                None,
+            );
+        }
+
+        // Try ECDSA key
+        let ecdsa_key = ssh_dir.join("id_ecdsa");
+        if ecdsa_key.exists() {
+            return Cred::ssh_key(
+                username_from_url.unwrap_or("git"),
+                None,
+                &ecdsa_key,
+                None,
+            );
+        }
+    }
+
+    // Fall back to SSH agent
+    Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
+}
+
 /// Remote operations for a repository
 pub struct RemoteOps<'a> {
     repo: &'a Git2Repository,
@@ -94,82 +240,281 @@ impl<'a> RemoteOps<'a> {
     }
 
     /// Fetch from remote
+    ///
+    /// The progress callback can cancel the transfer by returning `false`.
     pub fn fetch(
         &self,
         remote_name: &str,
         refspecs: &[&str],
-        progress_callback: Option<Box<dyn FnMut(TransferProgress) + '_>>,
+        progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
     ) -> Result<String> {
+        let options = FetchOptions {
+            refspecs: refspecs.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        Ok(self.fetch_with_options(remote_name, &options, progress_callback)?.summary())
+    }
+
+    /// Fetch from remote, reporting every ref the fetch created, moved or pruned
+    ///
+    /// The progress callback can cancel the transfer by returning `false`.
+    pub fn fetch_with_options(
+        &self,
+        remote_name: &str,
+        options: &FetchOptions,
+        progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
+    ) -> Result<FetchReport> {
         let mut remote = self.repo.find_remote(remote_name)?;
-        let mut callbacks = RemoteCallbacks::new();
+        let url = remote.url().unwrap_or("").to_string();
+        let mut tips = Vec::new();
 
-        // Set up progress callback if provided
-        if let Some(mut callback) = progress_callback {
-            callbacks.transfer_progress(move |progress| {
-                callback(progress.into());
+        {
+            let mut callbacks = RemoteCallbacks::new();
+
+            // Set up progress callback if provided
+            if let Some(mut callback) = progress_callback {
+                callbacks.transfer_progress(move |progress| callback(progress.into()));
+            }
+
+            // Set up authentication
+            callbacks.credentials(credentials);
+
+            // Pruned refs are reported with a zero new id
+            callbacks.update_tips(|refname, old, new| {
+                tips.push((refname.to_string(), old, new));
                 true
             });
+
+            let mut fetch_options = Git2FetchOptions::new();
+            fetch_options.remote_callbacks(callbacks);
+            fetch_options.download_tags(match options.tags {
+                TagMode::Auto => AutotagOption::Unspecified,
+                TagMode::All => AutotagOption::All,
+                TagMode::None => AutotagOption::None,
+            });
+            if options.prune {
+                fetch_options.prune(FetchPrune::On);
+            }
+
+            // The local transport cannot negotiate an unshallow; the boundary is
+            // dropped below if the history behind it is complete
+            if options.unshallow && !is_local_url(&url) {
+                fetch_options.depth(i32::MAX);
+            } else if let Some(depth) = options.depth {
+                fetch_options.depth(depth.min(i32::MAX as u32) as i32);
+            }
+
+            let refspecs: Vec<&str> = options.refspecs.iter().map(|s| s.as_str()).collect();
+            remote.fetch(&refspecs, Some(&mut fetch_options), None)?;
+        }
+
+        if options.unshallow && is_local_url(&url) {
+            self.drop_shallow_boundary()?;
+        }
+
+        let lookup = CommitLookup::new(self.repo);
+        let mut updates = Vec::with_capacity(tips.len());
+        for (name, old, new) in tips {
+            let kind = if new.is_zero() {
+                RefUpdateKind::Deleted
+            } else if old.is_zero() {
+                RefUpdateKind::New
+            } else if lookup.is_descendant_of(new, old).unwrap_or(false) {
+                RefUpdateKind::FastForward
+            } else {
+                RefUpdateKind::Forced
+            };
+
+            updates.push(RefUpdate {
+                name,
+                kind,
+                old: Some(old).filter(|id| !id.is_zero()).map(|id| id.to_string()),
+                new: Some(new).filter(|id| !id.is_zero()).map(|id| id.to_string()),
+            });
         }
 
-        // Set up authentication
-        callbacks.credentials(|_url, username_from_url, _allowed_types| {
-            // Try different SSH key types
-            if let Ok(home) = std::env::var("HOME") {
-                let ssh_dir = Path::new(&home).join(".ssh");
-
-                // Try ed25519 key first (modern and secure)
-                let ed25519_key = ssh_dir.join("id_ed25519");
-                if ed25519_key.exists() {
-                    return Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &ed25519_key,
-                        None,
-                    );
-                }
+        // libgit2 skips these without calling update_tips
+        for head in remote.list()? {
+            let Some(name) = options.refspecs.iter().find_map(|spec| non_forced_destination(spec, head.name())) else {
+                continue;
+            };
+            if updates.iter().any(|update| update.name == name) {
+                continue;
+            }
+            let Ok(local) = self.repo.refname_to_id(&name) else {
+                continue;
+            };
+            if local != head.oid() && !lookup.is_descendant_of(head.oid(), local).unwrap_or(false) {
+                updates.push(RefUpdate {
+                    name,
+                    kind: RefUpdateKind::Rejected,
+                    old: Some(local.to_string()),
+                    new: Some(head.oid().to_string()),
+                });
+            }
+        }
 
-                // Try RSA key
-                let rsa_key = ssh_dir.join("id_rsa");
-                if rsa_key.exists() {
-                    return Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &rsa_key,
-                        None,
-                    );
-                }
+        let write_commit_graph = options.write_commit_graph || self.repo.config()
+            .and_then(|config| config.get_bool("fetch.writeCommitGraph"))
+            .unwrap_or(false);
+        // Best effort, as in git: the refs are already updated
+        let mut commit_graph_error = None;
+        if write_commit_graph && !updates.is_empty() && !self.repo.is_shallow() {
+            if let Err(e) = CommitGraphOps::new(self.repo).update() {
+                commit_graph_error = Some(format!("{:#}", e));
+            }
+        }
 
-                // Try ECDSA key
-                let ecdsa_key = ssh_dir.join("id_ecdsa");
-                if ecdsa_key.exists() {
-                    return Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &ecdsa_key,
-                        None,
-                    );
+        let stats = remote.stats();
+        Ok(FetchReport {
+            remote: remote_name.to_string(),
+            url,
+            received_objects: stats.received_objects(),
+            received_bytes: stats.received_bytes(),
+            updates,
+            error: None,
+            commit_graph_error,
+        })
+    }
+
+    /// Fetch every configured remote, in configuration order
+    ///
+    /// Like `git fetch --all`, a remote that fails does not stop the others;
+    /// its report carries the error. Cancelling through the progress callback
+    /// stops the whole fetch.
+    pub fn fetch_all(
+        &self,
+        options: &FetchOptions,
+        mut progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
+    ) -> Result<Vec<FetchReport>> {
+        let cancelled = Cell::new(false);
+        let mut reports = Vec::new();
+        for remote_name in self.repo.remotes()?.iter().flatten() {
+            let progress = progress_callback.as_mut().map(|callback| {
+                Box::new(|progress| {
+                    let keep_going = callback(progress);
+                    if !keep_going {
+                        cancelled.set(true);
+                    }
+                    keep_going
+                }) as Box<dyn FnMut(TransferProgress) -> bool + '_>
+            });
+            match self.fetch_with_options(remote_name, options, progress) {
+                Ok(report) => reports.push(report),
+                Err(e) if cancelled.get() => return Err(e),
+                Err(e) => reports.push(FetchReport {
+                    remote: remote_name.to_string(),
+                    url: self.repo.find_remote(remote_name)
+                        .ok()
+                        .and_then(|remote| remote.url().map(str::to_string))
+                        .unwrap_or_default(),
+                    received_objects: 0,
+                    received_bytes: 0,
+                    updates: Vec::new(),
+                    error: Some(e.to_string()),
+                    commit_graph_error: None,
+                }),
+            }
+        }
+        Ok(reports)
+    }
+
+    /// Commits that became reachable through `updates` and were not reachable
+    /// from any other ref, newest first
+    pub fn new_commits(&self, updates: &[RefUpdate]) -> Result<Vec<String>> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+
+        let mut pushed = false;
+        for update in updates {
+            if let Some(new) = &update.new {
+                revwalk.push(git2::Oid::from_str(new)?)?;
+                pushed = true;
+            }
+            if let Some(old) = &update.old {
+                // The old tip may have been pruned from the object database
+                let _ = revwalk.hide(git2::Oid::from_str(old)?);
+            }
+        }
+        if !pushed {
+            return Ok(Vec::new());
+        }
+
+        for reference in self.repo.references()? {
+            // Resolve symbolic refs such as "refs/remotes/origin/HEAD" to the ref they follow
+            let Ok(reference) = reference?.resolve() else {
+                continue;
+            };
+            let updated = reference.name().is_some_and(|name| updates.iter().any(|u| u.name == name));
+            if !updated {
+                if let Ok(commit) = reference.peel_to_commit() {
+                    revwalk.hide(commit.id())?;
                 }
             }
+        }
 
-            // Fall back to SSH agent
-            Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
-        });
+        revwalk.map(|oid| Ok(oid?.to_string())).collect()
+    }
 
-        let mut fetch_options = FetchOptions::new();
-        fetch_options.remote_callbacks(callbacks);
+    /// Turn a shallow repository complete once all history below its
+    /// shallow commits is present
+    ///
+    /// The local transport never sends history the repository claims to
+    /// have, so this only succeeds when the missing objects arrived some other
+    /// way; removing the boundary otherwise would break every revwalk.
+    fn drop_shallow_boundary(&self) -> Result<()> {
+        let shallow_file = self.repo.path().join("shallow");
+        let Ok(content) = std::fs::read_to_string(&shallow_file) else {
+            return Ok(());
+        };
 
-        // Perform fetch
-        remote.fetch(refspecs, Some(&mut fetch_options), None)?;
+        let missing = |oid: git2::Oid| {
+            anyhow::anyhow!(
+                "Unshallow is not supported over the local transport: object {} below the shallow boundary is missing",
+                oid
+            )
+        };
+        let odb = self.repo.odb()?;
+        let mut commits = content.lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| git2::Oid::from_str(line.trim()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let mut trees = Vec::new();
+        let mut seen = HashSet::new();
+
+        while let Some(oid) = commits.pop() {
+            if !seen.insert(oid) {
+                continue;
+            }
+            // Grafted commits report no parents, so read them from the raw object
+            let object = odb.read(oid).map_err(|_| missing(oid))?;
+            let raw = String::from_utf8_lossy(object.data());
+            for header in raw.lines().take_while(|l| !l.is_empty()) {
+                if let Some(tree) = header.strip_prefix("tree ") {
+                    trees.push(git2::Oid::from_str(tree)?);
+                } else if let Some(parent) = header.strip_prefix("parent ") {
+                    commits.push(git2::Oid::from_str(parent)?);
+                }
+            }
+        }
 
-        // Get fetch head information
-        let stats = remote.stats();
-        let msg = format!(
-            "Fetched {} objects, {} bytes",
-            stats.received_objects(),
-            stats.received_bytes()
-        );
+        while let Some(oid) = trees.pop() {
+            if !seen.insert(oid) {
+                continue;
+            }
+            let tree = self.repo.find_tree(oid).map_err(|_| missing(oid))?;
+            for entry in tree.iter() {
+                match entry.kind() {
+                    Some(git2::ObjectType::Tree) => trees.push(entry.id()),
+                    Some(git2::ObjectType::Blob) if !odb.exists(entry.id()) => return Err(missing(entry.id())),
+                    // Submodule commits live in other repositories
+                    _ => {}
+                }
+            }
+        }
 
-        Ok(msg)
+        std::fs::remove_file(shallow_file)?;
+        Ok(())
     }
 
     /// Pull from remote (fetch + merge)
@@ -177,7 +522,7 @@ impl<'a> RemoteOps<'a> {
         &self,
         remote_name: &str,
         branch_name: &str,
-        progress_callback: Option<Box<dyn FnMut(TransferProgress) + '_>>,
+        progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
     ) -> Result<String> {
         // First fetch
         let fetch_msg = self.fetch(remote_name, &[], progress_callback)?;
@@ -232,67 +577,62 @@ impl<'a> RemoteOps<'a> {
     }
 
     /// Push to remote
+    ///
+    /// The progress callback can cancel the push by returning `false`;
+    /// refspecs pushed before that stay pushed.
     pub fn push(
         &self,
         remote_name: &str,
         refspecs: &[&str],
-        progress_callback: Option<Box<dyn FnMut(TransferProgress) + '_>>,
+        progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
     ) -> Result<String> {
         let mut remote = self.repo.find_remote(remote_name)?;
-        let mut callbacks = RemoteCallbacks::new();
 
-        // Set up progress callback if provided
-        if let Some(mut callback) = progress_callback {
-            callbacks.transfer_progress(move |progress| {
-                callback(progress.into());
-                true
-            });
-        }
-
-        // Set up authentication
-        callbacks.credentials(|_url, username_from_url, _allowed_types| {
-            // Try different SSH key types
-            if let Ok(home) = std::env::var("HOME") {
-                let ssh_dir = Path::new(&home).join(".ssh");
-
-                // Try ed25519 key first (modern and secure)
-                let ed25519_key = ssh_dir.join("id_ed25519");
-                if ed25519_key.exists() {
-                    return Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &ed25519_key,
-                        None,
-                    );
-                }
-
-                // Try RSA key
-                let rsa_key = ssh_dir.join("id_rsa");
-                if rsa_key.exists() {
-                    return Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &rsa_key,
-                        None,
-                    );
+        // Packing is reported as delta progress, the upload as object progress.
+        // Those callbacks cannot fail, so a cancel request seen there is kept
+        // until the push can stop cleanly: in the negotiation or sideband
+        // callbacks, or before the next refspec.
+        let progress_callback = RefCell::new(progress_callback);
+        let cancelled = Cell::new(false);
+        let report = |progress: TransferProgress| {
+            if let Some(callback) = progress_callback.borrow_mut().as_mut() {
+                if !callback(progress) {
+                    cancelled.set(true);
                 }
-
-                // Try ECDSA key
-                let ecdsa_key = ssh_dir.join("id_ecdsa");
-                if ecdsa_key.exists() {
-                    return Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &ecdsa_key,
-                        None,
-                    );
+            }
+        };
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.push_negotiation(|_| {
+            report(TransferProgress::default());
+            if cancelled.get() {
+                return Err(git2::Error::from_str("Push cancelled"));
+            }
+            Ok(())
+        });
+        callbacks.sideband_progress(|_| !cancelled.get());
+        callbacks.pack_progress(|stage, current, total| {
+            let mut progress = TransferProgress::default();
+            match stage {
+                PackBuilderStage::AddingObjects => progress.indexed_objects = current,
+                PackBuilderStage::Deltafication => {
+                    progress.indexed_deltas = current;
+                    progress.total_deltas = total;
                 }
             }
-
-            // Fall back to SSH agent
-            Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
+            report(progress);
+        });
+        callbacks.push_transfer_progress(|current, total, bytes| {
+            report(TransferProgress {
+                total_objects: total,
+                received_objects: current,
+                received_bytes: bytes,
+                ..Default::default()
+            });
         });
 
+        // Set up authentication
+        callbacks.credentials(credentials);
+
         callbacks.push_update_reference(|refname, status| {
             if let Some(msg) = status {
                 eprintln!("Push failed for {}: {}", refname, msg);
@@ -316,12 +656,54 @@ impl<'a> RemoteOps<'a> {
             refspecs.to_vec()
         };
 
-        // Perform push
-        remote.push(&final_refspecs, Some(&mut push_options))?;
+        // Push one refspec at a time so a cancelled push stops between them
+        for refspec in final_refspecs {
+            if cancelled.get() {
+                anyhow::bail!("Push cancelled");
+            }
+            if let Err(e) = remote.push(&[refspec], Some(&mut push_options)) {
+                if cancelled.get() {
+                    anyhow::bail!("Push cancelled");
+                }
+                return Err(e.into());
+            }
+        }
 
         Ok(format!("Pushed to {}", remote_name))
     }
 
+    /// Push a notes ref to the same ref on the remote; "ci" means `refs/notes/ci`
+    pub fn push_notes(
+        &self,
+        remote_name: &str,
+        notes_ref: &str,
+        progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
+    ) -> Result<String> {
+        let notes_ref = full_notes_ref(notes_ref);
+        let refspec = format!("{0}:{0}", notes_ref);
+        self.push(remote_name, &[&refspec], progress_callback)?;
+        Ok(format!("Pushed {} to {}", notes_ref, remote_name))
+    }
+
+    /// Fetch a notes ref (or "*" for all of them) into the same local ref
+    ///
+    /// Like git, this refuses to overwrite local notes that are not an
+    /// ancestor of the fetched ones; those are reported as rejected.
+    pub fn fetch_notes(
+        &self,
+        remote_name: &str,
+        notes_ref: &str,
+        progress_callback: Option<Box<dyn FnMut(TransferProgress) -> bool + '_>>,
+    ) -> Result<FetchReport> {
+        let notes_ref = full_notes_ref(notes_ref);
+        let options = FetchOptions {
+            refspecs: vec![format!("{0}:{0}", notes_ref)],
+            tags: TagMode::None,
+            ..Default::default()
+        };
+        self.fetch_with_options(remote_name, &options, progress_callback)
+    }
+
     /// Get remote tracking branch for current branch
     pub fn get_upstream(&self) -> Result<Option<(String, String)>> {
         let head = self.repo.head()?;
@@ -1,19 +1,29 @@
 use anyhow::Result;
-use git2::Repository as Git2Repository;
+use git2::{Repository as Git2Repository, RepositoryOpenFlags};
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
-use crate::diff::{Diff, FileDiff, DiffStats};
-use crate::commit::{Commit, Status, FileStatus};
-use crate::remote::{RemoteInfo, RemoteOps};
-use crate::stash::{StashInfo, StashOps};
-use crate::tag::{TagInfo, TagOps};
-use crate::merge::{MergeOps, MergeResult, ConflictResolution};
-use crate::rebase::{RebaseOps, RebaseResult};
-use crate::cherry_pick::{CherryPickOps, CherryPickResult};
+use crate::diff::{Diff, DiffConfig, FileDiff, DiffStats};
+use crate::commit::{Commit, FixupKind, Status, FileStatus, StatusReport};
+use crate::remote::{FetchOptions, FetchReport, RemoteInfo, RemoteOps};
+use crate::clone::{clone_repository, CloneOptions, CloneProgressCallback};
+use crate::branch::BranchOps;
+use crate::message::MessageOps;
+use crate::notes::NoteOps;
+use crate::ignore::IgnoreOps;
+use crate::analytics::{AnalyticsOps, AnalyticsOptions, RepoAnalytics};
+use crate::commit_graph::{CommitGraphOps, CommitLookup};
+use crate::stash::{StashInfo, StashOps, StashSaveOptions, StashDiff};
+use crate::tag::{DescribeOptions, Description, TagInfo, TagOps, TagSort};
+use crate::merge::{MergeOps, MergePrediction, MergeResult, ConflictResolution};
+use crate::rebase::{CommitMetadata, IdentityRewrite, MoveDestination, RebaseOps, RebasePrediction, RebaseResult, RebaseTodo, SplitPart};
+use crate::undo::{UndoEntry, UndoLog};
+use crate::range_diff::{RangeDiff, RangeDiffOps};
+use crate::cherry_pick::{CherryPickOps, CherryPickResult, PickOptions};
 
 pub struct Repository {
     path: PathBuf,
-    git_repo: Git2Repository,
+    pub(crate) git_repo: Git2Repository,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +41,15 @@ pub struct BranchInfo {
     pub commit_id: String,
     pub is_head: bool,
     pub is_remote: bool,
+    /// Upstream branch, e.g. "origin/main" (local branches only)
+    pub upstream: Option<String>,
+    /// Commits on this branch that are not on its upstream
+    pub ahead: usize,
+    /// Commits on the upstream that are not on this branch
+    pub behind: usize,
+    pub last_commit_timestamp: i64,
+    /// An upstream is configured but its ref no longer exists
+    pub upstream_gone: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -58,6 +77,8 @@ pub struct CommitWithParents {
     pub author: String,
     pub email: String,
     pub timestamp: i64,
+    /// Note in the requested notes ref, see [`Repository::get_commits_with_notes`]
+    pub notes: Option<String>,
 }
 
 impl Repository {
@@ -72,6 +93,44 @@ impl Repository {
         })
     }
 
+    /// Discover the repository containing `path`
+    ///
+    /// Like git run from a subdirectory, `path` and its parents are searched for a
+    /// repository (bare repositories and linked worktrees included), stopping at
+    /// `GIT_CEILING_DIRECTORIES`. When `GIT_DIR` is set it is used instead, together
+    /// with `GIT_WORK_TREE`.
+    pub fn discover<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::discover_with_ceiling(path, &[] as &[&Path])
+    }
+
+    /// Discover the repository containing `path`, not searching above `ceiling_dirs`
+    pub fn discover_with_ceiling<P, C>(path: P, ceiling_dirs: &[C]) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        C: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+
+        let git_repo = if std::env::var_os("GIT_DIR").is_some() {
+            Git2Repository::open_from_env()?
+        } else {
+            let mut ceilings: Vec<OsString> = ceiling_dirs.iter()
+                .map(|dir| dir.as_ref().as_os_str().to_os_string())
+                .collect();
+            if let Some(env) = std::env::var_os("GIT_CEILING_DIRECTORIES") {
+                ceilings.extend(std::env::split_paths(&env).map(|dir| dir.into_os_string()));
+            }
+
+            Git2Repository::open_ext(&path, RepositoryOpenFlags::empty(), &ceilings)
+                .map_err(|e| anyhow::anyhow!("Not a git repository (or any parent up to a ceiling): {}: {}", path.display(), e.message()))?
+        };
+
+        Ok(Repository {
+            path,
+            git_repo,
+        })
+    }
+
     /// Initialize a new repository
     pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
@@ -83,51 +142,102 @@ impl Repository {
         })
     }
 
+    /// Clone `url` into `path`
+    ///
+    /// The progress callback can cancel the clone by returning `false`.
+    pub fn clone_from<P: AsRef<Path>>(
+        url: &str,
+        path: P,
+        options: &CloneOptions,
+        progress_callback: Option<CloneProgressCallback<'_>>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let git_repo = clone_repository(url, &path, options, progress_callback)?;
+
+        Ok(Repository {
+            path,
+            git_repo,
+        })
+    }
+
     /// Get repository status
     pub fn is_clean(&self) -> Result<bool> {
+        self.require_workdir("Status")?;
         let statuses = self.git_repo.statuses(None)?;
         Ok(statuses.is_empty())
     }
 
     /// List all branches
     pub fn list_branches(&self) -> Result<Vec<BranchInfo>> {
-        let mut branches = Vec::new();
-        let head = self.git_repo.head()?;
-        let head_name = head.shorthand().unwrap_or("");
+        self.branch_ops().list()
+    }
 
-        // Local branches
-        for branch in self.git_repo.branches(Some(git2::BranchType::Local))? {
-            let (branch, _) = branch?;
-            let name = branch.name()?.unwrap_or("").to_string();
-            let commit = branch.get().peel_to_commit()?;
-
-            branches.push(BranchInfo {
-                name: name.clone(),
-                commit_id: commit.id().to_string(),
-                is_head: name == head_name,
-                is_remote: false,
-            });
-        }
+    /// Path the repository was opened from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-        // Remote branches
-        for branch in self.git_repo.branches(Some(git2::BranchType::Remote))? {
-            let (branch, _) = branch?;
-            let name = branch.name()?.unwrap_or("").to_string();
-            let commit = branch.get().peel_to_commit()?;
-
-            branches.push(BranchInfo {
-                name,
-                commit_id: commit.id().to_string(),
-                is_head: false,
-                is_remote: true,
-            });
+    /// Working tree root, `None` for bare repositories
+    pub fn workdir(&self) -> Option<&Path> {
+        self.git_repo.workdir()
+    }
+
+    /// The git directory (`.git`, the bare repository, or a linked worktree's admin directory)
+    pub fn git_dir(&self) -> &Path {
+        self.git_repo.path()
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.git_repo.is_bare()
+    }
+
+    /// Whether this is a linked worktree (created with `git worktree add`)
+    pub fn is_worktree(&self) -> bool {
+        self.git_repo.is_worktree()
+    }
+
+    /// Working tree root, or an error naming `operation` for bare repositories
+    pub fn require_workdir(&self, operation: &str) -> Result<&Path> {
+        self.git_repo.workdir().ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} requires a working tree, but {} is a bare repository",
+                operation,
+                self.git_repo.path().display()
+            )
+        })
+    }
+
+    /// Open another handle to this repository for ops that own their handle,
+    /// keeping the working tree this one was opened with
+    fn reopen(&self) -> Result<Git2Repository> {
+        let repo = Git2Repository::open(self.git_repo.path())?;
+        if let Some(workdir) = self.git_repo.workdir() {
+            if repo.workdir() != Some(workdir) {
+                repo.set_workdir(workdir, false)?;
+            }
         }
+        Ok(repo)
+    }
 
-        Ok(branches)
+    /// Full id of the commit a revision (branch, tag, `HEAD~2`, short id, ...) points at
+    pub fn resolve_commit(&self, rev: &str) -> Result<String> {
+        let commit = self.git_repo.revparse_single(rev)?.peel_to_commit()?;
+        Ok(commit.id().to_string())
     }
 
     /// Get recent commits
     pub fn get_commits(&self, count: usize) -> Result<Vec<CommitInfo>> {
+        self.get_commits_with_progress(count, &mut |_| true)
+    }
+
+    /// Get recent commits, reporting the number walked so far
+    ///
+    /// The walk stops with an error when `progress` returns `false`.
+    pub fn get_commits_with_progress(
+        &self,
+        count: usize,
+        progress: &mut dyn FnMut(usize) -> bool,
+    ) -> Result<Vec<CommitInfo>> {
         let mut revwalk = self.git_repo.revwalk()?;
         // Default revision walk from HEAD
         revwalk.push_head()?;
@@ -137,6 +247,9 @@ impl Repository {
             if i >= count {
                 break;
             }
+            if i % 100 == 0 && !progress(i) {
+                return Err(anyhow::anyhow!("Commit walk cancelled"));
+            }
 
             let oid = oid?;
             let commit = self.git_repo.find_commit(oid)?;
@@ -155,6 +268,20 @@ impl Repository {
 
     /// Get recent commits with their parent commit ids (topological + time order)
     pub fn get_commits_with_parents(&self, count: usize) -> Result<Vec<CommitWithParents>> {
+        self.walk_commits_with_parents(count, None)
+    }
+
+    /// Like [`Repository::get_commits_with_parents`], with each commit's note
+    /// from `notes_ref` (the configured notes ref when `None`)
+    pub fn get_commits_with_notes(&self, count: usize, notes_ref: Option<&str>) -> Result<Vec<CommitWithParents>> {
+        let notes = match notes_ref {
+            Some(notes_ref) => NoteOps::with_ref(&self.git_repo, notes_ref),
+            None => NoteOps::new(&self.git_repo),
+        };
+        self.walk_commits_with_parents(count, Some(&notes))
+    }
+
+    fn walk_commits_with_parents(&self, count: usize, notes: Option<&NoteOps>) -> Result<Vec<CommitWithParents>> {
         use git2::Sort;
 
         let mut revwalk = self.git_repo.revwalk()?;
@@ -181,9 +308,18 @@ impl Repository {
                 author: commit.author().name().unwrap_or("").to_string(),
                 email: commit.author().email().unwrap_or("").to_string(),
                 timestamp: commit.time().seconds(),
+                notes: None,
             });
         }
 
+        if let Some(notes) = notes {
+            let ids: Vec<String> = commits.iter().map(|c| c.id.clone()).collect();
+            let mut found = notes.read_many(&ids)?;
+            for commit in &mut commits {
+                commit.notes = found.remove(&commit.id);
+            }
+        }
+
         Ok(commits)
     }
 
@@ -269,6 +405,7 @@ impl Repository {
 
     /// Checkout a branch
     pub fn checkout_branch(&self, name: &str) -> Result<()> {
+        self.require_workdir("Checkout")?;
         let branch = self.git_repo.find_branch(name, git2::BranchType::Local)?;
         let reference = branch.get();
         let object = reference.peel(git2::ObjectType::Commit)?;
@@ -279,8 +416,82 @@ impl Repository {
         Ok(())
     }
 
+    // Branch operations
+
+    /// Get branch operations handler
+    pub fn branch_ops(&self) -> BranchOps<'_> {
+        BranchOps::new(&self.git_repo)
+    }
+
+    /// Get commit message handler (template, linting, editor)
+    pub fn message_ops(&self) -> MessageOps<'_> {
+        MessageOps::new(&self.git_repo)
+    }
+
+    /// Notes handler for the configured notes ref (`core.notesRef`)
+    pub fn notes_ops(&self) -> NoteOps<'_> {
+        NoteOps::new(&self.git_repo)
+    }
+
+    /// Notes handler for another notes ref; "ci" means `refs/notes/ci`
+    pub fn notes_ops_for(&self, notes_ref: &str) -> NoteOps<'_> {
+        NoteOps::with_ref(&self.git_repo, notes_ref)
+    }
+
+    /// Ignore rules handler: add rules, explain matches, list ignored paths
+    pub fn ignore_ops(&self) -> IgnoreOps<'_> {
+        IgnoreOps::new(&self.git_repo)
+    }
+
+    /// Commit-graph writer
+    pub fn commit_graph_ops(&self) -> CommitGraphOps<'_> {
+        CommitGraphOps::new(&self.git_repo)
+    }
+
+    /// Parents, dates and generations of commits, through the commit-graph when there is one
+    pub fn commit_lookup(&self) -> CommitLookup<'_> {
+        CommitLookup::new(&self.git_repo)
+    }
+
+    /// Get history analytics handler
+    pub fn analytics_ops(&self) -> AnalyticsOps<'_> {
+        AnalyticsOps::new(&self.git_repo)
+    }
+
+    /// Contributor, churn, coupling and activity statistics for the history of HEAD
+    pub fn analytics(&self, options: &AnalyticsOptions) -> Result<RepoAnalytics> {
+        self.analytics_ops().compute(options)
+    }
+
+    /// Delete a local branch, refusing unmerged branches unless forced
+    pub fn delete_branch(&self, name: &str, force: bool) -> Result<String> {
+        self.branch_ops().delete(name, force)
+    }
+
+    /// Rename a local branch
+    pub fn rename_branch(&self, old_name: &str, new_name: &str, force: bool) -> Result<String> {
+        self.branch_ops().rename(old_name, new_name, force)
+    }
+
+    /// Set or unset the upstream of a local branch
+    pub fn set_branch_upstream(&self, name: &str, upstream: Option<&str>) -> Result<String> {
+        self.branch_ops().set_upstream(name, upstream)
+    }
+
+    /// Check out a remote branch as a new local tracking branch
+    pub fn checkout_remote_branch(&self, remote_branch: &str, local_name: Option<&str>) -> Result<String> {
+        self.require_workdir("Checkout")?;
+        self.branch_ops().checkout_remote(remote_branch, local_name)
+    }
+
+    /// Get a diff engine configured with custom options
+    pub fn diff_with_config(&self, config: DiffConfig) -> Diff<'_> {
+        Diff::with_config(&self.git_repo, config)
+    }
+
     /// Get diff between working directory and index
     pub fn diff_workdir_to_index(&self) -> Result<Vec<FileDiff>> {
+        self.require_workdir("Working tree diff")?;
         let diff = Diff::new(&self.git_repo);
         diff.workdir_to_index()
     }
@@ -305,12 +516,14 @@ impl Repository {
 
     /// Get diff statistics for working directory
     pub fn diff_stats(&self) -> Result<DiffStats> {
+        self.require_workdir("Working tree diff")?;
         let diffs = self.diff_workdir_to_index()?;
         Ok(DiffStats::from_diffs(&diffs))
     }
 
     /// Get diff for a specific file in working directory
     pub fn diff_file<P: AsRef<Path>>(&self, path: P) -> Result<FileDiff> {
+        self.require_workdir("Working tree diff")?;
         let diff = Diff::new(&self.git_repo);
         diff.file_diff(path.as_ref())
     }
@@ -323,48 +536,114 @@ impl Repository {
 
     /// Stage a file
     pub fn stage_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.require_workdir("Staging")?;
         let commit = Commit::new(&self.git_repo);
         commit.stage_file(path)
     }
 
     /// Stage all files
     pub fn stage_all(&self) -> Result<()> {
+        self.require_workdir("Staging")?;
         let commit = Commit::new(&self.git_repo);
         commit.stage_all()
     }
 
     /// Unstage a file
     pub fn unstage_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.require_workdir("Unstaging")?;
         let commit = Commit::new(&self.git_repo);
         commit.unstage_file(path)
     }
 
     /// Reset all staged files
     pub fn reset_index(&self) -> Result<()> {
+        self.require_workdir("Unstaging")?;
         let commit = Commit::new(&self.git_repo);
         commit.reset_index()
     }
 
     /// Create a commit
     pub fn commit(&self, message: &str, author_name: &str, author_email: &str) -> Result<String> {
+        self.require_workdir("Commit")?;
         let commit = Commit::new(&self.git_repo);
         commit.create(message, author_name, author_email)
     }
 
+    /// Commit staged changes as a `fixup!`/`squash!`/`amend!` of `target`
+    pub fn commit_fixup(
+        &self,
+        target: &str,
+        kind: FixupKind,
+        message: Option<&str>,
+        author_name: &str,
+        author_email: &str,
+    ) -> Result<String> {
+        self.require_workdir("Commit")?;
+        let commit = Commit::new(&self.git_repo);
+        commit.create_fixup(target, kind, message, author_name, author_email)
+    }
+
+    /// Fold the staged changes into `target` in one step: commit a fixup and autosquash it
+    ///
+    /// Nothing is committed when the history cannot be rewritten, and when the
+    /// rewrite fails or conflicts the changes are left staged as before.
+    pub fn fixup_into(&self, target: &str, author_name: &str, author_email: &str) -> Result<RebaseResult> {
+        self.require_workdir("Commit")?;
+        if self.git_repo.state() != git2::RepositoryState::Clean {
+            return Err(anyhow::anyhow!("Another operation is in progress ({:?})", self.git_repo.state()));
+        }
+        let target = self.git_repo.revparse_single(target)?.peel_to_commit()?;
+        let upstream = target.parent_ids().next().map(|id| id.to_string());
+        let ops = RebaseOps::from_repo(self.reopen()?);
+
+        // Merges in the way or a target outside HEAD's history fail before anything is committed
+        let todo = ops.todo(upstream.as_deref())?;
+        if !todo.items.iter().any(|item| item.commit == target.id().to_string()) {
+            return Err(anyhow::anyhow!(
+                "Commit {} is not in the history of HEAD",
+                &target.id().to_string()[..8]
+            ));
+        }
+
+        let head = self.git_repo.head()?.peel_to_commit()?;
+        self.commit_fixup(&target.id().to_string(), FixupKind::Fixup, None, author_name, author_email)?;
+        let result = ops.todo(upstream.as_deref()).and_then(|mut todo| {
+            todo.autosquash();
+            ops.run_todo(&todo, "rebase (fixup)")
+        });
+        match result {
+            Ok(result) if result.success => Ok(result),
+            result => {
+                self.git_repo.reset(head.as_object(), git2::ResetType::Soft, None)?;
+                result
+            }
+        }
+    }
+
     /// Amend the last commit
     pub fn amend_commit(&self, message: Option<&str>) -> Result<String> {
+        self.require_workdir("Amend")?;
         let commit = Commit::new(&self.git_repo);
         commit.amend(message)
     }
 
     /// Get file statuses
     pub fn get_status(&self) -> Result<Vec<FileStatus>> {
+        self.require_workdir("Status")?;
         let status = Status::new(&self.git_repo);
         status.get_all()
     }
 
+    /// Get a porcelain-v2-like status report with separate index and working tree states
+    pub fn get_status_report(&self, include_ignored: bool) -> Result<StatusReport> {
+        self.require_workdir("Status")?;
+        let status = Status::new(&self.git_repo);
+        status.report(include_ignored)
+    }
+
     /// Check if there are staged changes
     pub fn has_staged_changes(&self) -> Result<bool> {
+        self.require_workdir("Status")?;
         let status = Status::new(&self.git_repo);
         status.has_staged_changes()
     }
@@ -396,8 +675,19 @@ impl Repository {
         self.remote_ops().fetch(remote_name, &[], None)
     }
 
+    /// Fetch from remote, reporting updated refs
+    pub fn fetch_with_options(&self, remote_name: &str, options: &FetchOptions) -> Result<FetchReport> {
+        self.remote_ops().fetch_with_options(remote_name, options, None)
+    }
+
+    /// Fetch all remotes
+    pub fn fetch_all(&self, options: &FetchOptions) -> Result<Vec<FetchReport>> {
+        self.remote_ops().fetch_all(options, None)
+    }
+
     /// Pull from remote
     pub fn pull(&self, remote_name: &str, branch_name: &str) -> Result<String> {
+        self.require_workdir("Pull")?;
         self.remote_ops().pull(remote_name, branch_name, None)
     }
 
@@ -416,54 +706,81 @@ impl Repository {
         self.remote_ops().set_upstream(remote_name, branch_name)
     }
 
+    /// Push a notes ref to the same ref on a remote
+    pub fn push_notes(&self, remote_name: &str, notes_ref: &str) -> Result<String> {
+        self.remote_ops().push_notes(remote_name, notes_ref, None)
+    }
+
+    /// Fetch a notes ref (or "*" for all) from a remote into the same local ref
+    pub fn fetch_notes(&self, remote_name: &str, notes_ref: &str) -> Result<FetchReport> {
+        self.remote_ops().fetch_notes(remote_name, notes_ref, None)
+    }
+
     // Stash operations
 
     /// Save changes to stash
     pub fn stash_save(&self, message: Option<&str>, include_untracked: bool) -> Result<String> {
-        let mut ops = StashOps::new(&self.path)?;
+        self.require_workdir("Stash")?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
         ops.save(message, include_untracked)
     }
 
     /// List all stashes
     pub fn stash_list(&self) -> Result<Vec<StashInfo>> {
-        let mut ops = StashOps::new(&self.path)?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
         ops.list()
     }
 
-    /// Apply a stash
-    pub fn stash_apply(&self, index: usize) -> Result<String> {
-        let mut ops = StashOps::new(&self.path)?;
-        ops.apply(index)
+    /// Save changes to stash with path limiting, keep-index or staged-only modes
+    pub fn stash_save_with_options(&self, options: &StashSaveOptions) -> Result<String> {
+        self.require_workdir("Stash")?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
+        ops.save_with_options(options)
+    }
+
+    /// Apply a stash, optionally restoring its staged state
+    pub fn stash_apply(&self, index: usize, restore_index: bool) -> Result<MergeResult> {
+        self.require_workdir("Stash apply")?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
+        ops.apply(index, restore_index)
     }
 
     /// Pop a stash (apply and remove)
-    pub fn stash_pop(&self, index: Option<usize>) -> Result<String> {
+    pub fn stash_pop(&self, index: Option<usize>, restore_index: bool) -> Result<MergeResult> {
+        self.require_workdir("Stash pop")?;
         let index = index.unwrap_or(0);
-        let mut ops = StashOps::new(&self.path)?;
-        ops.pop(index)
+        let mut ops = StashOps::from_repo(self.reopen()?);
+        ops.pop(index, restore_index)
+    }
+
+    /// Create a branch from a stash's base commit and apply the stash there
+    pub fn stash_branch(&self, name: &str, index: usize) -> Result<MergeResult> {
+        self.require_workdir("Stash branch")?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
+        ops.branch(name, index)
     }
 
     /// Drop a stash
     pub fn stash_drop(&self, index: usize) -> Result<String> {
-        let mut ops = StashOps::new(&self.path)?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
         ops.drop(index)
     }
 
     /// Clear all stashes
     pub fn stash_clear(&self) -> Result<String> {
-        let mut ops = StashOps::new(&self.path)?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
         ops.clear()
     }
 
     /// Show a stash
-    pub fn stash_show(&self, index: usize) -> Result<String> {
-        let mut ops = StashOps::new(&self.path)?;
+    pub fn stash_show(&self, index: usize) -> Result<StashDiff> {
+        let mut ops = StashOps::from_repo(self.reopen()?);
         ops.show(index)
     }
 
     /// Check if there are any stashes
     pub fn has_stashes(&self) -> Result<bool> {
-        let mut ops = StashOps::new(&self.path)?;
+        let mut ops = StashOps::from_repo(self.reopen()?);
         ops.has_stashes()
     }
 
@@ -477,37 +794,67 @@ impl Repository {
         message: Option<&str>,
         force: bool,
     ) -> Result<String> {
-        let ops = TagOps::new(&self.path)?;
+        let ops = TagOps::from_repo(self.reopen()?);
         ops.create(name, target, message, force)
     }
 
     /// List all tags
     pub fn tag_list(&self, pattern: Option<&str>) -> Result<Vec<TagInfo>> {
-        let ops = TagOps::new(&self.path)?;
+        let ops = TagOps::from_repo(self.reopen()?);
         ops.list(pattern)
     }
 
+    /// List tags by name, version or date
+    pub fn tag_list_sorted(&self, pattern: Option<&str>, sort: TagSort, descending: bool) -> Result<Vec<TagInfo>> {
+        let ops = TagOps::from_repo(self.reopen()?);
+        ops.list_sorted(pattern, sort, descending)
+    }
+
+    /// Tags containing a commit
+    pub fn tag_contains(&self, commit_ref: &str) -> Result<Vec<String>> {
+        let ops = TagOps::from_repo(self.reopen()?);
+        ops.contains(commit_ref)
+    }
+
     /// Delete a tag
     pub fn tag_delete(&self, name: &str) -> Result<String> {
-        let mut ops = TagOps::new(&self.path)?;
+        let mut ops = TagOps::from_repo(self.reopen()?);
         ops.delete(name)
     }
 
+    /// Delete a tag on a remote
+    pub fn tag_delete_remote(&self, remote_name: &str, name: &str) -> Result<String> {
+        let ops = TagOps::from_repo(self.reopen()?);
+        ops.delete_remote(remote_name, name)
+    }
+
+    /// Name a commit (HEAD when `None`) after its nearest tag
+    pub fn describe(&self, commit_ref: Option<&str>, options: &DescribeOptions) -> Result<Description> {
+        let ops = TagOps::from_repo(self.reopen()?);
+        ops.describe(commit_ref, options)
+    }
+
+    /// Describe many commits, e.g. for graph labels; undescribable commits are left out
+    pub fn describe_many(&self, commits: &[String], options: &DescribeOptions) -> Result<std::collections::HashMap<String, Description>> {
+        let ops = TagOps::from_repo(self.reopen()?);
+        ops.describe_many(commits, options)
+    }
+
     /// Show tag details
     pub fn tag_show(&self, name: &str) -> Result<String> {
-        let ops = TagOps::new(&self.path)?;
+        let ops = TagOps::from_repo(self.reopen()?);
         ops.show(name)
     }
 
     /// Push tags to remote
     pub fn tag_push(&self, remote_name: &str, tag_name: Option<&str>, force: bool) -> Result<String> {
-        let ops = TagOps::new(&self.path)?;
+        let ops = TagOps::from_repo(self.reopen()?);
         ops.push(remote_name, tag_name, force)
     }
 
     /// Check if a tag exists
     pub fn tag_exists(&self, name: &str) -> Result<bool> {
-        let ops = TagOps::new(&self.path)?;
+        let ops = TagOps::from_repo(self.reopen()?);
         Ok(ops.exists(name))
     }
 
@@ -515,37 +862,48 @@ impl Repository {
 
     /// Merge a branch into the current branch
     pub fn merge_branch(&self, branch_name: &str, message: Option<&str>) -> Result<MergeResult> {
-        let ops = MergeOps::new(&self.path)?;
+        self.require_workdir("Merge")?;
+        let ops = MergeOps::from_repo(self.reopen()?);
         ops.merge_branch(branch_name, message)
     }
 
+    /// Work out what merging `rev` into HEAD would do without touching the
+    /// index or working tree
+    pub fn predict_merge(&self, rev: &str) -> Result<MergePrediction> {
+        let ops = MergeOps::from_repo(self.reopen()?);
+        ops.predict_merge(rev)
+    }
+
     /// Abort an in-progress merge
     pub fn merge_abort(&self) -> Result<String> {
-        let ops = MergeOps::new(&self.path)?;
+        self.require_workdir("Merge abort")?;
+        let ops = MergeOps::from_repo(self.reopen()?);
         ops.abort_merge()
     }
 
     /// Continue an in-progress merge
     pub fn merge_continue(&self, message: Option<&str>) -> Result<MergeResult> {
-        let ops = MergeOps::new(&self.path)?;
+        self.require_workdir("Merge continue")?;
+        let ops = MergeOps::from_repo(self.reopen()?);
         ops.continue_merge(message)
     }
 
     /// Get merge status
     pub fn merge_status(&self) -> Result<String> {
-        let ops = MergeOps::new(&self.path)?;
+        let ops = MergeOps::from_repo(self.reopen()?);
         ops.merge_status()
     }
 
     /// Get list of conflicted files
     pub fn merge_conflicts(&self) -> Result<Vec<String>> {
-        let ops = MergeOps::new(&self.path)?;
+        let ops = MergeOps::from_repo(self.reopen()?);
         ops.get_conflicts()
     }
 
     /// Resolve a conflict
     pub fn merge_resolve_conflict(&self, file_path: &str, resolution: ConflictResolution) -> Result<String> {
-        let ops = MergeOps::new(&self.path)?;
+        self.require_workdir("Conflict resolution")?;
+        let ops = MergeOps::from_repo(self.reopen()?);
         ops.resolve_conflict(file_path, resolution)
     }
 
@@ -553,76 +911,189 @@ impl Repository {
 
     /// Rebase current branch onto another branch
     pub fn rebase_onto(&self, target_branch: &str) -> Result<RebaseResult> {
-        let ops = RebaseOps::new(&self.path)?;
+        self.require_workdir("Rebase")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
         ops.rebase_onto(target_branch)
     }
 
+    /// Work out whether rebasing HEAD onto `upstream` (or `onto`) would
+    /// conflict, and at which commit, without touching the working tree
+    pub fn predict_rebase(&self, upstream: &str, onto: Option<&str>) -> Result<RebasePrediction> {
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.predict_rebase(upstream, onto)
+    }
+
     /// Start an interactive rebase
     pub fn rebase_interactive(&self, upstream: &str, onto: Option<&str>) -> Result<RebaseResult> {
-        let ops = RebaseOps::new(&self.path)?;
+        self.require_workdir("Rebase")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
         ops.start_interactive(upstream, onto)
     }
 
     /// Continue an in-progress rebase
     pub fn rebase_continue(&self) -> Result<RebaseResult> {
-        let mut ops = RebaseOps::new(&self.path)?;
+        self.require_workdir("Rebase continue")?;
+        let mut ops = RebaseOps::from_repo(self.reopen()?);
         ops.continue_rebase()
     }
 
     /// Abort an in-progress rebase
     pub fn rebase_abort(&self) -> Result<String> {
-        let ops = RebaseOps::new(&self.path)?;
+        self.require_workdir("Rebase abort")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
         ops.abort_rebase()
     }
 
     /// Skip current commit in rebase
     pub fn rebase_skip(&self) -> Result<RebaseResult> {
-        let mut ops = RebaseOps::new(&self.path)?;
+        self.require_workdir("Rebase skip")?;
+        let mut ops = RebaseOps::from_repo(self.reopen()?);
         ops.skip_commit()
     }
 
+    /// Todo list for `upstream..HEAD` (all of HEAD's history without `upstream`)
+    pub fn rebase_todo(&self, upstream: Option<&str>) -> Result<RebaseTodo> {
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.todo(upstream)
+    }
+
+    /// Replay an edited todo list and move HEAD to the result
+    pub fn rebase_run_todo(&self, todo: &RebaseTodo) -> Result<RebaseResult> {
+        self.require_workdir("Rebase")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.run_todo(todo, "rebase")
+    }
+
+    /// Fold `fixup!`/`squash!`/`amend!` commits into the commits they target
+    pub fn autosquash(&self, upstream: Option<&str>) -> Result<RebaseResult> {
+        self.require_workdir("Autosquash")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.autosquash(upstream)
+    }
+
+    /// Split a commit into the given parts plus a commit with whatever is left,
+    /// replaying the commits on top of it
+    pub fn split_commit(&self, commit_ref: &str, parts: &[SplitPart]) -> Result<RebaseResult> {
+        self.require_workdir("Split")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.split_commit(commit_ref, parts)
+    }
+
+    /// Move the commits `oldest..=newest` of the current branch elsewhere in
+    /// it or onto another branch
+    pub fn move_commits(&self, oldest: &str, newest: &str, destination: &MoveDestination) -> Result<RebaseResult> {
+        self.require_workdir("Move")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.move_commits(oldest, newest, destination)
+    }
+
+    /// Change the message, author or committer of any commit in HEAD's
+    /// history, replaying the commits after it
+    pub fn rewrite_commit(&self, commit_ref: &str, metadata: &CommitMetadata) -> Result<RebaseResult> {
+        self.require_workdir("Rewrite")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.rewrite_commit(commit_ref, metadata)
+    }
+
+    /// Rewrite the identity of every commit by one email in `upstream..HEAD`
+    /// (all of HEAD's history when `upstream` is `None`)
+    pub fn rewrite_identity(&self, upstream: Option<&str>, rewrite: &IdentityRewrite) -> Result<RebaseResult> {
+        self.require_workdir("Rewrite")?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
+        ops.rewrite_identity(upstream, rewrite)
+    }
+
+    /// Undo the last history rewrite (move, split, autosquash, ...)
+    ///
+    /// Returns `None` when there is nothing to undo.
+    pub fn undo(&self) -> Result<Option<UndoEntry>> {
+        self.require_workdir("Undo")?;
+        UndoLog::new(&self.git_repo).undo()
+    }
+
+    /// Operations that can be undone, oldest first
+    pub fn undo_entries(&self) -> Result<Vec<UndoEntry>> {
+        UndoLog::new(&self.git_repo).entries()
+    }
+
+    /// Compare two versions of a patch series given as `A..B` ranges
+    pub fn range_diff(&self, old_range: &str, new_range: &str) -> Result<RangeDiff> {
+        RangeDiffOps::new(&self.git_repo).compare(old_range, new_range)
+    }
+
+    /// Compare two tips of a branch from their merge base, e.g. `branch@{1}`
+    /// and `branch` after a rebase
+    pub fn range_diff_tips(&self, old_tip: &str, new_tip: &str) -> Result<RangeDiff> {
+        RangeDiffOps::new(&self.git_repo).compare_tips(old_tip, new_tip)
+    }
+
+    /// Range-diff of what the last undoable rewrite did to the first ref it moved
+    pub fn range_diff_last_rewrite(&self) -> Result<RangeDiff> {
+        let entry = self.undo_entries()?.pop()
+            .ok_or_else(|| anyhow::anyhow!("No history rewrite to compare"))?;
+        let (old, new) = entry.changes.iter()
+            .find_map(|change| Some((change.old.as_deref()?, change.new.as_deref()?)))
+            .ok_or_else(|| anyhow::anyhow!("'{}' did not rewrite a branch", entry.description))?;
+        self.range_diff_tips(old, new)
+    }
+
     /// Get rebase status
     pub fn rebase_status(&self) -> Result<String> {
-        let ops = RebaseOps::new(&self.path)?;
+        let ops = RebaseOps::from_repo(self.reopen()?);
         ops.rebase_status()
     }
 
     // Cherry-pick operations
 
     /// Cherry-pick a single commit
-    pub fn cherry_pick(&self, commit_ref: &str) -> Result<CherryPickResult> {
-        let ops = CherryPickOps::new(&self.path)?;
-        ops.pick_commit(commit_ref)
+    pub fn cherry_pick(&self, commit_ref: &str, options: &PickOptions) -> Result<CherryPickResult> {
+        self.require_workdir("Cherry-pick")?;
+        let ops = CherryPickOps::from_repo(self.reopen()?);
+        ops.pick_commit_with(commit_ref, options)
+    }
+
+    /// Cherry-pick the commits in `start..end`, skipping those already applied
+    pub fn cherry_pick_range(&self, start_ref: &str, end_ref: &str, options: &PickOptions) -> Result<Vec<CherryPickResult>> {
+        self.require_workdir("Cherry-pick")?;
+        let ops = CherryPickOps::from_repo(self.reopen()?);
+        ops.pick_range_with(start_ref, end_ref, options)
     }
 
-    /// Cherry-pick a range of commits
-    pub fn cherry_pick_range(&self, start_ref: &str, end_ref: &str) -> Result<Vec<CherryPickResult>> {
-        let ops = CherryPickOps::new(&self.path)?;
-        ops.pick_range(start_ref, end_ref)
+    /// Revert commits in the given order, pausing on the first conflict
+    pub fn revert(&self, commit_refs: &[String], options: &PickOptions) -> Result<Vec<CherryPickResult>> {
+        self.require_workdir("Revert")?;
+        let ops = CherryPickOps::from_repo(self.reopen()?);
+        ops.revert_commits(commit_refs, options)
     }
 
-    /// Continue a cherry-pick after resolving conflicts
-    pub fn cherry_pick_continue(&self) -> Result<CherryPickResult> {
-        let ops = CherryPickOps::new(&self.path)?;
-        ops.continue_pick()
+    /// Continue a cherry-pick or revert after resolving conflicts, then
+    /// apply the rest of its commits
+    pub fn cherry_pick_continue(&self) -> Result<Vec<CherryPickResult>> {
+        self.require_workdir("Cherry-pick continue")?;
+        let ops = CherryPickOps::from_repo(self.reopen()?);
+        ops.continue_sequence()
     }
 
     /// Abort a cherry-pick in progress
     pub fn cherry_pick_abort(&self) -> Result<String> {
-        let ops = CherryPickOps::new(&self.path)?;
+        self.require_workdir("Cherry-pick abort")?;
+        let ops = CherryPickOps::from_repo(self.reopen()?);
         ops.abort_pick()
     }
 
     /// Get cherry-pick status
     pub fn cherry_pick_status(&self) -> Result<String> {
-        let ops = CherryPickOps::new(&self.path)?;
+        let ops = CherryPickOps::from_repo(self.reopen()?);
         ops.pick_status()
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::commit::StatusEntryKind;
+    use crate::message::LintRule;
+    use crate::rebase::TodoAction;
     use tempfile::TempDir;
     use std::fs;
     use std::io::Write;
@@ -644,13 +1115,13 @@ mod tests {
     }
 
     /// Helper to write a file
-    fn write_file<P: AsRef<std::path::Path>>(p: P, content: &str) {
+    pub(crate) fn write_file<P: AsRef<std::path::Path>>(p: P, content: &str) {
         let mut f = fs::File::create(p).unwrap();
         f.write_all(content.as_bytes()).unwrap();
     }
 
     /// Make a commit with a new or updated file
-    fn make_commit(repo: &Repository, workdir: &std::path::Path, name: &str, content: &str, msg: &str) -> String {
+    pub(crate) fn make_commit(repo: &Repository, workdir: &std::path::Path, name: &str, content: &str, msg: &str) -> String {
         write_file(workdir.join(name), content);
         let inner = Commit::new(&repo.git_repo);
         inner.stage_file(name).unwrap();
@@ -760,4 +1231,673 @@ mod tests {
         assert!(map.values().flatten().any(|r| matches!(r.ref_type, RefType::Branch)));
         assert!(map.values().flatten().any(|r| matches!(r.ref_type, RefType::Tag)));
     }
+
+    #[test]
+    fn test_branch_tracking_and_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        make_commit(&repo, temp_dir.path(), "t.txt", "1", "c1");
+        let main = repo.git_repo.head().unwrap().shorthand().unwrap().to_string();
+
+        repo.create_branch("feature", None).unwrap();
+        repo.checkout_branch("feature").unwrap();
+        make_commit(&repo, temp_dir.path(), "t.txt", "2", "c2");
+        repo.set_branch_upstream("feature", Some(&main)).unwrap();
+        repo.checkout_branch(&main).unwrap();
+
+        let branches = repo.list_branches().unwrap();
+        let feature = branches.iter().find(|b| b.name == "feature").unwrap();
+        assert_eq!(feature.upstream.as_deref(), Some(main.as_str()));
+        assert_eq!((feature.ahead, feature.behind), (1, 0));
+        assert!(!feature.upstream_gone);
+
+        // Unmerged work is protected unless forced
+        assert!(repo.delete_branch("feature", false).is_err());
+        repo.rename_branch("feature", "topic", false).unwrap();
+        repo.delete_branch("topic", true).unwrap();
+        assert!(!repo.list_branches().unwrap().iter().any(|b| b.name == "topic"));
+    }
+
+    #[test]
+    fn test_status_report_separates_index_and_worktree() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let content = "line 1\nline 2\nline 3\nline 4\n";
+        make_commit(&repo, temp_dir.path(), "a.txt", content, "c1");
+        make_commit(&repo, temp_dir.path(), "b.txt", "b", "c2");
+
+        // Staged rename a.txt -> moved.txt
+        fs::rename(temp_dir.path().join("a.txt"), temp_dir.path().join("moved.txt")).unwrap();
+        let mut index = repo.git_repo.index().unwrap();
+        index.remove_path(std::path::Path::new("a.txt")).unwrap();
+        index.add_path(std::path::Path::new("moved.txt")).unwrap();
+        index.write().unwrap();
+
+        // Staged and then further modified b.txt, plus an untracked file
+        write_file(temp_dir.path().join("b.txt"), "staged");
+        repo.stage_file("b.txt").unwrap();
+        write_file(temp_dir.path().join("b.txt"), "unstaged");
+        write_file(temp_dir.path().join("new.txt"), "new");
+
+        let report = repo.get_status_report(false).unwrap();
+        assert!(report.branch.head.is_some());
+        assert!(report.branch.oid.is_some());
+
+        let moved = report.entries.iter().find(|e| e.path == "moved.txt").unwrap();
+        assert_eq!(moved.kind, StatusEntryKind::Renamed);
+        assert_eq!(moved.orig_path.as_deref(), Some("a.txt"));
+        assert_eq!(moved.similarity, Some(100));
+        assert_eq!(moved.xy(), "R.");
+
+        let b = report.entries.iter().find(|e| e.path == "b.txt").unwrap();
+        assert_eq!(b.xy(), "MM");
+        assert!(b.is_staged() && b.is_unstaged());
+
+        let untracked = report.entries.iter().find(|e| e.path == "new.txt").unwrap();
+        assert_eq!(untracked.kind, StatusEntryKind::Untracked);
+        assert_eq!(untracked.xy(), "??");
+    }
+
+    #[test]
+    fn test_diff_with_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        make_commit(&repo, temp_dir.path(), "a.txt", "fn main() {\n    run();\n}\n", "c1");
+        make_commit(&repo, temp_dir.path(), "b.txt", "b\n", "c2");
+
+        // Whitespace-only change in a.txt, real change in b.txt
+        write_file(temp_dir.path().join("a.txt"), "fn main() {\n\trun();\n}\n");
+        write_file(temp_dir.path().join("b.txt"), "changed\n");
+
+        let config = DiffConfig {
+            whitespace: crate::diff::WhitespaceMode::IgnoreAll,
+            ..DiffConfig::default()
+        };
+        let diffs = repo.diff_with_config(config).workdir_to_index().unwrap();
+        let a = diffs.iter().find(|d| d.file.path == "a.txt");
+        assert!(a.map(|d| d.lines.is_empty()).unwrap_or(true));
+
+        let config = DiffConfig {
+            pathspecs: vec!["b.txt".to_string()],
+            context_lines: 0,
+            ..DiffConfig::default()
+        };
+        let diffs = repo.diff_with_config(config).workdir_to_index().unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].file.path, "b.txt");
+    }
+
+    #[test]
+    fn test_discover_subdir_bare_and_worktree() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("main");
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        let repo = Repository::init(&root).unwrap();
+        make_commit(&repo, &root, "a.txt", "a", "c1");
+
+        // From a subdirectory
+        let found = Repository::discover(root.join("src/nested")).unwrap();
+        assert_eq!(found.workdir().unwrap().canonicalize().unwrap(), root.canonicalize().unwrap());
+        assert!(!found.is_bare());
+        assert!(found.get_status_report(false).is_ok());
+
+        // Ceiling directories stop the search
+        assert!(Repository::discover_with_ceiling(root.join("src/nested"), &[root.join("src")]).is_err());
+
+        // Bare repositories open, but working tree operations fail clearly
+        let bare_path = temp_dir.path().join("bare.git");
+        git2::Repository::init_bare(&bare_path).unwrap();
+        let bare = Repository::discover(&bare_path).unwrap();
+        assert!(bare.is_bare());
+        assert!(bare.workdir().is_none());
+        let err = bare.get_status().unwrap_err().to_string();
+        assert!(err.contains("bare"), "{}", err);
+
+        // Linked worktrees
+        let wt_path = temp_dir.path().join("wt");
+        repo.git_repo.worktree("wt", &wt_path, None).unwrap();
+        let wt = Repository::discover(&wt_path).unwrap();
+        assert!(wt.is_worktree());
+        assert!(wt.stash_list().is_ok());
+    }
+
+    #[test]
+    fn test_fetch_report_prune_and_tags() {
+        use crate::remote::{RefUpdateKind, TagMode};
+
+        let source_dir = TempDir::new().unwrap();
+        let source = Repository::init(source_dir.path()).unwrap();
+        let base = make_commit(&source, source_dir.path(), "a.txt", "1", "base");
+        source.create_branch("gone", None).unwrap();
+        source.create_branch("rewritten", None).unwrap();
+
+        let clone_dir = TempDir::new().unwrap();
+        let url = format!("file://{}", source_dir.path().display());
+        let clone = Repository::clone_from(&url, clone_dir.path().join("c"), &Default::default(), None).unwrap();
+        let head = source.git_repo.head().unwrap().shorthand().unwrap().to_string();
+
+        // Fast-forward the default branch, rewrite one branch, delete another, add a tag
+        let tip = make_commit(&source, source_dir.path(), "a.txt", "2", "next");
+        let base_commit = source.git_repo.find_commit(git2::Oid::from_str(&base).unwrap()).unwrap();
+        let tree = base_commit.tree().unwrap();
+        let sig = git2::Signature::now("Tester", "tester@example.com").unwrap();
+        let other = source.git_repo.commit(None, &sig, &sig, "other", &tree, &[]).unwrap();
+        source.git_repo.reference("refs/heads/rewritten", other, true, "rewrite").unwrap();
+        source.git_repo.find_branch("gone", git2::BranchType::Local).unwrap().delete().unwrap();
+        source.tag_create("v1", Some(&tip), None, false).unwrap();
+
+        let report = clone.fetch_with_options("origin", &FetchOptions {
+            prune: true,
+            tags: TagMode::All,
+            ..Default::default()
+        }).unwrap();
+
+        let kind_of = |name: &str| report.updates.iter().find(|u| u.name == name).map(|u| u.kind);
+        assert_eq!(kind_of(&format!("refs/remotes/origin/{}", head)), Some(RefUpdateKind::FastForward));
+        assert_eq!(kind_of("refs/remotes/origin/rewritten"), Some(RefUpdateKind::Forced));
+        assert_eq!(kind_of("refs/remotes/origin/gone"), Some(RefUpdateKind::Deleted));
+        assert_eq!(kind_of("refs/tags/v1"), Some(RefUpdateKind::New));
+
+        let new_commits = clone.remote_ops().new_commits(&report.updates).unwrap();
+        assert_eq!(new_commits.len(), 2);
+        assert!(new_commits.contains(&tip));
+
+        let reports = clone.fetch_all(&FetchOptions::default()).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].updates.is_empty());
+
+        // An unreachable remote is reported without stopping the others
+        clone.git_repo.remote("broken", "file:///nonexistent/gitup-remote").unwrap();
+        clone.git_repo.remote("zzz", &url).unwrap();
+        let reports = clone.fetch_all(&FetchOptions::default()).unwrap();
+        let names: Vec<&str> = reports.iter().map(|r| r.remote.as_str()).collect();
+        assert_eq!(names, ["broken", "origin", "zzz"]);
+        assert!(reports[0].error.is_some());
+        assert!(reports[1].error.is_none());
+        assert!(reports[2].error.is_none() && !reports[2].updates.is_empty());
+
+        // A commit-graph that cannot be updated does not fail the fetch
+        make_commit(&source, source_dir.path(), "a.txt", "3", "later");
+        std::fs::write(clone.git_repo.path().join("objects/info/commit-graph"), b"garbage").unwrap();
+        let report = clone.fetch_with_options("origin", &FetchOptions {
+            write_commit_graph: true,
+            ..Default::default()
+        }).unwrap();
+        assert!(!report.updates.is_empty());
+        assert!(report.commit_graph_error.is_some());
+    }
+
+    #[test]
+    fn test_unshallow_over_local_transport() {
+        let source_dir = TempDir::new().unwrap();
+        let source = Repository::init(source_dir.path()).unwrap();
+        make_commit(&source, source_dir.path(), "a.txt", "1", "first");
+        make_commit(&source, source_dir.path(), "b.txt", "2", "second");
+        let tip = source.git_repo.head().unwrap().peel_to_commit().unwrap();
+        let url = format!("file://{}", source_dir.path().display());
+
+        // A truly shallow repository: the tip and the bare parent commit object
+        // are present, the parent's tree is not
+        let shallow = |all_objects: bool| {
+            let dir = TempDir::new().unwrap();
+            let repo = Repository::init(dir.path()).unwrap();
+            let from = source.git_repo.odb().unwrap();
+            let mut oids = vec![tip.id(), tip.tree_id(), tip.parent_id(0).unwrap()];
+            oids.extend(tip.tree().unwrap().iter().map(|entry| entry.id()));
+            if all_objects {
+                from.foreach(|oid| {
+                    oids.push(*oid);
+                    true
+                }).unwrap();
+            }
+            let to = repo.git_repo.odb().unwrap();
+            for oid in oids {
+                let object = from.read(oid).unwrap();
+                to.write(object.kind(), object.data()).unwrap();
+            }
+            drop(to);
+            std::fs::write(repo.git_repo.path().join("shallow"), format!("{}\n", tip.id())).unwrap();
+            repo.git_repo.reference("refs/heads/master", tip.id(), true, "").unwrap();
+            repo.add_remote("origin", &url).unwrap();
+            (dir, repo)
+        };
+        let unshallow = FetchOptions { unshallow: true, ..Default::default() };
+
+        let (_dir, repo) = shallow(false);
+        assert!(repo.git_repo.is_shallow());
+        let err = repo.fetch_with_options("origin", &unshallow).unwrap_err();
+        assert!(err.to_string().contains("not supported over the local transport"), "{}", err);
+        assert!(repo.git_repo.is_shallow());
+
+        // With the history already present the boundary is dropped
+        let (dir, repo) = shallow(true);
+        repo.fetch_with_options("origin", &unshallow).unwrap();
+        assert!(!repo.git_repo.is_shallow());
+        let reopened = git2::Repository::open(dir.path()).unwrap();
+        assert_eq!(reopened.find_commit(tip.id()).unwrap().parent_count(), 1);
+    }
+
+    #[test]
+    fn test_commit_template_and_lint_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+        let messages = repo.message_ops();
+        assert_eq!(messages.template().unwrap(), None);
+
+        write_file(dir.join(".gitmessage"), "feat: \n\n# Refs: TICKET-123\n");
+        let mut config = git2::Repository::open(dir).unwrap().config().unwrap();
+        config.set_str("commit.template", ".gitmessage").unwrap();
+        config.set_bool("gitup.lint.conventional", true).unwrap();
+        config.set_str("gitup.lint.types", "feat, fix").unwrap();
+        config.set_i64("gitup.lint.subjectMaxLength", 50).unwrap();
+        config.set_str("gitup.lint.requiredTrailers", "Refs").unwrap();
+
+        assert_eq!(messages.template().unwrap().unwrap(), "feat: \n\n# Refs: TICKET-123\n");
+        let lint = messages.lint_config().unwrap();
+        assert!(lint.conventional);
+        assert_eq!(lint.types, vec!["feat".to_string(), "fix".to_string()]);
+        assert_eq!(lint.subject_max_length, 50);
+
+        let rules: Vec<LintRule> = messages.lint("docs: readme\n").unwrap().iter().map(|f| f.rule).collect();
+        assert_eq!(rules, vec![LintRule::ConventionalType, LintRule::MissingTrailer]);
+        assert!(messages.lint("fix: readme\n\nRefs: TICKET-1\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fixup_squash_amend_and_autosquash() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let a = make_commit(&repo, dir, "a.txt", "1\n", "Add a");
+        let b = make_commit(&repo, dir, "b.txt", "1\n", "Add b");
+        make_commit(&repo, dir, "c.txt", "1\n", "Add c");
+
+        let stage = |name: &str, content: &str| {
+            write_file(dir.join(name), content);
+            Commit::new(&repo.git_repo).stage_file(name).unwrap();
+        };
+        stage("a.txt", "1\n2\n");
+        repo.commit_fixup(&a, FixupKind::Fixup, None, "Tester", "tester@example.com").unwrap();
+        stage("b.txt", "1\n2\n");
+        repo.commit_fixup(&b, FixupKind::Squash, Some("More about b"), "Tester", "tester@example.com").unwrap();
+        repo.commit_fixup("HEAD~2", FixupKind::Amend, Some("Add c, reworded\n"), "Tester", "tester@example.com").unwrap();
+
+        let mut todo = repo.rebase_todo(Some(&a)).unwrap();
+        todo.autosquash();
+        let actions: Vec<TodoAction> = todo.items.iter().map(|item| item.action).collect();
+        // The fixup of `a` targets a commit outside the list and stays a pick
+        assert_eq!(actions, vec![TodoAction::Pick, TodoAction::Squash, TodoAction::Pick, TodoAction::FixupWithMessage, TodoAction::Pick]);
+
+        let result = repo.autosquash(None).unwrap();
+        assert!(result.success, "{}", result.message);
+        let messages: Vec<String> = repo.get_commits(10).unwrap().into_iter().map(|c| c.message).collect();
+        assert_eq!(messages, vec!["Add c, reworded", "Add b", "Add a"]);
+
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent(0).unwrap().message().unwrap(), "Add b\n\nMore about b");
+        let root = head.parent(0).unwrap().parent(0).unwrap();
+        assert_ne!(root.id().to_string(), a);
+        let blob = root.tree().unwrap().get_path(std::path::Path::new("a.txt")).unwrap().to_object(&repo.git_repo).unwrap();
+        assert_eq!(blob.as_blob().unwrap().content(), b"1\n2\n");
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "1\n2\n");
+
+        // One step: fold staged changes into the middle commit
+        stage("b.txt", "1\n2\n3\n");
+        let middle = head.parent(0).unwrap().id().to_string();
+        let result = repo.fixup_into(&middle, "Tester", "tester@example.com").unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(repo.get_commits(10).unwrap().len(), 3);
+        assert!(!repo.has_staged_changes().unwrap());
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.parent(0).unwrap().parent(0).unwrap().id(), root.id());
+        assert_eq!(repo.autosquash(None).unwrap().message, "Nothing to autosquash");
+
+        // A target outside HEAD's history commits nothing and keeps the changes staged
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        let side = repo.git_repo.commit(None, &head.author(), &head.committer(), "Side", &head.tree().unwrap(), &[&head]).unwrap();
+        stage("b.txt", "1\n2\n3\n4\n");
+        let err = repo.fixup_into(&side.to_string(), "Tester", "tester@example.com").unwrap_err();
+        assert!(err.to_string().contains("not in the history"), "{}", err);
+        assert_eq!(repo.git_repo.head().unwrap().target(), Some(head.id()));
+        assert!(repo.has_staged_changes().unwrap());
+    }
+
+    #[test]
+    fn test_split_commit_by_file_and_hunk() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let lines: Vec<String> = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        make_commit(&repo, dir, "a.txt", &lines.concat(), "Add a");
+        let mut changed = lines.clone();
+        changed[1] = "line two\n".to_string();
+        changed[18] = "line nineteen\n".to_string();
+        write_file(dir.join("b.txt"), "b\n");
+        Commit::new(&repo.git_repo).stage_file("b.txt").unwrap();
+        let big = make_commit(&repo, dir, "a.txt", &changed.concat(), "Big change");
+        make_commit(&repo, dir, "c.txt", "c\n", "Add c");
+        let old_tree = repo.git_repo.head().unwrap().peel_to_tree().unwrap().id();
+
+        let parts = vec![
+            SplitPart { message: "Rename line two".to_string(), changes: vec!["a.txt#0".parse().unwrap()] },
+            SplitPart { message: "Add b".to_string(), changes: vec!["b.txt".parse().unwrap()] },
+        ];
+        let result = repo.split_commit(&big, &parts).unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(result.rebased_commits.len(), 4);
+
+        let messages: Vec<String> = repo.get_commits(10).unwrap().into_iter().map(|c| c.message).collect();
+        assert_eq!(messages, vec!["Add c", "Big change", "Add b", "Rename line two", "Add a"]);
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.tree_id(), old_tree);
+
+        // The first part only has the first hunk
+        let first = repo.git_repo.revparse_single("HEAD~3").unwrap().peel_to_commit().unwrap();
+        let blob = first.tree().unwrap().get_path(std::path::Path::new("a.txt")).unwrap().to_object(&repo.git_repo).unwrap();
+        let content = String::from_utf8(blob.as_blob().unwrap().content().to_vec()).unwrap();
+        assert!(content.contains("line two\n") && content.contains("line 19\n"));
+        assert!(first.tree().unwrap().get_path(std::path::Path::new("b.txt")).is_err());
+
+        // Splitting HEAD works in place; bad selections are rejected
+        assert!(repo.split_commit("HEAD", &[SplitPart { message: "x".to_string(), changes: vec!["a.txt".parse().unwrap()] }]).is_err());
+        assert!(repo.split_commit("HEAD~1", &[SplitPart { message: "x".to_string(), changes: vec!["a.txt#5".parse().unwrap()] }]).is_err());
+        write_file(dir.join("d.txt"), "d\n");
+        Commit::new(&repo.git_repo).stage_file("d.txt").unwrap();
+        make_commit(&repo, dir, "e.txt", "e\n", "Add d and e");
+        let result = repo.split_commit("HEAD", &[SplitPart { message: "Add d".to_string(), changes: vec!["d.txt".parse().unwrap()] }]).unwrap();
+        assert!(result.success, "{}", result.message);
+        let messages: Vec<String> = repo.get_commits(2).unwrap().into_iter().map(|c| c.message).collect();
+        assert_eq!(messages, vec!["Add d and e", "Add d"]);
+    }
+
+    #[test]
+    fn test_rewrite_commit_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let a = make_commit(&repo, dir, "a.txt", "a\n", "A");
+        make_commit(&repo, dir, "b.txt", "b\n", "B");
+        make_commit(&repo, dir, "c.txt", "c\n", "C");
+        let head_tree = repo.git_repo.head().unwrap().peel_to_tree().unwrap().id();
+
+        // Reword and re-author the root commit, the rest follows
+        let metadata = CommitMetadata {
+            message: Some("First\n".to_string()),
+            author_name: Some("Someone".to_string()),
+            author_email: Some("someone@example.com".to_string()),
+            author_time: Some((1_000_000_000, 120)),
+            ..Default::default()
+        };
+        let result = repo.rewrite_commit(&a, &metadata).unwrap();
+        assert!(result.success, "{}", result.message);
+        let root = repo.git_repo.revparse_single("HEAD~2").unwrap().peel_to_commit().unwrap();
+        assert_eq!(root.summary(), Some("First"));
+        assert_eq!(root.author().email(), Some("someone@example.com"));
+        assert_eq!(root.author().when().seconds(), 1_000_000_000);
+        assert_eq!(root.author().when().offset_minutes(), 120);
+        assert_eq!(repo.git_repo.head().unwrap().peel_to_tree().unwrap().id(), head_tree);
+        assert!(repo.rewrite_commit("HEAD", &CommitMetadata::default()).is_err());
+
+        // Bulk: move everything by the test identity to a new email
+        let old_email = repo.git_repo.head().unwrap().peel_to_commit().unwrap().author().email().unwrap().to_string();
+        let rewrite = IdentityRewrite {
+            old_email: old_email.to_uppercase(),
+            new_email: Some("new@example.com".to_string()),
+            committer: true,
+            ..Default::default()
+        };
+        let result = repo.rewrite_identity(None, &rewrite).unwrap();
+        assert!(result.success, "{}", result.message);
+        let emails: Vec<String> = repo.get_commits(10).unwrap().into_iter().map(|c| c.email).collect();
+        assert_eq!(emails, vec!["new@example.com", "new@example.com", "someone@example.com"]);
+
+        // Both rewrites are journaled
+        assert_eq!(repo.undo_entries().unwrap().len(), 2);
+        repo.undo().unwrap();
+        repo.undo().unwrap();
+        assert_eq!(repo.resolve_commit("HEAD~2").unwrap(), a);
+    }
+
+    #[test]
+    fn test_cherry_pick_merge_and_revert_sequence() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let a = make_commit(&repo, dir, "a.txt", "a\n", "A");
+        let main = repo.git_repo.head().unwrap().shorthand().unwrap().to_string();
+        repo.create_branch("feature", Some(&a)).unwrap();
+        repo.checkout_branch("feature").unwrap();
+        let f = make_commit(&repo, dir, "f.txt", "f\n", "F");
+        repo.checkout_branch(&main).unwrap();
+        let m = make_commit(&repo, dir, "m.txt", "m\n", "M");
+
+        // Merge feature by hand: M's tree plus f.txt
+        write_file(dir.join("f.txt"), "f\n");
+        repo.stage_file("f.txt").unwrap();
+        let tree = repo.git_repo.find_tree(repo.git_repo.index().unwrap().write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("Tester", "tester@example.com").unwrap();
+        let parents = [&repo.git_repo.find_commit(git2::Oid::from_str(&m).unwrap()).unwrap(),
+                       &repo.git_repo.find_commit(git2::Oid::from_str(&f).unwrap()).unwrap()];
+        let merge = repo.git_repo.commit(Some("HEAD"), &sig, &sig, "Merge feature\n", &tree, &parents).unwrap().to_string();
+
+        // Pick the merge onto a branch from A, relative to the first parent
+        repo.create_branch("other", Some(&a)).unwrap();
+        repo.checkout_branch("other").unwrap();
+        assert!(repo.cherry_pick(&merge, &PickOptions::default()).is_err());
+        let options = PickOptions { mainline: Some(1), record_origin: true, signoff: true, ..Default::default() };
+        let result = repo.cherry_pick(&merge, &options).unwrap();
+        assert!(result.success, "{}", result.message);
+        assert!(dir.join("f.txt").exists() && !dir.join("m.txt").exists());
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        let message = head.message().unwrap();
+        assert!(message.contains(&format!("(cherry picked from commit {})\nSigned-off-by: ", merge)), "{}", message);
+
+        // F's patch is already on this branch
+        let results = repo.cherry_pick_range(&a, "feature", &PickOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].skipped);
+
+        // Stage only
+        make_commit(&repo, dir, "g.txt", "1\n", "G1");
+        let g2 = make_commit(&repo, dir, "g.txt", "2\n", "G2");
+        let g3 = make_commit(&repo, dir, "g.txt", "3\n", "G3");
+        let options = PickOptions { no_commit: true, ..Default::default() };
+        let result = &repo.revert(std::slice::from_ref(&g3), &options).unwrap()[0];
+        assert!(result.success && result.picked_commit.is_none());
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), g3);
+        assert_eq!(fs::read_to_string(dir.join("g.txt")).unwrap(), "2\n");
+        assert_eq!(repo.git_repo.state(), git2::RepositoryState::Clean);
+        repo.git_repo.reset(&repo.git_repo.revparse_single(&g3).unwrap(), git2::ResetType::Hard, None).unwrap();
+
+        // Reverting G2 before G3 conflicts; the sequence resumes after resolving
+        let results = repo.revert(&[g2.clone(), g3.clone()], &PickOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].success);
+        assert_eq!(results[0].conflicts, vec!["g.txt"]);
+        assert!(repo.cherry_pick_status().unwrap().contains("1 more"));
+        write_file(dir.join("g.txt"), "3\n");
+        repo.stage_file("g.txt").unwrap();
+        let results = repo.cherry_pick_continue().unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.success));
+        assert_eq!(fs::read_to_string(dir.join("g.txt")).unwrap(), "2\n");
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("Revert \"G3\""));
+        assert!(head.message().unwrap().contains(&format!("This reverts commit {}.", g3)));
+        assert_eq!(head.parent(0).unwrap().summary(), Some("Revert \"G2\""));
+        assert_eq!(repo.git_repo.state(), git2::RepositoryState::Clean);
+    }
+
+    #[test]
+    fn test_no_commit_sequence_stages_every_step() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        make_commit(&repo, dir, "g.txt", "1\n", "G1");
+        let g2 = make_commit(&repo, dir, "g.txt", "2\n", "G2");
+        let g3 = make_commit(&repo, dir, "g.txt", "3\n", "G3");
+        let h = make_commit(&repo, dir, "h.txt", "h\n", "H");
+        let options = PickOptions { no_commit: true, ..Default::default() };
+
+        // Both reverts end up staged on top of each other
+        let results = repo.revert(&[h.clone(), g3.clone(), g2.clone()], &options).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.success && r.picked_commit.is_none()), "{:?}", results);
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), h);
+        assert_eq!(fs::read_to_string(dir.join("g.txt")).unwrap(), "1\n");
+        assert!(!dir.join("h.txt").exists());
+        let mut index = repo.git_repo.index().unwrap();
+        index.read(true).unwrap();
+        let staged = repo.git_repo.find_tree(index.write_tree().unwrap()).unwrap();
+        assert!(staged.get_name("h.txt").is_none());
+        assert_eq!(repo.git_repo.state(), git2::RepositoryState::Clean);
+        assert!(!repo.git_repo.path().join("gitup/sequencer.json").exists());
+
+        // Picking them back keeps stacking on the index
+        repo.git_repo.reset(&repo.git_repo.revparse_single(&g2).unwrap(), git2::ResetType::Hard, None).unwrap();
+        let results = repo.cherry_pick_range(&g2, &h, &options).unwrap();
+        assert!(results.iter().all(|r| r.success), "{:?}", results);
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), g2);
+        assert_eq!(fs::read_to_string(dir.join("g.txt")).unwrap(), "3\n");
+        assert_eq!(fs::read_to_string(dir.join("h.txt")).unwrap(), "h\n");
+
+        // Unstaged changes to a path a step touches are refused before anything is written
+        repo.git_repo.reset(&repo.git_repo.revparse_single(&h).unwrap(), git2::ResetType::Hard, None).unwrap();
+        write_file(dir.join("h.txt"), "local\n");
+        assert!(repo.revert(&[h.clone(), g3.clone()], &options).is_err());
+        assert_eq!(fs::read_to_string(dir.join("g.txt")).unwrap(), "3\n");
+        assert_eq!(fs::read_to_string(dir.join("h.txt")).unwrap(), "local\n");
+    }
+
+    #[test]
+    fn test_predict_merge_and_rebase_conflicts() {
+        use crate::merge::MergeKind;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+        let base = make_commit(&repo, dir, "a.txt", "1\n", "base");
+        let main = repo.git_repo.head().unwrap().shorthand().unwrap().to_string();
+        repo.create_branch("topic", Some(&base)).unwrap();
+        repo.create_branch("side", Some(&base)).unwrap();
+        make_commit(&repo, dir, "a.txt", "main\n", "main change");
+        make_commit(&repo, dir, "c.txt", "c\n", "add c");
+
+        repo.checkout_branch("topic").unwrap();
+        make_commit(&repo, dir, "t.txt", "t\n", "add t");
+        let conflicting = make_commit(&repo, dir, "a.txt", "topic\n", "topic change");
+        make_commit(&repo, dir, "u.txt", "u\n", "add u");
+
+        // Merging topic into main conflicts on a.txt, without touching anything
+        repo.checkout_branch(&main).unwrap();
+        let prediction = repo.predict_merge("topic").unwrap();
+        assert_eq!(prediction.kind, MergeKind::Normal);
+        assert_eq!(prediction.conflicts, vec!["a.txt"]);
+        assert!(prediction.tree.is_none());
+        let changed: Vec<String> = prediction.changes.iter().map(|(p, s)| format!("{} {:?}", p, s)).collect();
+        assert_eq!(changed, vec!["a.txt Modified", "t.txt Added", "u.txt Added"]);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "main\n");
+        assert!(repo.get_status().unwrap().is_empty());
+        assert_eq!(repo.git_repo.state(), git2::RepositoryState::Clean);
+
+        assert_eq!(repo.predict_merge(&base).unwrap().kind, MergeKind::UpToDate);
+        let side = repo.predict_merge("side").unwrap();
+        assert_eq!(side.kind, MergeKind::UpToDate);
+
+        // Rebasing topic onto main stops at its second commit
+        repo.checkout_branch("topic").unwrap();
+        let prediction = repo.predict_rebase(&main, None).unwrap();
+        assert_eq!(prediction.commits, 3);
+        let first = prediction.first_conflict.unwrap();
+        assert_eq!((first.id.as_str(), first.position), (conflicting.as_str(), 2));
+        assert_eq!(first.conflicts, vec!["a.txt"]);
+        assert_eq!(repo.predict_merge(&main).unwrap().kind, MergeKind::Normal);
+
+        // A clean rebase previews the resulting tree
+        repo.checkout_branch("side").unwrap();
+        make_commit(&repo, dir, "s.txt", "s\n", "add s");
+        let prediction = repo.predict_rebase(&main, None).unwrap();
+        assert!(prediction.first_conflict.is_none());
+        let tree = repo.git_repo.find_tree(git2::Oid::from_str(&prediction.tree.unwrap()).unwrap()).unwrap();
+        assert!(tree.get_name("s.txt").is_some() && tree.get_name("c.txt").is_some());
+        repo.checkout_branch(&main).unwrap();
+        let clean = repo.predict_merge("side").unwrap();
+        assert_eq!(clean.kind, MergeKind::Normal);
+        assert!(clean.is_clean() && clean.tree.is_some());
+        repo.checkout_branch("topic").unwrap();
+        repo.git_repo.reset(&repo.git_repo.revparse_single(&base).unwrap(), git2::ResetType::Hard, None).unwrap();
+        assert_eq!(repo.predict_merge(&main).unwrap().kind, MergeKind::FastForward);
+    }
+
+    #[test]
+    fn test_commit_graph_write_read_and_ancestry() {
+        use crate::commit_graph::{CommitGraph, GENERATION_INFINITY};
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+        let root = git2::Oid::from_str(&make_commit(&repo, dir, "a.txt", "1\n", "root")).unwrap();
+
+        let raw = &repo.git_repo;
+        let sig = git2::Signature::now("Tester", "tester@example.com").unwrap();
+        let tree = raw.find_commit(root).unwrap().tree().unwrap();
+        let commit = |msg: &str, parents: &[git2::Oid]| {
+            let parents: Vec<_> = parents.iter().map(|id| raw.find_commit(*id).unwrap()).collect();
+            raw.commit(None, &sig, &sig, msg, &tree, &parents.iter().collect::<Vec<_>>()).unwrap()
+        };
+        let (a, b, c) = (commit("a", &[root]), commit("b", &[root]), commit("c", &[root]));
+        let octopus = commit("octopus", &[a, b, c]);
+        let top = commit("top", &[octopus]);
+        let other = commit("other", &[b]);
+        raw.reference("refs/heads/topic", top, false, "").unwrap();
+        raw.reference("refs/heads/other", other, false, "").unwrap();
+
+        let ops = repo.commit_graph_ops();
+        assert_eq!(ops.write().unwrap(), 7);
+        assert_eq!(ops.update().unwrap(), None);
+
+        let graph = CommitGraph::load(raw).unwrap().unwrap();
+        assert_eq!(graph.len(), 7);
+        let read = graph.commit(octopus).unwrap();
+        assert_eq!(read.parents, vec![a, b, c]);
+        assert_eq!(read.time, raw.find_commit(octopus).unwrap().time().seconds());
+        assert_eq!(read.tree, tree.id());
+        let generations: Vec<u32> = [root, a, octopus, top].iter().map(|id| graph.generation(*id)).collect();
+        assert_eq!(generations, vec![1, 2, 3, 4]);
+
+        // Generation-bounded ancestry agrees with libgit2's walks
+        let lookup = repo.commit_lookup();
+        let all = [root, a, b, c, octopus, top, other];
+        for &x in &all {
+            for &y in &all {
+                assert_eq!(lookup.is_descendant_of(x, y).unwrap(), raw.graph_descendant_of(x, y).unwrap());
+                assert_eq!(lookup.ahead_behind(x, y).unwrap(), raw.graph_ahead_behind(x, y).unwrap());
+            }
+        }
+
+        // New commits are not in the graph until it is updated
+        let newer = commit("newer", &[top]);
+        raw.reference("refs/heads/topic", newer, true, "").unwrap();
+        assert_eq!(repo.commit_lookup().generation(newer), GENERATION_INFINITY);
+        assert!(repo.commit_lookup().is_descendant_of(newer, b).unwrap());
+        assert_eq!(ops.update().unwrap(), Some(8));
+        assert_eq!(repo.commit_lookup().generation(newer), 5);
+
+        // Writing a single file replaces a split chain
+        let info = dir.join(".git/objects/info");
+        let chain_dir = info.join("commit-graphs");
+        std::fs::create_dir_all(&chain_dir).unwrap();
+        std::fs::rename(info.join("commit-graph"), chain_dir.join("graph-0123.graph")).unwrap();
+        std::fs::write(chain_dir.join("commit-graph-chain"), "0123\n").unwrap();
+        assert_eq!(CommitGraph::load(raw).unwrap().unwrap().len(), 8);
+        assert_eq!(ops.write().unwrap(), 8);
+        assert!(info.join("commit-graph").exists());
+        assert!(!chain_dir.exists());
+    }
 }
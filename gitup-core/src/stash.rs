@@ -1,10 +1,14 @@
 use anyhow::Result;
 use git2::{
     Repository as Git2Repository, Signature, StashApplyOptions, StashFlags, Oid,
+    StashSaveOptions as Git2StashSaveOptions, ApplyLocation, DiffOptions, ErrorCode, BranchType,
+    build::CheckoutBuilder,
 };
 use chrono::{DateTime, Local, TimeZone};
 use std::path::Path;
 use std::str::FromStr;
+use crate::diff::{Diff, FileDiff};
+use crate::merge::MergeResult;
 
 /// Stash entry information
 #[derive(Debug, Clone)]
@@ -15,6 +19,30 @@ pub struct StashInfo {
     pub timestamp: DateTime<Local>,
 }
 
+/// Options for creating a stash
+#[derive(Debug, Clone, Default)]
+pub struct StashSaveOptions {
+    pub message: Option<String>,
+    pub include_untracked: bool,
+    /// Leave staged changes in the index and working tree (`--keep-index`)
+    pub keep_index: bool,
+    /// Stash only the staged changes (`--staged`)
+    pub staged: bool,
+    /// Limit the stash to these paths (empty for everything)
+    pub paths: Vec<String>,
+}
+
+/// Contents of a stash entry, split the way `git stash show --include-untracked` sees it
+#[derive(Debug, Clone)]
+pub struct StashDiff {
+    /// Changes that were staged (base -> index commit)
+    pub index: Vec<FileDiff>,
+    /// Unstaged changes (index commit -> stash commit)
+    pub working_tree: Vec<FileDiff>,
+    /// Untracked files saved with `--include-untracked`
+    pub untracked: Vec<FileDiff>,
+}
+
 /// Stash operations for a repository
 pub struct StashOps {
     repo: Git2Repository,
@@ -26,29 +54,141 @@ impl StashOps {
         Ok(StashOps { repo })
     }
 
+    /// Use an already opened repository
+    pub fn from_repo(repo: Git2Repository) -> Self {
+        StashOps { repo }
+    }
+
     /// Save current changes to stash
     pub fn save(&mut self, message: Option<&str>, include_untracked: bool) -> Result<String> {
+        self.save_with_options(&StashSaveOptions {
+            message: message.map(|m| m.to_string()),
+            include_untracked,
+            ..Default::default()
+        })
+    }
+
+    /// Save changes to stash with path limiting, keep-index or staged-only modes
+    pub fn save_with_options(&mut self, options: &StashSaveOptions) -> Result<String> {
+        let message = options.message.as_deref();
+
+        if options.staged {
+            if options.keep_index || options.include_untracked || !options.paths.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "--staged cannot be combined with --keep-index, --include-untracked or paths"
+                ));
+            }
+            self.save_staged(message)?;
+            let msg = message.unwrap_or("WIP on current branch");
+            return Ok(format!("Saved index state: {}", msg));
+        }
+
         // Get signature for stash
         let sig = self.get_signature()?;
 
         // Determine stash flags
-        let flags = if include_untracked {
-            StashFlags::INCLUDE_UNTRACKED
+        let mut flags = StashFlags::DEFAULT;
+        if options.include_untracked {
+            flags |= StashFlags::INCLUDE_UNTRACKED;
+        }
+        if options.keep_index {
+            flags |= StashFlags::KEEP_INDEX;
+        }
+
+        if options.paths.is_empty() {
+            self.repo.stash_save2(&sig, message, Some(flags))?;
         } else {
-            StashFlags::DEFAULT
-        };
+            // libgit2 resets the whole working tree after a path-limited stash,
+            // so keep everything and reset only the stashed paths ourselves
+            let mut save_opts = Git2StashSaveOptions::new(sig.clone());
+            save_opts.flags(Some(flags | StashFlags::KEEP_ALL));
+            for path in &options.paths {
+                save_opts.pathspec(path.as_str());
+            }
+            let stash_oid = self.repo.stash_save_ext(Some(&mut save_opts))?;
 
-        // Create stash
-        let _stash_oid = self.repo.stash_save2(
-            &sig,
-            message,
-            Some(flags),
-        )?;
+            let mut checkout = CheckoutBuilder::new();
+            checkout.force().remove_untracked(options.include_untracked);
+            for path in &options.paths {
+                checkout.path(path.as_str());
+            }
+            if options.keep_index {
+                self.repo.checkout_index(None, Some(&mut checkout))?;
+            } else {
+                let head = self.repo.head()?.peel_to_commit()?;
+                self.repo.checkout_tree(head.as_object(), Some(&mut checkout))?;
+            }
+
+            // git2's StashSaveOptions cannot carry a message, so relabel the reflog entry
+            if let Some(msg) = message {
+                let label = format!("On {}: {}", self.branch_label(), msg);
+                let mut reflog = self.repo.reflog("refs/stash")?;
+                reflog.remove(0, false)?;
+                reflog.append(stash_oid, &sig, Some(&label))?;
+                reflog.write()?;
+            }
+        }
 
         let msg = message.unwrap_or("WIP on current branch");
         Ok(format!("Saved working directory and index state: {}", msg))
     }
 
+    /// Stash only the staged changes, leaving unstaged edits in the working tree
+    fn save_staged(&mut self, message: Option<&str>) -> Result<Oid> {
+        let sig = self.get_signature()?;
+        let head_commit = self.repo.head()?.peel_to_commit()?;
+        let head_tree = head_commit.tree()?;
+
+        let mut index = self.repo.index()?;
+        let index_tree = self.repo.find_tree(index.write_tree()?)?;
+        if index_tree.id() == head_tree.id() {
+            return Err(anyhow::anyhow!("No staged changes to stash"));
+        }
+
+        let branch = self.branch_label();
+        let summary = format!(
+            "{} {}",
+            &head_commit.id().to_string()[..7],
+            head_commit.summary().unwrap_or("")
+        );
+
+        // Same shape as a regular stash: parents are HEAD and the index commit
+        let index_oid = self.repo.commit(
+            None,
+            &sig,
+            &sig,
+            &format!("index on {}: {}", branch, summary),
+            &index_tree,
+            &[&head_commit],
+        )?;
+        let index_commit = self.repo.find_commit(index_oid)?;
+
+        let stash_message = match message {
+            Some(msg) => format!("On {}: {}", branch, msg),
+            None => format!("WIP on {}: {}", branch, summary),
+        };
+        let stash_oid = self.repo.commit(
+            None,
+            &sig,
+            &sig,
+            &stash_message,
+            &index_tree,
+            &[&head_commit, &index_commit],
+        )?;
+
+        // Remove the staged changes from both the index and the working tree,
+        // and only record the stash once they are gone
+        let mut diff_opts = DiffOptions::new();
+        diff_opts.reverse(true).show_binary(true);
+        let diff = self.repo.diff_tree_to_index(Some(&head_tree), Some(&index), Some(&mut diff_opts))?;
+        self.repo.apply(&diff, ApplyLocation::Both, None)?;
+
+        self.repo.reference_ensure_log("refs/stash")?;
+        self.repo.reference("refs/stash", stash_oid, true, &stash_message)?;
+
+        Ok(stash_oid)
+    }
+
     /// List all stashes
     pub fn list(&mut self) -> Result<Vec<StashInfo>> {
         let mut stashes = Vec::new();
@@ -81,24 +221,113 @@ impl StashOps {
     }
 
     /// Apply a stash (without removing it)
-    pub fn apply(&mut self, index: usize) -> Result<String> {
+    ///
+    /// With `restore_index` the staged state is reinstated as well (`--index`).
+    pub fn apply(&mut self, index: usize, restore_index: bool) -> Result<MergeResult> {
         let mut opts = StashApplyOptions::new();
+        if restore_index {
+            opts.reinstantiate_index();
+        }
+
+        // Pick up index changes made through other handles since we opened the repo
+        self.repo.index()?.read(false)?;
 
         // Apply the stash
-        self.repo.stash_apply(index, Some(&mut opts))?;
+        if let Err(e) = self.repo.stash_apply(index, Some(&mut opts)) {
+            if e.code() == ErrorCode::Conflict || e.code() == ErrorCode::MergeConflict {
+                let conflicts = self.get_conflicts()?;
+                return Ok(MergeResult {
+                    success: false,
+                    message: format!("Could not apply stash@{{{}}}: {}", index, e.message()),
+                    conflicts,
+                    merged_commit: None,
+                });
+            }
+            return Err(e.into());
+        }
+
+        // Conflicts in the working tree are written to the index
+        let conflicts = self.get_conflicts()?;
+        if !conflicts.is_empty() {
+            return Ok(MergeResult {
+                success: false,
+                message: format!("Conflicts while applying stash@{{{}}}", index),
+                conflicts,
+                merged_commit: None,
+            });
+        }
 
-        Ok(format!("Applied stash@{{{}}}", index))
+        Ok(MergeResult {
+            success: true,
+            message: format!("Applied stash@{{{}}}", index),
+            conflicts: vec![],
+            merged_commit: None,
+        })
     }
 
     /// Pop a stash (apply and remove)
-    pub fn pop(&mut self, index: usize) -> Result<String> {
+    ///
+    /// The stash is kept if applying it produced conflicts.
+    pub fn pop(&mut self, index: usize, restore_index: bool) -> Result<MergeResult> {
         // First apply the stash
-        self.apply(index)?;
+        let mut result = self.apply(index, restore_index)?;
+        if !result.success {
+            result.message = format!("{}; the stash entry is kept", result.message);
+            return Ok(result);
+        }
 
         // Then drop it
         self.drop(index)?;
 
-        Ok(format!("Dropped and applied stash@{{{}}}", index))
+        result.message = format!("Dropped and applied stash@{{{}}}", index);
+        Ok(result)
+    }
+
+    /// Create a branch at the stash's base commit, check it out and pop the stash onto it
+    ///
+    /// The base is checked out before the branch is created, so a working
+    /// tree that conflicts with it leaves no branch behind. If the stash
+    /// cannot be applied at all, HEAD is moved back and the branch deleted.
+    pub fn branch(&mut self, name: &str, index: usize) -> Result<MergeResult> {
+        let stash_oid = self.stash_oid(index)?;
+        if self.repo.find_branch(name, BranchType::Local).is_ok() {
+            anyhow::bail!("A branch named '{}' already exists", name);
+        }
+
+        let (previous_ref, previous_oid) = {
+            let previous = self.repo.head()?;
+            let previous_ref = previous.name().filter(|_| previous.is_branch()).map(str::to_string);
+            (previous_ref, previous.peel_to_commit()?.id())
+        };
+        {
+            let base = self.repo.find_commit(stash_oid)?.parent(0)?;
+            self.repo.checkout_tree(base.as_object(), Some(CheckoutBuilder::new().safe()))?;
+            let branch = self.repo.branch(name, &base, false)?;
+            let refname = branch
+                .get()
+                .name()
+                .ok_or_else(|| anyhow::anyhow!("Invalid branch name"))?
+                .to_string();
+            self.repo.set_head(&refname)?;
+        }
+
+        let mut result = match self.pop(index, true) {
+            Ok(result) => result,
+            Err(e) => {
+                let previous = self.repo.find_commit(previous_oid)?;
+                self.repo.checkout_tree(previous.as_object(), Some(CheckoutBuilder::new().safe()))?;
+                match &previous_ref {
+                    Some(previous_ref) => self.repo.set_head(previous_ref)?,
+                    None => self.repo.set_head_detached(previous_oid)?,
+                }
+                self.repo.find_branch(name, BranchType::Local)?.delete()?;
+                return Err(e);
+            }
+        };
+        if result.success {
+            result.message = format!("Switched to a new branch '{}' and applied stash@{{{}}}", name, index);
+        }
+        Ok(result)
     }
 
     /// Drop (remove) a stash
@@ -122,9 +351,35 @@ impl StashOps {
         Ok(format!("Dropped {} stash entries", dropped))
     }
 
-    /// Show diff of a stash
-    pub fn show(&mut self, index: usize) -> Result<String> {
-        // Get the stash commit
+    /// Show diff of a stash, split into staged, unstaged and untracked parts
+    pub fn show(&mut self, index: usize) -> Result<StashDiff> {
+        let oid = self.stash_oid(index)?;
+        let commit = self.repo.find_commit(oid)?;
+
+        // A stash commit has the base commit, the index commit and optionally
+        // the untracked files commit as parents
+        let base_tree = commit.parent(0)?.tree()?;
+        let index_tree = commit.parent(1)?.tree()?;
+        let stash_tree = commit.tree()?;
+        let untracked_tree = if commit.parent_count() > 2 {
+            Some(commit.parent(2)?.tree()?)
+        } else {
+            None
+        };
+
+        let diff = Diff::new(&self.repo);
+        Ok(StashDiff {
+            index: diff.between_trees(Some(&base_tree), Some(&index_tree))?,
+            working_tree: diff.between_trees(Some(&index_tree), Some(&stash_tree))?,
+            untracked: match untracked_tree {
+                Some(tree) => diff.between_trees(None, Some(&tree))?,
+                None => Vec::new(),
+            },
+        })
+    }
+
+    /// Find the commit of a stash entry
+    fn stash_oid(&mut self, index: usize) -> Result<Oid> {
         let mut stash_oid = None;
         self.repo.stash_foreach(|idx, _name, oid| {
             if idx == index {
@@ -135,36 +390,34 @@ impl StashOps {
             }
         })?;
 
-        let oid = stash_oid.ok_or_else(|| anyhow::anyhow!("Stash not found"))?;
-        let commit = self.repo.find_commit(oid)?;
-
-        // Get the diff between stash and its parent
-        let parent = commit.parent(0)?;
-        let stash_tree = commit.tree()?;
-        let parent_tree = parent.tree()?;
-
-        let diff = self.repo.diff_tree_to_tree(
-            Some(&parent_tree),
-            Some(&stash_tree),
-            None,
-        )?;
+        stash_oid.ok_or_else(|| anyhow::anyhow!("Stash not found"))
+    }
 
-        // Format diff output
-        let mut output = String::new();
-        output.push_str(&format!("stash@{{{}}}: {}\n", index, commit.message().unwrap_or("")));
-        output.push_str(&format!("Author: {}\n", commit.author().name().unwrap_or("Unknown")));
-        output.push_str(&format!("Date: {}\n\n",
-            Local.timestamp_opt(commit.time().seconds(), 0).single()
-                .unwrap_or_else(Local::now)));
+    /// Short name of the current branch, as used in stash messages
+    fn branch_label(&self) -> String {
+        self.repo
+            .head()
+            .ok()
+            .and_then(|head| head.shorthand().map(|s| s.to_string()))
+            .unwrap_or_else(|| "(no branch)".to_string())
+    }
 
-        // Add diff statistics
-        let stats = diff.stats()?;
-        output.push_str(&format!(" {} files changed, {} insertions(+), {} deletions(-)\n",
-            stats.files_changed(),
-            stats.insertions(),
-            stats.deletions()));
+    /// Get list of conflicted files
+    fn get_conflicts(&self) -> Result<Vec<String>> {
+        let mut conflicts = Vec::new();
+        let index = self.repo.index()?;
+
+        for entry in index.conflicts()?.flatten() {
+            let side = entry.our.or(entry.their).or(entry.ancestor);
+            if let Some(side) = side {
+                let path = String::from_utf8_lossy(&side.path).into_owned();
+                if !conflicts.contains(&path) {
+                    conflicts.push(path);
+                }
+            }
+        }
 
-        Ok(output)
+        Ok(conflicts)
     }
 
     /// Get signature for stash operations
@@ -195,4 +448,156 @@ impl StashOps {
         let stashes = self.list()?;
         Ok(stashes.into_iter().find(|s| s.index == index))
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commit::Commit;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Create a repository with a.txt and b.txt committed
+    fn setup() -> (TempDir, Git2Repository) {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Git2Repository::init(temp_dir.path()).unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), "b\n").unwrap();
+        {
+            let commit = Commit::new(&repo);
+            commit.stage_all().unwrap();
+            commit.create("init", "Tester", "tester@example.com").unwrap();
+        }
+        (temp_dir, repo)
+    }
+
+    #[test]
+    fn test_stash_staged_only() {
+        let (temp_dir, repo) = setup();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+        Commit::new(&repo).stage_file("a.txt").unwrap();
+        fs::write(dir.join("b.txt"), "b changed\n").unwrap();
+
+        let mut ops = StashOps::new(dir).unwrap();
+        ops.save_with_options(&StashSaveOptions {
+            message: Some("staged work".to_string()),
+            staged: true,
+            ..Default::default()
+        }).unwrap();
+
+        // Staged change is gone, unstaged change is kept
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b changed\n");
+
+        let shown = ops.show(0).unwrap();
+        assert_eq!(shown.index.len(), 1);
+        assert_eq!(shown.index[0].file.path, "a.txt");
+        assert!(shown.working_tree.is_empty());
+        assert!(shown.untracked.is_empty());
+        assert!(ops.list().unwrap()[0].message.contains("staged work"));
+
+        let result = ops.pop(0, true).unwrap();
+        assert!(result.success);
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\ntwo\nthree\nfour\n");
+        assert!(repo.statuses(None).unwrap().iter()
+            .any(|e| e.path() == Some("a.txt") && e.status().is_index_modified()));
+        assert!(!ops.has_stashes().unwrap());
+    }
+
+    #[test]
+    fn test_stash_staged_failed_apply_leaves_no_entry() {
+        let (temp_dir, repo) = setup();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+        Commit::new(&repo).stage_file("a.txt").unwrap();
+        // The working tree no longer matches the staged lines, so they cannot be taken out
+        fs::write(dir.join("a.txt"), "rewritten\n").unwrap();
+
+        let mut ops = StashOps::new(dir).unwrap();
+        assert!(ops.save_with_options(&StashSaveOptions { staged: true, ..Default::default() }).is_err());
+        assert!(!ops.has_stashes().unwrap());
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "rewritten\n");
+        assert!(repo.statuses(None).unwrap().iter()
+            .any(|e| e.path() == Some("a.txt") && e.status().is_index_modified()));
+    }
+
+    #[test]
+    fn test_stash_paths() {
+        let (temp_dir, _repo) = setup();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "one\n").unwrap();
+        fs::write(dir.join("b.txt"), "b changed\n").unwrap();
+
+        let mut ops = StashOps::new(dir).unwrap();
+        ops.save_with_options(&StashSaveOptions {
+            message: Some("only a".to_string()),
+            paths: vec!["a.txt".to_string()],
+            ..Default::default()
+        }).unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\ntwo\nthree\n");
+        assert_eq!(fs::read_to_string(dir.join("b.txt")).unwrap(), "b changed\n");
+
+        let stashes = ops.list().unwrap();
+        assert_eq!(stashes.len(), 1);
+        assert!(stashes[0].message.contains("only a"));
+
+        let shown = ops.show(0).unwrap();
+        let paths: Vec<_> = shown.working_tree.iter().map(|d| d.file.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt"]);
+    }
+
+    #[test]
+    fn test_stash_pop_conflict_keeps_entry() {
+        let (temp_dir, repo) = setup();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "one\nstashed\nthree\n").unwrap();
+
+        let mut ops = StashOps::new(dir).unwrap();
+        ops.save(None, false).unwrap();
+
+        fs::write(dir.join("a.txt"), "one\ncommitted\nthree\n").unwrap();
+        {
+            let commit = Commit::new(&repo);
+            commit.stage_file("a.txt").unwrap();
+            commit.create("conflicting", "Tester", "tester@example.com").unwrap();
+        }
+
+        let result = ops.pop(0, false).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.conflicts, vec!["a.txt".to_string()]);
+        assert!(ops.has_stashes().unwrap());
+    }
+
+    #[test]
+    fn test_stash_branch_conflicting_worktree_leaves_no_branch() {
+        let (temp_dir, repo) = setup();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a.txt"), "one\nstashed\nthree\n").unwrap();
+
+        let mut ops = StashOps::new(dir).unwrap();
+        ops.save(None, false).unwrap();
+
+        fs::write(dir.join("a.txt"), "one\ncommitted\nthree\n").unwrap();
+        {
+            let commit = Commit::new(&repo);
+            commit.stage_file("a.txt").unwrap();
+            commit.create("moved on", "Tester", "tester@example.com").unwrap();
+        }
+
+        // A local edit to a file that differs at the stash base blocks the checkout
+        fs::write(dir.join("a.txt"), "one\nlocal\nthree\n").unwrap();
+        assert!(ops.branch("from-stash", 0).is_err());
+        assert!(repo.find_branch("from-stash", BranchType::Local).is_err());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("master"));
+        assert!(ops.has_stashes().unwrap());
+
+        // Retrying once the working tree is clean succeeds
+        repo.checkout_head(Some(CheckoutBuilder::new().force())).unwrap();
+        let result = ops.branch("from-stash", 0).unwrap();
+        assert!(result.success);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("from-stash"));
+        assert_eq!(fs::read_to_string(dir.join("a.txt")).unwrap(), "one\nstashed\nthree\n");
+        assert!(!ops.has_stashes().unwrap());
+    }
+}
@@ -3,7 +3,12 @@ use git2::{
     ObjectType, Oid, Repository as Git2Repository, Signature,
 };
 use chrono::{DateTime, Local, TimeZone};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
 use std::path::Path;
+use crate::commit_graph::CommitLookup;
+use crate::remote::credentials;
 
 /// Tag information
 #[derive(Debug, Clone)]
@@ -16,6 +21,92 @@ pub struct TagInfo {
     pub is_annotated: bool,
 }
 
+/// Order of a tag listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagSort {
+    #[default]
+    Name,
+    /// Version order: `v1.10` after `v1.9`, `1.0.0-rc.1` before `1.0.0`
+    Version,
+    /// Tagger date, or commit date for lightweight tags
+    Date,
+}
+
+/// Options for `describe`
+#[derive(Debug, Clone)]
+pub struct DescribeOptions {
+    /// Consider lightweight tags too, not only annotated ones
+    pub tags: bool,
+    /// Only consider tags matching this glob
+    pub pattern: Option<String>,
+    /// Hex digits of the abbreviated commit id
+    pub abbrev: usize,
+    /// Use the `<tag>-<distance>-g<id>` form even on a tagged commit
+    pub long: bool,
+    /// Fall back to the abbreviated commit id when no tag is reachable
+    pub always: bool,
+    /// Suffix appended when the working tree has changes; only for HEAD
+    pub dirty: Option<String>,
+    /// Tags found before settling on the closest one; fewer is faster
+    pub candidates: usize,
+}
+
+impl Default for DescribeOptions {
+    fn default() -> Self {
+        DescribeOptions {
+            tags: false,
+            pattern: None,
+            abbrev: 7,
+            long: false,
+            always: false,
+            dirty: None,
+            candidates: 10,
+        }
+    }
+}
+
+/// A commit named after its nearest tag, as `git describe` does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Description {
+    pub commit: String,
+    pub tag: Option<String>,
+    /// Commits reachable from `commit` but not from the tag
+    pub distance: usize,
+    pub abbreviated: String,
+    pub long: bool,
+    /// Dirty suffix, set when the working tree has changes
+    pub dirty: Option<String>,
+}
+
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tag {
+            Some(tag) if self.distance == 0 && !self.long => write!(f, "{}", tag)?,
+            Some(tag) => write!(f, "{}-{}-g{}", tag, self.distance, self.abbreviated)?,
+            None => write!(f, "{}", self.abbreviated)?,
+        }
+        if let Some(suffix) = &self.dirty {
+            write!(f, "{}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tag chosen to name a commit in `describe`
+struct TagName {
+    name: String,
+    annotated: bool,
+    date: i64,
+}
+
+/// A tag reached during the describe walk
+struct Candidate<'n> {
+    tag: &'n TagName,
+    depth: usize,
+    flag: u32,
+    found_order: usize,
+}
+
 /// Tag operations for a repository
 pub struct TagOps {
     repo: Git2Repository,
@@ -27,6 +118,11 @@ impl TagOps {
         Ok(TagOps { repo })
     }
 
+    /// Use an already opened repository
+    pub fn from_repo(repo: Git2Repository) -> Self {
+        TagOps { repo }
+    }
+
     /// Create a new tag
     pub fn create(
         &self,
@@ -80,6 +176,11 @@ impl TagOps {
 
     /// List all tags
     pub fn list(&self, pattern: Option<&str>) -> Result<Vec<TagInfo>> {
+        self.list_sorted(pattern, TagSort::Name, false)
+    }
+
+    /// List tags in the given order, newest or highest first when `descending`
+    pub fn list_sorted(&self, pattern: Option<&str>, sort: TagSort, descending: bool) -> Result<Vec<TagInfo>> {
         let mut tags = Vec::new();
         let tag_names = self.repo.tag_names(pattern)?;
 
@@ -89,11 +190,77 @@ impl TagOps {
             }
         }
 
-        // Sort by name
-        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        match sort {
+            TagSort::Name => tags.sort_by(|a, b| a.name.cmp(&b.name)),
+            TagSort::Version => tags.sort_by(|a, b| compare_versions(&a.name, &b.name)),
+            TagSort::Date => {
+                let dates: HashMap<String, i64> = tags.iter()
+                    .map(|tag| (tag.name.clone(), self.tag_date(tag)))
+                    .collect();
+                tags.sort_by(|a, b| dates[&a.name].cmp(&dates[&b.name]).then_with(|| a.name.cmp(&b.name)));
+            }
+        }
+        if descending {
+            tags.reverse();
+        }
+        Ok(tags)
+    }
+
+    /// Tags whose commit is `commit_ref` or one of its descendants, in version order
+    pub fn contains(&self, commit_ref: &str) -> Result<Vec<String>> {
+        let commit = self.repo.revparse_single(commit_ref)?.peel_to_commit()?.id();
+        let lookup = CommitLookup::new(&self.repo);
+        let mut tags = Vec::new();
+
+        for name in self.repo.tag_names(None)?.iter().flatten() {
+            let tagged = match self.tag_commit(name) {
+                Some(tagged) => tagged,
+                None => continue,
+            };
+            if tagged == commit || lookup.is_descendant_of(tagged, commit)? {
+                tags.push(name.to_string());
+            }
+        }
+        tags.sort_by(|a, b| compare_versions(a, b));
         Ok(tags)
     }
 
+    /// Describe a commit (HEAD when `None`) by its nearest tag
+    pub fn describe(&self, commit_ref: Option<&str>, options: &DescribeOptions) -> Result<Description> {
+        if commit_ref.is_some() && options.dirty.is_some() {
+            return Err(anyhow::anyhow!("--dirty only applies to HEAD"));
+        }
+        let commit = match commit_ref {
+            Some(rev) => self.repo.revparse_single(rev)?.peel_to_commit()?.id(),
+            None => self.repo.head()?.peel_to_commit()?.id(),
+        };
+
+        let names = self.describe_names(options)?;
+        let mut description = self.describe_with(commit, &names, options)?;
+        if let Some(suffix) = &options.dirty {
+            if self.is_dirty()? {
+                description.dirty = Some(suffix.clone());
+            }
+        }
+        Ok(description)
+    }
+
+    /// Describe many commits at once; commits no tag reaches are left out
+    pub fn describe_many(&self, commits: &[String], options: &DescribeOptions) -> Result<HashMap<String, Description>> {
+        let names = self.describe_names(options)?;
+        let mut descriptions = HashMap::new();
+        if names.is_empty() {
+            return Ok(descriptions);
+        }
+        let options = DescribeOptions { always: false, ..options.clone() };
+        for commit in commits {
+            if let Ok(description) = self.describe_with(Oid::from_str(commit)?, &names, &options) {
+                descriptions.insert(commit.clone(), description);
+            }
+        }
+        Ok(descriptions)
+    }
+
     /// Get information about a specific tag
     pub fn get_tag_info(&self, name: &str) -> Result<TagInfo> {
         let full_name = format!("refs/tags/{}", name);
@@ -180,67 +347,77 @@ impl TagOps {
         Ok(output)
     }
 
-    /// Push tags to remote
-    pub fn push(&self, remote_name: &str, tag_name: Option<&str>, _force: bool) -> Result<String> {
-        let refspecs = if let Some(tag) = tag_name {
-            // Push specific tag
-            vec![format!("refs/tags/{}:refs/tags/{}", tag, tag)]
-        } else {
-            // Push all tags
-            vec!["refs/tags/*:refs/tags/*".to_string()]
+    /// Push tags to remote; `force` replaces tags the remote has at another commit
+    pub fn push(&self, remote_name: &str, tag_name: Option<&str>, force: bool) -> Result<String> {
+        let refspec = match tag_name {
+            Some(tag) => format!("refs/tags/{0}:refs/tags/{0}", tag),
+            None => "refs/tags/*:refs/tags/*".to_string(),
         };
+        let refspec = if force { format!("+{}", refspec) } else { refspec };
+        self.push_refspecs(remote_name, &[refspec], !force)?;
 
-        let refspecs_str: Vec<&str> = refspecs.iter().map(|s| s.as_str()).collect();
+        if let Some(tag) = tag_name {
+            Ok(format!("Pushed tag '{}' to {}", tag, remote_name))
+        } else {
+            Ok(format!("Pushed all tags to {}", remote_name))
+        }
+    }
+
+    /// Delete a tag on the remote; the local tag is kept
+    pub fn delete_remote(&self, remote_name: &str, name: &str) -> Result<String> {
+        self.push_refspecs(remote_name, &[format!(":refs/tags/{}", name)], false)?;
+        Ok(format!("Deleted tag '{}' on {}", name, remote_name))
+    }
 
-        // Push directly without using RemoteOps
+    /// Push refspecs, failing on any rejected update; with `keep_tags`,
+    /// like git, refuse to move a tag the remote already has, even forward
+    fn push_refspecs(&self, remote_name: &str, refspecs: &[String], keep_tags: bool) -> Result<()> {
         let mut remote = self.repo.find_remote(remote_name)?;
+        let mut rejected = Vec::new();
+        let mut existing = Vec::new();
 
-        // Set up push options
-        let mut push_options = git2::PushOptions::new();
         let mut callbacks = git2::RemoteCallbacks::new();
-
-        // Set up authentication (reuse from remote.rs)
-        callbacks.credentials(|_url, username_from_url, _allowed_types| {
-            if let Ok(home) = std::env::var("HOME") {
-                let ssh_dir = Path::new(&home).join(".ssh");
-
-                // Try ed25519 key first
-                let ed25519_key = ssh_dir.join("id_ed25519");
-                if ed25519_key.exists() {
-                    return git2::Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &ed25519_key,
-                        None,
-                    );
-                }
-
-                // Try RSA key
-                let rsa_key = ssh_dir.join("id_rsa");
-                if rsa_key.exists() {
-                    return git2::Cred::ssh_key(
-                        username_from_url.unwrap_or("git"),
-                        None,
-                        &rsa_key,
-                        None,
-                    );
+        callbacks.credentials(credentials);
+        callbacks.push_negotiation(|updates| {
+            if keep_tags {
+                for update in updates {
+                    let moved = !update.src().is_zero() && update.src() != update.dst();
+                    if let Some(tag) = update.dst_refname().and_then(|r| r.strip_prefix("refs/tags/")) {
+                        if moved {
+                            existing.push(tag.to_string());
+                        }
+                    }
                 }
             }
-
-            // Fall back to SSH agent
-            git2::Cred::ssh_key_from_agent(username_from_url.unwrap_or("git"))
+            if existing.is_empty() {
+                Ok(())
+            } else {
+                Err(git2::Error::from_str("tag already exists on the remote"))
+            }
+        });
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(msg) = status {
+                rejected.push(format!("{}: {}", refname, msg));
+            }
+            Ok(())
         });
 
+        let mut push_options = git2::PushOptions::new();
         push_options.remote_callbacks(callbacks);
-
-        // Perform push
-        remote.push(&refspecs_str, Some(&mut push_options))?;
-
-        if let Some(tag) = tag_name {
-            Ok(format!("Pushed tag '{}' to {}", tag, remote_name))
-        } else {
-            Ok(format!("Pushed all tags to {}", remote_name))
+        let result = remote.push(refspecs, Some(&mut push_options));
+        drop(push_options);
+
+        if !existing.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Tag(s) {} already exist on {} at another commit; use force to replace them",
+                existing.join(", "), remote_name
+            ));
+        }
+        result?;
+        if !rejected.is_empty() {
+            return Err(anyhow::anyhow!("Push to {} rejected: {}", remote_name, rejected.join(", ")));
         }
+        Ok(())
     }
 
     /// Verify a tag's signature (if signed)
@@ -268,9 +445,304 @@ impl TagOps {
         Ok(Signature::now(&name, &email)?)
     }
 
+    /// Commit a tag points at, peeling annotated tags
+    fn tag_commit(&self, name: &str) -> Option<Oid> {
+        self.repo.find_reference(&format!("refs/tags/{}", name)).ok()?
+            .peel_to_commit().ok()
+            .map(|commit| commit.id())
+    }
+
+    fn tag_date(&self, tag: &TagInfo) -> i64 {
+        tag.timestamp.map(|t| t.timestamp()).unwrap_or_else(|| {
+            Oid::from_str(&tag.target).ok()
+                .and_then(|oid| self.repo.find_commit(oid).ok())
+                .map(|commit| commit.time().seconds())
+                .unwrap_or(0)
+        })
+    }
+
+    /// The tag naming each tagged commit; annotated tags win, then the newest
+    fn describe_names(&self, options: &DescribeOptions) -> Result<HashMap<Oid, TagName>> {
+        let mut names: HashMap<Oid, TagName> = HashMap::new();
+
+        for name in self.repo.tag_names(options.pattern.as_deref())?.iter().flatten() {
+            let reference = match self.repo.find_reference(&format!("refs/tags/{}", name)) {
+                Ok(reference) => reference,
+                Err(_) => continue,
+            };
+            let commit = match reference.peel_to_commit() {
+                Ok(commit) => commit,
+                Err(_) => continue,
+            };
+            let annotated_tag = reference.target().and_then(|oid| self.repo.find_tag(oid).ok());
+            if annotated_tag.is_none() && !options.tags {
+                continue;
+            }
+
+            let candidate = TagName {
+                name: name.to_string(),
+                annotated: annotated_tag.is_some(),
+                date: annotated_tag.as_ref()
+                    .and_then(|tag| tag.tagger().map(|t| t.when().seconds()))
+                    .unwrap_or_else(|| commit.time().seconds()),
+            };
+            let better = match names.get(&commit.id()) {
+                Some(existing) => (candidate.annotated, candidate.date) > (existing.annotated, existing.date),
+                None => true,
+            };
+            if better {
+                names.insert(commit.id(), candidate);
+            }
+        }
+        Ok(names)
+    }
+
+    /// git's describe walk: visit history newest first, counting for each
+    /// tag found the commits it cannot reach, and keep the smallest count
+    fn describe_with(&self, commit: Oid, names: &HashMap<Oid, TagName>, options: &DescribeOptions) -> Result<Description> {
+        let abbreviated = self.repo.find_object(commit, None)?
+            .short_id()?
+            .as_str()
+            .map(|short| short.to_string())
+            .unwrap_or_default();
+        let abbreviated = if abbreviated.len() >= options.abbrev {
+            abbreviated
+        } else {
+            commit.to_string()[..options.abbrev.min(40)].to_string()
+        };
+        let mut description = Description {
+            commit: commit.to_string(),
+            tag: None,
+            distance: 0,
+            abbreviated,
+            long: options.long,
+            dirty: None,
+        };
+
+        if let Some(tag) = names.get(&commit) {
+            description.tag = Some(tag.name.clone());
+            return Ok(description);
+        }
+
+        let max_candidates = options.candidates.clamp(1, 31);
+        let mut flags: HashMap<Oid, u32> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        let mut candidates: Vec<Candidate> = Vec::new();
+        let mut seen_commits = 0;
+        let mut gave_up_on = None;
+
+        flags.insert(commit, 0);
+        queue.push((self.repo.find_commit(commit)?.time().seconds(), commit));
+
+        while let Some((time, oid)) = queue.pop() {
+            seen_commits += 1;
+            if let Some(tag) = names.get(&oid) {
+                if candidates.len() < max_candidates {
+                    let flag = 1 << candidates.len();
+                    candidates.push(Candidate { tag, depth: seen_commits - 1, flag, found_order: candidates.len() });
+                    *flags.entry(oid).or_default() |= flag;
+                } else {
+                    gave_up_on = Some((time, oid));
+                    break;
+                }
+            }
+            let commit_flags = flags[&oid];
+            for candidate in candidates.iter_mut() {
+                if commit_flags & candidate.flag == 0 {
+                    candidate.depth += 1;
+                }
+            }
+            if !candidates.is_empty() && queue.is_empty() {
+                break;
+            }
+            self.queue_parents(oid, commit_flags, &mut flags, &mut queue)?;
+        }
+
+        candidates.sort_by(|a, b| a.depth.cmp(&b.depth).then(a.found_order.cmp(&b.found_order)));
+        let best = match candidates.first_mut() {
+            Some(best) => best,
+            None if options.always => return Ok(description),
+            None => return Err(anyhow::anyhow!("No tags can describe '{}'", commit)),
+        };
+
+        if let Some(entry) = gave_up_on {
+            queue.push(entry);
+        }
+
+        // Finish counting the commits the best tag cannot reach
+        while let Some((_, oid)) = queue.pop() {
+            let commit_flags = flags[&oid];
+            if commit_flags & best.flag != 0 {
+                if queue.iter().all(|(_, queued)| flags[queued] & best.flag != 0) {
+                    break;
+                }
+            } else {
+                best.depth += 1;
+            }
+            self.queue_parents(oid, commit_flags, &mut flags, &mut queue)?;
+        }
+
+        description.tag = Some(best.tag.name.clone());
+        description.distance = best.depth;
+        Ok(description)
+    }
+
+    /// Pass a commit's flags on to its parents, queueing those not seen yet
+    fn queue_parents(
+        &self,
+        oid: Oid,
+        commit_flags: u32,
+        flags: &mut HashMap<Oid, u32>,
+        queue: &mut BinaryHeap<(i64, Oid)>,
+    ) -> Result<()> {
+        for parent in self.repo.find_commit(oid)?.parents() {
+            let seen = flags.contains_key(&parent.id());
+            *flags.entry(parent.id()).or_default() |= commit_flags;
+            if !seen {
+                queue.push((parent.time().seconds(), parent.id()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the index or working tree differ from HEAD, untracked files aside
+    fn is_dirty(&self) -> Result<bool> {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(false).include_ignored(false);
+        Ok(!self.repo.statuses(Some(&mut options))?.is_empty())
+    }
+
     /// Check if a tag exists
     pub fn exists(&self, name: &str) -> bool {
         let full_name = format!("refs/tags/{}", name);
         self.repo.find_reference(&full_name).is_ok()
     }
-}
\ No newline at end of file
+}
+
+/// Compare tag names as versions, falling back to a natural order for
+/// names that are not versions
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (parse_version(a), parse_version(b)) {
+        (Some((a_core, a_pre)), Some((b_core, b_pre))) => {
+            let len = a_core.len().max(b_core.len());
+            let pad = |core: &[u64]| (0..len).map(|i| core.get(i).copied().unwrap_or(0)).collect::<Vec<_>>();
+            pad(&a_core).cmp(&pad(&b_core))
+                .then_with(|| match (a_pre, b_pre) {
+                    (None, None) => Ordering::Equal,
+                    (None, Some(_)) => Ordering::Greater,
+                    (Some(_), None) => Ordering::Less,
+                    (Some(a_pre), Some(b_pre)) => compare_natural(a_pre, b_pre),
+                })
+                .then_with(|| a.cmp(b))
+        }
+        _ => compare_natural(a, b),
+    }
+}
+
+/// `v1.2.3-rc.1+build` as ([1, 2, 3], Some("rc.1")); build metadata is ignored
+fn parse_version(name: &str) -> Option<(Vec<u64>, Option<&str>)> {
+    let version = name.strip_prefix('v').or_else(|| name.strip_prefix('V')).unwrap_or(name);
+    let version = version.split('+').next().unwrap_or(version);
+    let (core, pre) = match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    };
+    let core = core.split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    Some((core, pre))
+}
+
+/// Order runs of digits by value and everything else byte-wise
+fn compare_natural(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let a_num = std::str::from_utf8(&a[..a_len]).unwrap_or("0").trim_start_matches('0');
+                let b_num = std::str::from_utf8(&b[..b_len]).unwrap_or("0").trim_start_matches('0');
+                let order = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::{make_commit, write_file};
+    use crate::repository::Repository;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tag_sorting_contains_describe_and_remote() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+        let c1 = make_commit(&repo, dir, "a.txt", "1\n", "first");
+        let c2 = make_commit(&repo, dir, "a.txt", "2\n", "second");
+        let c3 = make_commit(&repo, dir, "a.txt", "3\n", "third");
+        let c4 = make_commit(&repo, dir, "a.txt", "4\n", "fourth");
+        repo.tag_create("v1.0", Some(&c1), Some("release 1.0"), false).unwrap();
+        repo.tag_create("wip", Some(&c3), None, false).unwrap();
+        for name in ["v1.10.0", "v1.9.0", "v1.10.0-rc.1"] {
+            repo.tag_create(name, Some(&c4), None, false).unwrap();
+        }
+
+        let names = |tags: Vec<TagInfo>| tags.into_iter().map(|t| t.name).collect::<Vec<_>>();
+        assert_eq!(names(repo.tag_list_sorted(Some("v*"), TagSort::Version, false).unwrap()),
+            vec!["v1.0", "v1.9.0", "v1.10.0-rc.1", "v1.10.0"]);
+        assert_eq!(names(repo.tag_list_sorted(Some("v1.1*"), TagSort::Version, true).unwrap()),
+            vec!["v1.10.0", "v1.10.0-rc.1"]);
+        assert_eq!(repo.tag_contains(&c2).unwrap(), vec!["v1.9.0", "v1.10.0-rc.1", "v1.10.0", "wip"]);
+
+        // Annotated tags only by default; lightweight ones with `tags`
+        let short = |id: &str| id[..7].to_string();
+        let options = DescribeOptions { pattern: Some("[vw]1.0*".to_string()), ..Default::default() };
+        assert_eq!(repo.describe(None, &options).unwrap().to_string(), format!("v1.0-3-g{}", short(&c4)));
+        let options = DescribeOptions { tags: true, pattern: Some("w*".to_string()), ..Default::default() };
+        assert_eq!(repo.describe(None, &options).unwrap().to_string(), format!("wip-1-g{}", short(&c4)));
+        assert_eq!(repo.describe(Some(&c3), &options).unwrap().to_string(), "wip");
+        assert!(repo.describe(Some(&c2), &options).is_err());
+        let always = DescribeOptions { always: true, ..options.clone() };
+        assert_eq!(repo.describe(Some(&c2), &always).unwrap().to_string(), short(&c2));
+
+        write_file(dir.join("a.txt"), "changed\n");
+        let dirty = DescribeOptions { dirty: Some("-dirty".to_string()), ..options.clone() };
+        assert_eq!(repo.describe(None, &dirty).unwrap().to_string(), format!("wip-1-g{}-dirty", short(&c4)));
+        assert!(repo.describe(Some(&c4), &dirty).is_err());
+        let labels = repo.describe_many(&[c1.clone(), c2.clone(), c4.clone()], &options).unwrap();
+        assert_eq!(labels.len(), 1);
+        assert_eq!(labels[&c4].distance, 1);
+
+        // Pushing a moved tag needs force; deleting on the remote keeps the local tag
+        let bare_path = temp_dir.path().join("remote.git");
+        let bare = git2::Repository::init_bare(&bare_path).unwrap();
+        repo.add_remote("origin", &format!("file://{}", bare_path.display())).unwrap();
+        repo.tag_push("origin", Some("wip"), false).unwrap();
+        repo.tag_create("wip", Some(&c4), None, true).unwrap();
+        assert!(repo.tag_push("origin", Some("wip"), false).is_err());
+        repo.tag_push("origin", Some("wip"), true).unwrap();
+        assert_eq!(bare.refname_to_id("refs/tags/wip").unwrap().to_string(), c4);
+        repo.tag_delete_remote("origin", "wip").unwrap();
+        assert!(bare.refname_to_id("refs/tags/wip").is_err());
+        assert!(repo.tag_exists("wip").unwrap());
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::Result;
+use git2::{build::CheckoutBuilder, Oid, Repository as Git2Repository};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Number of operations kept in the undo log
+const MAX_UNDO_ENTRIES: usize = 50;
+
+/// A ref moved by an operation; `None` means the ref did not exist
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RefChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// One undoable operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoEntry {
+    pub description: String,
+    pub time: i64,
+    pub changes: Vec<RefChange>,
+}
+
+/// Log of history rewrites, kept in `.git/gitup/undo.json`
+pub struct UndoLog<'a> {
+    repo: &'a Git2Repository,
+}
+
+impl<'a> UndoLog<'a> {
+    pub fn new(repo: &'a Git2Repository) -> Self {
+        UndoLog { repo }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.repo.path().join("gitup").join("undo.json")
+    }
+
+    /// Recorded operations, oldest first
+    pub fn entries(&self) -> Result<Vec<UndoEntry>> {
+        match std::fs::read_to_string(self.path()) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, entries: &[UndoEntry]) -> Result<()> {
+        let path = self.path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(entries)?)?;
+        Ok(())
+    }
+
+    pub fn record(&self, description: &str, changes: Vec<RefChange>) -> Result<()> {
+        let mut entries = self.entries()?;
+        entries.push(UndoEntry {
+            description: description.to_string(),
+            time: chrono::Utc::now().timestamp(),
+            changes,
+        });
+        let excess = entries.len().saturating_sub(MAX_UNDO_ENTRIES);
+        entries.drain(..excess);
+        self.save(&entries)
+    }
+
+    /// Put the refs of the last operation back, returning what was undone
+    ///
+    /// Refuses when any of them has moved since, so later work is not lost.
+    pub fn undo(&self) -> Result<Option<UndoEntry>> {
+        let mut entries = self.entries()?;
+        let Some(entry) = entries.pop() else {
+            return Ok(None);
+        };
+
+        for change in &entry.changes {
+            let current = self.repo.refname_to_id(&change.name).ok().map(|id| id.to_string());
+            if current != change.new {
+                return Err(anyhow::anyhow!(
+                    "{} has moved since '{}', not undoing it",
+                    change.name, entry.description
+                ));
+            }
+        }
+
+        let reverse: Vec<RefChange> = entry.changes.iter()
+            .map(|change| RefChange { name: change.name.clone(), old: change.new.clone(), new: change.old.clone() })
+            .collect();
+        set_refs(self.repo, &reverse, &format!("undo: {}", entry.description))?;
+        self.save(&entries)?;
+        Ok(Some(entry))
+    }
+}
+
+/// Move refs together and record the operation in the undo log
+///
+/// `changes` only needs `name` and `new`; the old values are looked up.
+pub(crate) fn update_refs(repo: &Git2Repository, changes: &[(String, Oid)], description: &str) -> Result<()> {
+    let changes: Vec<RefChange> = changes.iter()
+        .map(|(name, new)| RefChange {
+            name: name.clone(),
+            old: repo.refname_to_id(name).ok().map(|id| id.to_string()),
+            new: Some(new.to_string()),
+        })
+        .filter(|change| change.old != change.new)
+        .collect();
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    set_refs(repo, &changes, description)?;
+    UndoLog::new(repo).record(description, changes)
+}
+
+/// Apply ref changes in one transaction, bringing the working tree along
+/// when the checked-out ref moves
+///
+/// Every ref is locked before the working tree is touched, so a ref that
+/// cannot be locked leaves everything as it was. Dropping the transaction
+/// when the checkout fails releases the locks again.
+fn set_refs(repo: &Git2Repository, changes: &[RefChange], reason: &str) -> Result<()> {
+    let mut transaction = repo.transaction()?;
+    for change in changes {
+        transaction.lock_ref(&change.name)?;
+    }
+    for change in changes {
+        match &change.new {
+            Some(new) => transaction.set_target(&change.name, Oid::from_str(new)?, None, reason)?,
+            None => transaction.remove(&change.name)?,
+        }
+    }
+
+    let head = repo.head()?;
+    let checked_out = if head.is_branch() { head.name().unwrap_or("HEAD").to_string() } else { "HEAD".to_string() };
+    if let Some(new) = changes.iter().find(|c| c.name == checked_out).and_then(|c| c.new.as_ref()) {
+        let commit = repo.find_commit(Oid::from_str(new)?)?;
+        repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::repository::tests::{make_commit, write_file};
+    use crate::repository::Repository;
+    use crate::rebase::MoveDestination;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_move_commits_and_undo() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let a = make_commit(&repo, dir, "a.txt", "a\n", "A");
+        let b = make_commit(&repo, dir, "b.txt", "b\n", "B");
+        let c = make_commit(&repo, dir, "c.txt", "c\n", "C");
+        let d = make_commit(&repo, dir, "d.txt", "d\n", "D");
+        let summaries = |repo: &Repository| -> Vec<String> {
+            repo.get_commits(10).unwrap().into_iter().map(|c| c.message).collect()
+        };
+
+        // Move B..C to the top, then D right after A
+        let result = repo.move_commits(&b, &c, &MoveDestination::After(d.clone())).unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(summaries(&repo), vec!["C", "B", "D", "A"]);
+        let head = repo.git_repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.tree().unwrap().len(), 4);
+
+        let new_d = repo.resolve_commit("HEAD~2").unwrap();
+        repo.move_commits(&new_d, &new_d, &MoveDestination::After(a.clone())).unwrap();
+        assert_eq!(summaries(&repo), vec!["C", "B", "D", "A"]);
+        assert_eq!(repo.resolve_commit("HEAD~2").unwrap(), new_d);
+
+        // Move the top commit onto another branch, both branches change together
+        repo.create_branch("other", Some(&a)).unwrap();
+        let top = repo.resolve_commit("HEAD").unwrap();
+        let result = repo.move_commits(&top, &top, &MoveDestination::OntoBranch("other".to_string())).unwrap();
+        assert!(result.success, "{}", result.message);
+        assert_eq!(summaries(&repo), vec!["B", "D", "A"]);
+        assert!(!dir.join("c.txt").exists());
+        let other = repo.git_repo.revparse_single("other").unwrap().peel_to_commit().unwrap();
+        assert_eq!(other.summary(), Some("C"));
+        assert_eq!(other.parent_id(0).unwrap().to_string(), a);
+
+        // Undo puts both branches back, then the reorder before it
+        assert_eq!(repo.undo_entries().unwrap().len(), 2);
+        let undone = repo.undo().unwrap().unwrap();
+        assert_eq!(undone.changes.len(), 2);
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), top);
+        assert_eq!(repo.resolve_commit("other").unwrap(), a);
+        assert!(dir.join("c.txt").exists());
+        repo.undo().unwrap();
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), d);
+        assert!(repo.undo().unwrap().is_none());
+        assert!(repo.move_commits(&b, &c, &MoveDestination::After(b.clone())).is_err());
+    }
+
+    #[test]
+    fn test_undo_leaves_repo_untouched_when_refs_cannot_move() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let repo = Repository::init(dir).unwrap();
+
+        let a = make_commit(&repo, dir, "a.txt", "a\n", "A");
+        let b = make_commit(&repo, dir, "b.txt", "b\n", "B");
+        let c = make_commit(&repo, dir, "c.txt", "c\n", "C");
+        repo.create_branch("other", Some(&a)).unwrap();
+        repo.move_commits(&c, &c, &MoveDestination::OntoBranch("other".to_string())).unwrap();
+        let moved = repo.resolve_commit("HEAD").unwrap();
+        assert_eq!(moved, b);
+        let head_ref = repo.git_repo.head().unwrap().name().unwrap().to_string();
+        let lock = repo.git_repo.path().join(format!("{}.lock", head_ref));
+
+        // A stale lock fails the undo before the working tree is checked out
+        std::fs::write(&lock, "").unwrap();
+        assert!(repo.undo().is_err());
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), moved);
+        assert!(!dir.join("c.txt").exists());
+        assert!(repo.get_status().unwrap().is_empty());
+        std::fs::remove_file(&lock).unwrap();
+
+        // A failed checkout releases the locks it took
+        write_file(dir.join("c.txt"), "local\n");
+        assert!(repo.undo().is_err());
+        assert!(!lock.exists());
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), moved);
+        assert_ne!(repo.resolve_commit("other").unwrap(), a);
+
+        std::fs::remove_file(dir.join("c.txt")).unwrap();
+        assert!(repo.undo().unwrap().is_some());
+        assert_eq!(repo.resolve_commit("other").unwrap(), a);
+        assert_eq!(repo.resolve_commit("HEAD").unwrap(), c);
+    }
+}
@@ -0,0 +1,286 @@
+use anyhow::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use crate::diff::{DiffLine, FileDiff, LineOrigin};
+
+/// Token count product above which lines are not diffed token by token
+const MAX_LCS_CELLS: usize = 250_000;
+
+/// How lines are split into tokens for intra-line diffs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TokenMode {
+    /// Runs of word characters, runs of whitespace and single punctuation characters
+    Word,
+    /// Single characters
+    Char,
+    /// Every regex match is a token, as with `git diff --word-diff-regex`;
+    /// text between matches forms tokens of its own
+    Regex(String),
+}
+
+/// Changed byte range within a line's content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordOpKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A run of tokens that is unchanged, deleted or inserted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordOp {
+    pub kind: WordOpKind,
+    pub text: String,
+}
+
+/// Splits lines into tokens and diffs them
+pub struct Tokenizer {
+    mode: TokenMode,
+    regex: Option<Regex>,
+}
+
+impl Tokenizer {
+    pub fn new(mode: TokenMode) -> Result<Self> {
+        let regex = match &mode {
+            TokenMode::Regex(pattern) => Some(Regex::new(pattern)?),
+            _ => None,
+        };
+        Ok(Tokenizer { mode, regex })
+    }
+
+    pub fn mode(&self) -> &TokenMode {
+        &self.mode
+    }
+
+    /// Split text into tokens that together cover the whole text
+    pub fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        match (&self.mode, &self.regex) {
+            (TokenMode::Regex(_), Some(regex)) => {
+                let mut tokens = Vec::new();
+                let mut last = 0;
+                for m in regex.find_iter(text).filter(|m| !m.as_str().is_empty()) {
+                    if m.start() > last {
+                        tokens.push(&text[last..m.start()]);
+                    }
+                    tokens.push(m.as_str());
+                    last = m.end();
+                }
+                if last < text.len() {
+                    tokens.push(&text[last..]);
+                }
+                tokens
+            }
+            (TokenMode::Char, _) => text
+                .char_indices()
+                .map(|(i, c)| &text[i..i + c.len_utf8()])
+                .collect(),
+            _ => {
+                // 0 = word, 1 = whitespace, 2 = anything else (one token per char)
+                let class = |c: char| {
+                    if c.is_alphanumeric() || c == '_' {
+                        0
+                    } else if c.is_whitespace() {
+                        1
+                    } else {
+                        2
+                    }
+                };
+
+                let mut tokens = Vec::new();
+                let mut start = 0;
+                let mut prev: Option<u8> = None;
+                for (i, c) in text.char_indices() {
+                    let current = class(c);
+                    if let Some(prev) = prev {
+                        if prev != current || current == 2 {
+                            tokens.push(&text[start..i]);
+                            start = i;
+                        }
+                    }
+                    prev = Some(current);
+                }
+                if start < text.len() {
+                    tokens.push(&text[start..]);
+                }
+                tokens
+            }
+        }
+    }
+
+    /// Diff two lines token by token
+    pub fn diff(&self, old: &str, new: &str) -> Vec<WordOp> {
+        let old_tokens = self.tokenize(old);
+        let new_tokens = self.tokenize(new);
+
+        // Trim the common prefix and suffix before running the LCS
+        let prefix = old_tokens.iter()
+            .zip(&new_tokens)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = old_tokens[prefix..].iter().rev()
+            .zip(new_tokens[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let old_mid = &old_tokens[prefix..old_tokens.len() - suffix];
+        let new_mid = &new_tokens[prefix..new_tokens.len() - suffix];
+
+        let mut ops = Vec::new();
+        push_op(&mut ops, WordOpKind::Equal, &old_tokens[..prefix].concat());
+
+        if old_mid.len() * new_mid.len() > MAX_LCS_CELLS {
+            push_op(&mut ops, WordOpKind::Delete, &old_mid.concat());
+            push_op(&mut ops, WordOpKind::Insert, &new_mid.concat());
+        } else {
+            // lengths[i][j] = LCS length of old_mid[i..] and new_mid[j..]
+            let (n, m) = (old_mid.len(), new_mid.len());
+            let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+            for i in (0..n).rev() {
+                for j in (0..m).rev() {
+                    lengths[i][j] = if old_mid[i] == new_mid[j] {
+                        lengths[i + 1][j + 1] + 1
+                    } else {
+                        lengths[i + 1][j].max(lengths[i][j + 1])
+                    };
+                }
+            }
+
+            let (mut i, mut j) = (0, 0);
+            while i < n || j < m {
+                if i < n && j < m && old_mid[i] == new_mid[j] {
+                    push_op(&mut ops, WordOpKind::Equal, old_mid[i]);
+                    i += 1;
+                    j += 1;
+                } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+                    push_op(&mut ops, WordOpKind::Delete, old_mid[i]);
+                    i += 1;
+                } else {
+                    push_op(&mut ops, WordOpKind::Insert, new_mid[j]);
+                    j += 1;
+                }
+            }
+        }
+
+        push_op(&mut ops, WordOpKind::Equal, &old_tokens[old_tokens.len() - suffix..].concat());
+        ops
+    }
+
+    /// Changed byte ranges of the old and new line
+    pub fn changed_ranges(&self, old: &str, new: &str) -> (Vec<ChangeRange>, Vec<ChangeRange>) {
+        let mut old_ranges = Vec::new();
+        let mut new_ranges = Vec::new();
+        let (mut old_pos, mut new_pos) = (0, 0);
+
+        for op in self.diff(old, new) {
+            let len = op.text.len();
+            match op.kind {
+                WordOpKind::Equal => {
+                    old_pos += len;
+                    new_pos += len;
+                }
+                WordOpKind::Delete => {
+                    old_ranges.push(ChangeRange { start: old_pos, end: old_pos + len });
+                    old_pos += len;
+                }
+                WordOpKind::Insert => {
+                    new_ranges.push(ChangeRange { start: new_pos, end: new_pos + len });
+                    new_pos += len;
+                }
+            }
+        }
+
+        (old_ranges, new_ranges)
+    }
+}
+
+/// Append an op, merging it into the previous one when both have the same kind
+fn push_op(ops: &mut Vec<WordOp>, kind: WordOpKind, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match ops.last_mut() {
+        Some(last) if last.kind == kind => last.text.push_str(text),
+        _ => ops.push(WordOp { kind, text: text.to_string() }),
+    }
+}
+
+/// Pair deletion lines with the addition lines that directly follow them
+///
+/// Returns `(deletion index, addition index)` pairs; in a block of deletions
+/// followed by additions, the n-th deletion is paired with the n-th addition.
+pub fn paired_lines(lines: &[DiffLine]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        if !matches!(lines[i].origin, LineOrigin::Deletion) {
+            i += 1;
+            continue;
+        }
+
+        let del_start = i;
+        while i < lines.len() && matches!(lines[i].origin, LineOrigin::Deletion) {
+            i += 1;
+        }
+        let add_start = i;
+        while i < lines.len() && matches!(lines[i].origin, LineOrigin::Addition) {
+            i += 1;
+        }
+
+        pairs.extend((del_start..add_start).zip(add_start..i));
+    }
+
+    pairs
+}
+
+/// Fill in the changed ranges of paired deletion/addition lines
+pub fn annotate(file_diff: &mut FileDiff, tokenizer: &Tokenizer) {
+    for (del, add) in paired_lines(&file_diff.lines) {
+        let (old_ranges, new_ranges) = tokenizer.changed_ranges(
+            line_text(&file_diff.lines[del]),
+            line_text(&file_diff.lines[add]),
+        );
+        file_diff.lines[del].changes = old_ranges;
+        file_diff.lines[add].changes = new_ranges;
+    }
+}
+
+/// Line content without its line terminator
+pub fn line_text(line: &DiffLine) -> &str {
+    line.content.trim_end_matches(['\n', '\r'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_ranges() {
+        let tokenizer = Tokenizer::new(TokenMode::Word).unwrap();
+        let (old, new) = tokenizer.changed_ranges("let total = count + 1;", "let total = count + 2;");
+        assert_eq!(old, vec![ChangeRange { start: 20, end: 21 }]);
+        assert_eq!(new, vec![ChangeRange { start: 20, end: 21 }]);
+
+        let (old, new) = tokenizer.changed_ranges("call(alpha)", "call(beta)");
+        assert_eq!(old, vec![ChangeRange { start: 5, end: 10 }]);
+        assert_eq!(new, vec![ChangeRange { start: 5, end: 9 }]);
+    }
+
+    #[test]
+    fn test_char_and_regex_modes() {
+        let tokenizer = Tokenizer::new(TokenMode::Char).unwrap();
+        let (old, new) = tokenizer.changed_ranges("colour", "color");
+        assert_eq!(old, vec![ChangeRange { start: 4, end: 5 }]);
+        assert!(new.is_empty());
+
+        let tokenizer = Tokenizer::new(TokenMode::Regex(r"[^,]+".to_string())).unwrap();
+        assert_eq!(tokenizer.tokenize("a b,c"), vec!["a b", ",", "c"]);
+        assert!(Tokenizer::new(TokenMode::Regex("(".to_string())).is_err());
+    }
+}
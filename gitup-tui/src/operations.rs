@@ -1,6 +1,7 @@
 use std::collections::VecDeque;
 use anyhow::{Result, bail};
 use git2::Repository as Git2Repository;
+use gitup_core::{CommitMetadata, RebaseOps};
 
 /// Operations that can be performed on the Git repository
 #[derive(Debug, Clone)]
@@ -357,12 +358,18 @@ impl OperationsManager {
     }
 
     fn reword_commit(&mut self, commit: &str, message: &str) -> Result<OperationResult> {
-        // Would implement commit message editing
+        let ops = RebaseOps::from_repo(Git2Repository::open(self.repository.path())?);
+        let metadata = CommitMetadata {
+            message: Some(message.to_string()),
+            ..Default::default()
+        };
+        let result = ops.rewrite_commit(commit, &metadata)?;
+
         Ok(OperationResult {
-            success: true,
-            message: format!("Reworded commit {}", &commit[..7]),
-            changed_refs: vec![],
-            conflicts: vec![],
+            success: result.success,
+            message: result.message,
+            changed_refs: if result.success { vec!["HEAD".to_string()] } else { vec![] },
+            conflicts: result.conflicts,
         })
     }
 
@@ -7,16 +7,25 @@ use ratatui::{
 };
 
 use graph::{
-    GitWalker, SeamlessViewport,
+    GitWalker, HistoryPager, SeamlessViewport,
     TextLayout, CjkMode, CommitMessageFormatter,
     CellRouter, CharsetProfile, ConflictResolver,
     ViewportCarryOver, Color as GraphColor,
 };
 
+/// Commits loaded per page of history
+const PAGE_SIZE: usize = 2000;
+
 /// Integration to add enhanced graph to existing TUI
 pub struct EnhancedGraphIntegration {
+    /// Walker kept open to update the DAG on refresh
+    walker: GitWalker,
+    /// Paged walk loading older history as the viewport scrolls
+    pager: HistoryPager,
     /// The DAG from new graph module
     dag: graph::Dag,
+    /// Layout builder, holding the lane checkpoints of `rows`
+    builder: graph::layout::SimpleGraphBuilder,
     /// Compact rows with lane compression
     rows: Vec<graph::Row>,
     /// Seamless viewport
@@ -38,14 +47,16 @@ impl EnhancedGraphIntegration {
     pub fn new(repo_path: &str) -> Result<Self> {
         // Load repository using our walker
         let walker = GitWalker::new(Some(repo_path))?;
-        let dag = walker.into_dag(None)?;
+        let (mut dag, mut pager) = walker.paged_dag(PAGE_SIZE)?;
+        let page = walker.next_page(&mut pager, &mut dag)?;
 
         // Build simple layout with continuous lines
         let mut builder = graph::layout::SimpleGraphBuilder::new(12);
-        let rows = builder.build_rows(&dag);
+        let rows = builder.extend_rows(&dag, &page);
 
         // Create seamless viewport
-        let viewport = SeamlessViewport::new(30, rows.len());
+        let mut viewport = SeamlessViewport::new(30, rows.len());
+        viewport.more_history = pager.has_more();
 
         // CJK support
         let cjk_mode = if TextLayout::detect_cjk_from_locale() {
@@ -64,7 +75,10 @@ impl EnhancedGraphIntegration {
         let renderer = graph::TuiRenderer::new(12, profile);
 
         Ok(Self {
+            walker,
+            pager,
             dag,
+            builder,
             rows,
             viewport,
             formatter,
@@ -180,13 +194,29 @@ impl EnhancedGraphIntegration {
                 }
             }
         }
+
+        // Older history is still to be loaded below the last row
+        if self.viewport.more_history && visible_rows.len() < area.height as usize {
+            let y = area.y + visible_rows.len() as u16;
+            buf.set_stringn(area.x, y, "loading more history...", area.width as usize, Style::default().fg(Color::DarkGray));
+        }
+    }
+
+    /// Load the next page of history below the current rows
+    pub fn load_more(&mut self) -> Result<()> {
+        let page = self.walker.next_page(&mut self.pager, &mut self.dag)?;
+        let rows = self.builder.extend_rows(&self.dag, &page);
+        self.rows.extend(rows);
+        self.viewport.total_rows = self.rows.len();
+        self.viewport.more_history = self.pager.has_more();
+        Ok(())
     }
 
     /// Handle keyboard input
     pub fn handle_input(&mut self, code: crossterm::event::KeyCode) -> bool {
         use crossterm::event::KeyCode;
 
-        match code {
+        let handled = match code {
             KeyCode::Char('j') | KeyCode::Down => {
                 if self.viewport.cursor < self.viewport.total_rows - 1 {
                     self.viewport.cursor += 1;
@@ -230,7 +260,13 @@ impl EnhancedGraphIntegration {
                 true
             }
             _ => false,
+        };
+
+        // Fetch the next page before the cursor reaches the last loaded row
+        if handled && self.viewport.wants_more() && self.load_more().is_err() {
+            self.viewport.more_history = false;
         }
+        handled
     }
 
     /// Get selected commit SHA
@@ -243,14 +279,33 @@ impl EnhancedGraphIntegration {
     }
 
     /// Refresh graph from repository
-    pub fn refresh(&mut self, repo_path: &str) -> Result<()> {
-        let walker = GitWalker::new(Some(repo_path))?;
-        self.dag = walker.into_dag(None)?;
-
-        let mut builder = graph::layout::SimpleGraphBuilder::new(12);
-        self.rows = builder.build_rows(&self.dag);
+    ///
+    /// Only commits that appeared or disappeared since the last refresh are
+    /// walked and laid out; the cursor stays on the selected commit. If the
+    /// history has to be walked again, paging starts over from the top.
+    pub fn refresh(&mut self) -> Result<()> {
+        let update = self.walker.update_paged_dag(&mut self.dag, &mut self.pager)?;
+        if update.is_empty() {
+            return Ok(());
+        }
 
-        self.viewport = SeamlessViewport::new(30, self.rows.len());
+        let selected = self.selected_commit();
+        let rows = std::mem::take(&mut self.rows);
+        self.rows = self.builder.update_rows(&self.dag, rows, &update);
+
+        let viewport = &mut self.viewport;
+        viewport.total_rows = self.rows.len();
+        viewport.more_history = self.pager.has_more();
+        viewport.cursor = selected
+            .and_then(|id| self.rows.iter().position(|row| row.commit_id == id))
+            .unwrap_or(0)
+            .min(self.rows.len().saturating_sub(1));
+        if viewport.cursor < viewport.top {
+            viewport.top = viewport.cursor;
+        } else if viewport.cursor >= viewport.top + viewport.height {
+            viewport.top = viewport.cursor + 1 - viewport.height;
+        }
+        viewport.top = viewport.top.min(viewport.total_rows.saturating_sub(viewport.height));
         Ok(())
     }
 }
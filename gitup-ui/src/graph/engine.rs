@@ -2,7 +2,7 @@ use anyhow::Result;
 use std::collections::HashMap;
 use ratatui::style::Color;
 
-use gitup_core::{Repository, CommitWithParents, RefInfo, RefType};
+use gitup_core::{Repository, CommitWithParents, DescribeOptions, RefType};
 
 use super::types::*;
 use super::lane_manager::LaneManager;
@@ -11,14 +11,22 @@ use super::lane_manager::LaneManager;
 /// builds a trivial single-lane graph to unblock subsequent work.
 pub struct GraphEngine {
     pub max_count: usize,
+    /// Describe label of each labelled tip, `None` if it has none; kept across builds
+    label_cache: HashMap<String, Option<String>>,
+    /// Tags the cached labels were worked out from
+    label_tags: HashMap<String, String>,
 }
 
 impl Default for GraphEngine {
-    fn default() -> Self { Self { max_count: 500 } }
+    fn default() -> Self { Self::new(500) }
 }
 
 impl GraphEngine {
-    pub fn build(&self, repo: &Repository) -> Result<GitGraph> {
+    pub fn new(max_count: usize) -> Self {
+        Self { max_count, label_cache: HashMap::new(), label_tags: HashMap::new() }
+    }
+
+    pub fn build(&mut self, repo: &Repository) -> Result<GitGraph> {
         let commits: Vec<CommitWithParents> = repo.get_commits_with_parents(self.max_count)?;
         let refs_by_oid = repo.list_refs_by_oid()?;
 
@@ -75,6 +83,34 @@ impl GraphEngine {
             }
         }
 
+        // describe labels for HEAD and branch tips; repos without tags get none.
+        // A label only changes with the tags, so tips described before are not walked again
+        let labelled: Vec<String> = nodes.iter().enumerate()
+            .filter(|(row, n)| *row == 0 || n.primary_branch.is_some())
+            .map(|(_, n)| n.id.clone())
+            .collect();
+        if self.label_tags != tags {
+            self.label_cache.clear();
+            self.label_tags = tags.clone();
+        }
+        self.label_cache.retain(|id, _| labelled.contains(id));
+        let missing: Vec<String> = labelled.iter()
+            .filter(|id| !self.label_cache.contains_key(*id))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            let describe = DescribeOptions { tags: true, ..Default::default() };
+            if let Ok(mut found) = repo.describe_many(&missing, &describe) {
+                for id in missing {
+                    let label = found.remove(&id).map(|d| d.to_string());
+                    self.label_cache.insert(id, label);
+                }
+            }
+        }
+        let labels = self.label_cache.iter()
+            .filter_map(|(id, label)| Some((id.clone(), label.clone()?)))
+            .collect();
+
         // lanes output
         let palette = vec![
             Color::Cyan, Color::Green, Color::Yellow, Color::Magenta, Color::Blue, Color::Red,
@@ -96,7 +132,7 @@ impl GraphEngine {
             branch_colors.insert(name.clone(), palette[idx]);
         }
 
-        Ok(GitGraph { nodes, edges, lanes, branches, tags, branch_colors })
+        Ok(GitGraph { nodes, edges, lanes, branches, tags, labels, branch_colors })
     }
 }
 
@@ -118,10 +154,27 @@ mod tests {
         repo.stage_file("x.txt").unwrap();
         repo.commit("c1", "A", "a@a").unwrap();
 
-        let engine = GraphEngine::default();
+        let mut engine = GraphEngine::default();
         let g = engine.build(&repo).unwrap();
         assert!(!g.nodes.is_empty());
         assert_eq!(g.lanes.len(), 1);
         assert!(g.edges.iter().all(|e| e.lane == 0));
     }
+
+    #[test]
+    fn labels_follow_new_tags() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        write_file(dir.path().join("x.txt"), "1");
+        repo.stage_file("x.txt").unwrap();
+        repo.commit("c1", "A", "a@a").unwrap();
+
+        let mut engine = GraphEngine::default();
+        let head = engine.build(&repo).unwrap().nodes[0].id.clone();
+        assert!(engine.build(&repo).unwrap().labels.is_empty());
+
+        // The cached "no label" is dropped once a tag appears
+        repo.tag_create("v1", Some(&head), None, false).unwrap();
+        assert_eq!(engine.build(&repo).unwrap().labels.get(&head).map(String::as_str), Some("v1"));
+    }
 }
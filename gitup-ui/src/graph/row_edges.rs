@@ -82,7 +82,7 @@ mod tests {
             edges: vec![ GraphEdge { from: "c2".into(), to: "c1".into(), lane: 0 } ],
             lanes: vec![ Lane { index: 0, color: Color::Cyan, active: true } ],
             branches: Default::default(),
-            tags: Default::default(),
+            tags: Default::default(), labels: Default::default(),
             branch_colors: Default::default(),
         }
     }
@@ -103,7 +103,7 @@ mod tests {
                 GraphEdge { from: "m".into(), to: "f".into(), lane: 0 },
             ],
             lanes: vec![ Lane { index: 0, color: Color::Cyan, active: true }, Lane { index: 1, color: Color::Green, active: true } ],
-            branches: Default::default(), tags: Default::default(), branch_colors: Default::default(),
+            branches: Default::default(), tags: Default::default(), labels: Default::default(), branch_colors: Default::default(),
         }
     }
 
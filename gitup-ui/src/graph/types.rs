@@ -47,5 +47,7 @@ pub struct GitGraph {
     pub lanes: Vec<Lane>,
     pub branches: HashMap<String, String>,
     pub tags: HashMap<String, String>,
+    /// `git describe` names of branch tips and HEAD, by commit id
+    pub labels: HashMap<String, String>,
     pub branch_colors: HashMap<String, Color>,
 }
@@ -2,8 +2,9 @@ use ratatui::{
     buffer::Buffer,
     layout::Rect,
     widgets::Widget,
-    style::{Style, Color},
+    style::{Style, Color, Modifier},
 };
+use std::collections::HashSet;
 
 use super::types::*;
 use super::row_edges::{ProcessedRow, RowEdges};
@@ -13,15 +14,22 @@ pub struct AdvancedGraphWidget<'a> {
     pub rows: &'a [ProcessedRow],
     pub top: usize,
     pub ascii_only: bool,
+    /// Commits drawn emphasized, e.g. those that arrived with a fetch
+    pub highlighted: Option<&'a HashSet<String>>,
 }
 
 impl<'a> AdvancedGraphWidget<'a> {
     pub fn new(graph: &'a GitGraph, rows: &'a [ProcessedRow]) -> Self {
-        Self { graph, rows, top: 0, ascii_only: false }
+        Self { graph, rows, top: 0, ascii_only: false, highlighted: None }
     }
 
     pub fn top(mut self, top: usize) -> Self { self.top = top; self }
     pub fn ascii(mut self, ascii: bool) -> Self { self.ascii_only = ascii; self }
+    pub fn highlight(mut self, ids: &'a HashSet<String>) -> Self { self.highlighted = Some(ids); self }
+
+    fn is_highlighted(&self, id: &str) -> bool {
+        self.highlighted.map(|ids| ids.contains(id)).unwrap_or(false)
+    }
 
     fn lane_node_x(&self, area: Rect, lane: usize) -> u16 { area.x + (lane as u16) * 2 }
     fn lane_line_x(&self, area: Rect, lane: usize) -> u16 { self.lane_node_x(area, lane) }
@@ -96,6 +104,7 @@ impl<'a> Widget for AdvancedGraphWidget<'a> {
             if let Some(&lane) = node_lane_by_id.get(prow.commit_id.as_str()) {
                 let x_node = self.lane_node_x(area, lane);
                 let is_head = self.is_head(&prow.commit_id);
+                let highlighted = self.is_highlighted(&prow.commit_id);
                 let cell = buf.get_mut(x_node, screen_y);
                 cell.set_char(self.char_node(is_head));
                 let col = if is_head { Color::Green } else { self.color_for_node(&prow.commit_id) };
@@ -112,7 +121,10 @@ impl<'a> Widget for AdvancedGraphWidget<'a> {
                         .map(|n| n.message.as_str()).unwrap_or("");
 
                     // Format and truncate text with proper CJK handling
-                    let text = format!("{} {}", short, message);
+                    let text = match self.graph.labels.get(&prow.commit_id) {
+                        Some(label) => format!("{} ({}) {}", short, label, message),
+                        None => format!("{} {}", short, message),
+                    };
                     let available_width = (area.width - (text_x - area.x)) as usize;
 
                     // Use grapheme clusters for proper text handling
@@ -138,6 +150,9 @@ impl<'a> Widget for AdvancedGraphWidget<'a> {
                                 if x < area.x + area.width {
                                     let c = buf.get_mut(x, screen_y);
                                     c.set_char(ch);
+                                    if highlighted {
+                                        c.set_style(Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD));
+                                    }
 
                                     // For wide characters, skip the next cell
                                     if width == 2 && x + 1 < area.x + area.width {
@@ -177,7 +192,7 @@ mod tests {
             edges: vec![ GraphEdge { from: "c2".into(), to: "c1".into(), lane: 0 } ],
             lanes: vec![ Lane { index: 0, color: Color::Cyan, active: true } ],
             branches: Default::default(),
-            tags: Default::default(),
+            tags: Default::default(), labels: Default::default(),
             branch_colors: Default::default(),
         }
     }
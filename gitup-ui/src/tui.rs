@@ -4,7 +4,13 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use gitup_core::{Repository, CommitInfo, BranchInfo, CommitFileStatus, StatusType};
+use gitup_core::{
+    Repository, CommitInfo, BranchInfo, CommitFileStatus, StatusType,
+    ChangeRange, DiffLine, FileDiff, TokenMode, Tokenizer,
+    JobEvent, JobId, JobOutput, JobProgress, JobRunner, FetchOptions, TagMode,
+    AnalyticsOptions, RepoAnalytics, LintConfig, MoveDestination, LintFinding, lint_message,
+    MergeKind, MergePrediction, IgnoreFile,
+};
 use crate::simple_graph::{SimpleGraph, SimpleGraphWidget};
 use crate::graph::{engine::GraphEngine, row_edges::{RowEdgesBuilder, ProcessedRow}, widget::AdvancedGraphWidget, types::GitGraph};
 use crate::enhanced_graph::EnhancedGraphIntegration;
@@ -17,13 +23,14 @@ use ratatui::{
     style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{
-        Block, Borders, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation,
+        Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation,
         ScrollbarState, Tabs, Wrap,
     },
     Frame, Terminal,
 };
 use std::{
     cell::RefCell,
+    collections::{HashMap, HashSet},
     io,
     path::{Path, PathBuf},
     time::{Duration, Instant},
@@ -63,6 +70,10 @@ pub struct App {
     pub selected_file: ListState,
     pub diff_content: String,
     pub diff_line_count: usize,
+    /// Intra-line changed ranges per line of `diff_content`
+    pub diff_emphasis: HashMap<usize, Vec<ChangeRange>>,
+    /// Tokenizer mode for intra-line highlighting, `None` to disable
+    pub word_diff: Option<TokenMode>,
     pub scroll_state: ScrollbarState,
     pub scroll_position: u16,
     pub should_quit: bool,
@@ -72,6 +83,7 @@ pub struct App {
     pub show_graph: bool,
     pub simple_graph: SimpleGraph,
     // Advanced graph cache/state
+    pub graph_engine: GraphEngine,
     pub graph_data: Option<GitGraph>,
     pub graph_rows: Vec<ProcessedRow>,
     pub graph_top: usize,
@@ -96,14 +108,45 @@ pub struct App {
     // Navigation context
     pub viewing_commit: Option<String>,  // Currently viewing commit's files
     pub previous_tab: Option<usize>,     // For Esc navigation
+
+    // Background jobs (fetch, pull, push)
+    pub jobs: JobRunner,
+    pub job_status: Option<JobStatus>,
+    /// Commits that arrived with the last fetch, highlighted in the history
+    pub fetched_commits: HashSet<String>,
+
+    // Stats tab, computed in the background on first visit
+    pub analytics: Option<RepoAnalytics>,
+    pub analytics_job: Option<JobId>,
+
+    /// Commit message rules, checked as `:w <message>` is typed
+    pub lint_config: LintConfig,
+
+    /// Commits cut with `x` (oldest, newest, count), waiting to be pasted with `p`
+    pub cut_commits: Option<(String, String, usize)>,
+
+    /// In-memory merge of each hovered branch into HEAD, `None` if it could not be worked out
+    pub merge_risk: HashMap<String, Option<MergePrediction>>,
+    /// Merge checks run here, apart from `jobs`, so they never hold up a fetch or push
+    pub merge_jobs: JobRunner,
+    /// Branch whose merge check is running, shown as "computing…"
+    pub merge_risk_job: Option<(JobId, String)>,
+}
+
+/// Progress of the background job shown in the status bar
+pub struct JobStatus {
+    pub id: JobId,
+    pub name: String,
+    pub progress: Option<JobProgress>,
 }
 
 impl App {
     pub fn new<P: AsRef<Path>>(repo_path: P) -> Result<Self> {
-        let repository = Repository::open(repo_path)?;
+        let repository = Repository::discover(repo_path)?;
         let commits = repository.get_commits(50)?;
         let branches = repository.list_branches()?;
         let status = repository.get_status()?;
+        let lint_config = repository.message_ops().lint_config().unwrap_or_default();
 
         let status_files: Vec<(String, StatusType)> = status
             .into_iter()
@@ -132,6 +175,8 @@ impl App {
             selected_file,
             diff_content: String::new(),
             diff_line_count: 0,
+            diff_emphasis: HashMap::new(),
+            word_diff: Some(TokenMode::Word),
             scroll_state: ScrollbarState::default(),
             scroll_position: 0,
             should_quit: false,
@@ -140,6 +185,7 @@ impl App {
             // Graph visualization
             show_graph: false,
             simple_graph: SimpleGraph::new(),
+            graph_engine: GraphEngine::new(200),
             graph_data: None,
             graph_rows: Vec::new(),
             graph_top: 0,
@@ -164,12 +210,142 @@ impl App {
             // Navigation context
             viewing_commit: None,
             previous_tab: None,
+
+            jobs: JobRunner::new(),
+            job_status: None,
+            fetched_commits: HashSet::new(),
+            analytics: None,
+            analytics_job: None,
+            lint_config,
+            cut_commits: None,
+            merge_risk: HashMap::new(),
+            merge_jobs: JobRunner::new(),
+            merge_risk_job: None,
         })
     }
 
+    /// Apply events from background jobs
+    fn process_job_events(&mut self) {
+        while let Some(event) = self.jobs.try_recv() {
+            match event {
+                JobEvent::Started { id, name } => {
+                    self.job_status = Some(JobStatus { id, name, progress: None });
+                }
+                JobEvent::Progress { id, progress } => {
+                    if let Some(status) = self.job_status.as_mut().filter(|s| s.id == id) {
+                        status.progress = Some(progress);
+                    }
+                }
+                JobEvent::Finished { id, result } => {
+                    let name = self.take_job_name(id);
+                    let msg = match result {
+                        Ok(JobOutput::Message(msg)) => msg,
+                        Ok(JobOutput::Fetch(reports)) => {
+                            let updates: Vec<_> = reports.iter().flat_map(|r| r.updates.iter().cloned()).collect();
+                            self.fetched_commits = self.repository.remote_ops()
+                                .new_commits(&updates)
+                                .unwrap_or_default()
+                                .into_iter()
+                                .collect();
+                            let mut msg = format!(
+                                "{}: {} refs updated, {} new commits",
+                                name,
+                                updates.len(),
+                                self.fetched_commits.len()
+                            );
+                            for report in &reports {
+                                if let Some(error) = &report.error {
+                                    msg.push_str(&format!("; {} failed: {}", report.remote, error));
+                                }
+                                if let Some(error) = &report.commit_graph_error {
+                                    msg.push_str(&format!("; commit-graph not updated: {}", error));
+                                }
+                            }
+                            msg
+                        }
+                        Ok(JobOutput::Analytics(analytics)) => {
+                            self.analytics_job = None;
+                            let msg = format!("Analyzed {} commits", analytics.commits);
+                            self.analytics = Some(*analytics);
+                            self.message = Some((msg, Instant::now()));
+                            continue;
+                        }
+                        Ok(_) => format!("{} finished", name),
+                        Err(e) => format!("{} failed: {}", name, e),
+                    };
+                    self.message = Some((msg, Instant::now()));
+                    let _ = self.refresh();
+                }
+                JobEvent::Cancelled { id } => {
+                    if self.analytics_job == Some(id) {
+                        self.analytics_job = None;
+                    }
+                    let name = self.take_job_name(id);
+                    self.message = Some((format!("{} cancelled", name), Instant::now()));
+                }
+            }
+        }
+    }
+
+    /// Start computing the stats tab unless it is loaded or another job is running
+    fn ensure_analytics(&mut self) {
+        if self.analytics.is_some() || self.analytics_job.is_some() || self.jobs.is_busy() {
+            return;
+        }
+        let options = AnalyticsOptions { max_commits: Some(5000), ..Default::default() };
+        self.analytics_job = Some(self.jobs.analytics(self.repository.git_dir().to_path_buf(), options));
+    }
+
+    /// Predict merging the hovered branch into HEAD in the background, once
+    /// per branch until the next refresh
+    fn ensure_merge_risk(&mut self) {
+        if self.merge_risk_job.is_some() {
+            return;
+        }
+        let branch = match self.selected_branch.selected().and_then(|i| self.branches.get(i)) {
+            Some(branch) if !branch.is_head => branch.name.clone(),
+            _ => return,
+        };
+        if self.merge_risk.contains_key(&branch) {
+            return;
+        }
+        let id = self.merge_jobs.predict_merge(self.repository.git_dir().to_path_buf(), branch.clone());
+        self.merge_risk_job = Some((id, branch));
+    }
+
+    /// Collect finished merge checks; results from before a refresh are dropped
+    fn process_merge_risk_events(&mut self) {
+        while let Some(event) = self.merge_jobs.try_recv() {
+            let id = event.id();
+            let prediction = match event {
+                JobEvent::Finished { result: Ok(JobOutput::MergePrediction(prediction)), .. } => Some(*prediction),
+                JobEvent::Finished { .. } | JobEvent::Cancelled { .. } => None,
+                _ => continue,
+            };
+            if let Some((_, branch)) = self.merge_risk_job.take_if(|(job, _)| *job == id) {
+                self.merge_risk.insert(branch, prediction);
+            }
+        }
+    }
+
+    /// Clear the status bar job if it is `id`, returning the job name
+    fn take_job_name(&mut self, id: JobId) -> String {
+        match self.job_status.take() {
+            Some(status) if status.id == id => status.name,
+            other => {
+                self.job_status = other;
+                "Job".to_string()
+            }
+        }
+    }
+
     pub fn refresh(&mut self) -> Result<()> {
         self.commits = self.repository.get_commits(50)?;
         self.branches = self.repository.list_branches()?;
+        self.merge_risk.clear();
+        if self.merge_risk_job.take().is_some() {
+            self.merge_jobs.cancel_all();
+        }
 
         let status = self.repository.get_status()?;
         self.status_files = status
@@ -177,6 +353,10 @@ impl App {
             .map(|f| (f.path, f.status))
             .collect();
 
+        if let Some(graph) = &self.enhanced_graph {
+            graph.borrow_mut().refresh()?;
+        }
+
         // Invalidate cached graph; it will be lazily rebuilt on draw
         self.graph_data = None;
         self.graph_rows.clear();
@@ -187,8 +367,7 @@ impl App {
 
     /// Build or rebuild advanced graph cache
     pub fn rebuild_graph(&mut self) -> Result<()> {
-        let engine = GraphEngine { max_count: 200 };
-        let graph = engine.build(&self.repository)?;
+        let graph = self.graph_engine.build(&self.repository)?;
         let rows = RowEdgesBuilder::build(&graph);
         self.graph_rows = rows;
         self.graph_data = Some(graph);
@@ -215,14 +394,14 @@ impl App {
     }
 
     pub fn next_tab(&mut self) {
-        self.current_tab = (self.current_tab + 1) % 4;
+        self.current_tab = (self.current_tab + 1) % 5;
     }
 
     pub fn previous_tab(&mut self) {
         if self.current_tab > 0 {
             self.current_tab -= 1;
         } else {
-            self.current_tab = 3;
+            self.current_tab = 4;
         }
     }
 
@@ -336,26 +515,42 @@ impl App {
         self.selected_file.select(Some(i));
     }
 
+    /// Fill in intra-line changed ranges according to the word diff mode
+    fn annotate_diff(&self, diff: &mut FileDiff) {
+        if let Some(mode) = &self.word_diff {
+            if let Ok(tokenizer) = Tokenizer::new(mode.clone()) {
+                gitup_core::word_diff::annotate(diff, &tokenizer);
+            }
+        }
+    }
+
     fn load_commit_diff(&mut self) {
         if let Some(i) = self.selected_commit.selected() {
             if let Some(commit) = self.commits.get(i) {
                 if let Ok(diffs) = self.repository.diff_for_commit(&commit.id) {
                     let mut content = String::new();
-                    for diff in diffs {
+                    let mut emphasis = HashMap::new();
+                    let mut counter = LineCounter::default();
+                    let notes = self.repository.notes_ops();
+                    if let Ok(Some(note)) = notes.read(&commit.id) {
+                        content.push_str(&format!("Notes ({}):\n", notes.notes_ref().trim_start_matches("refs/notes/")));
+                        for line in note.lines() {
+                            content.push_str(&format!("    {}\n", line));
+                        }
+                        content.push('\n');
+                    }
+                    for mut diff in diffs {
+                        self.annotate_diff(&mut diff);
                         content.push_str(&format!("--- {}\n", diff.file.path));
-                        for line in diff.lines {
-                            let prefix = match line.origin {
-                                gitup_core::LineOrigin::Addition => "+",
-                                gitup_core::LineOrigin::Deletion => "-",
-                                gitup_core::LineOrigin::Context => " ",
-                            };
-                            content.push_str(&format!("{}{}", prefix, line.content));
-                            if !line.content.ends_with('\n') {
-                                content.push('\n');
-                            }
+                        if let Some(change) = &diff.content {
+                            content.push_str(&format!("{}\n", change.describe()));
+                        }
+                        for line in &diff.lines {
+                            push_diff_line(&mut content, &mut emphasis, &mut counter, line);
                         }
                     }
                     self.diff_content = content;
+                    self.diff_emphasis = emphasis;
                     self.diff_line_count = self.diff_content.lines().count();
                     self.scroll_position = 0;
                     self.scroll_state = self.scroll_state
@@ -400,14 +595,20 @@ impl App {
         if let Some(commit_id) = &self.viewing_commit {
             if let Ok(diffs) = self.repository.diff_for_commit(commit_id) {
                 // Find the specific file's diff
-                for diff in diffs {
+                for mut diff in diffs {
                     if diff.file.path == file_path {
+                        self.annotate_diff(&mut diff);
                         let mut content = String::new();
+                        let mut emphasis = HashMap::new();
+                        let mut counter = LineCounter::default();
                         content.push_str(&format!("Commit: {}\n", &commit_id[..8.min(commit_id.len())]));
                         content.push_str(&format!("File: {}\n", file_path));
                         content.push_str(&format!("diff --git a/{} b/{}\n", file_path, file_path));
                         content.push_str(&format!("--- a/{}\n", file_path));
                         content.push_str(&format!("+++ b/{}\n", file_path));
+                        if let Some(change) = &diff.content {
+                            content.push_str(&format!("{}\n", change.describe()));
+                        }
 
                         // Add hunks
                         for hunk in &diff.hunks {
@@ -416,18 +617,11 @@ impl App {
 
                         // Add lines
                         for line in &diff.lines {
-                            let prefix = match line.origin {
-                                gitup_core::LineOrigin::Addition => "+",
-                                gitup_core::LineOrigin::Deletion => "-",
-                                gitup_core::LineOrigin::Context => " ",
-                            };
-                            content.push_str(&format!("{}{}", prefix, line.content));
-                            if !line.content.ends_with('\n') {
-                                content.push('\n');
-                            }
+                            push_diff_line(&mut content, &mut emphasis, &mut counter, line);
                         }
 
                         self.diff_content = content;
+                        self.diff_emphasis = emphasis;
                         self.diff_line_count = self.diff_content.lines().count();
                         self.scroll_position = 0;
                         self.scroll_state = self.scroll_state
@@ -474,8 +668,11 @@ impl App {
         }
 
         match diff_result {
-            Some(diff) => {
+            Some(mut diff) => {
+                self.annotate_diff(&mut diff);
                 let mut content = String::new();
+                let mut emphasis = HashMap::new();
+                let mut counter = LineCounter::default();
 
                 // Add header
                 if is_new_file {
@@ -503,24 +700,19 @@ impl App {
 
                 // Add lines - for new files, all lines should be additions
                 for line in &diff.lines {
-                    let prefix = match line.origin {
-                        gitup_core::LineOrigin::Addition => "+",
-                        gitup_core::LineOrigin::Deletion => "-",
-                        gitup_core::LineOrigin::Context => " ",
-                    };
-                    content.push_str(&format!("{}{}", prefix, line.content));
-                    if !line.content.ends_with('\n') {
-                        content.push('\n');
-                    }
+                    push_diff_line(&mut content, &mut emphasis, &mut counter, line);
                 }
 
-                if diff.lines.is_empty() && !diff.binary {
+                if let Some(change) = &diff.content {
+                    content.push_str(&format!("\n{}\n", change.describe()));
+                } else if diff.lines.is_empty() && !diff.binary {
                     content.push_str("\n(Empty file)\n");
                 } else if diff.binary {
                     content.push_str("\n(Binary file)\n");
                 }
 
                 self.diff_content = content;
+                self.diff_emphasis = emphasis;
                 self.diff_line_count = self.diff_content.lines().count();
                 self.scroll_position = 0;
                 self.scroll_state = self.scroll_state
@@ -559,6 +751,7 @@ impl App {
                 }
 
                 self.diff_content = content;
+                self.diff_emphasis.clear();
                 self.diff_line_count = self.diff_content.lines().count();
                 self.scroll_position = 0;
                 self.scroll_state = self.scroll_state
@@ -581,6 +774,35 @@ impl App {
         }
     }
 
+    /// Ignore the selected untracked file, or every file with its extension, in the nearest .gitignore
+    pub fn ignore_selected_file(&mut self, extension: bool) {
+        let path = match self.selected_file.selected().and_then(|i| self.status_files.get(i)) {
+            Some((path, StatusType::Untracked)) => path.clone(),
+            Some(_) => {
+                self.message = Some(("Only untracked files can be ignored".to_string(), Instant::now()));
+                return;
+            }
+            None => return,
+        };
+
+        let ignore = self.repository.ignore_ops();
+        let result = if extension {
+            ignore.ignore_extension(&path, IgnoreFile::Nearest)
+        } else {
+            ignore.ignore_path(&path, IgnoreFile::Nearest)
+        };
+        match result {
+            Ok(rule) if rule.added => {
+                self.message = Some((format!("Added '{}' to {}", rule.pattern, rule.file), Instant::now()));
+                let _ = self.refresh();
+            }
+            Ok(rule) => {
+                self.message = Some((format!("{} already has '{}'", rule.file, rule.pattern), Instant::now()));
+            }
+            Err(e) => self.message = Some((format!("Failed to ignore: {}", e), Instant::now())),
+        }
+    }
+
     pub fn unstage_selected_file(&mut self) {
         if let Some(i) = self.selected_file.selected() {
             if let Some((path, _)) = self.status_files.get(i) {
@@ -594,10 +816,152 @@ impl App {
         }
     }
 
+    /// Fold the staged changes into the selected commit (fixup + autosquash)
+    pub fn fixup_selected_commit(&mut self) {
+        let Some(commit) = self.selected_commit.selected().and_then(|i| self.commits.get(i)).cloned() else {
+            return;
+        };
+        match self.repository.has_staged_changes() {
+            Ok(true) => {}
+            Ok(false) => {
+                self.message = Some(("Nothing staged to fix up".to_string(), Instant::now()));
+                return;
+            }
+            Err(e) => {
+                self.message = Some((format!("Fixup failed: {}", e), Instant::now()));
+                return;
+            }
+        }
+
+        let short = &commit.id[..8.min(commit.id.len())];
+        self.message = Some((match self.repository.fixup_into(&commit.id, "GitUp User", "user@gitup.app") {
+            Ok(result) if result.success => format!("Fixed up {} {}", short, commit.message),
+            Ok(result) => format!("Fixup not applied, changes left staged: {}", result.message),
+            Err(e) => format!("Fixup failed: {}", e),
+        }, Instant::now()));
+
+        let _ = self.refresh();
+        if self.show_graph {
+            let _ = self.rebuild_graph();
+        }
+    }
+
+    /// Cut the selected commit and the `count - 1` older ones below it
+    pub fn cut_selected_commits(&mut self, count: usize) {
+        let Some(i) = self.selected_commit.selected().filter(|i| *i < self.commits.len()) else {
+            return;
+        };
+        let last = (i + count.max(1) - 1).min(self.commits.len() - 1);
+        let newest = self.commits[i].id.clone();
+        let oldest = self.commits[last].id.clone();
+        let count = last - i + 1;
+        self.cut_commits = Some((oldest, newest, count));
+        self.message = Some((
+            format!("Cut {} commit(s): p pastes after the selected commit, or onto it if it is a branch tip", count),
+            Instant::now(),
+        ));
+    }
+
+    /// Move the cut commits after the selected commit, or onto the branch
+    /// whose tip is selected
+    pub fn paste_cut_commits(&mut self) {
+        let Some((oldest, newest, count)) = self.cut_commits.clone() else {
+            self.message = Some(("Nothing cut; press x on a commit first".to_string(), Instant::now()));
+            return;
+        };
+        let Some(target) = self.selected_commit.selected().and_then(|i| self.commits.get(i)).cloned() else {
+            return;
+        };
+
+        let destination = match self.branches.iter().find(|b| !b.is_remote && !b.is_head && b.commit_id == target.id) {
+            Some(branch) => MoveDestination::OntoBranch(branch.name.clone()),
+            None => MoveDestination::After(target.id.clone()),
+        };
+        match self.repository.move_commits(&oldest, &newest, &destination) {
+            Ok(result) if result.success => {
+                self.cut_commits = None;
+                self.message = Some((format!("{} (u: undo)", result.message), Instant::now()));
+            }
+            Ok(result) => {
+                self.message = Some((format!("Move stopped: {}", result.message), Instant::now()));
+            }
+            Err(e) => {
+                self.message = Some((format!("Move of {} commit(s) failed: {}", count, e), Instant::now()));
+            }
+        }
+
+        let _ = self.refresh();
+        if self.show_graph {
+            let _ = self.rebuild_graph();
+        }
+    }
+
+    /// Undo the last history rewrite
+    pub fn undo_last_rewrite(&mut self) {
+        self.message = Some((match self.repository.undo() {
+            Ok(Some(entry)) => format!("Undid: {}", entry.description),
+            Ok(None) => "Nothing to undo".to_string(),
+            Err(e) => format!("Undo failed: {}", e),
+        }, Instant::now()));
+
+        let _ = self.refresh();
+        if self.show_graph {
+            let _ = self.rebuild_graph();
+        }
+    }
+
+    /// Show a range-diff in the diff tab: `[]` compares before and after the
+    /// last rewrite, `[OLD]` compares e.g. `@{1}` with HEAD, `[OLD...NEW]`
+    /// two tips and `[OLD NEW]` two ranges
+    pub fn show_range_diff(&mut self, args: &[String]) {
+        let result = match args {
+            [] => self.repository.range_diff_last_rewrite(),
+            [tips] => match tips.split_once("...") {
+                Some((old, new)) => self.repository.range_diff_tips(old, new),
+                None => self.repository.range_diff_tips(tips, "HEAD"),
+            },
+            [old, new, ..] => self.repository.range_diff(old, new),
+        };
+        let range_diff = match result {
+            Ok(range_diff) => range_diff,
+            Err(e) => {
+                self.message = Some((format!("Range-diff failed: {}", e), Instant::now()));
+                return;
+            }
+        };
+
+        // Inner diff lines unindented so they are colored like a diff
+        let mut content = format!("range-diff {} {}\n", range_diff.old_range, range_diff.new_range);
+        for line in range_diff.to_string().lines() {
+            content.push_str(line.strip_prefix("    ").unwrap_or(line));
+            content.push('\n');
+        }
+        self.diff_content = content;
+        self.diff_emphasis = HashMap::new();
+        self.diff_line_count = self.diff_content.lines().count();
+        self.scroll_position = 0;
+        self.scroll_state = self.scroll_state
+            .position(0)
+            .content_length(self.diff_line_count);
+        self.previous_tab = Some(self.current_tab);
+        self.current_tab = 3;
+    }
+
     pub fn checkout_selected_branch(&mut self) {
         if let Some(i) = self.selected_branch.selected() {
             if let Some(branch) = self.branches.get(i) {
-                if !branch.is_remote && !branch.is_head {
+                if branch.is_remote {
+                    // Remote branch: create a local tracking branch
+                    match self.repository.checkout_remote_branch(&branch.name, None) {
+                        Ok(local) => {
+                            self.message = Some((format!("Checked out: {} (tracking {})", local, branch.name), Instant::now()));
+                            let _ = self.refresh();
+                        }
+                        Err(e) => {
+                            self.message = Some((format!("Failed to checkout: {}", e), Instant::now()));
+                        }
+                    }
+                } else if !branch.is_head {
                     if let Err(e) = self.repository.checkout_branch(&branch.name) {
                         self.message = Some((format!("Failed to checkout: {}", e), Instant::now()));
                     } else {
@@ -609,6 +973,31 @@ impl App {
         }
     }
 
+    pub fn delete_selected_branch(&mut self, force: bool) {
+        if let Some(i) = self.selected_branch.selected() {
+            if let Some(branch) = self.branches.get(i) {
+                if branch.is_remote {
+                    self.message = Some(("Cannot delete remote branches here".to_string(), Instant::now()));
+                    return;
+                }
+                match self.repository.delete_branch(&branch.name, force) {
+                    Ok(msg) => {
+                        self.message = Some((msg, Instant::now()));
+                        let _ = self.refresh();
+                        if let Some(sel) = self.selected_branch.selected() {
+                            if sel >= self.branches.len() {
+                                self.selected_branch.select(Some(self.branches.len().saturating_sub(1)));
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        self.message = Some((format!("Failed to delete: {}", e), Instant::now()));
+                    }
+                }
+            }
+        }
+    }
+
     pub fn scroll_down(&mut self, amount: u16) {
         if self.current_tab == 3 {  // Only scroll in diff tab
             let max_scroll = self.diff_line_count.saturating_sub(10) as u16; // Keep some lines visible
@@ -710,6 +1099,15 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<
             }
         }
 
+        app.process_job_events();
+        app.process_merge_risk_events();
+        if app.current_tab == 4 {
+            app.ensure_analytics();
+        }
+        if app.current_tab == 1 {
+            app.ensure_merge_risk();
+        }
+
         // Process graph events (debounced)
         while let Some(ev) = app.event_bus.try_recv() {
             match ev {
@@ -768,6 +1166,12 @@ fn handle_normal_mode(app: &mut App, key: crossterm::event::KeyEvent) {
     let count = app.count.unwrap_or(1);
 
     match key.code {
+        // Cancel running background jobs first
+        KeyCode::Esc if app.jobs.is_busy() => {
+            app.jobs.cancel_all();
+            app.message = Some(("Cancelling...".to_string(), Instant::now()));
+            return;
+        }
         // Exit graph view with Esc (when on Commits tab)
         KeyCode::Esc if app.current_tab == 0 && app.show_graph => {
             app.show_graph = false;
@@ -779,11 +1183,11 @@ fn handle_normal_mode(app: &mut App, key: crossterm::event::KeyEvent) {
             if app.current_tab > 0 {
                 app.current_tab -= 1;
             } else {
-                app.current_tab = 3;
+                app.current_tab = 4;
             }
         }
         KeyCode::Char('l') | KeyCode::Right => {
-            app.current_tab = (app.current_tab + 1) % 4;
+            app.current_tab = (app.current_tab + 1) % 5;
         }
         KeyCode::Char('j') | KeyCode::Down => {
             for _ in 0..count {
@@ -869,10 +1273,11 @@ fn handle_normal_mode(app: &mut App, key: crossterm::event::KeyEvent) {
         KeyCode::Char('2') => app.current_tab = 1,
         KeyCode::Char('3') => app.current_tab = 2,
         KeyCode::Char('4') => app.current_tab = 3,
+        KeyCode::Char('5') => app.current_tab = 4,
 
         // Additional navigation shortcuts
         KeyCode::Char('H') => app.current_tab = 0,  // Shift+H to first tab
-        KeyCode::Char('L') => app.current_tab = 3,  // Shift+L to last tab
+        KeyCode::Char('L') => app.current_tab = 4,  // Shift+L to last tab
         KeyCode::Char('M') => {
             // M - go to middle
             match app.current_tab {
@@ -957,7 +1362,19 @@ fn handle_normal_mode(app: &mut App, key: crossterm::event::KeyEvent) {
         // Git operations
         KeyCode::Char('s') if app.current_tab == 2 => app.stage_selected_file(),
         KeyCode::Char('u') if app.current_tab == 2 => app.unstage_selected_file(),
+        KeyCode::Char('I') if app.current_tab == 2 => app.ignore_selected_file(false),
+        KeyCode::Char('E') if app.current_tab == 2 => app.ignore_selected_file(true),
         KeyCode::Char('c') if app.current_tab == 1 => app.checkout_selected_branch(),
+        KeyCode::Char('F') if app.current_tab == 0 => app.fixup_selected_commit(),
+        KeyCode::Char('x') if app.current_tab == 0 => app.cut_selected_commits(count),
+        KeyCode::Char('p') if app.current_tab == 0 => app.paste_cut_commits(),
+        KeyCode::Char('u') if app.current_tab == 0 && !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.undo_last_rewrite()
+        }
+        KeyCode::Char('d') if app.current_tab == 1 && !key.modifiers.contains(KeyModifiers::CONTROL) => {
+            app.delete_selected_branch(false)
+        }
+        KeyCode::Char('D') if app.current_tab == 1 => app.delete_selected_branch(true),
         KeyCode::Enter => {
             match app.current_tab {
                 0 => {
@@ -1023,7 +1440,8 @@ fn handle_normal_mode(app: &mut App, key: crossterm::event::KeyEvent) {
             app.use_enhanced_graph = !app.use_enhanced_graph;
             if app.use_enhanced_graph && app.enhanced_graph.is_none() {
                 // Initialize enhanced graph on first use
-                if let Ok(graph) = EnhancedGraphIntegration::new(".") {
+                let repo_path = app.repository.git_dir().to_string_lossy().into_owned();
+                if let Ok(graph) = EnhancedGraphIntegration::new(&repo_path) {
                     app.enhanced_graph = Some(RefCell::new(graph));
                 }
             }
@@ -1036,11 +1454,11 @@ fn handle_normal_mode(app: &mut App, key: crossterm::event::KeyEvent) {
         KeyCode::Char('w') if app.current_tab == 0 => {
             #[cfg(feature = "watch")]
             {
-                use std::path::Path;
                 if !app.graph_watch_enabled {
                     // start watcher
                     let sender = app.event_bus.sender();
-                    let mut watcher = GitWatcher::new(Path::new("."), sender).ok();
+                    let root = app.repository.workdir().unwrap_or_else(|| app.repository.git_dir());
+                    let mut watcher = GitWatcher::new(root, sender).ok();
                     if let Some(w) = watcher.as_mut() { let _ = w.watch(); }
                     app.graph_watcher = watcher;
                     app.graph_watch_enabled = app.graph_watcher.is_some();
@@ -1201,6 +1619,15 @@ fn handle_command_mode(app: &mut App, key: crossterm::event::KeyEvent) {
         KeyCode::Backspace => {
             app.command_buffer.pop();
         }
+        KeyCode::Tab if matches!(app.command_buffer.trim_end(), "w" | "w!") => {
+            // Start the message from commit.template
+            if let Ok(Some(template)) = app.repository.message_ops().template() {
+                let subject = template.lines()
+                    .find(|line| !line.starts_with('#') && !line.trim().is_empty())
+                    .unwrap_or("");
+                app.command_buffer = format!("{} {}", app.command_buffer.trim_end(), subject.trim_end());
+            }
+        }
         KeyCode::Char(c) => {
             app.command_buffer.push(c);
         }
@@ -1208,6 +1635,62 @@ fn handle_command_mode(app: &mut App, key: crossterm::event::KeyEvent) {
     }
 }
 
+/// Commit message typed after `:w` or `:w!`, with its byte offset in the
+/// buffer and whether linting should be bypassed
+fn command_commit_message(buffer: &str) -> Option<(usize, &str, bool)> {
+    let (command, rest) = buffer.split_once(' ')?;
+    let force = match command {
+        "w" | "write" => false,
+        "w!" | "write!" => true,
+        _ => return None,
+    };
+    let message = rest.trim_start();
+    Some((buffer.len() - message.len(), message.trim_end(), force))
+}
+
+/// Command line with lint problems of a `:w` message underlined
+fn command_line(app: &App) -> Line<'static> {
+    let buffer = &app.command_buffer;
+    let Some((offset, message, false)) = command_commit_message(buffer) else {
+        return Line::from(format!(":{}", buffer));
+    };
+    let findings = lint_message(message, &app.lint_config);
+    let mut styles = vec![Style::default().fg(Color::Gray); buffer.len()];
+    for finding in &findings {
+        if let Some(span) = finding.span.filter(|span| span.line == 0) {
+            let color = if finding.is_error() { Color::Red } else { Color::Yellow };
+            for style in &mut styles[offset + span.start..offset + span.end] {
+                *style = style.fg(color).add_modifier(Modifier::UNDERLINED);
+            }
+        }
+    }
+
+    let mut spans = vec![Span::raw(":")];
+    let mut start = 0;
+    for (i, _) in buffer.char_indices().skip(1).chain([(buffer.len(), ' ')]) {
+        if i == buffer.len() || styles[i] != styles[start] {
+            spans.push(Span::styled(buffer[start..i].to_string(), styles[start]));
+            start = i;
+        }
+    }
+    if let Some(finding) = findings.first() {
+        spans.push(Span::styled(
+            format!("  {} [{}]", finding.message, finding.rule.name()),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    Line::from(spans)
+}
+
+/// Lint errors that block a commit, as one status line
+fn blocking_lint_errors(findings: &[LintFinding]) -> Option<String> {
+    let errors: Vec<&str> = findings.iter()
+        .filter(|f| f.is_error())
+        .map(|f| f.message.as_str())
+        .collect();
+    (!errors.is_empty()).then(|| format!("Commit message: {} (:w! to commit anyway)", errors.join("; ")))
+}
+
 fn handle_search_mode(app: &mut App, key: crossterm::event::KeyEvent) {
     match key.code {
         KeyCode::Esc => {
@@ -1236,10 +1719,14 @@ fn execute_command(app: &mut App) {
 
     match parts[0] {
         "q" | "quit" => app.should_quit = true,
-        "w" | "write" => {
-            // Commit staged changes
-            if parts.len() > 1 {
-                let message = parts[1..].join(" ");
+        "w" | "write" | "w!" | "write!" => {
+            // Commit staged changes, linting the message unless forced with "!"
+            if let Some((_, message, force)) = command_commit_message(&app.command_buffer) {
+                let message = message.to_string();
+                if let Some(errors) = blocking_lint_errors(&lint_message(&message, &app.lint_config)).filter(|_| !force) {
+                    app.message = Some((errors, Instant::now()));
+                    return;
+                }
                 // Use default author info for now
                 if let Err(e) = app.repository.commit(&message, "GitUp User", "user@gitup.app") {
                     app.message = Some((format!("Commit failed: {}", e), Instant::now()));
@@ -1267,6 +1754,86 @@ fn execute_command(app: &mut App) {
                 let _ = app.refresh();
             }
         }
+        "rename" if parts.len() > 2 => {
+            // Rename a branch
+            match app.repository.rename_branch(parts[1], parts[2], false) {
+                Ok(msg) => {
+                    app.message = Some((msg, Instant::now()));
+                    let _ = app.refresh();
+                }
+                Err(e) => app.message = Some((format!("Rename failed: {}", e), Instant::now())),
+            }
+        }
+        "upstream" if parts.len() > 1 => {
+            // Set upstream (":upstream branch origin/branch") or unset it (":upstream branch")
+            match app.repository.set_branch_upstream(parts[1], parts.get(2).copied()) {
+                Ok(msg) => {
+                    app.message = Some((msg, Instant::now()));
+                    let _ = app.refresh();
+                }
+                Err(e) => app.message = Some((format!("Failed to set upstream: {}", e), Instant::now())),
+            }
+        }
+        "fetch" | "pull" | "push" if app.jobs.is_busy() => {
+            app.message = Some(("Another job is running (Esc to cancel)".to_string(), Instant::now()));
+        }
+        "fetch" => {
+            // ":fetch [remote|--all] [--prune] [--tags|--no-tags]"
+            let mut remote = Some("origin".to_string());
+            let mut options = FetchOptions::default();
+            for arg in &parts[1..] {
+                match *arg {
+                    "--all" => remote = None,
+                    "--prune" | "-p" => options.prune = true,
+                    "--tags" => options.tags = TagMode::All,
+                    "--no-tags" => options.tags = TagMode::None,
+                    name => remote = Some(name.to_string()),
+                }
+            }
+            app.jobs.fetch(app.repository.git_dir().to_path_buf(), remote, options);
+        }
+        "pull" => {
+            let remote = parts.get(1).unwrap_or(&"origin").to_string();
+            let branch = match parts.get(2) {
+                Some(branch) => branch.to_string(),
+                None => app.branches.iter()
+                    .find(|b| b.is_head)
+                    .map(|b| b.name.clone())
+                    .unwrap_or_else(|| "main".to_string()),
+            };
+            app.jobs.pull(app.repository.git_dir().to_path_buf(), remote, branch);
+        }
+        "push" => {
+            let remote = parts.get(1).unwrap_or(&"origin").to_string();
+            app.jobs.push(app.repository.git_dir().to_path_buf(), remote);
+        }
+        "undo" => app.undo_last_rewrite(),
+        "range-diff" | "rd" => {
+            let args: Vec<String> = parts[1..].iter().map(|arg| arg.to_string()).collect();
+            app.show_range_diff(&args);
+        }
+        "stats" => {
+            // Recompute the stats tab
+            app.analytics = None;
+            app.current_tab = 4;
+            app.ensure_analytics();
+        }
+        "worddiff" if parts.len() > 1 => {
+            // Intra-line highlighting: ":worddiff word|char|off|<regex>"
+            let mode = match parts[1] {
+                "off" => None,
+                "word" => Some(TokenMode::Word),
+                "char" => Some(TokenMode::Char),
+                _ => Some(TokenMode::Regex(parts[1..].join(" "))),
+            };
+            match mode.clone().map(Tokenizer::new).transpose() {
+                Ok(_) => {
+                    app.word_diff = mode;
+                    app.message = Some((format!("Word diff: {}", parts[1..].join(" ")), Instant::now()));
+                }
+                Err(e) => app.message = Some((format!("Invalid word regex: {}", e), Instant::now())),
+            }
+        }
         "checkout" | "co" if parts.len() > 1 => {
             // Checkout branch
             if let Err(e) = app.repository.checkout_branch(parts[1]) {
@@ -1337,7 +1904,7 @@ fn ui(f: &mut Frame, app: &App) {
         .split(size);
 
     // Draw tabs
-    let titles = vec!["Commits", "Branches", "Status", "Diff"];
+    let titles = vec!["Commits", "Branches", "Status", "Diff", "Stats"];
     let tabs = Tabs::new(titles)
         .block(Block::default().borders(Borders::ALL).title("GitUp"))
         .select(app.current_tab)
@@ -1355,6 +1922,7 @@ fn ui(f: &mut Frame, app: &App) {
         1 => draw_branches_tab(f, app, chunks[1]),
         2 => draw_status_tab(f, app, chunks[1]),
         3 => draw_diff_tab(f, app, chunks[1]),
+        4 => draw_stats_tab(f, app, chunks[1]),
         _ => {}
     }
 
@@ -1387,7 +1955,8 @@ fn draw_commits_tab(f: &mut Frame, app: &App, area: Rect) {
             let top = app.graph_top;
             let widget = AdvancedGraphWidget::new(graph, rows)
                 .ascii(app.graph_ascii)
-                .top(top);
+                .top(top)
+                .highlight(&app.fetched_commits);
             f.render_widget(widget, area);
         } else {
             // Fallback to simple graph on failure
@@ -1397,13 +1966,30 @@ fn draw_commits_tab(f: &mut Frame, app: &App, area: Rect) {
         }
     } else {
         // Use traditional list view
+        let cut: HashSet<&str> = match &app.cut_commits {
+            Some((oldest, newest, _)) => {
+                let position = |id: &str| app.commits.iter().position(|c| c.id == id);
+                match (position(newest), position(oldest)) {
+                    (Some(start), Some(end)) => app.commits[start..=end].iter().map(|c| c.id.as_str()).collect(),
+                    _ => HashSet::new(),
+                }
+            }
+            None => HashSet::new(),
+        };
         let commits: Vec<ListItem> = app
             .commits
             .iter()
             .map(|c| {
+                let id_style = if cut.contains(c.id.as_str()) {
+                    Style::default().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+                } else if app.fetched_commits.contains(&c.id) {
+                    Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
                 let content = vec![
                     Line::from(vec![
-                        Span::styled(&c.id[..8], Style::default().fg(Color::Yellow)),
+                        Span::styled(&c.id[..8], id_style),
                         Span::raw(" - "),
                         Span::raw(&c.author),
                     ]),
@@ -1427,10 +2013,12 @@ fn draw_commits_tab(f: &mut Frame, app: &App, area: Rect) {
 }
 
 fn draw_branches_tab(f: &mut Frame, app: &App, area: Rect) {
+    let selected = app.selected_branch.selected();
     let branches: Vec<ListItem> = app
         .branches
         .iter()
-        .map(|b| {
+        .enumerate()
+        .map(|(i, b)| {
             let style = if b.is_head {
                 Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
             } else if b.is_remote {
@@ -1440,10 +2028,37 @@ fn draw_branches_tab(f: &mut Frame, app: &App, area: Rect) {
             };
 
             let prefix = if b.is_head { "* " } else { "  " };
-            ListItem::new(Line::from(vec![
+            let mut spans = vec![
                 Span::raw(prefix),
                 Span::styled(&b.name, style),
-            ]))
+            ];
+
+            // Tracking info: upstream name and divergence
+            if let Some(upstream) = &b.upstream {
+                spans.push(Span::styled(format!(" [{}", upstream), Style::default().fg(Color::Blue)));
+                if b.upstream_gone {
+                    spans.push(Span::styled(": gone", Style::default().fg(Color::Red)));
+                } else {
+                    if b.ahead > 0 {
+                        spans.push(Span::styled(format!(" ↑{}", b.ahead), Style::default().fg(Color::Green)));
+                    }
+                    if b.behind > 0 {
+                        spans.push(Span::styled(format!(" ↓{}", b.behind), Style::default().fg(Color::Yellow)));
+                    }
+                }
+                spans.push(Span::styled("]", Style::default().fg(Color::Blue)));
+            }
+
+            // Conflict risk of merging the hovered branch into HEAD
+            if selected == Some(i) {
+                if let Some(prediction) = app.merge_risk.get(&b.name) {
+                    spans.push(merge_risk_span(prediction.as_ref()));
+                } else if app.merge_risk_job.as_ref().is_some_and(|(_, name)| *name == b.name) {
+                    spans.push(Span::styled("  merge: computing…", Style::default().fg(Color::DarkGray)));
+                }
+            }
+
+            ListItem::new(Line::from(spans))
         })
         .collect();
 
@@ -1459,6 +2074,23 @@ fn draw_branches_tab(f: &mut Frame, app: &App, area: Rect) {
     f.render_stateful_widget(branches_list, area, &mut app.selected_branch.clone());
 }
 
+fn merge_risk_span(prediction: Option<&MergePrediction>) -> Span<'static> {
+    match prediction {
+        None => Span::styled("  merge: ?", Style::default().fg(Color::DarkGray)),
+        Some(p) => match p.kind {
+            MergeKind::UpToDate => Span::styled("  merge: up to date", Style::default().fg(Color::DarkGray)),
+            MergeKind::FastForward => Span::styled("  merge: fast-forward", Style::default().fg(Color::Green)),
+            MergeKind::Normal if p.is_clean() => {
+                Span::styled(format!("  merge: ✓ clean ({} files)", p.changes.len()), Style::default().fg(Color::Green))
+            }
+            MergeKind::Normal => Span::styled(
+                format!("  merge: ⚠ {} conflicts", p.conflicts.len()),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+        },
+    }
+}
+
 fn draw_status_tab(f: &mut Frame, app: &App, area: Rect) {
     let files: Vec<ListItem> = app
         .status_files
@@ -1499,6 +2131,63 @@ fn draw_status_tab(f: &mut Frame, app: &App, area: Rect) {
     f.render_stateful_widget(files_list, area, &mut app.selected_file.clone());
 }
 
+/// Running line count of the diff tab content as it grows
+#[derive(Default)]
+struct LineCounter {
+    counted: usize,
+    lines: usize,
+}
+
+impl LineCounter {
+    /// Lines in `content`, scanning only what was appended since the last call
+    fn lines(&mut self, content: &str) -> usize {
+        self.lines += content[self.counted..].matches('\n').count();
+        self.counted = content.len();
+        self.lines
+    }
+}
+
+/// Append a diff line to the diff tab content, recording its intra-line changes
+fn push_diff_line(
+    content: &mut String,
+    emphasis: &mut HashMap<usize, Vec<ChangeRange>>,
+    counter: &mut LineCounter,
+    line: &DiffLine,
+) {
+    let prefix = match line.origin {
+        gitup_core::LineOrigin::Addition => "+",
+        gitup_core::LineOrigin::Deletion => "-",
+        gitup_core::LineOrigin::Context => " ",
+    };
+    if !line.changes.is_empty() {
+        emphasis.insert(counter.lines(content), line.changes.clone());
+    }
+    content.push_str(&format!("{}{}", prefix, line.content));
+    if !line.content.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+/// Split a diff line into spans, emphasizing the changed ranges (offset by the one-char prefix)
+fn emphasized_line<'a>(line: &'a str, ranges: &[ChangeRange], color: Color) -> Line<'a> {
+    let base = Style::default().fg(color);
+    let strong = Style::default().fg(Color::Black).bg(color).add_modifier(Modifier::BOLD);
+
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    for range in ranges {
+        let (start, end) = ((range.start + 1).min(line.len()), (range.end + 1).min(line.len()));
+        if start < pos || !line.is_char_boundary(start) || !line.is_char_boundary(end) {
+            continue;
+        }
+        spans.push(Span::styled(&line[pos..start], base));
+        spans.push(Span::styled(&line[start..end], strong));
+        pos = end;
+    }
+    spans.push(Span::styled(&line[pos..], base));
+    Line::from(spans)
+}
+
 fn draw_diff_tab(f: &mut Frame, app: &App, area: Rect) {
     // Split area for content and scrollbar
     let chunks = Layout::default()
@@ -1509,11 +2198,19 @@ fn draw_diff_tab(f: &mut Frame, app: &App, area: Rect) {
     let lines: Vec<Line> = app
         .diff_content
         .lines()
-        .map(|line| {
+        .enumerate()
+        .map(|(idx, line)| {
+            let ranges = app.diff_emphasis.get(&idx);
             if line.starts_with('+') && !line.starts_with("+++") {
-                Line::from(Span::styled(line, Style::default().fg(Color::Green)))
+                match ranges {
+                    Some(ranges) => emphasized_line(line, ranges, Color::Green),
+                    None => Line::from(Span::styled(line, Style::default().fg(Color::Green))),
+                }
             } else if line.starts_with('-') && !line.starts_with("---") {
-                Line::from(Span::styled(line, Style::default().fg(Color::Red)))
+                match ranges {
+                    Some(ranges) => emphasized_line(line, ranges, Color::Red),
+                    None => Line::from(Span::styled(line, Style::default().fg(Color::Red))),
+                }
             } else if line.starts_with("@@") {
                 Line::from(Span::styled(line, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)))
             } else if line.starts_with("diff --git") {
@@ -1565,6 +2262,92 @@ fn draw_diff_tab(f: &mut Frame, app: &App, area: Rect) {
     }
 }
 
+fn draw_stats_tab(f: &mut Frame, app: &App, area: Rect) {
+    let Some(stats) = &app.analytics else {
+        let text = if app.analytics_job.is_some() { "Analyzing history..." } else { "No statistics (:stats to compute)" };
+        let paragraph = Paragraph::new(text)
+            .block(Block::default().borders(Borders::ALL).title("Statistics"));
+        f.render_widget(paragraph, area);
+        return;
+    };
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(9)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(rows[0]);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(columns[1]);
+
+    let authors: Vec<ListItem> = stats.authors.iter()
+        .map(|a| ListItem::new(Line::from(vec![
+            Span::styled(format!("{:>5} ", a.commits), Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{} ", a.name)),
+            Span::styled(format!("+{}", a.lines_added), Style::default().fg(Color::Green)),
+            Span::raw(" "),
+            Span::styled(format!("-{}", a.lines_removed), Style::default().fg(Color::Red)),
+        ])))
+        .collect();
+    f.render_widget(
+        List::new(authors).block(Block::default().borders(Borders::ALL)
+            .title(format!("Contributors ({} commits)", stats.commits))),
+        columns[0],
+    );
+
+    let churn: Vec<ListItem> = stats.churn.iter()
+        .map(|c| ListItem::new(Line::from(vec![
+            Span::styled(format!("{:>6} ", c.churn()), Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{} ", c.path)),
+            Span::styled(format!("({} commits, {} authors)", c.commits, c.authors), Style::default().fg(Color::DarkGray)),
+        ])))
+        .collect();
+    f.render_widget(
+        List::new(churn).block(Block::default().borders(Borders::ALL).title("Hotspots (lines changed)")),
+        right[0],
+    );
+
+    let coupling: Vec<ListItem> = stats.coupling.iter()
+        .map(|c| ListItem::new(Line::from(vec![
+            Span::styled(format!("{:>4.0}% ", c.degree * 100.0), Style::default().fg(Color::Yellow)),
+            Span::raw(format!("{} <-> {} ", c.file_a, c.file_b)),
+            Span::styled(format!("({} together)", c.shared_commits), Style::default().fg(Color::DarkGray)),
+        ])))
+        .collect();
+    f.render_widget(
+        List::new(coupling).block(Block::default().borders(Borders::ALL).title("Change coupling")),
+        right[1],
+    );
+
+    // Weekday x hour heatmap, shaded relative to the busiest hour
+    let max = stats.heatmap.iter().flatten().copied().max().unwrap_or(0).max(1);
+    let shades = [' ', '░', '▒', '▓', '█'];
+    let mut lines = vec![Line::from(Span::styled(
+        format!("     {}", (0..24).map(|h| format!("{:<2}", h % 10)).collect::<String>()),
+        Style::default().fg(Color::DarkGray),
+    ))];
+    for (day, hours) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().zip(&stats.heatmap) {
+        let cells: String = hours.iter()
+            .map(|&n| {
+                let shade = shades[(n * 4).div_ceil(max).min(4)];
+                format!("{}{}", shade, shade)
+            })
+            .collect();
+        lines.push(Line::from(vec![
+            Span::raw(format!("{}  ", day)),
+            Span::styled(cells, Style::default().fg(Color::Green)),
+        ]));
+    }
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title("Commits by weekday and hour")),
+        rows[1],
+    );
+}
+
 fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     // Split status bar into mode indicator and help text
     let chunks = Layout::default()
@@ -1596,29 +2379,56 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
 
     f.render_widget(mode_widget, chunks[0]);
 
+    // Progress bar for a running background job
+    if let (Some(job), VimMode::Normal) = (&app.job_status, &app.vim_mode) {
+        let ratio = job.progress.as_ref().and_then(|p| p.ratio());
+        let detail = match &job.progress {
+            Some(JobProgress::Transfer(p)) => format!(
+                " {}/{} objects, {} KiB",
+                p.received_objects,
+                p.total_objects,
+                p.received_bytes / 1024
+            ),
+            _ => String::new(),
+        };
+        let gauge = Gauge::default()
+            .block(Block::default().borders(Borders::TOP))
+            .gauge_style(Style::default().fg(Color::Cyan))
+            .ratio(ratio.unwrap_or(0.0))
+            .label(format!(
+                "{}{} {} (Esc: cancel)",
+                job.name,
+                ratio.map(|r| format!(" {:.0}%", r * 100.0)).unwrap_or_default(),
+                detail
+            ));
+        f.render_widget(gauge, chunks[1]);
+        return;
+    }
+
     // Draw command/search buffer or help text
     let text = match app.vim_mode {
-        VimMode::Command => format!(":{}", app.command_buffer),
-        VimMode::Search => format!("/{}", app.search_buffer),
+        VimMode::Command => command_line(app),
+        VimMode::Search => Line::from(format!("/{}", app.search_buffer)),
         _ => {
             if let Some((msg, _)) = &app.message {
                 msg.clone()
             } else {
                 // Vim-style help text
                 match app.current_tab {
-                    0 => "j/k: ↑↓ | Enter: view files | /: search | :: command | q: quit",
-                    1 => "j/k: ↑↓ | c/Enter: checkout | b: create branch | /: search",
+                    0 => "j/k: ↑↓ | Enter: view files | F: fixup staged | x/p: cut/paste commits | u: undo | /: search | q: quit",
+                    1 => "j/k: ↑↓ | c/Enter: checkout | d/D: delete/force | /: search",
                     2 => {
                         if app.viewing_commit.is_some() {
                             "j/k: ↑↓ | Enter: view diff | Esc: back to commits"
                         } else {
-                            "j/k: ↑↓ | s: stage | u: unstage | Enter: view diff | v: visual"
+                            "j/k: ↑↓ | s: stage | u: unstage | I/E: ignore file/extension | Enter: view diff | v: visual"
                         }
                     },
-                    3 => "j/k: scroll | gg/G: top/bottom | Ctrl-d/u: page | Esc: back | q: quit",
+                    3 => "j/k: scroll | gg/G: top/bottom | Ctrl-d/u: page | :worddiff word|char|off | :range-diff [@{1}] | Esc: back",
+                    4 => ":stats: recompute | h/l: switch tab | q: quit",
                     _ => "h/j/k/l: navigate | :: command | /: search | q: quit",
                 }.to_string()
-            }
+            }.into()
         }
     };
 
@@ -0,0 +1,141 @@
+//! History walks and ancestry queries on a synthetic repository of
+//! `COMMITS` commits, without and with a commit-graph file.
+//!
+//! Run with `cargo bench -p graph`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use git2::{Commit, Oid, Repository, Signature, Time};
+use gitup_commit_graph::{CommitGraphOps, CommitLookup};
+use graph::GitWalker;
+use std::hint::black_box;
+use tempfile::TempDir;
+
+/// Commits in the synthetic history
+const COMMITS: usize = 30_000;
+/// A side branch of `SIDE_LENGTH` commits is merged back every `MERGE_EVERY` commits
+const MERGE_EVERY: usize = 100;
+const SIDE_LENGTH: usize = 5;
+/// Commits on the long-lived topic branch, forked early from the mainline
+const TOPIC_LENGTH: usize = 200;
+/// Commits loaded by the first page of a paged walk
+const PAGE_SIZE: usize = 2000;
+
+/// Commits the ancestry queries run between
+struct Fixture {
+    _dir: TempDir,
+    path: String,
+    /// Tip of the mainline
+    head: Oid,
+    /// Tip of the topic branch
+    topic: Oid,
+    /// An early mainline commit
+    early: Oid,
+}
+
+/// Build the synthetic repository: a mainline with regular merges of short
+/// side branches, and a topic branch forked near the root
+fn build_repo() -> Fixture {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    let tree = repo.find_tree(repo.treebuilder(None).unwrap().write().unwrap()).unwrap();
+
+    let mut time = 1_600_000_000;
+    let mut commit = |message: &str, parents: &[&Commit]| -> Oid {
+        time += 60;
+        let sig = Signature::new("Bench", "bench@example.com", &Time::new(time, 0)).unwrap();
+        repo.commit(None, &sig, &sig, message, &tree, parents).unwrap()
+    };
+
+    let mut head = repo.find_commit(commit("root", &[])).unwrap();
+    let mut early = head.id();
+    let mut topic = None;
+    let mut count = 1;
+    while count < COMMITS {
+        if count % MERGE_EVERY == 0 {
+            let mut side = head.clone();
+            for i in 0..SIDE_LENGTH {
+                side = repo.find_commit(commit(&format!("side {count} {i}"), &[&side])).unwrap();
+            }
+            head = repo.find_commit(commit(&format!("merge {count}"), &[&head, &side])).unwrap();
+            count += SIDE_LENGTH + 1;
+        } else {
+            head = repo.find_commit(commit(&format!("main {count}"), &[&head])).unwrap();
+            count += 1;
+        }
+
+        if topic.is_none() && count >= COMMITS / 20 {
+            early = head.id();
+            let mut tip = head.clone();
+            for i in 0..TOPIC_LENGTH {
+                tip = repo.find_commit(commit(&format!("topic {i}"), &[&tip])).unwrap();
+            }
+            count += TOPIC_LENGTH;
+            topic = Some(tip.id());
+        }
+    }
+
+    repo.reference("refs/heads/main", head.id(), true, "bench").unwrap();
+    repo.reference("refs/heads/topic", topic.unwrap(), true, "bench").unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+
+    Fixture {
+        path: dir.path().to_str().unwrap().to_string(),
+        _dir: dir,
+        head: head.id(),
+        topic: topic.unwrap(),
+        early,
+    }
+}
+
+/// Write or remove the commit-graph file
+fn set_commit_graph(path: &str, enabled: bool) {
+    let repo = Repository::open(path).unwrap();
+    let ops = CommitGraphOps::new(&repo);
+    if enabled {
+        ops.write().unwrap();
+    } else if ops.path().exists() {
+        std::fs::remove_file(ops.path()).unwrap();
+    }
+}
+
+fn bench_commit_graph(c: &mut Criterion) {
+    let fixture = build_repo();
+
+    for (label, enabled) in [("without commit-graph", false), ("with commit-graph", true)] {
+        set_commit_graph(&fixture.path, enabled);
+        let walker = GitWalker::new(Some(&fixture.path)).unwrap();
+        let repo = Repository::open(&fixture.path).unwrap();
+
+        let mut group = c.benchmark_group("walk");
+        group.sample_size(10);
+        group.bench_function(format!("into_dag {label}"), |b| {
+            b.iter(|| black_box(walker.into_dag(None).unwrap()))
+        });
+        group.bench_function(format!("paged_dag first page {label}"), |b| {
+            b.iter(|| {
+                let (mut dag, mut pager) = walker.paged_dag(PAGE_SIZE).unwrap();
+                black_box(walker.next_page(&mut pager, &mut dag).unwrap())
+            })
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("ancestry");
+        group.sample_size(20);
+        group.bench_function(format!("is_descendant_of {label}"), |b| {
+            b.iter(|| {
+                let lookup = CommitLookup::new(&repo);
+                black_box(lookup.is_descendant_of(fixture.early, fixture.head).unwrap())
+            })
+        });
+        group.bench_function(format!("ahead_behind {label}"), |b| {
+            b.iter(|| {
+                let lookup = CommitLookup::new(&repo);
+                black_box(lookup.ahead_behind(fixture.topic, fixture.head).unwrap())
+            })
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_commit_graph);
+criterion_main!(benches);
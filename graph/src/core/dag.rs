@@ -1,5 +1,5 @@
 use super::{node::CommitNode, edge::Edge};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Directed Acyclic Graph representing commit history
 #[derive(Debug, Clone)]
@@ -10,6 +10,27 @@ pub struct Dag {
     pub edges: Vec<Edge>,
     /// Quick lookup: commit ID -> children IDs
     pub children: HashMap<String, Vec<String>>,
+    /// Ref tips the history was walked from, sorted
+    pub tips: Vec<String>,
+    /// Parents of loaded commits that are not loaded themselves yet
+    pub pending: HashSet<String>,
+}
+
+/// What an incremental update changed in a DAG
+#[derive(Debug, Clone, Default)]
+pub struct DagUpdate {
+    /// Commits walked from the new tips, newest first
+    pub added: Vec<String>,
+    /// Commits no tip reaches any more
+    pub removed: HashSet<String>,
+    /// The history could not be patched and was walked again from scratch
+    pub rebuilt: bool,
+}
+
+impl DagUpdate {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && !self.rebuilt
+    }
 }
 
 impl Dag {
@@ -18,6 +39,8 @@ impl Dag {
             nodes: HashMap::new(),
             edges: Vec::new(),
             children: HashMap::new(),
+            tips: Vec::new(),
+            pending: HashSet::new(),
         }
     }
 
@@ -34,6 +57,10 @@ impl Dag {
             };
             self.edges.push(edge);
 
+            if !self.nodes.contains_key(parent_id) {
+                self.pending.insert(parent_id.clone());
+            }
+
             // Update children map
             self.children
                 .entry(parent_id.clone())
@@ -41,9 +68,43 @@ impl Dag {
                 .push(id.clone());
         }
 
+        self.pending.remove(&id);
         self.nodes.insert(id, node);
     }
 
+    /// Remove commits along with their edges
+    pub fn remove_nodes(&mut self, ids: &HashSet<String>) {
+        if ids.is_empty() {
+            return;
+        }
+
+        for id in ids {
+            if let Some(node) = self.nodes.remove(id) {
+                for parent_id in &node.parents {
+                    if let Some(children) = self.children.get_mut(parent_id) {
+                        children.retain(|child| child != id);
+                        if children.is_empty() {
+                            self.children.remove(parent_id);
+                            self.pending.remove(parent_id);
+                        }
+                    }
+                }
+            }
+            self.children.remove(id);
+        }
+        self.edges.retain(|edge| !ids.contains(&edge.from));
+    }
+
+    /// Check if a commit is in the DAG
+    pub fn contains(&self, commit_id: &str) -> bool {
+        self.nodes.contains_key(commit_id)
+    }
+
+    /// Check if a commit is referenced as a parent but not loaded yet
+    pub fn is_pending(&self, commit_id: &str) -> bool {
+        self.pending.contains(commit_id)
+    }
+
     /// Get all root commits (no parents)
     pub fn roots(&self) -> Vec<&CommitNode> {
         self.nodes
@@ -4,4 +4,4 @@ pub mod dag;
 
 pub use node::CommitNode;
 pub use edge::{Edge, EdgeType};
-pub use dag::{Dag, DagStats};
\ No newline at end of file
+pub use dag::{Dag, DagStats, DagUpdate};
\ No newline at end of file
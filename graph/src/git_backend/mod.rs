@@ -1,3 +1,3 @@
 pub mod walker;
 
-pub use walker::GitWalker;
\ No newline at end of file
+pub use walker::{GitWalker, HistoryPager};
\ No newline at end of file
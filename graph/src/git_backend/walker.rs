@@ -1,16 +1,104 @@
 use git2::{Repository, Sort, Commit, Oid, Signature};
 use chrono::{Utc, TimeZone};
 use anyhow::{Result, Context};
-use crate::core::{Dag, CommitNode};
+use crate::core::{Dag, DagUpdate, CommitNode};
+use gitup_commit_graph::CommitLookup;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 
 pub struct GitWalker {
     repo: Repository,
 }
 
+/// A history walk that loads commits a page at a time
+///
+/// Commits come newest first by committer date. With a commit-graph, its
+/// generation numbers keep every parent below all of its children. Without
+/// one a parent only follows the child it was reached through: as with
+/// `git log` without `--topo-order`, another child with a skewed older date
+/// can still come after it.
+pub struct HistoryPager {
+    page_size: usize,
+    /// Commits ready to load, by the date they are due
+    frontier: BinaryHeap<(i64, Oid)>,
+    /// Everything ever pushed to the frontier
+    queued: HashSet<Oid>,
+    /// Child counts from generation numbers, when there is a commit-graph
+    topo: Option<TopoWalk>,
+    loaded: usize,
+}
+
+/// Counts the children of commits ahead of loading them, so a commit is
+/// ready once its last child is loaded
+///
+/// Generation numbers bound the counting: every child of a commit has a
+/// higher generation, so exploring down to a commit's generation finds all
+/// of them.
+#[derive(Default)]
+struct TopoWalk {
+    /// Commits whose parents are not counted yet, highest generation first
+    unexplored: BinaryHeap<(u32, Oid)>,
+    seen: HashSet<Oid>,
+    /// Children of each explored commit that are not loaded yet
+    indegree: HashMap<Oid, u32>,
+}
+
+impl TopoWalk {
+    fn add(&mut self, lookup: &CommitLookup, oid: Oid) {
+        if self.seen.insert(oid) {
+            self.unexplored.push((lookup.generation(oid), oid));
+        }
+    }
+
+    /// Count the children of every commit down to `generation`
+    fn explore_to(&mut self, lookup: &CommitLookup, generation: u32) -> Result<()> {
+        while let Some(&(next, oid)) = self.unexplored.peek() {
+            if next < generation {
+                break;
+            }
+            self.unexplored.pop();
+            for parent in lookup.get(oid)?.parents {
+                *self.indegree.entry(parent).or_default() += 1;
+                self.add(lookup, parent);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HistoryPager {
+    /// Check if there are commits left to load
+    pub fn has_more(&self) -> bool {
+        !self.frontier.is_empty()
+    }
+
+    /// Number of commits loaded so far
+    pub fn loaded(&self) -> usize {
+        self.loaded
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Whether `oid` was reached by the walk, loaded or not
+    fn reached(&self, oid: Oid) -> bool {
+        match &self.topo {
+            Some(topo) => topo.seen.contains(&oid),
+            None => self.queued.contains(&oid),
+        }
+    }
+
+    fn queue(&mut self, oid: Oid, due: i64) {
+        if self.queued.insert(oid) {
+            self.frontier.push((due, oid));
+        }
+    }
+}
+
 impl GitWalker {
     pub fn new(repo_path: Option<&str>) -> Result<Self> {
         let repo = match repo_path {
-            Some(path) => Repository::open(path),
+            Some(path) => Repository::discover(path),
             None => Repository::open_from_env(),
         }.context("Failed to open repository")?;
 
@@ -19,16 +107,20 @@ impl GitWalker {
 
     /// Convert git repository commits to DAG
     pub fn into_dag(&self, limit: Option<usize>) -> Result<Dag> {
+        // The commit-graph gives the order without walking all of history first
+        if CommitLookup::new(&self.repo).graph().is_some() {
+            let (mut dag, mut pager) = self.paged_dag(limit.unwrap_or(usize::MAX))?;
+            self.next_page(&mut pager, &mut dag)?;
+            return Ok(dag);
+        }
+
         let mut dag = Dag::new();
         let mut revwalk = self.repo.revwalk()?;
 
         // Start from HEAD and all branches
-        revwalk.push_head()?;
-        for branch in self.repo.branches(None)? {
-            let (branch, _) = branch?;
-            if let Some(target) = branch.get().target() {
-                revwalk.push(target)?;
-            }
+        let tips = self.tips()?;
+        for tip in &tips {
+            revwalk.push(Oid::from_str(tip)?)?;
         }
 
         // Sort by topological order and time
@@ -50,9 +142,214 @@ impl GitWalker {
             }
         }
 
+        dag.tips = tips;
         Ok(dag)
     }
 
+    /// Start a paged walk from HEAD and all branches
+    ///
+    /// The returned DAG is empty until `next_page` loads commits into it.
+    pub fn paged_dag(&self, page_size: usize) -> Result<(Dag, HistoryPager)> {
+        let mut dag = Dag::new();
+        let lookup = CommitLookup::new(&self.repo);
+        let mut pager = HistoryPager {
+            page_size: page_size.max(1),
+            frontier: BinaryHeap::new(),
+            queued: HashSet::new(),
+            topo: lookup.graph().map(|_| TopoWalk::default()),
+            loaded: 0,
+        };
+
+        dag.tips = self.tips()?;
+        let tips = dag.tips.iter()
+            .map(|tip| Oid::from_str(tip))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        match &mut pager.topo {
+            Some(topo) => {
+                // A tip can be an ancestor of another tip
+                for &tip in &tips {
+                    topo.add(&lookup, tip);
+                }
+                let lowest = tips.iter().map(|&tip| lookup.generation(tip)).min().unwrap_or(0);
+                topo.explore_to(&lookup, lowest)?;
+                for &tip in &tips {
+                    if !topo.indegree.contains_key(&tip) {
+                        pager.frontier.push((lookup.get(tip)?.time, tip));
+                    }
+                }
+            }
+            None => {
+                for &tip in &tips {
+                    pager.queue(tip, lookup.get(tip)?.time);
+                }
+            }
+        }
+
+        Ok((dag, pager))
+    }
+
+    /// Load the next page of a paged walk into `dag`
+    ///
+    /// Returns the loaded commit ids, newest first. Parents outside the
+    /// loaded commits are left in `Dag::pending` until a later page.
+    pub fn next_page(&self, pager: &mut HistoryPager, dag: &mut Dag) -> Result<Vec<String>> {
+        let lookup = CommitLookup::new(&self.repo);
+        let mut page = Vec::new();
+
+        while page.len() < pager.page_size {
+            let Some((due, oid)) = pager.frontier.pop() else {
+                break;
+            };
+            let commit = self.repo.find_commit(oid)?;
+            match &mut pager.topo {
+                Some(topo) => {
+                    for parent in commit.parent_ids() {
+                        topo.explore_to(&lookup, lookup.generation(parent))?;
+                        let children = topo.indegree.entry(parent).or_default();
+                        *children = children.saturating_sub(1);
+                        if *children == 0 {
+                            topo.indegree.remove(&parent);
+                            pager.frontier.push((lookup.get(parent)?.time, parent));
+                        }
+                    }
+                }
+                None => {
+                    for parent in commit.parents() {
+                        pager.queue(parent.id(), parent.time().seconds().min(due));
+                    }
+                }
+            }
+
+            let node = self.commit_to_node(&commit)?;
+            if !dag.contains(&node.id) {
+                page.push(node.id.clone());
+                dag.add_node(node);
+            }
+        }
+
+        pager.loaded += page.len();
+        Ok(page)
+    }
+
+    /// HEAD and branch tips, the starting points of the walk
+    pub fn tips(&self) -> Result<Vec<String>> {
+        let head = self.repo.head()?.peel_to_commit()?.id();
+        let mut tips = vec![head.to_string()];
+        for branch in self.repo.branches(None)? {
+            let (branch, _) = branch?;
+            if let Some(target) = branch.get().target() {
+                tips.push(target.to_string());
+            }
+        }
+        tips.sort();
+        tips.dedup();
+        Ok(tips)
+    }
+
+    /// Bring a DAG from `into_dag(None)` up to date with the current tips
+    ///
+    /// Only commits between the old and new tips are walked: new commits
+    /// are added and commits no tip reaches any more are removed. If an old
+    /// tip is gone from the object database the whole history is walked again.
+    pub fn update_dag(&self, dag: &mut Dag) -> Result<DagUpdate> {
+        self.patch_dag(dag, None)
+    }
+
+    /// Bring a DAG from `paged_dag` up to date with the current tips
+    ///
+    /// Like `update_dag`, but when the history has to be walked again the
+    /// paged walk restarts: `pager` is replaced and only its first page is
+    /// loaded. This also happens when commits no tip reaches any more are
+    /// still waiting in the pager, which would otherwise load them later.
+    pub fn update_paged_dag(&self, dag: &mut Dag, pager: &mut HistoryPager) -> Result<DagUpdate> {
+        self.patch_dag(dag, Some(pager))
+    }
+
+    /// Patch `dag` to the current tips, walking the history again if it cannot be patched
+    fn patch_dag(&self, dag: &mut Dag, pager: Option<&mut HistoryPager>) -> Result<DagUpdate> {
+        let tips = self.tips()?;
+        let mut update = DagUpdate::default();
+        if tips == dag.tips {
+            return Ok(update);
+        }
+
+        let mut old_tips = Vec::new();
+        for tip in &dag.tips {
+            match Oid::from_str(tip).ok().filter(|oid| self.repo.find_commit(*oid).is_ok()) {
+                Some(oid) => old_tips.push(oid),
+                None => return self.rebuild_dag(dag, pager),
+            }
+        }
+        let new_tips = tips.iter()
+            .map(|tip| Oid::from_str(tip))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        // Reachable from a dropped tip but from none of the new ones
+        let dropped: Vec<Oid> = old_tips.iter()
+            .filter(|oid| !tips.contains(&oid.to_string()))
+            .copied()
+            .collect();
+        if !dropped.is_empty() {
+            let mut revwalk = self.repo.revwalk()?;
+            for oid in &dropped {
+                revwalk.push(*oid)?;
+            }
+            for oid in &new_tips {
+                revwalk.hide(*oid)?;
+            }
+            for oid in revwalk {
+                let oid = oid?;
+                let id = oid.to_string();
+                if dag.contains(&id) {
+                    update.removed.insert(id);
+                } else if pager.as_ref().is_some_and(|pager| pager.reached(oid)) {
+                    return self.rebuild_dag(dag, pager);
+                }
+            }
+        }
+
+        // Reachable from a new tip but from none of the old ones
+        let mut revwalk = self.repo.revwalk()?;
+        for oid in &new_tips {
+            revwalk.push(*oid)?;
+        }
+        for oid in &old_tips {
+            revwalk.hide(*oid)?;
+        }
+        revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        for oid in revwalk {
+            let commit = self.repo.find_commit(oid?)?;
+            if !dag.contains(&commit.id().to_string()) {
+                let node = self.commit_to_node(&commit)?;
+                update.added.push(node.id.clone());
+                dag.add_node(node);
+            }
+        }
+
+        dag.remove_nodes(&update.removed);
+        dag.tips = tips;
+        Ok(update)
+    }
+
+    /// Walk the history again from the current tips; a paged walk restarts with its first page
+    fn rebuild_dag(&self, dag: &mut Dag, pager: Option<&mut HistoryPager>) -> Result<DagUpdate> {
+        match pager {
+            Some(pager) => {
+                let (fresh, fresh_pager) = self.paged_dag(pager.page_size())?;
+                *dag = fresh;
+                *pager = fresh_pager;
+                self.next_page(pager, dag)?;
+            }
+            None => *dag = self.into_dag(None)?,
+        }
+        Ok(DagUpdate {
+            added: dag.nodes.keys().cloned().collect(),
+            rebuilt: true,
+            ..Default::default()
+        })
+    }
+
     /// Convert a git2::Commit to CommitNode
     fn commit_to_node(&self, commit: &Commit) -> Result<CommitNode> {
         let id = commit.id().to_string();
@@ -113,6 +410,7 @@ impl GitWalker {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
     use tempfile::TempDir;
 
     fn create_test_repo() -> Result<(TempDir, Repository)> {
@@ -221,4 +519,160 @@ mod tests {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_update_dag_matches_full_walk() -> Result<()> {
+        let (_dir, repo) = create_test_repo()?;
+
+        let base_oid = commit_to_repo(&repo, "Base commit", &[], Some("HEAD"))?;
+        let base_commit = repo.find_commit(base_oid)?;
+        let side_oid = commit_to_repo(&repo, "Side", &[&base_commit], None)?;
+        repo.branch("side", &repo.find_commit(side_oid)?, false)?;
+
+        let walker = GitWalker::new(Some(repo.path().to_str().unwrap()))?;
+        let mut dag = walker.into_dag(None)?;
+        assert!(walker.update_dag(&mut dag)?.is_empty());
+
+        // New commits on HEAD, and the side branch reset back to base
+        let next_oid = commit_to_repo(&repo, "Next", &[&base_commit], Some("HEAD"))?;
+        commit_to_repo(&repo, "Last", &[&repo.find_commit(next_oid)?], Some("HEAD"))?;
+        repo.branch("side", &base_commit, true)?;
+
+        let update = walker.update_dag(&mut dag)?;
+        assert_eq!(update.added.len(), 2);
+        assert_eq!(update.removed, HashSet::from([side_oid.to_string()]));
+        assert!(!update.rebuilt);
+
+        let full = walker.into_dag(None)?;
+        let ids = |dag: &Dag| dag.nodes.keys().cloned().collect::<HashSet<_>>();
+        let edges = |dag: &Dag| dag.edges.iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(ids(&dag), ids(&full));
+        assert_eq!(edges(&dag), edges(&full));
+        assert_eq!(dag.tips, full.tips);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_paged_walk_matches_full_walk() -> Result<()> {
+        let (_dir, repo) = create_test_repo()?;
+
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let commit_at = |seconds: i64, message: &str, parents: &[&Commit], update_ref: Option<&str>| -> Result<Commit> {
+            let sig = Signature::new("Test User", "test@example.com", &git2::Time::new(seconds, 0))?;
+            let oid = repo.commit(update_ref, &sig, &sig, message, &tree, parents)?;
+            Ok(repo.find_commit(oid)?)
+        };
+
+        // A long mainline and a newer side branch off the base commit
+        let fork = commit_at(1_000, "Base commit", &[], Some("HEAD"))?;
+        let mut parent = fork.clone();
+        for i in 0..6 {
+            parent = commit_at(2_000 + i, &format!("Main {i}"), &[&parent], Some("HEAD"))?;
+        }
+        let side = commit_at(3_000, "Side", &[&fork], None)?;
+        repo.branch("side", &side, false)?;
+
+        let walker = GitWalker::new(Some(repo.path().to_str().unwrap()))?;
+        let (mut dag, mut pager) = walker.paged_dag(3)?;
+        assert_eq!(dag.node_count(), 0);
+
+        let first = walker.next_page(&mut pager, &mut dag)?;
+        assert_eq!(first.len(), 3);
+        assert!(pager.has_more());
+        assert!(!dag.pending.is_empty());
+
+        let mut order = first;
+        while pager.has_more() {
+            order.extend(walker.next_page(&mut pager, &mut dag)?);
+        }
+        assert_eq!(pager.loaded(), 8);
+        assert!(dag.pending.is_empty());
+
+        // Children always come before their parents
+        for (idx, id) in order.iter().enumerate() {
+            for parent in &dag.nodes[id].parents {
+                assert!(order[idx + 1..].contains(parent));
+            }
+        }
+
+        let full = walker.into_dag(None)?;
+        let ids = |dag: &Dag| dag.nodes.keys().cloned().collect::<HashSet<_>>();
+        assert_eq!(ids(&dag), ids(&full));
+        assert_eq!(dag.tips, full.tips);
+
+        // A tip gone from the object database restarts the paged walk
+        let (mut dag, mut pager) = walker.paged_dag(3)?;
+        walker.next_page(&mut pager, &mut dag)?;
+        dag.tips.push("f".repeat(40));
+        let update = walker.update_paged_dag(&mut dag, &mut pager)?;
+        assert!(update.rebuilt);
+        assert_eq!((update.added.len(), pager.loaded()), (3, 3));
+        assert_eq!(walker.next_page(&mut pager, &mut dag)?.len(), 3);
+        assert!(pager.has_more());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_paged_update_drops_unloaded_history_of_deleted_branch() -> Result<()> {
+        for with_graph in [false, true] {
+            let (_dir, repo) = create_test_repo()?;
+            let main = repo.find_commit(commit_to_repo(&repo, "Main", &[], Some("HEAD"))?)?;
+            let mut parent = main.clone();
+            for i in 0..6 {
+                parent = repo.find_commit(commit_to_repo(&repo, &format!("Feature {i}"), &[&parent], None)?)?;
+            }
+            repo.branch("feature", &parent, false)?;
+            if with_graph {
+                gitup_commit_graph::CommitGraphOps::new(&repo).write()?;
+            }
+
+            let walker = GitWalker::new(Some(repo.path().to_str().unwrap()))?;
+            let (mut dag, mut pager) = walker.paged_dag(2)?;
+            walker.next_page(&mut pager, &mut dag)?;
+            repo.find_branch("feature", git2::BranchType::Local)?.delete()?;
+
+            let update = walker.update_paged_dag(&mut dag, &mut pager)?;
+            assert!(update.rebuilt);
+            while pager.has_more() {
+                walker.next_page(&mut pager, &mut dag)?;
+            }
+            assert_eq!(dag.nodes.len(), walker.into_dag(None)?.nodes.len());
+            assert_eq!(dag.nodes.len(), 1, "with commit-graph: {}", with_graph);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_paged_walk_with_commit_graph_is_topological() -> Result<()> {
+        let (_dir, repo) = create_test_repo()?;
+
+        // Same-second commits: only generation numbers can order these
+        let base = repo.find_commit(commit_to_repo(&repo, "Base commit", &[], Some("HEAD"))?)?;
+        let mut parent = base.clone();
+        for i in 0..6 {
+            parent = repo.find_commit(commit_to_repo(&repo, &format!("Main {i}"), &[&parent], Some("HEAD"))?)?;
+        }
+        let side = repo.find_commit(commit_to_repo(&repo, "Side", &[&base], None)?)?;
+        repo.branch("side", &side, false)?;
+        gitup_commit_graph::CommitGraphOps::new(&repo).write()?;
+
+        let walker = GitWalker::new(Some(repo.path().to_str().unwrap()))?;
+        let (mut dag, mut pager) = walker.paged_dag(2)?;
+        let mut order = Vec::new();
+        while pager.has_more() {
+            order.extend(walker.next_page(&mut pager, &mut dag)?);
+        }
+
+        assert_eq!(order.len(), 8);
+        for (idx, id) in order.iter().enumerate() {
+            for parent in &dag.nodes[id].parents {
+                assert!(order[idx + 1..].contains(parent));
+            }
+        }
+        assert_eq!(walker.into_dag(Some(3))?.node_count(), 3);
+
+        Ok(())
+    }
+}
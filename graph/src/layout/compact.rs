@@ -14,6 +14,8 @@ pub struct CompactRowBuilder {
     reserved_lanes: HashSet<LaneIdx>,
     /// Lane lifecycle tracking
     lane_ends: HashMap<String, usize>, // commit_id -> row where lane ends
+    /// Index of the next row to build
+    next_row: usize,
 }
 
 impl CompactRowBuilder {
@@ -27,6 +29,7 @@ impl CompactRowBuilder {
             active_lanes: HashMap::new(),
             reserved_lanes: HashSet::new(),
             lane_ends: HashMap::new(),
+            next_row: 0,
         }
     }
 
@@ -41,12 +44,37 @@ impl CompactRowBuilder {
         let mut sorted_commits: Vec<&CommitNode> = dag.nodes.values().collect();
         sorted_commits.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
 
-        for (row_idx, commit) in sorted_commits.iter().enumerate() {
+        for commit in sorted_commits {
             // Free lanes that are no longer needed
-            self.free_expired_lanes(row_idx);
+            self.free_expired_lanes(self.next_row);
 
-            let row = self.build_compact_row(commit, dag, row_idx);
+            let row = self.build_compact_row(commit, dag, self.next_row);
             rows.push(row);
+            self.next_row += 1;
+        }
+
+        rows
+    }
+
+    /// Lay out the next page of commits below the rows built so far
+    ///
+    /// `ids` must be newest first, as `GitWalker::next_page` returns them.
+    /// Lanes allocated for pending parents carry on into the new rows.
+    pub fn extend_rows(&mut self, dag: &Dag, ids: &[String]) -> Vec<Row> {
+        let commits: Vec<&CommitNode> = ids.iter().filter_map(|id| dag.nodes.get(id)).collect();
+
+        // Only the new leaves need lifetimes; older rows are already laid out
+        for commit in &commits {
+            if dag.get_children(&commit.id).is_empty() {
+                self.lane_ends.insert(commit.id.clone(), self.next_row);
+            }
+        }
+
+        let mut rows = Vec::with_capacity(commits.len());
+        for commit in commits {
+            self.free_expired_lanes(self.next_row);
+            rows.push(self.build_compact_row(commit, dag, self.next_row));
+            self.next_row += 1;
         }
 
         rows
@@ -366,4 +394,34 @@ mod tests {
         assert!(lanes_used.len() <= 5, "Linear history should use minimal lanes, got: {}", lanes_used.len());
         assert_eq!(rows.len(), 5, "Should have 5 commits");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_paged_layout_continues_lanes() {
+        let full = create_forked_dag();
+        let pages = [vec!["main2", "branch2", "main1"], vec!["branch1", "root"]];
+        let key = |rows: Vec<Row>| rows.into_iter()
+            .map(|r| (r.commit_id, r.primary_lane, r.lanes))
+            .collect::<Vec<_>>();
+
+        let all: Vec<String> = pages.concat().into_iter().map(String::from).collect();
+        let expected = key(CompactRowBuilder::new(5).extend_rows(&full, &all));
+
+        // Load the same commits a page at a time, leaving parents pending
+        let mut dag = Dag::new();
+        let mut builder = CompactRowBuilder::new(5);
+        let mut rows = Vec::new();
+        for page in &pages {
+            let ids: Vec<String> = page.iter().map(|id| id.to_string()).collect();
+            for id in &ids {
+                dag.add_node(full.nodes[id].clone());
+            }
+            rows.extend(builder.extend_rows(&dag, &ids));
+            if ids[0] == "main2" {
+                assert!(dag.is_pending("branch1") && dag.is_pending("root"));
+            }
+        }
+
+        assert!(dag.pending.is_empty());
+        assert_eq!(key(rows), expected);
+    }
+}
@@ -83,6 +83,17 @@ impl RowBuilder {
         rows
     }
 
+    /// Lay out the next page of commits below the rows built so far
+    ///
+    /// `ids` must be newest first, as `GitWalker::next_page` returns them.
+    /// Lanes reserved for pending parents carry on into the new rows.
+    pub fn extend_rows(&mut self, dag: &Dag, ids: &[String]) -> Vec<Row> {
+        ids.iter()
+            .filter_map(|id| dag.nodes.get(id))
+            .map(|commit| self.build_row(commit, dag))
+            .collect()
+    }
+
     /// Build a single row
     fn build_row(&mut self, commit: &CommitNode, dag: &Dag) -> Row {
         // Assign lane for this commit
@@ -317,4 +328,26 @@ mod tests {
         assert!(rows.iter().any(|r| r.commit_id == "b1"));
         assert!(rows.iter().any(|r| r.commit_id == "b2"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_paged_layout_continues_lanes() {
+        let full = create_test_dag();
+        let key = |rows: Vec<Row>| rows.into_iter()
+            .map(|r| (r.commit_id, r.primary_lane, r.lanes))
+            .collect::<Vec<_>>();
+        let all = ["ccc", "bbb", "aaa"].map(String::from);
+        let expected = key(RowBuilder::new(10).extend_rows(&full, &all));
+
+        let mut dag = Dag::new();
+        let mut builder = RowBuilder::new(10);
+        dag.add_node(full.nodes["ccc"].clone());
+        dag.add_node(full.nodes["bbb"].clone());
+        let mut rows = builder.extend_rows(&dag, &all[..2]);
+        assert!(dag.is_pending("aaa"));
+
+        // The pending parent keeps its lane into the next page
+        dag.add_node(full.nodes["aaa"].clone());
+        rows.extend(builder.extend_rows(&dag, &all[2..]));
+        assert_eq!(key(rows), expected);
+    }
+}
@@ -1,126 +1,362 @@
-use crate::core::{Dag, CommitNode};
+use crate::core::{Dag, DagUpdate, CommitNode};
 use crate::layout::{Row, Lane, LaneIdx};
-use std::collections::HashMap;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Rows between stored lane states
+const CHECKPOINT_INTERVAL: usize = 256;
+
+/// Lane state just before a row is laid out
+#[derive(Debug, Clone, PartialEq)]
+struct LaneState {
+    active_lanes: Vec<Option<String>>,
+    /// Lanes reserved for parents not laid out yet, sorted
+    reserved: Vec<(String, LaneIdx)>,
+}
 
 /// Simple graph builder that focuses on continuous lines
 pub struct SimpleGraphBuilder {
     max_lanes: usize,
+    /// Lane states of the last layout, every `CHECKPOINT_INTERVAL` rows, by commit
+    checkpoints: HashMap<String, LaneState>,
+    /// State after the last row, where the next page continues
+    tail: LayoutPass,
+    row_count: usize,
+}
+
+/// One pass laying out rows top to bottom
+struct LayoutPass {
+    max_lanes: usize,
+    /// Track which lanes are actively used
+    active_lanes: Vec<Option<String>>,
+    commit_lanes: HashMap<String, LaneIdx>,
+    /// Entries of `commit_lanes` for commits not laid out yet
+    pending: HashMap<String, LaneIdx>,
+}
+
+impl LayoutPass {
+    fn new(max_lanes: usize) -> Self {
+        Self {
+            max_lanes,
+            active_lanes: vec![None; max_lanes],
+            commit_lanes: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Whether the lane state equals `state`, without building a `LaneState`
+    fn matches(&self, state: &LaneState) -> bool {
+        self.active_lanes == state.active_lanes
+            && self.pending.len() == state.reserved.len()
+            && state.reserved.iter().all(|(id, lane)| self.pending.get(id) == Some(lane))
+    }
+
+    fn state(&self) -> LaneState {
+        let mut reserved: Vec<(String, LaneIdx)> = self.pending.iter()
+            .map(|(id, &lane)| (id.clone(), lane))
+            .collect();
+        reserved.sort();
+        LaneState { active_lanes: self.active_lanes.clone(), reserved }
+    }
+
+    fn row(&mut self, commit: &CommitNode) -> Row {
+        let active_lanes = &mut self.active_lanes;
+        let commit_lanes = &mut self.commit_lanes;
+
+        // Find or allocate a lane for this commit
+        let primary_lane = if let Some(existing_lane) = commit_lanes.get(&commit.id) {
+            *existing_lane
+        } else {
+            // Find first free lane
+            let lane = active_lanes
+                .iter()
+                .position(|l| l.is_none())
+                .unwrap_or(0);
+            commit_lanes.insert(commit.id.clone(), lane);
+            lane
+        };
+        self.pending.remove(&commit.id);
+
+        // Create the lanes array for this row
+        let mut lanes = vec![Lane::Empty; self.max_lanes];
+
+        // First, mark all lanes that have active commits passing through
+        for (lane_idx, active_commit) in active_lanes.iter().enumerate() {
+            if let Some(active_id) = active_commit {
+                if active_id != &commit.id {
+                    // This lane has a different commit, draw a line through
+                    lanes[lane_idx] = Lane::Pass;
+                }
+            }
+        }
+
+        // Set the current commit
+        lanes[primary_lane] = Lane::Commit;
+        active_lanes[primary_lane] = Some(commit.id.clone());
+
+        // Reserve lanes for parents
+        for (i, parent_id) in commit.parents.iter().enumerate() {
+            if !commit_lanes.contains_key(parent_id) {
+                // Allocate a lane for this parent
+                if i == 0 && active_lanes[primary_lane] == Some(commit.id.clone()) {
+                    // First parent inherits the same lane
+                    commit_lanes.insert(parent_id.clone(), primary_lane);
+                    self.pending.insert(parent_id.clone(), primary_lane);
+                } else {
+                    // Other parents get new lanes
+                    if let Some(free_lane) = active_lanes.iter().position(|l| l.is_none()) {
+                        commit_lanes.insert(parent_id.clone(), free_lane);
+                        self.pending.insert(parent_id.clone(), free_lane);
+                        active_lanes[free_lane] = Some(parent_id.clone());
+                        lanes[free_lane] = Lane::Pass;
+                    }
+                }
+            }
+        }
+
+        // Handle merge visualization
+        if commit.parents.len() > 1 {
+            let mut merge_targets = Vec::new();
+            for parent_id in &commit.parents[1..] {
+                if let Some(&parent_lane) = commit_lanes.get(parent_id) {
+                    if parent_lane != primary_lane {
+                        merge_targets.push(parent_lane);
+                        // Make sure the merge target lane is marked
+                        if lanes[parent_lane] == Lane::Empty {
+                            lanes[parent_lane] = Lane::Pass;
+                        }
+                    }
+                }
+            }
+            if !merge_targets.is_empty() {
+                lanes[primary_lane] = Lane::Merge(merge_targets);
+            }
+        }
+
+        // After processing, update active lanes for parent continuity
+        if commit.parents.len() == 1 {
+            // Single parent continues in the same lane
+            active_lanes[primary_lane] = Some(commit.parents[0].clone());
+        } else if commit.parents.is_empty() {
+            // No parents, free the lane
+            active_lanes[primary_lane] = None;
+        }
+        // For merge commits, the lane continues with first parent
+        else if !commit.parents.is_empty() {
+            active_lanes[primary_lane] = Some(commit.parents[0].clone());
+        }
+
+        Row {
+            commit_id: commit.id.clone(),
+            commit: commit.clone(),
+            lanes,
+            primary_lane,
+        }
+    }
+}
+
+/// A row of the updated layout: kept from the last one, or a new commit
+enum Slot<'a> {
+    Old(usize),
+    New(&'a CommitNode),
+}
+
+/// Whether `a` comes before `b` in date order, ties broken by the larger id
+fn newer(a: &CommitNode, b: &CommitNode) -> bool {
+    (a.timestamp, &a.id) > (b.timestamp, &b.id)
+}
+
+/// `ids` in the order rows are laid out: the walker's date order
+///
+/// Newest first by commit time, ties broken by the larger id, but a commit
+/// only once all of its children among `ids` are placed, so a parent with a
+/// skewed newer date still comes below them.
+fn date_order<'a>(dag: &'a Dag, ids: impl IntoIterator<Item = &'a String>) -> Vec<&'a CommitNode> {
+    let commits: HashMap<&str, &CommitNode> = ids.into_iter()
+        .filter_map(|id| dag.nodes.get_key_value(id))
+        .map(|(id, commit)| (id.as_str(), commit))
+        .collect();
+    let mut children: HashMap<&str, usize> = commits.keys()
+        .map(|&id| {
+            let count = dag.children.get(id)
+                .map_or(0, |kids| kids.iter().filter(|kid| commits.contains_key(kid.as_str())).count());
+            (id, count)
+        })
+        .collect();
+
+    let mut ready: BinaryHeap<_> = commits.values()
+        .filter(|commit| children[commit.id.as_str()] == 0)
+        .map(|commit| (commit.timestamp, commit.id.as_str()))
+        .collect();
+    let mut order = Vec::with_capacity(commits.len());
+    while let Some((_, id)) = ready.pop() {
+        let commit = commits[id];
+        order.push(commit);
+        for parent in &commit.parents {
+            if let Some(count) = children.get_mut(parent.as_str()) {
+                *count -= 1;
+                if *count == 0 {
+                    let parent = commits[parent.as_str()];
+                    ready.push((parent.timestamp, parent.id.as_str()));
+                }
+            }
+        }
+    }
+    order
 }
 
 impl SimpleGraphBuilder {
     pub fn new(max_lanes: usize) -> Self {
-        Self { max_lanes }
+        Self {
+            max_lanes,
+            checkpoints: HashMap::new(),
+            tail: LayoutPass::new(max_lanes),
+            row_count: 0,
+        }
     }
 
     pub fn build_rows(&mut self, dag: &Dag) -> Vec<Row> {
         let mut rows = Vec::new();
+        self.checkpoints.clear();
+        self.tail = LayoutPass::new(self.max_lanes);
+        self.row_count = 0;
 
-        // Sort commits by timestamp (newest first)
-        let mut commits: Vec<&CommitNode> = dag.nodes.values().collect();
-        commits.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        let commits = date_order(dag, dag.nodes.keys());
 
         if commits.is_empty() {
             return rows;
         }
 
-        // Track which lanes are actively used
-        let mut active_lanes: Vec<Option<String>> = vec![None; self.max_lanes];
-        let mut commit_lanes: HashMap<String, LaneIdx> = HashMap::new();
-
         // Build each row
-        for commit in &commits {
-            // Find or allocate a lane for this commit
-            let primary_lane = if let Some(existing_lane) = commit_lanes.get(&commit.id) {
-                *existing_lane
-            } else {
-                // Find first free lane
-                let lane = active_lanes
-                    .iter()
-                    .position(|l| l.is_none())
-                    .unwrap_or(0);
-                commit_lanes.insert(commit.id.clone(), lane);
-                lane
-            };
+        for commit in commits {
+            rows.push(self.next_row(commit));
+        }
 
-            // Create the lanes array for this row
-            let mut lanes = vec![Lane::Empty; self.max_lanes];
+        rows
+    }
 
-            // First, mark all lanes that have active commits passing through
-            for (lane_idx, active_commit) in active_lanes.iter().enumerate() {
-                if let Some(active_id) = active_commit {
-                    if active_id != &commit.id {
-                        // This lane has a different commit, draw a line through
-                        lanes[lane_idx] = Lane::Pass;
+    /// Lay out the next page of commits below the rows built so far
+    ///
+    /// `ids` are the commits `GitWalker::next_page` loaded; they are laid out
+    /// in date order like `build_rows`. Lanes reserved for pending parents
+    /// carry on into the new rows.
+    pub fn extend_rows(&mut self, dag: &Dag, ids: &[String]) -> Vec<Row> {
+        date_order(dag, ids)
+            .into_iter()
+            .map(|commit| self.next_row(commit))
+            .collect()
+    }
+
+    fn next_row(&mut self, commit: &CommitNode) -> Row {
+        if self.row_count.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.checkpoints.insert(commit.id.clone(), self.tail.state());
+        }
+        self.row_count += 1;
+        self.tail.row(commit)
+    }
+
+    /// Patch the rows of the last layout after `Dag` changes
+    ///
+    /// Rows are laid out again from the top only until the lane state
+    /// matches a checkpoint of the last layout below every change; the
+    /// rest of the old rows are kept as they are.
+    pub fn update_rows(&mut self, dag: &Dag, mut rows: Vec<Row>, update: &DagUpdate) -> Vec<Row> {
+        if update.is_empty() {
+            return rows;
+        }
+        if update.rebuilt || self.checkpoints.is_empty() {
+            return self.build_rows(dag);
+        }
+
+        let added = date_order(dag, &update.added);
+        let added_ids: HashSet<&str> = added.iter().map(|commit| commit.id.as_str()).collect();
+
+        // Surviving old rows a new commit must stay below: its children among them
+        let mut waiting: HashMap<&str, usize> = added.iter()
+            .filter_map(|commit| {
+                let count = dag.children.get(&commit.id)?.iter()
+                    .filter(|kid| dag.nodes.contains_key(*kid) && !added_ids.contains(kid.as_str()))
+                    .count();
+                (count > 0).then_some((commit.id.as_str(), count))
+            })
+            .collect();
+
+        // Merge the new commits into the surviving old rows
+        let mut slots = Vec::with_capacity(rows.len() + added.len());
+        let mut added = added.into_iter().peekable();
+        for (idx, row) in rows.iter().enumerate() {
+            if update.removed.contains(&row.commit_id) {
+                continue;
+            }
+            while let Some(commit) = added.next_if(|c| !waiting.contains_key(c.id.as_str()) && newer(c, &row.commit)) {
+                slots.push(Slot::New(commit));
+            }
+            slots.push(Slot::Old(idx));
+            for parent in &row.commit.parents {
+                if let Some(count) = waiting.get_mut(parent.as_str()) {
+                    *count -= 1;
+                    if *count == 0 {
+                        waiting.remove(parent.as_str());
                     }
                 }
             }
+        }
+        slots.extend(added.map(Slot::New));
 
-            // Set the current commit
-            lanes[primary_lane] = Lane::Commit;
-            active_lanes[primary_lane] = Some(commit.id.clone());
-
-            // Reserve lanes for parents
-            for (i, parent_id) in commit.parents.iter().enumerate() {
-                if !commit_lanes.contains_key(parent_id) {
-                    // Allocate a lane for this parent
-                    if i == 0 && active_lanes[primary_lane] == Some(commit.id.clone()) {
-                        // First parent inherits the same lane
-                        commit_lanes.insert(parent_id.clone(), primary_lane);
-                    } else {
-                        // Other parents get new lanes
-                        if let Some(free_lane) = active_lanes.iter().position(|l| l.is_none()) {
-                            commit_lanes.insert(parent_id.clone(), free_lane);
-                            active_lanes[free_lane] = Some(parent_id.clone());
-                            lanes[free_lane] = Lane::Pass;
-                        }
-                    }
+        // Below `clean_from`, slots are the old rows to the end, unchanged and in order
+        let mut clean_from = slots.len();
+        let mut expected = rows.len();
+        while clean_from > 0 {
+            match slots[clean_from - 1] {
+                Slot::Old(idx) if idx + 1 == expected => {
+                    clean_from -= 1;
+                    expected = idx;
                 }
+                _ => break,
             }
+        }
 
-            // Handle merge visualization
-            if commit.parents.len() > 1 {
-                let mut merge_targets = Vec::new();
-                for parent_id in &commit.parents[1..] {
-                    if let Some(&parent_lane) = commit_lanes.get(parent_id) {
-                        if parent_lane != primary_lane {
-                            merge_targets.push(parent_lane);
-                            // Make sure the merge target lane is marked
-                            if lanes[parent_lane] == Lane::Empty {
-                                lanes[parent_lane] = Lane::Pass;
-                            }
-                        }
+        let mut new_rows = Vec::with_capacity(slots.len());
+        let mut fresh = HashMap::new();
+        let mut pass = LayoutPass::new(self.max_lanes);
+        let mut splice_at = None;
+
+        for (slot_idx, slot) in slots.iter().enumerate() {
+            let commit = match slot {
+                Slot::Old(idx) => {
+                    let id = &rows[*idx].commit_id;
+                    if slot_idx >= clean_from && self.checkpoints.get(id).is_some_and(|state| pass.matches(state)) {
+                        splice_at = Some(*idx);
+                        break;
+                    }
+                    match dag.nodes.get(id) {
+                        Some(commit) => commit,
+                        None => continue,
                     }
                 }
-                if !merge_targets.is_empty() {
-                    lanes[primary_lane] = Lane::Merge(merge_targets);
-                }
+                Slot::New(commit) => *commit,
+            };
+            if slot_idx.is_multiple_of(CHECKPOINT_INTERVAL) {
+                fresh.insert(commit.id.clone(), pass.state());
             }
+            new_rows.push(pass.row(commit));
+        }
 
-
-            rows.push(Row {
-                commit_id: commit.id.clone(),
-                commit: (*commit).clone(),
-                lanes,
-                primary_lane,
-            });
-
-            // After processing, update active lanes for parent continuity
-            if commit.parents.len() == 1 {
-                // Single parent continues in the same lane
-                active_lanes[primary_lane] = Some(commit.parents[0].clone());
-            } else if commit.parents.is_empty() {
-                // No parents, free the lane
-                active_lanes[primary_lane] = None;
+        match splice_at {
+            Some(idx) => {
+                for row in &rows[..idx] {
+                    self.checkpoints.remove(&row.commit_id);
+                }
+                new_rows.extend(rows.split_off(idx));
             }
-            // For merge commits, the lane continues with first parent
-            else if !commit.parents.is_empty() {
-                active_lanes[primary_lane] = Some(commit.parents[0].clone());
+            None => {
+                self.checkpoints.clear();
+                self.tail = pass;
             }
         }
-
-        rows
+        self.checkpoints.extend(fresh);
+        self.row_count = new_rows.len();
+        new_rows
     }
-
 }
 
 #[cfg(test)]
@@ -168,4 +404,83 @@ mod tests {
             assert_eq!(row.primary_lane, 0);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_update_rows_matches_full_build() {
+        use chrono::{Duration, TimeZone};
+        use std::collections::HashSet;
+
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let node = |id: usize, parents: Vec<usize>| CommitNode::new(
+            format!("c{id}"),
+            parents.into_iter().map(|p| format!("c{p}")).collect(),
+            start + Duration::minutes(id as i64),
+            "Author".to_string(),
+            format!("Commit {id}"),
+        );
+
+        // Mainline with an extra merge parent every 50 commits
+        let mut dag = Dag::new();
+        dag.add_node(node(0, vec![]));
+        for id in 1..1000 {
+            let parents = if id % 50 == 10 { vec![id - 1, id - 9] } else { vec![id - 1] };
+            dag.add_node(node(id, parents));
+        }
+
+        let mut builder = SimpleGraphBuilder::new(8);
+        let rows = builder.build_rows(&dag);
+
+        // Drop the newest commit and add three on top of its parent
+        let removed = HashSet::from(["c999".to_string()]);
+        dag.remove_nodes(&removed);
+        let mut update = DagUpdate { removed, ..Default::default() };
+        for (id, parent) in [(1000, 998), (1001, 1000), (1002, 990)] {
+            dag.add_node(node(id, vec![parent]));
+            update.added.push(format!("c{id}"));
+        }
+
+        let key = |rows: &[Row]| rows.iter()
+            .map(|r| (r.commit_id.clone(), r.primary_lane, r.lanes.clone()))
+            .collect::<Vec<_>>();
+        let updated = builder.update_rows(&dag, rows, &update);
+        let full = SimpleGraphBuilder::new(8).build_rows(&dag);
+        assert_eq!(key(&updated), key(&full));
+    }
+
+    #[test]
+    fn test_skewed_parent_stays_below_children() {
+        use chrono::{Duration, TimeZone};
+
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let node = |id: &str, parents: &[&str], minutes: i64| CommitNode::new(
+            id.to_string(),
+            parents.iter().map(|p| p.to_string()).collect(),
+            start + Duration::minutes(minutes),
+            "Author".to_string(),
+            format!("Commit {id}"),
+        );
+
+        // c2 was committed with a clock ahead of its child c3
+        let mut dag = Dag::new();
+        dag.add_node(node("c1", &[], 0));
+        dag.add_node(node("c2", &["c1"], 60));
+        dag.add_node(node("c3", &["c2"], 10));
+
+        let rows = SimpleGraphBuilder::new(4).build_rows(&dag);
+        let ids: Vec<&str> = rows.iter().map(|r| r.commit_id.as_str()).collect();
+        assert_eq!(ids, ["c3", "c2", "c1"]);
+        assert!(rows.iter().all(|r| r.primary_lane == 0));
+
+        // The same when the skewed parent arrives in an update below its child
+        let mut dag = Dag::new();
+        dag.add_node(node("c3", &["c2"], 10));
+        let mut builder = SimpleGraphBuilder::new(4);
+        let rows = builder.build_rows(&dag);
+        dag.add_node(node("c2", &["c1"], 60));
+        dag.add_node(node("c1", &[], 0));
+        let update = DagUpdate { added: vec!["c2".to_string(), "c1".to_string()], ..Default::default() };
+        let rows = builder.update_rows(&dag, rows, &update);
+        let ids: Vec<&str> = rows.iter().map(|r| r.commit_id.as_str()).collect();
+        assert_eq!(ids, ["c3", "c2", "c1"]);
+    }
+}
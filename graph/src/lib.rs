@@ -3,8 +3,8 @@ pub mod git_backend;
 pub mod layout;
 pub mod render;
 
-pub use core::{Dag, CommitNode, Edge, EdgeType, DagStats};
-pub use git_backend::GitWalker;
+pub use core::{Dag, CommitNode, Edge, EdgeType, DagStats, DagUpdate};
+pub use git_backend::{GitWalker, HistoryPager};
 pub use layout::{Row, RowBuilder, Lane, LaneIdx, CompactRowBuilder, SimpleGraphBuilder};
 pub use render::{
     TuiRenderer, AsciiRenderer, Cell, Color,
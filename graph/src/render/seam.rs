@@ -382,6 +382,8 @@ pub struct SeamlessViewport {
     pub total_rows: usize,
     /// Carry-over state from row above viewport
     pub carry_over: Option<ViewportCarryOver>,
+    /// More history exists below the loaded rows
+    pub more_history: bool,
 }
 
 impl SeamlessViewport {
@@ -392,9 +394,17 @@ impl SeamlessViewport {
             cursor: 0,
             total_rows,
             carry_over: None,
+            more_history: false,
         }
     }
 
+    /// Check if the next page of history should be loaded
+    ///
+    /// True once the viewport is within a screen of the last loaded row.
+    pub fn wants_more(&self) -> bool {
+        self.more_history && self.top + 2 * self.height >= self.total_rows
+    }
+
     /// Update carry-over when viewport moves
     pub fn update_carry_over(&mut self, rows: &[crate::layout::Row], width: usize) {
         if self.top > 0 && self.top - 1 < rows.len() {
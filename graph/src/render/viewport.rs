@@ -14,6 +14,8 @@ pub struct Viewport {
     pub cursor: usize,
     /// Total number of rows
     pub total_rows: usize,
+    /// More history exists below the loaded rows
+    pub more_history: bool,
 }
 
 impl Viewport {
@@ -23,9 +25,23 @@ impl Viewport {
             height,
             cursor: 0,
             total_rows,
+            more_history: false,
         }
     }
 
+    /// Check if the next page of history should be loaded
+    ///
+    /// True once the viewport is within a screen of the last loaded row.
+    pub fn wants_more(&self) -> bool {
+        self.more_history && self.top + 2 * self.height >= self.total_rows
+    }
+
+    /// Account for rows appended by a page of history
+    pub fn extend(&mut self, total_rows: usize, more_history: bool) {
+        self.total_rows = total_rows;
+        self.more_history = more_history;
+    }
+
     /// Get the visible range of rows
     pub fn visible_range(&self) -> (usize, usize) {
         let start = self.top;
@@ -200,6 +216,9 @@ impl VirtualRenderer {
             self.viewport.total_rows,
             self.viewport.progress()
         ));
+        if self.viewport.wants_more() {
+            output.push_str(" | loading more history...");
+        }
 
         output
     }
@@ -221,6 +240,12 @@ impl VirtualRenderer {
         true
     }
 
+    /// Append a page of rows laid out below the current ones
+    pub fn append_rows(&mut self, rows: Vec<Row>, more_history: bool) {
+        self.rows.extend(rows);
+        self.viewport.extend(self.rows.len(), more_history);
+    }
+
     /// Get current viewport
     pub fn viewport(&self) -> &Viewport {
         &self.viewport
@@ -304,4 +329,18 @@ mod tests {
         let lines: Vec<_> = output.lines().collect();
         assert!(lines.len() <= 12); // 10 rows + status line + empty line
     }
+
+    #[test]
+    fn test_wants_more_history() {
+        let mut renderer = VirtualRenderer::new(create_test_rows(30), 10, 5, CharsetProfile::Utf8Straight);
+        assert!(!renderer.viewport().wants_more());
+
+        renderer.append_rows(create_test_rows(5), true);
+        assert_eq!(renderer.viewport().total_rows, 35);
+        assert!(!renderer.viewport().wants_more());
+
+        renderer.handle_input('G');
+        assert!(renderer.viewport().wants_more());
+        assert!(renderer.render().contains("loading more history"));
+    }
 }
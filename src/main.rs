@@ -1,6 +1,10 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use gitup_core::{Repository, FileStatus, ConflictResolution};
+use gitup_core::{
+    Repository, FileStatus, ConflictResolution, IgnoreFile, MergeKind, StatusEntryKind,
+    AnalyticsOptions, TimeBucket, CommitMetadata, FixupKind, PickOptions, IdentityRewrite, MoveDestination, SplitPart, SplitSelection, add_trailer, clean_message, sign_off, CloneOptions, CloneProgress, CloneProgressCallback, FetchOptions, RefUpdate, RefUpdateKind, TagMode, TagSort, DescribeOptions,
+    DiffConfig, DiffAlgorithm, WhitespaceMode, BinaryMode, TokenMode, Tokenizer, WordOpKind,
+};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -25,11 +29,42 @@ enum Commands {
         #[arg(default_value = ".")]
         path: PathBuf,
     },
+    /// Clone a repository
+    Clone {
+        /// URL or path of the repository to clone
+        url: String,
+        /// Directory to clone into (defaults to the repository name)
+        directory: Option<PathBuf>,
+        /// Check out this branch or tag instead of the remote's HEAD
+        #[arg(short, long)]
+        branch: Option<String>,
+        /// Create a shallow clone with this many commits of history
+        #[arg(long)]
+        depth: Option<u32>,
+        /// Create a bare repository
+        #[arg(long)]
+        bare: bool,
+        /// Create a bare mirror of all remote refs
+        #[arg(long)]
+        mirror: bool,
+        /// Also clone submodules, recursively
+        #[arg(long)]
+        recurse_submodules: bool,
+        /// Do not report progress
+        #[arg(short, long)]
+        quiet: bool,
+    },
     /// Show repository status
     Status {
         /// Path to the repository
         #[arg(default_value = ".")]
         path: PathBuf,
+        /// Machine-readable output in a porcelain v2 like format
+        #[arg(long)]
+        porcelain: bool,
+        /// Also show ignored files
+        #[arg(long)]
+        ignored: bool,
     },
     /// List branches
     Branches {
@@ -37,6 +72,14 @@ enum Commands {
         #[arg(default_value = ".")]
         path: PathBuf,
     },
+    /// Manage branches
+    Branch {
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        #[command(subcommand)]
+        command: BranchCommands,
+    },
     /// Show recent commits
     Log {
         /// Path to the repository
@@ -45,6 +88,36 @@ enum Commands {
         /// Number of commits to show
         #[arg(short, long, default_value = "10")]
         count: usize,
+        /// Show notes from the configured notes ref, or from REF
+        #[arg(long, value_name = "REF", num_args = 0..=1, default_missing_value = "")]
+        notes: Option<String>,
+    },
+    /// Show contributor, churn, coupling and activity statistics
+    Stats {
+        /// Path to the repository
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// Analyze at most this many commits
+        #[arg(short = 'n', long)]
+        max_count: Option<usize>,
+        /// Only commits on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        /// Only commits before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<chrono::NaiveDate>,
+        /// Include merge commits
+        #[arg(long)]
+        merges: bool,
+        /// Granularity of per-author activity
+        #[arg(long, value_enum, default_value = "month")]
+        period: PeriodArg,
+        /// Number of hotspots and coupled file pairs to show
+        #[arg(long, default_value = "10")]
+        top: usize,
+        /// Print the statistics as JSON
+        #[arg(long)]
+        json: bool,
     },
     /// Show diff
     Diff {
@@ -65,6 +138,48 @@ enum Commands {
         /// Show statistics only
         #[arg(long)]
         stat: bool,
+        /// Number of context lines
+        #[arg(short = 'U', long = "unified", default_value_t = 3)]
+        context: u32,
+        /// Merge hunks separated by up to this many lines
+        #[arg(long = "inter-hunk-context", default_value_t = 0)]
+        inter_hunk_context: u32,
+        /// Ignore all whitespace
+        #[arg(short = 'w', long = "ignore-all-space")]
+        ignore_all_space: bool,
+        /// Ignore changes in the amount of whitespace
+        #[arg(short = 'b', long = "ignore-space-change")]
+        ignore_space_change: bool,
+        /// Ignore whitespace at end of line
+        #[arg(long = "ignore-space-at-eol")]
+        ignore_space_at_eol: bool,
+        /// Line diff algorithm
+        #[arg(long = "diff-algorithm", value_enum, default_value = "myers")]
+        algorithm: DiffAlgorithmArg,
+        /// Rename similarity threshold in percent
+        #[arg(short = 'M', long = "find-renames", default_value_t = 50)]
+        rename_threshold: u16,
+        /// Disable rename detection
+        #[arg(long = "no-renames")]
+        no_renames: bool,
+        /// Detect copies, with the given similarity threshold in percent
+        #[arg(short = 'C', long = "find-copies", num_args = 0..=1, default_missing_value = "50")]
+        copy_threshold: Option<u16>,
+        /// Treat all files as text
+        #[arg(short = 'a', long)]
+        text: bool,
+        /// Treat all files as binary
+        #[arg(long, conflicts_with = "text")]
+        binary: bool,
+        /// Show changed words inline as [-removed-]{+added+}
+        #[arg(long = "word-diff", value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "word")]
+        word_diff: Option<WordDiffArg>,
+        /// Regex that defines a word for --word-diff (implies --word-diff)
+        #[arg(long = "word-diff-regex")]
+        word_diff_regex: Option<String>,
+        /// Limit the diff to these paths
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
     /// Stage files
     Stage {
@@ -93,9 +208,9 @@ enum Commands {
         /// Path to the repository
         #[arg(default_value = ".")]
         path: PathBuf,
-        /// Commit message
+        /// Commit message (opens the editor with commit.template when omitted)
         #[arg(short, long)]
-        message: String,
+        message: Option<String>,
         /// Author name
         #[arg(long)]
         author: Option<String>,
@@ -105,6 +220,24 @@ enum Commands {
         /// Amend the last commit
         #[arg(long)]
         amend: bool,
+        /// Add a Signed-off-by trailer
+        #[arg(short, long)]
+        signoff: bool,
+        /// Add a trailer, e.g. --trailer "Refs=ABC-123"
+        #[arg(long, value_name = "KEY=VALUE")]
+        trailer: Vec<String>,
+        /// Add a Co-authored-by trailer, e.g. --co-author "Name <email>"
+        #[arg(long, value_name = "NAME <EMAIL>")]
+        co_author: Vec<String>,
+        /// Commit even if the message fails linting
+        #[arg(short = 'n', long)]
+        no_verify: bool,
+        /// Make a fixup! commit for COMMIT; "amend:COMMIT" also replaces its message
+        #[arg(long, value_name = "COMMIT", conflicts_with_all = ["squash", "amend"])]
+        fixup: Option<String>,
+        /// Make a squash! commit for COMMIT
+        #[arg(long, value_name = "COMMIT", conflicts_with = "amend")]
+        squash: Option<String>,
     },
     /// Launch Terminal UI
     Tui {
@@ -128,6 +261,27 @@ enum Commands {
         /// Path to the repository
         #[arg(short = 'p', long, default_value = ".")]
         path: PathBuf,
+        /// Fetch all remotes
+        #[arg(long)]
+        all: bool,
+        /// Remove remote-tracking branches that no longer exist on the remote
+        #[arg(long)]
+        prune: bool,
+        /// Fetch all tags
+        #[arg(long, conflicts_with = "no_tags")]
+        tags: bool,
+        /// Do not fetch tags
+        #[arg(long)]
+        no_tags: bool,
+        /// Limit fetched history to this many commits
+        #[arg(long, conflicts_with = "unshallow")]
+        depth: Option<u32>,
+        /// Fetch the complete history of a shallow repository
+        #[arg(long)]
+        unshallow: bool,
+        /// Update the commit-graph file after fetching
+        #[arg(long)]
+        write_commit_graph: bool,
     },
     /// Pull from remote
     Pull {
@@ -184,6 +338,182 @@ enum Commands {
         #[command(subcommand)]
         command: RebaseCommands,
     },
+    /// Split a commit into several commits
+    Split {
+        /// Commit to split
+        commit: String,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Message of each new commit, in order
+        #[arg(short, long)]
+        message: Vec<String>,
+        /// Changes of each new commit: comma separated files, or FILE#N for the N-th hunk
+        #[arg(short, long)]
+        files: Vec<String>,
+        /// List the commit's files and numbered hunks instead of splitting
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Move commits to another position in the current branch or onto another branch
+    Move {
+        /// Oldest commit to move
+        first: String,
+        /// Newest commit to move (defaults to FIRST)
+        last: Option<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Put the commits right after this commit
+        #[arg(long, conflicts_with = "onto", required_unless_present = "onto")]
+        after: Option<String>,
+        /// Put the commits on top of this branch
+        #[arg(long)]
+        onto: Option<String>,
+    },
+    /// Rewrite the message, author or committer of a commit anywhere in history
+    Rewrite {
+        /// Commit to rewrite
+        commit: Option<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// New commit message
+        #[arg(short, long)]
+        message: Option<String>,
+        /// New author, as "Name <email>"
+        #[arg(long)]
+        author: Option<String>,
+        /// New author date (RFC 3339 or Unix time)
+        #[arg(long)]
+        author_date: Option<String>,
+        /// New committer, as "Name <email>"
+        #[arg(long)]
+        committer: Option<String>,
+        /// New committer date (RFC 3339 or Unix time)
+        #[arg(long)]
+        committer_date: Option<String>,
+        /// Rewrite every commit by this email instead of COMMIT, using --author for the new identity
+        #[arg(long, conflicts_with_all = ["commit", "message", "author_date", "committer", "committer_date"], requires = "author")]
+        old_email: Option<String>,
+        /// With --old-email, only rewrite commits after this one
+        #[arg(long, requires = "old_email")]
+        upstream: Option<String>,
+        /// With --old-email, also rewrite matching committers
+        #[arg(long, requires = "old_email")]
+        include_committer: bool,
+    },
+    /// Read and write notes on commits
+    Notes {
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Notes ref to use instead of core.notesRef, e.g. "ci" for refs/notes/ci
+        #[arg(long = "ref", global = true)]
+        notes_ref: Option<String>,
+        #[command(subcommand)]
+        command: NotesCommands,
+    },
+    /// Name a commit after the nearest tag reachable from it
+    Describe {
+        /// Commit to describe (default: HEAD)
+        commit: Option<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Use lightweight tags too, not only annotated ones
+        #[arg(long)]
+        tags: bool,
+        /// Only use tags matching this glob
+        #[arg(long = "match", value_name = "PATTERN")]
+        pattern: Option<String>,
+        /// Hex digits of the abbreviated commit id
+        #[arg(long, default_value_t = 7)]
+        abbrev: usize,
+        /// Always show the distance and commit id, even on a tagged commit
+        #[arg(long)]
+        long: bool,
+        /// Show the abbreviated commit id when no tag can describe it
+        #[arg(long)]
+        always: bool,
+        /// Append a suffix (default "-dirty") when the working tree has changes
+        #[arg(long, num_args = 0..=1, default_missing_value = "-dirty", value_name = "SUFFIX")]
+        dirty: Option<String>,
+        /// Tags considered before picking the closest one
+        #[arg(long, default_value_t = 10)]
+        candidates: usize,
+    },
+    /// Write or inspect the commit-graph file used for fast history walks
+    CommitGraph {
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        #[command(subcommand)]
+        command: CommitGraphCommands,
+    },
+    /// Add paths or patterns to an ignore file, or list ignored files
+    Ignore {
+        /// Paths to ignore, relative to the repository root
+        paths: Vec<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Ignore every file with the same extension instead of the path itself
+        #[arg(short, long)]
+        extension: bool,
+        /// Add a pattern as written (repeatable)
+        #[arg(long = "pattern", value_name = "PATTERN")]
+        patterns: Vec<String>,
+        /// Ignore file to write to
+        #[arg(long, value_enum, default_value = "nearest")]
+        to: IgnoreFileArg,
+        /// List ignored files instead
+        #[arg(short, long, conflicts_with_all = ["paths", "patterns"])]
+        list: bool,
+    },
+    /// Show whether paths are ignored and by which rule
+    CheckIgnore {
+        /// Paths to check, relative to the repository root
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Show the ignore file, line and pattern of the deciding rule
+        #[arg(short, long)]
+        verbose: bool,
+        /// Also show paths no rule ignores (with --verbose)
+        #[arg(short, long)]
+        non_matching: bool,
+        /// Check tracked paths too, instead of skipping them
+        #[arg(long)]
+        no_index: bool,
+    },
+    /// Compare two versions of a patch series
+    RangeDiff {
+        /// Old range (A..B), or OLD...NEW to compare two tips from their merge base
+        old: Option<String>,
+        /// New range (A..B)
+        new: Option<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        /// Compare before and after the last undoable rewrite instead
+        #[arg(long, conflicts_with_all = ["old", "new"])]
+        last_rewrite: bool,
+        /// Only list the pairs, without the diffs of modified commits
+        #[arg(short, long)]
+        summary: bool,
+    },
+    /// Undo the last history rewrite (move, split, autosquash, ...)
+    Undo {
+        /// Path to the repository
+        #[arg(default_value = ".")]
+        path: PathBuf,
+        /// List undoable operations instead
+        #[arg(short, long)]
+        list: bool,
+    },
     /// Cherry-pick commits
     CherryPick {
         /// Path to the repository
@@ -192,6 +522,106 @@ enum Commands {
         #[command(subcommand)]
         command: CherryPickCommands,
     },
+    /// Revert commits, in the order given; continue or abort with `cherry-pick`
+    Revert {
+        /// Commits to revert
+        #[arg(required = true)]
+        commits: Vec<String>,
+        /// Path to the repository
+        #[arg(short = 'p', long, default_value = ".")]
+        path: PathBuf,
+        #[command(flatten)]
+        options: PickArgs,
+    },
+}
+
+/// Options shared by cherry-pick and revert
+#[derive(clap::Args)]
+struct PickArgs {
+    /// Parent number (from 1) to compare merge commits against
+    #[arg(short, long)]
+    mainline: Option<u32>,
+    /// Stage the changes without committing
+    #[arg(short, long)]
+    no_commit: bool,
+    /// Add a Signed-off-by trailer
+    #[arg(short, long)]
+    signoff: bool,
+}
+
+impl PickArgs {
+    fn to_options(&self, record_origin: bool) -> PickOptions {
+        PickOptions {
+            mainline: self.mainline,
+            record_origin,
+            no_commit: self.no_commit,
+            signoff: self.signoff,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum CommitGraphCommands {
+    /// Write a commit-graph of every commit reachable from a ref
+    Write,
+    /// Write the commit-graph only if a ref points at a commit it lacks
+    Update,
+    /// Show the commit-graph files, or what it records about one commit
+    Info {
+        commit: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NotesCommands {
+    /// List notes and the commits they belong to
+    List,
+    /// Show the note on a commit
+    Show {
+        #[arg(default_value = "HEAD")]
+        commit: String,
+    },
+    /// Add a note to a commit
+    Add {
+        #[arg(short, long)]
+        message: String,
+        #[arg(default_value = "HEAD")]
+        commit: String,
+        /// Replace an existing note
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Replace the note on a commit (opens the editor without -m)
+    Edit {
+        #[arg(short, long)]
+        message: Option<String>,
+        #[arg(default_value = "HEAD")]
+        commit: String,
+    },
+    /// Add a paragraph to the note on a commit
+    Append {
+        #[arg(short, long)]
+        message: String,
+        #[arg(default_value = "HEAD")]
+        commit: String,
+    },
+    /// Remove the note on a commit
+    Remove {
+        #[arg(default_value = "HEAD")]
+        commit: String,
+    },
+    /// Push the notes ref to a remote
+    Push {
+        #[arg(default_value = "origin")]
+        remote: String,
+    },
+    /// Fetch the notes ref (or all notes refs with --all) from a remote
+    Fetch {
+        #[arg(default_value = "origin")]
+        remote: String,
+        #[arg(long)]
+        all: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -204,6 +634,15 @@ enum StashCommands {
         /// Include untracked files
         #[arg(short = 'u', long)]
         include_untracked: bool,
+        /// Keep staged changes in the index and working tree
+        #[arg(short = 'k', long)]
+        keep_index: bool,
+        /// Stash only the staged changes
+        #[arg(short = 'S', long)]
+        staged: bool,
+        /// Only stash these paths
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
     /// List all stashes
     List,
@@ -212,11 +651,25 @@ enum StashCommands {
         /// Stash index (default: 0)
         #[arg(default_value = "0")]
         index: usize,
+        /// Restore the staged state as well
+        #[arg(long = "index")]
+        restore_index: bool,
     },
     /// Pop a stash (apply and remove)
     Pop {
         /// Stash index (default: 0)
         index: Option<usize>,
+        /// Restore the staged state as well
+        #[arg(long = "index")]
+        restore_index: bool,
+    },
+    /// Create a branch from the stash's base commit and pop the stash onto it
+    Branch {
+        /// New branch name
+        name: String,
+        /// Stash index (default: 0)
+        #[arg(default_value = "0")]
+        index: usize,
     },
     /// Drop a stash
     Drop {
@@ -233,6 +686,52 @@ enum StashCommands {
     Clear,
 }
 
+#[derive(Subcommand)]
+enum BranchCommands {
+    /// Create a new branch
+    Create {
+        /// Branch name
+        name: String,
+        /// Start commit (default: HEAD)
+        target: Option<String>,
+    },
+    /// Delete a branch
+    Delete {
+        /// Branch name
+        name: String,
+        /// Delete even if the branch is not fully merged
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Rename a branch
+    Rename {
+        /// Current branch name
+        old: String,
+        /// New branch name
+        new: String,
+        /// Overwrite an existing branch with the new name
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Set the upstream of a branch
+    SetUpstream {
+        /// Branch name
+        name: String,
+        /// Upstream branch (e.g. origin/main)
+        upstream: String,
+    },
+    /// Remove the upstream of a branch
+    UnsetUpstream {
+        /// Branch name
+        name: String,
+    },
+    /// Check out a local branch, or a remote branch as a new tracking branch
+    Checkout {
+        /// Branch name (e.g. main or origin/feature)
+        name: String,
+    },
+}
+
 #[derive(Subcommand)]
 enum TagCommands {
     /// Create a new tag
@@ -253,11 +752,29 @@ enum TagCommands {
     List {
         /// Pattern to filter tags
         pattern: Option<String>,
+        /// Sort order
+        #[arg(long, value_enum, default_value = "name")]
+        sort: TagSortArg,
+        /// Highest version or newest date first
+        #[arg(short, long)]
+        reverse: bool,
+        /// Only tags containing this commit
+        #[arg(long, value_name = "COMMIT")]
+        contains: Option<String>,
+        /// Show the first line of annotated tag messages
+        #[arg(short = 'n', long)]
+        messages: bool,
     },
     /// Delete a tag
     Delete {
         /// Tag name
         name: String,
+        /// Also delete the tag on this remote
+        #[arg(long)]
+        remote: Option<String>,
+        /// With --remote, keep the local tag
+        #[arg(long, requires = "remote")]
+        keep_local: bool,
     },
     /// Show tag details
     Show {
@@ -307,6 +824,46 @@ enum MergeCommands {
         #[arg(value_enum)]
         strategy: ResolutionStrategy,
     },
+    /// Predict whether merging a branch would conflict, without touching the working tree
+    Predict {
+        /// Branch or commit to merge
+        rev: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum DiffAlgorithmArg {
+    Myers,
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum PeriodArg {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum WordDiffArg {
+    Word,
+    Char,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum TagSortArg {
+    Name,
+    Version,
+    Date,
+}
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum IgnoreFileArg {
+    Nearest,
+    Root,
+    Exclude,
 }
 
 #[derive(Clone, Copy, Debug, clap::ValueEnum)]
@@ -331,6 +888,11 @@ enum RebaseCommands {
         #[arg(long)]
         onto: Option<String>,
     },
+    /// Fold fixup!/squash!/amend! commits into the commits they target
+    Autosquash {
+        /// Rewrite commits after this one (default: back to the oldest target)
+        upstream: Option<String>,
+    },
     /// Continue an in-progress rebase
     Continue,
     /// Abort an in-progress rebase
@@ -339,6 +901,14 @@ enum RebaseCommands {
     Skip,
     /// Show rebase status
     Status,
+    /// Predict whether a rebase would conflict, without touching the working tree
+    Predict {
+        /// Upstream reference
+        upstream: String,
+        /// Onto reference (optional)
+        #[arg(long)]
+        onto: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -347,13 +917,23 @@ enum CherryPickCommands {
     Commit {
         /// Commit reference to cherry-pick
         commit: String,
+        /// Append "(cherry picked from commit ...)" to the message
+        #[arg(short = 'x')]
+        record_origin: bool,
+        #[command(flatten)]
+        options: PickArgs,
     },
-    /// Cherry-pick a range of commits
+    /// Cherry-pick the commits after START up to END, skipping those already applied
     Range {
-        /// Start commit reference
+        /// Start commit reference (excluded)
         start: String,
         /// End commit reference
         end: String,
+        /// Append "(cherry picked from commit ...)" to the messages
+        #[arg(short = 'x')]
+        record_origin: bool,
+        #[command(flatten)]
+        options: PickArgs,
     },
     /// Continue a cherry-pick after resolving conflicts
     Continue,
@@ -391,7 +971,7 @@ fn main() -> Result<()> {
 
     match cli.command {
         Commands::Open { path } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
             println!("Opened repository at: {}", path.display());
             if repo.is_clean()? {
                 println!("Repository is clean");
@@ -403,22 +983,171 @@ fn main() -> Result<()> {
             Repository::init(&path)?;
             println!("Initialized empty repository at: {}", path.display());
         }
-        Commands::Status { path } => {
-            let repo = Repository::open(&path)?;
-            if repo.is_clean()? {
-                println!("Working tree clean");
+        Commands::Clone { url, directory, branch, depth, bare, mirror, recurse_submodules, quiet } => {
+            let directory = directory.unwrap_or_else(|| {
+                let name = url.trim_end_matches('/').rsplit(['/', ':']).next().unwrap_or(&url);
+                let name = name.strip_suffix(".git").unwrap_or(name);
+                PathBuf::from(if bare || mirror { format!("{}.git", name) } else { name.to_string() })
+            });
+            let options = CloneOptions { branch, depth, bare, mirror, recurse_submodules };
+
+            eprintln!("Cloning into {}'{}'...", if bare || mirror { "bare repository " } else { "" }, directory.display());
+            let progress: Option<CloneProgressCallback> = if quiet {
+                None
             } else {
-                println!("Working tree has uncommitted changes");
+                Some(Box::new(|progress| {
+                    match progress {
+                        CloneProgress::Transfer(p) if p.received_objects < p.total_objects => eprint!(
+                            "\rReceiving objects: {}/{} ({} KiB)",
+                            p.received_objects, p.total_objects, p.received_bytes / 1024
+                        ),
+                        CloneProgress::Transfer(p) if p.total_deltas > 0 => {
+                            eprint!("\rResolving deltas: {}/{}          ", p.indexed_deltas, p.total_deltas)
+                        }
+                        CloneProgress::Transfer(_) => {}
+                        CloneProgress::Checkout { completed, total, .. } if total > 0 => {
+                            if completed == 0 {
+                                eprintln!();
+                            }
+                            eprint!("\rUpdating files: {}/{}               ", completed, total);
+                            if completed == total {
+                                eprintln!();
+                            }
+                        }
+                        CloneProgress::Checkout { .. } => {}
+                        CloneProgress::Submodule { path } => eprintln!("Cloning submodule '{}'", path),
+                    }
+                    true
+                }))
+            };
+
+            let repo = Repository::clone_from(&url, &directory, &options, progress)?;
+            if repo.is_bare() {
+                println!("Cloned {} into bare repository {}", url, directory.display());
+            } else {
+                println!("Cloned {} into {}", url, directory.display());
+            }
+        }
+        Commands::Status { path, porcelain, ignored } => {
+            let repo = Repository::discover(&path)?;
+            let report = repo.get_status_report(ignored)?;
+            let branch = &report.branch;
+
+            if porcelain {
+                println!("# branch.oid {}", branch.oid.as_deref().unwrap_or("(initial)"));
+                println!("# branch.head {}", branch.head.as_deref().unwrap_or("(detached)"));
+                if let Some(upstream) = &branch.upstream {
+                    println!("# branch.upstream {}", upstream);
+                    println!("# branch.ab +{} -{}", branch.ahead, branch.behind);
+                }
+
+                for entry in &report.entries {
+                    let sub = entry.submodule.map(|s| s.code()).unwrap_or_else(|| "N...".to_string());
+                    match entry.kind {
+                        StatusEntryKind::Ordinary => println!("1 {} {} {}", entry.xy(), sub, entry.path),
+                        StatusEntryKind::Renamed => println!(
+                            "2 {} {} R{} {}\t{}",
+                            entry.xy(),
+                            sub,
+                            entry.similarity.unwrap_or(0),
+                            entry.path,
+                            entry.orig_path.as_deref().unwrap_or("")
+                        ),
+                        StatusEntryKind::Unmerged => println!("u {} {} {}", entry.xy(), sub, entry.path),
+                        StatusEntryKind::Untracked => println!("? {}", entry.path),
+                        StatusEntryKind::Ignored => println!("! {}", entry.path),
+                    }
+                }
+                return Ok(());
+            }
+
+            match (&branch.head, &branch.oid) {
+                (Some(head), Some(_)) => println!("On branch {}", head),
+                (Some(head), None) => println!("On branch {} (no commits yet)", head),
+                (None, oid) => println!("HEAD detached at {}", oid.as_deref().map(|o| &o[..8]).unwrap_or("?")),
+            }
+            if let Some(upstream) = &branch.upstream {
+                match (branch.ahead, branch.behind) {
+                    (0, 0) => println!("Up to date with '{}'", upstream),
+                    (ahead, 0) => println!("Ahead of '{}' by {} commits", upstream, ahead),
+                    (0, behind) => println!("Behind '{}' by {} commits", upstream, behind),
+                    (ahead, behind) => println!(
+                        "Diverged from '{}': {} ahead, {} behind",
+                        upstream, ahead, behind
+                    ),
+                }
+            }
+
+            if report.entries.is_empty() {
+                println!("Working tree clean");
+                return Ok(());
+            }
+
+            let describe = |entry: &gitup_core::StatusEntry| match (&entry.orig_path, entry.similarity) {
+                (Some(orig), Some(score)) => format!("{} -> {} ({}%)", orig, entry.path, score),
+                (Some(orig), None) => format!("{} -> {}", orig, entry.path),
+                _ => entry.path.clone(),
+            };
+
+            let sections: [(&str, Vec<String>); 5] = [
+                ("Unmerged paths", report.entries.iter()
+                    .filter(|e| e.kind == StatusEntryKind::Unmerged)
+                    .map(|e| format!("{} {}", e.xy(), e.path))
+                    .collect()),
+                ("Changes to be committed", report.entries.iter()
+                    .filter(|e| e.is_staged())
+                    .map(|e| format!("{}  {}", e.index.code(), describe(e)))
+                    .collect()),
+                ("Changes not staged for commit", report.entries.iter()
+                    .filter(|e| e.is_unstaged())
+                    .map(|e| {
+                        let sub = e.submodule.map(|s| format!(" [{}]", s.code())).unwrap_or_default();
+                        format!(" {} {}{}", e.worktree.code(), e.path, sub)
+                    })
+                    .collect()),
+                ("Untracked files", report.entries.iter()
+                    .filter(|e| e.kind == StatusEntryKind::Untracked)
+                    .map(|e| e.path.clone())
+                    .collect()),
+                ("Ignored files", report.entries.iter()
+                    .filter(|e| e.kind == StatusEntryKind::Ignored)
+                    .map(|e| e.path.clone())
+                    .collect()),
+            ];
+
+            for (title, lines) in sections.iter().filter(|(_, lines)| !lines.is_empty()) {
+                println!("\n{}:", title);
+                for line in lines {
+                    println!("  {}", line);
+                }
             }
         }
         Commands::Branches { path } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
             let branches = repo.list_branches()?;
 
             println!("Local branches:");
             for branch in branches.iter().filter(|b| !b.is_remote) {
                 let marker = if branch.is_head { "* " } else { "  " };
-                println!("{}{}", marker, branch.name);
+                let tracking = match &branch.upstream {
+                    Some(upstream) if branch.upstream_gone => format!(" [{}: gone]", upstream),
+                    Some(upstream) => {
+                        let mut parts = Vec::new();
+                        if branch.ahead > 0 {
+                            parts.push(format!("ahead {}", branch.ahead));
+                        }
+                        if branch.behind > 0 {
+                            parts.push(format!("behind {}", branch.behind));
+                        }
+                        if parts.is_empty() {
+                            format!(" [{}]", upstream)
+                        } else {
+                            format!(" [{}: {}]", upstream, parts.join(", "))
+                        }
+                    }
+                    None => String::new(),
+                };
+                println!("{}{} {}{}", marker, branch.name, &branch.commit_id[..8], tracking);
             }
 
             println!("\nRemote branches:");
@@ -426,9 +1155,118 @@ fn main() -> Result<()> {
                 println!("  {}", branch.name);
             }
         }
-        Commands::Log { path, count } => {
-            let repo = Repository::open(&path)?;
+        Commands::Branch { path, command } => {
+            let repo = Repository::discover(&path)?;
+
+            match command {
+                BranchCommands::Create { name, target } => {
+                    repo.create_branch(&name, target.as_deref())?;
+                    println!("Created branch {}", name);
+                }
+                BranchCommands::Delete { name, force } => {
+                    let result = repo.delete_branch(&name, force)?;
+                    println!("{}", result);
+                }
+                BranchCommands::Rename { old, new, force } => {
+                    let result = repo.rename_branch(&old, &new, force)?;
+                    println!("{}", result);
+                }
+                BranchCommands::SetUpstream { name, upstream } => {
+                    let result = repo.set_branch_upstream(&name, Some(&upstream))?;
+                    println!("{}", result);
+                }
+                BranchCommands::UnsetUpstream { name } => {
+                    let result = repo.set_branch_upstream(&name, None)?;
+                    println!("{}", result);
+                }
+                BranchCommands::Checkout { name } => {
+                    let is_remote = repo.list_branches()?
+                        .iter()
+                        .any(|b| b.is_remote && b.name == name);
+                    if is_remote {
+                        let local = repo.checkout_remote_branch(&name, None)?;
+                        println!("Switched to a new branch '{}' tracking '{}'", local, name);
+                    } else {
+                        repo.checkout_branch(&name)?;
+                        println!("Switched to branch '{}'", name);
+                    }
+                }
+            }
+        }
+        Commands::Stats { path, max_count, since, until, merges, period, top, json } => {
+            let repo = Repository::discover(&path)?;
+            let midnight = |date: chrono::NaiveDate| date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+            let stats = repo.analytics(&AnalyticsOptions {
+                max_commits: max_count,
+                since: since.map(midnight),
+                until: until.map(midnight),
+                include_merges: merges,
+                bucket: match period {
+                    PeriodArg::Day => TimeBucket::Day,
+                    PeriodArg::Week => TimeBucket::Week,
+                    PeriodArg::Month => TimeBucket::Month,
+                },
+                top_files: top,
+                top_couplings: top,
+                ..Default::default()
+            })?;
+
+            if json {
+                println!("{}", stats.to_json()?);
+                return Ok(());
+            }
+
+            println!("{} commits", stats.commits);
+            println!("\nContributors:");
+            for author in &stats.authors {
+                println!(
+                    "  {:>5}  {} <{}>  +{} -{}",
+                    author.commits, author.name, author.email, author.lines_added, author.lines_removed
+                );
+                let activity: Vec<String> = author.activity.iter()
+                    .map(|p| format!("{}: {}", p.period, p.commits))
+                    .collect();
+                println!("         {}", activity.join(", "));
+            }
+
+            println!("\nHotspots:");
+            for file in &stats.churn {
+                println!(
+                    "  {:>6}  {}  ({} commits, {} authors, +{} -{})",
+                    file.churn(), file.path, file.commits, file.authors, file.lines_added, file.lines_removed
+                );
+            }
+
+            if !stats.coupling.is_empty() {
+                println!("\nChange coupling:");
+                for pair in &stats.coupling {
+                    println!(
+                        "  {:>4.0}%  {} <-> {}  ({} commits together)",
+                        pair.degree * 100.0, pair.file_a, pair.file_b, pair.shared_commits
+                    );
+                }
+            }
+
+            println!("\nCommits by weekday and hour:");
+            println!("       {}", (0..24).map(|h| format!("{:>3}", h)).collect::<String>());
+            for (day, hours) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().zip(&stats.heatmap) {
+                let cells: String = hours.iter()
+                    .map(|&n| if n == 0 { "  .".to_string() } else { format!("{:>3}", n) })
+                    .collect();
+                println!("  {}  {}", day, cells);
+            }
+        }
+        Commands::Log { path, count, notes } => {
+            let repo = Repository::discover(&path)?;
             let commits = repo.get_commits(count)?;
+            let notes_ops = notes.as_deref().map(|name| match name {
+                "" => repo.notes_ops(),
+                name => repo.notes_ops_for(name),
+            });
+            let mut notes = match &notes_ops {
+                Some(ops) => ops.read_many(&commits.iter().map(|c| c.id.clone()).collect::<Vec<_>>())?,
+                None => Default::default(),
+            };
 
             for commit in commits {
                 println!("commit {}", &commit.id[..8]);
@@ -437,19 +1275,74 @@ fn main() -> Result<()> {
                     .map(|dt| dt.to_string())
                     .unwrap_or_default());
                 println!("\n    {}\n", commit.message);
+                if let (Some(ops), Some(note)) = (&notes_ops, notes.remove(&commit.id)) {
+                    println!("Notes ({}):", ops.notes_ref().trim_start_matches("refs/notes/"));
+                    for line in note.lines() {
+                        println!("    {}", line);
+                    }
+                    println!();
+                }
             }
         }
-        Commands::Diff { path, staged, commit, from, to, stat } => {
-            let repo = Repository::open(&path)?;
+        Commands::Diff {
+            path, staged, commit, from, to, stat,
+            context, inter_hunk_context, ignore_all_space, ignore_space_change, ignore_space_at_eol,
+            algorithm, rename_threshold, no_renames, copy_threshold, text, binary,
+            word_diff, word_diff_regex, paths,
+        } => {
+            let repo = Repository::discover(&path)?;
+
+            let whitespace = if ignore_all_space {
+                WhitespaceMode::IgnoreAll
+            } else if ignore_space_change {
+                WhitespaceMode::IgnoreChange
+            } else if ignore_space_at_eol {
+                WhitespaceMode::IgnoreEol
+            } else {
+                WhitespaceMode::Default
+            };
+            let word_mode = match (word_diff_regex, word_diff) {
+                (Some(regex), _) => Some(TokenMode::Regex(regex)),
+                (None, Some(WordDiffArg::Word)) => Some(TokenMode::Word),
+                (None, Some(WordDiffArg::Char)) => Some(TokenMode::Char),
+                (None, None) => None,
+            };
+            let tokenizer = word_mode.clone().map(Tokenizer::new).transpose()?;
+
+            let config = DiffConfig {
+                context_lines: context,
+                interhunk_lines: inter_hunk_context,
+                whitespace,
+                algorithm: match algorithm {
+                    DiffAlgorithmArg::Myers => DiffAlgorithm::Myers,
+                    DiffAlgorithmArg::Minimal => DiffAlgorithm::Minimal,
+                    DiffAlgorithmArg::Patience => DiffAlgorithm::Patience,
+                    DiffAlgorithmArg::Histogram => DiffAlgorithm::Histogram,
+                },
+                renames: !no_renames,
+                rename_threshold,
+                copies: copy_threshold.is_some(),
+                copy_threshold: copy_threshold.unwrap_or(50),
+                pathspecs: paths,
+                binary: if text {
+                    BinaryMode::Text
+                } else if binary {
+                    BinaryMode::Binary
+                } else {
+                    BinaryMode::Auto
+                },
+                word_diff: word_mode,
+            };
+            let engine = repo.diff_with_config(config);
 
             let diffs = if let Some(commit_id) = commit {
-                repo.diff_for_commit(&commit_id)?
+                engine.for_commit(&commit_id)?
             } else if let (Some(old), Some(new)) = (from, to) {
-                repo.diff_between_commits(&old, &new)?
+                engine.between_commits(&old, &new)?
             } else if staged {
-                repo.diff_index_to_head()?
+                engine.index_to_head()?
             } else {
-                repo.diff_workdir_to_index()?
+                engine.workdir_to_index()?
             };
 
             if stat {
@@ -470,7 +1363,13 @@ fn main() -> Result<()> {
 
                     println!("{} {}", status_char, file_diff.file.path);
 
-                    if !file_diff.binary {
+                    if let Some(content) = &file_diff.content {
+                        println!("{}", content.describe());
+                    }
+
+                    if let (false, Some(tokenizer)) = (file_diff.binary, &tokenizer) {
+                        print_word_diff(&file_diff, tokenizer);
+                    } else if !file_diff.binary {
                         for line in &file_diff.lines {
                             let prefix = match line.origin {
                                 gitup_core::LineOrigin::Addition => "+",
@@ -482,7 +1381,7 @@ fn main() -> Result<()> {
                                 println!();
                             }
                         }
-                    } else {
+                    } else if file_diff.content.is_none() {
                         println!("Binary file");
                     }
                     println!();
@@ -490,7 +1389,7 @@ fn main() -> Result<()> {
             }
         }
         Commands::Stage { path, files, all } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             if all || files.is_empty() {
                 repo.stage_all()?;
@@ -503,7 +1402,7 @@ fn main() -> Result<()> {
             }
         }
         Commands::Unstage { path, files, all } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             if all || files.is_empty() {
                 repo.reset_index()?;
@@ -515,28 +1414,82 @@ fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Commit { path, message, author, email, amend } => {
-            let repo = Repository::open(&path)?;
+        Commands::Commit { path, message, author, email, amend, signoff, trailer, co_author, no_verify, fixup, squash } => {
+            let repo = Repository::discover(&path)?;
+
+            let fixup = match (fixup.as_deref(), squash.as_deref()) {
+                (Some(target), _) => Some(match target.strip_prefix("amend:") {
+                    Some(target) => (FixupKind::Amend, target.to_string()),
+                    None => (FixupKind::Fixup, target.to_string()),
+                }),
+                (None, Some(target)) => Some((FixupKind::Squash, target.to_string())),
+                (None, None) => None,
+            };
+            let message_only = amend || matches!(fixup, Some((FixupKind::Amend, _)));
 
             // Check if there are changes to commit
-            if !amend && !repo.has_staged_changes()? {
+            if !message_only && !repo.has_staged_changes()? {
                 println!("No changes staged for commit");
                 return Ok(());
             }
 
+            // Get author info from git config or use defaults
+            let config = git2::Config::open_default().ok();
+            let author_name = author.or_else(|| {
+                config.as_ref().and_then(|c| c.get_string("user.name").ok())
+            }).unwrap_or_else(|| "GitUp User".to_string());
+
+            let author_email = email.or_else(|| {
+                config.as_ref().and_then(|c| c.get_string("user.email").ok())
+            }).unwrap_or_else(|| "gitup@local".to_string());
+
+            // fixup!/squash!/amend! subjects are folded in later, so they are not linted
+            if let Some((kind, target)) = fixup {
+                let commit_id = repo.commit_fixup(&target, kind, message.as_deref(), &author_name, &author_email)?;
+                println!("Created commit: {}", &commit_id[..8]);
+                return Ok(());
+            }
+
+            let messages = repo.message_ops();
+            let mut message = match message {
+                Some(message) => clean_message(&message)?,
+                None => {
+                    let initial = if amend {
+                        messages.head_message()?
+                    } else {
+                        messages.template()?.unwrap_or_default()
+                    };
+                    messages.edit(&initial)?
+                }
+            };
+
+            for entry in &trailer {
+                let (key, value) = entry.split_once(['=', ':'])
+                    .ok_or_else(|| anyhow::anyhow!("Invalid trailer '{}', expected KEY=VALUE", entry))?;
+                message = add_trailer(&message, key.trim(), value.trim());
+            }
+            for co in &co_author {
+                message = add_trailer(&message, "Co-authored-by", co.trim());
+            }
+            if signoff {
+                message = sign_off(&message, &author_name, &author_email);
+            }
+
+            let findings = messages.lint(&message)?;
+            for finding in &findings {
+                let location = finding.span
+                    .map(|span| format!("line {}: ", span.line + 1))
+                    .unwrap_or_default();
+                let level = if finding.is_error() { "error" } else { "warning" };
+                eprintln!("{}: {}{} [{}]", level, location, finding.message, finding.rule.name());
+            }
+            if !no_verify && findings.iter().any(|f| f.is_error()) {
+                return Err(anyhow::anyhow!("Commit message failed linting (use --no-verify to commit anyway)"));
+            }
+
             let commit_id = if amend {
                 repo.amend_commit(Some(&message))?
             } else {
-                // Get author info from git config or use defaults
-                let config = git2::Config::open_default().ok();
-                let author_name = author.or_else(|| {
-                    config.as_ref().and_then(|c| c.get_string("user.name").ok())
-                }).unwrap_or_else(|| "GitUp User".to_string());
-
-                let author_email = email.or_else(|| {
-                    config.as_ref().and_then(|c| c.get_string("user.email").ok())
-                }).unwrap_or_else(|| "gitup@local".to_string());
-
                 repo.commit(&message, &author_name, &author_email)?
             };
 
@@ -546,7 +1499,7 @@ fn main() -> Result<()> {
             gitup_ui::run_tui(&path)?;
         }
         Commands::Remote { path, command } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             match command {
                 RemoteCommands::List => {
@@ -586,14 +1539,49 @@ fn main() -> Result<()> {
                 }
             }
         }
-        Commands::Fetch { remote, path } => {
-            let repo = Repository::open(&path)?;
-            println!("Fetching from {}...", remote);
-            let result = repo.fetch(&remote)?;
-            println!("{}", result);
+        Commands::Fetch { remote, path, all, prune, tags, no_tags, depth, unshallow, write_commit_graph } => {
+            let repo = Repository::discover(&path)?;
+            let options = FetchOptions {
+                prune,
+                tags: if tags { TagMode::All } else if no_tags { TagMode::None } else { TagMode::Auto },
+                depth,
+                unshallow,
+                write_commit_graph,
+                ..Default::default()
+            };
+
+            let reports = if all {
+                repo.fetch_all(&options)?
+            } else {
+                vec![repo.fetch_with_options(&remote, &options)?]
+            };
+
+            let mut failed = 0;
+            for report in &reports {
+                if let Some(error) = &report.error {
+                    eprintln!("error: Could not fetch {}: {}", report.remote, error);
+                    failed += 1;
+                    continue;
+                }
+                if let Some(error) = &report.commit_graph_error {
+                    eprintln!("warning: Could not update the commit-graph: {}", error);
+                }
+                if report.updates.is_empty() {
+                    println!("{}: up to date", report.remote);
+                    continue;
+                }
+                println!("From {}", report.url);
+                for update in &report.updates {
+                    print_ref_update(update);
+                }
+                println!("{}", report.summary());
+            }
+            if failed > 0 {
+                return Err(anyhow::anyhow!("Could not fetch {} of {} remotes", failed, reports.len()));
+            }
         }
         Commands::Pull { remote, branch, path } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             // Get current branch if not specified
             let branch_name = if let Some(b) = branch {
@@ -612,7 +1600,7 @@ fn main() -> Result<()> {
             println!("{}", result);
         }
         Commands::Push { remote, path, set_upstream } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             println!("Pushing to {}...", remote);
             let result = repo.push(&remote)?;
@@ -634,11 +1622,18 @@ fn main() -> Result<()> {
             }
         }
         Commands::Stash { path, command } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             match command {
-                StashCommands::Save { message, include_untracked } => {
-                    let result = repo.stash_save(message.as_deref(), include_untracked)?;
+                StashCommands::Save { message, include_untracked, keep_index, staged, paths } => {
+                    let options = gitup_core::StashSaveOptions {
+                        message,
+                        include_untracked,
+                        keep_index,
+                        staged,
+                        paths,
+                    };
+                    let result = repo.stash_save_with_options(&options)?;
                     println!("{}", result);
                 }
                 StashCommands::List => {
@@ -651,21 +1646,41 @@ fn main() -> Result<()> {
                         }
                     }
                 }
-                StashCommands::Apply { index } => {
-                    let result = repo.stash_apply(index)?;
-                    println!("{}", result);
+                StashCommands::Apply { index, restore_index } => {
+                    let result = repo.stash_apply(index, restore_index)?;
+                    print_stash_result(&result);
                 }
-                StashCommands::Pop { index } => {
-                    let result = repo.stash_pop(index)?;
-                    println!("{}", result);
+                StashCommands::Pop { index, restore_index } => {
+                    let result = repo.stash_pop(index, restore_index)?;
+                    print_stash_result(&result);
+                }
+                StashCommands::Branch { name, index } => {
+                    let result = repo.stash_branch(&name, index)?;
+                    print_stash_result(&result);
                 }
                 StashCommands::Drop { index } => {
                     let result = repo.stash_drop(index)?;
                     println!("{}", result);
                 }
                 StashCommands::Show { index } => {
-                    let result = repo.stash_show(index)?;
-                    println!("{}", result);
+                    let stash = repo.stash_show(index)?;
+                    let sections = [
+                        ("Staged", &stash.index),
+                        ("Unstaged", &stash.working_tree),
+                        ("Untracked", &stash.untracked),
+                    ];
+                    for (title, diffs) in sections {
+                        if diffs.is_empty() {
+                            continue;
+                        }
+                        let stats = gitup_core::DiffStats::from_diffs(diffs);
+                        println!("{}:", title);
+                        for file_diff in diffs.iter() {
+                            println!("  {}", file_diff.file.path);
+                        }
+                        println!(" {} files changed, {} insertions(+), {} deletions(-)\n",
+                            stats.files_changed, stats.insertions, stats.deletions);
+                    }
                 }
                 StashCommands::Clear => {
                     let result = repo.stash_clear()?;
@@ -674,30 +1689,47 @@ fn main() -> Result<()> {
             }
         }
         Commands::Tag { path, command } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             match command {
                 TagCommands::Create { name, target, message, force } => {
                     let result = repo.tag_create(&name, target.as_deref(), message.as_deref(), force)?;
                     println!("{}", result);
                 }
-                TagCommands::List { pattern } => {
-                    let tags = repo.tag_list(pattern.as_deref())?;
+                TagCommands::List { pattern, sort, reverse, contains, messages } => {
+                    let sort = match sort {
+                        TagSortArg::Name => TagSort::Name,
+                        TagSortArg::Version => TagSort::Version,
+                        TagSortArg::Date => TagSort::Date,
+                    };
+                    let mut tags = repo.tag_list_sorted(pattern.as_deref(), sort, reverse)?;
+                    if let Some(commit) = &contains {
+                        let containing = repo.tag_contains(commit)?;
+                        tags.retain(|tag| containing.contains(&tag.name));
+                    }
                     if tags.is_empty() {
                         println!("No tags found");
                     } else {
                         for tag in tags {
-                            if tag.is_annotated {
-                                println!("{} (annotated)", tag.name);
-                            } else {
-                                println!("{}", tag.name);
+                            let subject = tag.message.as_deref()
+                                .and_then(|m| m.lines().next())
+                                .filter(|_| messages);
+                            match (subject, tag.is_annotated) {
+                                (Some(subject), _) => println!("{:<20} {}", tag.name, subject),
+                                (None, true) => println!("{} (annotated)", tag.name),
+                                (None, false) => println!("{}", tag.name),
                             }
                         }
                     }
                 }
-                TagCommands::Delete { name } => {
-                    let result = repo.tag_delete(&name)?;
-                    println!("{}", result);
+                TagCommands::Delete { name, remote, keep_local } => {
+                    if let Some(remote) = &remote {
+                        println!("{}", repo.tag_delete_remote(remote, &name)?);
+                    }
+                    if !keep_local {
+                        let result = repo.tag_delete(&name)?;
+                        println!("{}", result);
+                    }
                 }
                 TagCommands::Show { name } => {
                     let result = repo.tag_show(&name)?;
@@ -710,7 +1742,7 @@ fn main() -> Result<()> {
             }
         }
         Commands::Merge { path, command } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             match command {
                 MergeCommands::Branch { name, message } => {
@@ -759,6 +1791,33 @@ fn main() -> Result<()> {
                     let status = repo.merge_status()?;
                     println!("{}", status);
                 }
+                MergeCommands::Predict { rev } => {
+                    let prediction = repo.predict_merge(&rev)?;
+                    match prediction.kind {
+                        MergeKind::UpToDate => println!("Already up to date"),
+                        MergeKind::FastForward => println!("Fast-forward, no conflicts"),
+                        MergeKind::Normal if prediction.is_clean() => println!("Merges cleanly"),
+                        MergeKind::Normal => {
+                            println!("Would conflict in {} file(s):", prediction.conflicts.len());
+                            for conflict in &prediction.conflicts {
+                                println!("  - {}", conflict);
+                            }
+                        }
+                    }
+                    if !prediction.changes.is_empty() {
+                        println!("\nFiles changed:");
+                        for (path, status) in &prediction.changes {
+                            let status_char = match status {
+                                FileStatus::Added => "+",
+                                FileStatus::Deleted => "-",
+                                FileStatus::Modified => "M",
+                                FileStatus::Renamed => "R",
+                                _ => "?",
+                            };
+                            println!("  {} {}", status_char, path);
+                        }
+                    }
+                }
                 MergeCommands::Conflicts => {
                     let conflicts = repo.merge_conflicts()?;
                     if conflicts.is_empty() {
@@ -782,7 +1841,7 @@ fn main() -> Result<()> {
             }
         }
         Commands::Rebase { path, command } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             match command {
                 RebaseCommands::Onto { branch } => {
@@ -819,6 +1878,21 @@ fn main() -> Result<()> {
                         }
                     }
                 }
+                RebaseCommands::Autosquash { upstream } => {
+                    let result = repo.autosquash(upstream.as_deref())?;
+
+                    if result.success {
+                        println!("{}", result.message);
+                        for commit in &result.rebased_commits {
+                            println!("  - {}", &commit[..8]);
+                        }
+                    } else {
+                        println!("CONFLICT: {}", result.message);
+                        for conflict in &result.conflicts {
+                            println!("  - {}", conflict);
+                        }
+                    }
+                }
                 RebaseCommands::Continue => {
                     let result = repo.rebase_continue()?;
 
@@ -852,15 +1926,326 @@ fn main() -> Result<()> {
                     let status = repo.rebase_status()?;
                     println!("{}", status);
                 }
+                RebaseCommands::Predict { upstream, onto } => {
+                    let prediction = repo.predict_rebase(&upstream, onto.as_deref())?;
+                    match &prediction.first_conflict {
+                        None => println!("All {} commit(s) would apply cleanly", prediction.commits),
+                        Some(conflict) => {
+                            println!("Commit {}/{} would conflict: {} {}",
+                                conflict.position, prediction.commits, &conflict.id[..8], conflict.summary);
+                            for path in &conflict.conflicts {
+                                println!("  - {}", path);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Split { commit, path, message, files, list } => {
+            let repo = Repository::discover(&path)?;
+            let commit = repo.resolve_commit(&commit)?;
+
+            if list {
+                for diff in repo.diff_for_commit(&commit)? {
+                    println!("{}", diff.file.path);
+                    for (i, hunk) in diff.hunks.iter().enumerate() {
+                        println!("  {}#{}  {}", diff.file.path, i, hunk.header.trim_end());
+                    }
+                }
+                return Ok(());
+            }
+
+            if message.len() != files.len() {
+                return Err(anyhow::anyhow!("Give one --message for each --files"));
+            }
+            let parts = message.into_iter().zip(files)
+                .map(|(message, files)| {
+                    let changes = files.split(',')
+                        .map(|s| s.trim().parse())
+                        .collect::<Result<Vec<SplitSelection>>>()?;
+                    Ok(SplitPart { message, changes })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let result = repo.split_commit(&commit, &parts)?;
+            if result.success {
+                println!("{}", result.message);
+                for id in &result.rebased_commits {
+                    println!("  - {}", &id[..8]);
+                }
+            } else {
+                println!("CONFLICT: {}", result.message);
+                for conflict in &result.conflicts {
+                    println!("  - {}", conflict);
+                }
+            }
+        }
+        Commands::Move { first, last, path, after, onto } => {
+            let repo = Repository::discover(&path)?;
+            let last = last.unwrap_or_else(|| first.clone());
+            let destination = match (after, onto) {
+                (_, Some(branch)) => MoveDestination::OntoBranch(branch),
+                (Some(commit), None) => MoveDestination::After(commit),
+                (None, None) => unreachable!("clap requires --after or --onto"),
+            };
+
+            let result = repo.move_commits(&first, &last, &destination)?;
+            if result.success {
+                println!("{}", result.message);
+                for id in &result.rebased_commits {
+                    println!("  - {}", &id[..8]);
+                }
+            } else {
+                println!("CONFLICT: {}", result.message);
+                for conflict in &result.conflicts {
+                    println!("  - {}", conflict);
+                }
+            }
+        }
+        Commands::Rewrite {
+            commit, path, message, author, author_date, committer, committer_date,
+            old_email, upstream, include_committer,
+        } => {
+            let repo = Repository::discover(&path)?;
+
+            let result = if let Some(old_email) = old_email {
+                let (name, email) = parse_identity(author.as_deref().unwrap_or_default())?;
+                let rewrite = IdentityRewrite {
+                    old_email,
+                    new_name: Some(name),
+                    new_email: Some(email),
+                    committer: include_committer,
+                };
+                repo.rewrite_identity(upstream.as_deref(), &rewrite)?
+            } else {
+                let commit = commit.ok_or_else(|| anyhow::anyhow!("Give a commit to rewrite, or --old-email"))?;
+                let (author_name, author_email) = author.as_deref().map(parse_identity).transpose()?.unzip();
+                let (committer_name, committer_email) = committer.as_deref().map(parse_identity).transpose()?.unzip();
+                let metadata = CommitMetadata {
+                    message: message.as_deref().map(clean_message).transpose()?,
+                    author_name,
+                    author_email,
+                    author_time: author_date.as_deref().map(parse_date).transpose()?,
+                    committer_name,
+                    committer_email,
+                    committer_time: committer_date.as_deref().map(parse_date).transpose()?,
+                };
+                repo.rewrite_commit(&commit, &metadata)?
+            };
+
+            if result.success {
+                println!("{}", result.message);
+                for id in &result.rebased_commits {
+                    println!("  - {}", &id[..8]);
+                }
+            } else {
+                println!("CONFLICT: {}", result.message);
+                for conflict in &result.conflicts {
+                    println!("  - {}", conflict);
+                }
+            }
+        }
+        Commands::CommitGraph { path, command } => {
+            let repo = Repository::discover(&path)?;
+            let ops = repo.commit_graph_ops();
+
+            match command {
+                CommitGraphCommands::Write => {
+                    let commits = ops.write()?;
+                    println!("Wrote {} commits to {}", commits, ops.path().display());
+                }
+                CommitGraphCommands::Update => match ops.update()? {
+                    Some(commits) => println!("Wrote {} commits to {}", commits, ops.path().display()),
+                    None => println!("Commit-graph is up to date"),
+                },
+                CommitGraphCommands::Info { commit } => {
+                    let lookup = repo.commit_lookup();
+                    let Some(graph) = lookup.graph() else {
+                        return Err(anyhow::anyhow!("No commit-graph; run `gitup commit-graph write`"));
+                    };
+                    match commit {
+                        None => println!("{} commits in {} file(s)", graph.len(), graph.layer_count()),
+                        Some(rev) => {
+                            let oid = git2::Oid::from_str(&repo.resolve_commit(&rev)?)?;
+                            let commit = graph.commit(oid)
+                                .ok_or_else(|| anyhow::anyhow!("{} is not in the commit-graph", rev))?;
+                            println!("commit {}", oid);
+                            println!("tree {}", commit.tree);
+                            for parent in &commit.parents {
+                                println!("parent {}", parent);
+                            }
+                            println!("generation {}", commit.generation);
+                            println!("date {}", commit.time);
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Describe { commit, path, tags, pattern, abbrev, long, always, dirty, candidates } => {
+            let repo = Repository::discover(&path)?;
+            let options = DescribeOptions { tags, pattern, abbrev, long, always, dirty, candidates };
+            println!("{}", repo.describe(commit.as_deref(), &options)?);
+        }
+        Commands::Ignore { paths, path, extension, patterns, to, list } => {
+            let repo = Repository::discover(&path)?;
+            let ignore = repo.ignore_ops();
+
+            if list {
+                for ignored in ignore.list_ignored()? {
+                    println!("{}", ignored);
+                }
+                return Ok(());
+            }
+            if paths.is_empty() && patterns.is_empty() {
+                return Err(anyhow::anyhow!("Nothing to ignore: give paths or --pattern"));
+            }
+
+            let file = match to {
+                IgnoreFileArg::Nearest => IgnoreFile::Nearest,
+                IgnoreFileArg::Root => IgnoreFile::Root,
+                IgnoreFileArg::Exclude => IgnoreFile::Exclude,
+            };
+            let mut rules = Vec::new();
+            for pattern in &patterns {
+                rules.push(ignore.add_pattern(pattern, file)?);
+            }
+            for ignored in &paths {
+                rules.push(if extension {
+                    ignore.ignore_extension(ignored, file)?
+                } else {
+                    ignore.ignore_path(ignored, file)?
+                });
+            }
+            for rule in rules {
+                if rule.added {
+                    println!("Added '{}' to {}", rule.pattern, rule.file);
+                } else {
+                    println!("{} already has '{}'", rule.file, rule.pattern);
+                }
+            }
+        }
+        Commands::CheckIgnore { paths, path, verbose, non_matching, no_index } => {
+            let repo = Repository::discover(&path)?;
+            let ignore = repo.ignore_ops();
+            let mut any_ignored = false;
+
+            for checked in &paths {
+                let matched = ignore.explain(checked, no_index)?;
+                let ignored = matched.as_ref().is_some_and(|m| m.ignored);
+                any_ignored |= ignored;
+                match (&matched, verbose) {
+                    (Some(rule), true) => println!("{}", rule),
+                    (None, true) if non_matching => println!("::\t{}", checked),
+                    (_, false) if ignored => println!("{}", checked),
+                    _ => {}
+                }
+            }
+            if !any_ignored {
+                std::process::exit(1);
+            }
+        }
+        Commands::Notes { path, notes_ref, command } => {
+            let repo = Repository::discover(&path)?;
+            let notes = match &notes_ref {
+                Some(name) => repo.notes_ops_for(name),
+                None => repo.notes_ops(),
+            };
+
+            match command {
+                NotesCommands::List => {
+                    for note in notes.list()? {
+                        let first_line = note.message.lines().next().unwrap_or("");
+                        println!("{}  {}", &note.commit[..8], first_line);
+                    }
+                }
+                NotesCommands::Show { commit } => match notes.read(&commit)? {
+                    Some(note) => print!("{}", note),
+                    None => return Err(anyhow::anyhow!("No note on {} in {}", commit, notes.notes_ref())),
+                },
+                NotesCommands::Add { message, commit, force } => {
+                    notes.add(&commit, &message, force)?;
+                    println!("Added note to {}", commit);
+                }
+                NotesCommands::Edit { message, commit } => {
+                    let message = match message {
+                        Some(message) => message,
+                        None => repo.message_ops().edit(&notes.read(&commit)?.unwrap_or_default())?,
+                    };
+                    notes.edit(&commit, &message)?;
+                    println!("Updated note on {}", commit);
+                }
+                NotesCommands::Append { message, commit } => {
+                    notes.append(&commit, &message)?;
+                    println!("Appended to note on {}", commit);
+                }
+                NotesCommands::Remove { commit } => {
+                    notes.remove(&commit)?;
+                    println!("Removed note on {}", commit);
+                }
+                NotesCommands::Push { remote } => {
+                    println!("{}", repo.push_notes(&remote, notes.notes_ref())?);
+                }
+                NotesCommands::Fetch { remote, all } => {
+                    let report = repo.fetch_notes(&remote, if all { "*" } else { notes.notes_ref() })?;
+                    println!("{}", report.summary());
+                    for update in &report.updates {
+                        print_ref_update(update);
+                    }
+                }
+            }
+        }
+        Commands::RangeDiff { old, new, path, last_rewrite, summary } => {
+            let repo = Repository::discover(&path)?;
+
+            let mut range_diff = match (old, new) {
+                _ if last_rewrite => repo.range_diff_last_rewrite()?,
+                (Some(old), Some(new)) => repo.range_diff(&old, &new)?,
+                (Some(tips), None) => {
+                    let (old_tip, new_tip) = tips.split_once("...")
+                        .ok_or_else(|| anyhow::anyhow!("Give two ranges, OLD...NEW or --last-rewrite"))?;
+                    repo.range_diff_tips(old_tip, new_tip)?
+                }
+                _ => return Err(anyhow::anyhow!("Give two ranges, OLD...NEW or --last-rewrite")),
+            };
+
+            if summary {
+                for pair in &mut range_diff.pairs {
+                    pair.diff.clear();
+                }
+            }
+            print!("{}", range_diff);
+        }
+        Commands::Undo { path, list } => {
+            let repo = Repository::discover(&path)?;
+
+            if list {
+                for entry in repo.undo_entries()?.iter().rev() {
+                    let time = chrono::DateTime::from_timestamp(entry.time, 0)
+                        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default();
+                    println!("{}  {}", time, entry.description);
+                }
+                return Ok(());
+            }
+
+            match repo.undo()? {
+                Some(entry) => {
+                    println!("Undid: {}", entry.description);
+                    for change in &entry.changes {
+                        let short = |id: &Option<String>| id.as_deref().map(|id| id[..8].to_string()).unwrap_or_else(|| "(none)".to_string());
+                        println!("  {}: {} -> {}", change.name, short(&change.new), short(&change.old));
+                    }
+                }
+                None => println!("Nothing to undo"),
             }
         }
         Commands::CherryPick { path, command } => {
-            let repo = Repository::open(&path)?;
+            let repo = Repository::discover(&path)?;
 
             match command {
-                CherryPickCommands::Commit { commit } => {
+                CherryPickCommands::Commit { commit, record_origin, options } => {
                     println!("Cherry-picking commit '{}'...", &commit[..8.min(commit.len())]);
-                    let result = repo.cherry_pick(&commit)?;
+                    let result = repo.cherry_pick(&commit, &options.to_options(record_origin))?;
 
                     if result.success {
                         println!("{}", result.message);
@@ -878,10 +2263,10 @@ fn main() -> Result<()> {
                         }
                     }
                 }
-                CherryPickCommands::Range { start, end } => {
+                CherryPickCommands::Range { start, end, record_origin, options } => {
                     println!("Cherry-picking commits from {} to {}...",
                         &start[..8.min(start.len())], &end[..8.min(end.len())]);
-                    let results = repo.cherry_pick_range(&start, &end)?;
+                    let results = repo.cherry_pick_range(&start, &end, &options.to_options(record_origin))?;
 
                     for result in results {
                         if result.success {
@@ -900,19 +2285,19 @@ fn main() -> Result<()> {
                     }
                 }
                 CherryPickCommands::Continue => {
-                    let result = repo.cherry_pick_continue()?;
-
-                    if result.success {
-                        println!("{}", result.message);
-                        if let Some(picked) = result.picked_commit {
-                            println!("Created commit: {}", &picked[..8]);
-                        }
-                    } else {
-                        println!("Cannot continue: {}", result.message);
-                        if !result.conflicts.is_empty() {
-                            println!("\nConflicted files:");
-                            for conflict in &result.conflicts {
-                                println!("  - {}", conflict);
+                    for result in repo.cherry_pick_continue()? {
+                        if result.success {
+                            println!("{}", result.message);
+                            if let Some(picked) = result.picked_commit {
+                                println!("Created commit: {}", &picked[..8]);
+                            }
+                        } else {
+                            println!("Cannot continue: {}", result.message);
+                            if !result.conflicts.is_empty() {
+                                println!("\nConflicted files:");
+                                for conflict in &result.conflicts {
+                                    println!("  - {}", conflict);
+                                }
                             }
                         }
                     }
@@ -927,7 +2312,111 @@ fn main() -> Result<()> {
                 }
             }
         }
+        Commands::Revert { commits, path, options } => {
+            let repo = Repository::discover(&path)?;
+
+            for result in repo.revert(&commits, &options.to_options(false))? {
+                if result.success {
+                    println!("{}", result.message);
+                    if let Some(created) = result.picked_commit {
+                        println!("Created commit: {}", &created[..8]);
+                    }
+                } else {
+                    println!("CONFLICT: {}", result.message);
+                    for conflict in &result.conflicts {
+                        println!("  - {}", conflict);
+                    }
+                    println!("\nResolve conflicts, stage changes, and run 'gitup cherry-pick continue'");
+                }
+            }
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Print the outcome of applying a stash
+/// Parse "Name <email>"
+fn parse_identity(identity: &str) -> Result<(String, String)> {
+    let (name, rest) = identity.split_once('<')
+        .ok_or_else(|| anyhow::anyhow!("Expected \"Name <email>\", got '{}'", identity))?;
+    let email = rest.strip_suffix('>')
+        .ok_or_else(|| anyhow::anyhow!("Expected \"Name <email>\", got '{}'", identity))?;
+    Ok((name.trim().to_string(), email.trim().to_string()))
+}
+
+/// Parse an RFC 3339 date or Unix time into seconds and UTC offset in minutes
+fn parse_date(date: &str) -> Result<(i64, i32)> {
+    if let Ok(seconds) = date.parse::<i64>() {
+        return Ok((seconds, 0));
+    }
+    let date = chrono::DateTime::parse_from_rfc3339(date)
+        .map_err(|e| anyhow::anyhow!("Invalid date '{}': {}", date, e))?;
+    Ok((date.timestamp(), date.offset().local_minus_utc() / 60))
+}
+
+fn print_stash_result(result: &gitup_core::MergeResult) {
+    if result.success {
+        println!("{}", result.message);
+    } else {
+        println!("CONFLICT: {}", result.message);
+        if !result.conflicts.is_empty() {
+            println!("\nConflicted files:");
+            for conflict in &result.conflicts {
+                println!("  - {}", conflict);
+            }
+        }
+    }
+}
+
+/// Print a file diff with paired lines merged into [-removed-]{+added+} form
+/// Print a fetched ref update the way `git fetch` does
+fn print_ref_update(update: &RefUpdate) {
+    let short = |id: &Option<String>| id.as_deref().map(|id| &id[..id.len().min(7)]).unwrap_or("").to_string();
+    let name = update.name
+        .strip_prefix("refs/remotes/")
+        .or_else(|| update.name.strip_prefix("refs/tags/"))
+        .or_else(|| update.name.strip_prefix("refs/heads/"))
+        .unwrap_or(&update.name);
+    let is_tag = update.name.starts_with("refs/tags/");
+
+    let (flag, summary, note) = match update.kind {
+        RefUpdateKind::New if is_tag => ('*', "[new tag]".to_string(), ""),
+        RefUpdateKind::New => ('*', "[new branch]".to_string(), ""),
+        RefUpdateKind::FastForward => (' ', format!("{}..{}", short(&update.old), short(&update.new)), ""),
+        RefUpdateKind::Forced => ('+', format!("{}...{}", short(&update.old), short(&update.new)), "  (forced update)"),
+        RefUpdateKind::Deleted => ('-', "[deleted]".to_string(), ""),
+        RefUpdateKind::Rejected => ('!', "[rejected]".to_string(), "  (non-fast-forward)"),
+    };
+    println!(" {} {:<17} {}{}", flag, summary, name, note);
+}
+
+fn print_word_diff(file_diff: &gitup_core::FileDiff, tokenizer: &Tokenizer) {
+    let pairs = gitup_core::word_diff::paired_lines(&file_diff.lines);
+
+    for (idx, line) in file_diff.lines.iter().enumerate() {
+        let text = gitup_core::word_diff::line_text(line);
+        match line.origin {
+            gitup_core::LineOrigin::Context => println!("{}", text),
+            gitup_core::LineOrigin::Deletion => match pairs.iter().find(|(del, _)| *del == idx) {
+                Some(&(_, add)) => {
+                    let new_text = gitup_core::word_diff::line_text(&file_diff.lines[add]);
+                    for op in tokenizer.diff(text, new_text) {
+                        match op.kind {
+                            WordOpKind::Equal => print!("{}", op.text),
+                            WordOpKind::Delete => print!("[-{}-]", op.text),
+                            WordOpKind::Insert => print!("{{+{}+}}", op.text),
+                        }
+                    }
+                    println!();
+                }
+                None => println!("[-{}-]", text),
+            },
+            gitup_core::LineOrigin::Addition => {
+                if !pairs.iter().any(|(_, add)| *add == idx) {
+                    println!("{{+{}+}}", text);
+                }
+            }
+        }
+    }
+}